@@ -0,0 +1,65 @@
+//! Benchmarks of accessing linker sets.
+//!
+//! Run with `cargo bench`; each benchmark reports the mean time of an
+//! iteration.  Arguments filter the benchmarks by name.
+
+use linker_set::*;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+set_declare!(handlers, u64);
+
+macro_rules! entries {
+    ($($name:ident = $value:expr),* $(,)?) => {
+        $(
+            #[set_entry(handlers)]
+            static $name: u64 = $value;
+        )*
+    };
+}
+
+entries! {
+    H0 = 0, H1 = 1, H2 = 2, H3 = 3, H4 = 4, H5 = 5, H6 = 6, H7 = 7,
+    H8 = 8, H9 = 9, H10 = 10, H11 = 11, H12 = 12, H13 = 13, H14 = 14,
+    H15 = 15,
+}
+
+const TARGET: Duration = Duration::from_millis(500);
+
+fn bench<F>(name: &str, filters: &[String], mut f: F)
+where
+    F: FnMut() -> u64,
+{
+    if !filters.is_empty() && !filters.iter().any(|x| name.contains(x)) {
+        return;
+    }
+    let mut iters = 1u64;
+    loop {
+        let start = Instant::now();
+        for _ in 0..iters {
+            black_box(f());
+        }
+        let elapsed = start.elapsed();
+        if elapsed >= TARGET {
+            let ns = elapsed.as_nanos() as f64 / iters as f64;
+            println!("{:<24} {:>10.2} ns/iter", name, ns);
+            return;
+        }
+        iters *= 2;
+    }
+}
+
+fn main() {
+    let filters = std::env::args()
+        .skip(1)
+        .filter(|a| !a.starts_with('-'))
+        .collect::<Vec<_>>();
+    let filters = filters.as_slice();
+
+    bench("set_len", filters, || set!(handlers).len() as u64);
+    bench("handle_len", filters, || handlers::handle().len() as u64);
+    bench("set_index", filters, || set!(handlers)[black_box(7)]);
+    bench("handle_index", filters, || handlers::handle()[black_box(7)]);
+    bench("set_sum", filters, || set!(handlers).iter().sum());
+    bench("handle_sum", filters, || handlers::handle().iter().sum());
+}
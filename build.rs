@@ -0,0 +1,36 @@
+use std::env;
+use std::process::Command;
+
+/// Returns the minor version of the compiler building the crate.
+fn rustc_minor() -> Option<u32> {
+    let rustc = env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let out = Command::new(rustc).arg("--version").output().ok()?;
+    let version = String::from_utf8(out.stdout).ok()?;
+    let version = version.split_whitespace().nth(1)?;
+    version.split('.').nth(1)?.parse().ok()
+}
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=RUSTC");
+    println!("cargo:rustc-check-cfg=cfg(uninhabited_patterns)");
+    println!("cargo:rustc-check-cfg=cfg(const_static_refs)");
+    let minor = rustc_minor().unwrap_or(u32::MAX);
+    // a pattern of an uninhabited type is irrefutable from 1.82, which
+    // is how set_declare! rejects element types with no values
+    if minor >= 82 {
+        println!("cargo:rustc-cfg=uninhabited_patterns");
+    }
+    // a const may refer to any static from 1.90, as the const array of
+    // the entries of a sealed set does
+    if minor >= 90 {
+        println!("cargo:rustc-cfg=const_static_refs");
+    }
+    // #[used] statics are marked SHF_GNU_RETAIN on ELF from 1.89; before
+    // that, --gc-sections drops the sections that only inspection reads,
+    // which the tests of this crate inspect in their own binaries
+    let vendor = env::var("CARGO_CFG_TARGET_VENDOR").unwrap_or_default();
+    let os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    if minor < 89 && vendor != "apple" && os != "illumos" {
+        println!("cargo:rustc-link-arg=-Wl,--no-gc-sections");
+    }
+}
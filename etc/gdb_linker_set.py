@@ -0,0 +1,143 @@
+"""GDB support for linker sets.
+
+Load into GDB with ``source gdb_linker_set.py``.  This registers
+pretty-printers for ``linker_set::LinkerSet`` and
+``linker_set::LinkerSetIter``, which show the elements of a set rather than
+raw pointers, and a ``linker-set`` command:
+
+    (gdb) linker-set            list the declared sets
+    (gdb) linker-set stuff      print the elements of the set "stuff"
+
+The command works on a running process, a core dump, or a bare binary.  It
+relies only on the interface described in ``linker_set::debug_support``.
+
+This script is experimental; no test runs it in GDB.
+"""
+
+import json
+import re
+
+import gdb
+
+SCHEMA_SECTION = "set_linker_set_schema"
+
+
+def _elements(ptr, length):
+    for i in range(length):
+        yield "[%d]" % i, (ptr + i).dereference()
+
+
+class LinkerSetPrinter:
+    def __init__(self, val):
+        s = val["slice"]
+        self.ptr = s["data_ptr"]
+        self.len = int(s["length"])
+
+    def to_string(self):
+        return "LinkerSet(len=%d)" % self.len
+
+    def children(self):
+        return _elements(self.ptr, self.len)
+
+    def display_hint(self):
+        return "array"
+
+
+class LinkerSetIterPrinter:
+    def __init__(self, val):
+        self.ptr = val["next"]
+        size = self.ptr.type.target().sizeof
+        stop = int(val["stop"])
+        self.len = (stop - int(self.ptr)) // size if size else 0
+
+    def to_string(self):
+        return "LinkerSetIter(remaining=%d)" % self.len
+
+    def children(self):
+        return _elements(self.ptr, self.len)
+
+    def display_hint(self):
+        return "array"
+
+
+def _lookup(val):
+    name = val.type.strip_typedefs().name or ""
+    if re.match(r"^linker_set::LinkerSet<.*>$", name):
+        return LinkerSetPrinter(val)
+    if re.match(r"^linker_set::LinkerSetIter<.*>$", name):
+        return LinkerSetIterPrinter(val)
+    return None
+
+
+def _sections():
+    """Returns the address ranges of the sections of all loaded objects."""
+    ranges = {}
+    out = gdb.execute("info files", to_string=True)
+    for m in re.finditer(r"(0x[0-9a-f]+) - (0x[0-9a-f]+) is (\S+)", out):
+        start, stop, name = int(m[1], 16), int(m[2], 16), m[3]
+        ranges.setdefault(name, []).append((start, stop))
+    return ranges
+
+
+def _schema(sections):
+    inferior = gdb.selected_inferior()
+    for start, stop in sections.get(SCHEMA_SECTION, []):
+        data = bytes(inferior.read_memory(start, stop - start))
+        for line in data.decode("utf-8", "replace").splitlines():
+            if line:
+                yield json.loads(line)
+
+
+def _element_type(desc):
+    placeholder = "%s::__EMPTY_SET" % desc["module"]
+    sym = gdb.lookup_static_symbol(placeholder)
+    if sym is None:
+        sym = gdb.lookup_global_symbol(placeholder)
+    if sym is not None:
+        return sym.type.strip_typedefs().target()
+    try:
+        return gdb.lookup_type(desc["type"])
+    except gdb.error:
+        return None
+
+
+class LinkerSetCommand(gdb.Command):
+    """List the declared linker sets, or print the elements of one.
+
+Usage: linker-set [NAME]"""
+
+    def __init__(self):
+        super().__init__("linker-set", gdb.COMMAND_DATA)
+
+    def invoke(self, arg, from_tty):
+        sections = _sections()
+        name = arg.strip()
+        found = False
+        for desc in _schema(sections):
+            section = desc["section"]
+            ranges = sections.get(section, [])
+            size = desc["size"]
+            length = sum(stop - start for start, stop in ranges) // size
+            if not name:
+                print("%s: %d x %s (%s)"
+                      % (desc["name"], length, desc["type"], desc["module"]))
+                continue
+            if desc["name"] != name:
+                continue
+            found = True
+            ty = _element_type(desc)
+            if ty is None:
+                raise gdb.GdbError("cannot find type %s" % desc["type"])
+            print("%s: %d x %s" % (name, length, desc["type"]))
+            i = 0
+            for start, stop in ranges:
+                ptr = gdb.Value(start).cast(ty.pointer())
+                for _, elem in _elements(ptr, (stop - start) // size):
+                    print("[%d] = %s" % (i, elem))
+                    i += 1
+        if name and not found:
+            raise gdb.GdbError("no such linker set: %s" % name)
+
+
+gdb.pretty_printers.append(_lookup)
+LinkerSetCommand()
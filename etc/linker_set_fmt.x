@@ -0,0 +1,10 @@
+/* Keep the format strings interned by linker_set::intern! out of the
+ * loaded image.  Add this script to the link with -Tlinker_set_fmt.x. */
+SECTIONS
+{
+  .linker_set_fmt 0 (INFO) :
+  {
+    KEEP(*(.linker_set_fmt));
+  }
+}
+INSERT AFTER .bss;
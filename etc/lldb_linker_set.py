@@ -0,0 +1,63 @@
+"""LLDB support for linker sets.
+
+Load into LLDB with ``command script import lldb_linker_set.py``.  This
+registers a synthetic child provider and summary for
+``linker_set::LinkerSet``, which show the elements of a set rather than raw
+pointers.
+
+This script is experimental; no test runs it in LLDB.
+"""
+
+import lldb
+
+PATTERN = "^linker_set::LinkerSet<.+>$"
+
+
+class LinkerSetProvider:
+    def __init__(self, valobj, internal_dict):
+        self.valobj = valobj
+        self.update()
+
+    def update(self):
+        s = self.valobj.GetChildMemberWithName("slice")
+        self.ptr = s.GetChildMemberWithName("data_ptr")
+        self.len = s.GetChildMemberWithName("length").GetValueAsUnsigned()
+        self.elem = self.ptr.GetType().GetPointeeType()
+        self.size = self.elem.GetByteSize()
+        return False
+
+    def num_children(self):
+        return self.len
+
+    def get_child_index(self, name):
+        try:
+            return int(name.lstrip("[").rstrip("]"))
+        except ValueError:
+            return -1
+
+    def get_child_at_index(self, index):
+        if not 0 <= index < self.len:
+            return None
+        addr = self.ptr.GetValueAsUnsigned() + index * self.size
+        return self.valobj.CreateValueFromAddress(
+            "[%d]" % index, addr, self.elem
+        )
+
+    def has_children(self):
+        return True
+
+
+def summary(valobj, internal_dict):
+    return "LinkerSet(len=%d)" % valobj.GetNumChildren()
+
+
+def __lldb_init_module(debugger, internal_dict):
+    debugger.HandleCommand(
+        'type synthetic add -x "%s" -w linker_set --python-class %s.%s'
+        % (PATTERN, __name__, LinkerSetProvider.__name__)
+    )
+    debugger.HandleCommand(
+        'type summary add -x "%s" -w linker_set -F %s.summary'
+        % (PATTERN, __name__)
+    )
+    debugger.HandleCommand("type category enable linker_set")
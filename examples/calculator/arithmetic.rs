@@ -0,0 +1,45 @@
+//! The operations of arithmetic.
+
+use crate::{applies, operation, Operation};
+use linker_set::*;
+
+macro_rules! binary {
+    (
+        $ident:ident, $name:literal, $help:literal,
+        |$a:ident, $b:ident| $e:expr
+    ) => {
+        #[set_entry(operation, key = $name, self_test = applies)]
+        static $ident: Operation = Operation {
+            arity: 2,
+            help: $help,
+            apply: |args| {
+                let ($a, $b) = (args[0], args[1]);
+                $e
+            },
+        };
+    };
+}
+
+binary!(ADD, "add", "the sum of two numbers", |a, b| Ok(a + b));
+binary!(
+    SUB,
+    "sub",
+    "the difference of two numbers",
+    |a, b| Ok(a - b)
+);
+binary!(MUL, "mul", "the product of two numbers", |a, b| Ok(a * b));
+binary!(DIV, "div", "the quotient of two numbers", |a, b| match b {
+    0.0 => Err("division by zero".to_string()),
+    _ => Ok(a / b),
+});
+binary!(POW, "pow", "a number raised to a power", |a, b| Ok(
+    a.powf(b)
+));
+
+/* replaced by that of the roots module, which has a higher priority */
+#[set_entry(operation, key = "sqrt", self_test = applies)]
+static SQRT: Operation = Operation {
+    arity: 1,
+    help: "the square root of a number, as a power of one half",
+    apply: |args| Ok(args[0].powf(0.5)),
+};
@@ -0,0 +1,137 @@
+//! A calculator whose operations are registered by the modules that
+//! implement them.
+//!
+//! The calculator itself knows no operation: each module places those it
+//! implements into the keyed `operation` set, where a module can replace
+//! an operation of another by registering it at a higher priority, as the
+//! `roots` module does with `sqrt`.  Each operation is checked by a self
+//! test, and the set as a whole by an invariant.
+//!
+//! ```text
+//! cargo run --example calculator -- add 1 2
+//! cargo run --example calculator -- --list
+//! cargo run --example calculator -- --check
+//! cargo run --example calculator -- --sets
+//! ```
+//!
+//! `--sets` reads the linker sets from the binary of the calculator, as
+//! `cargo linker-set` does, to show where each operation was placed.
+
+mod arithmetic;
+mod roots;
+
+use linker_set::inspect::{self, Elf};
+use linker_set::*;
+use std::process::ExitCode;
+
+/// An operation of the calculator.
+pub struct Operation {
+    /// The number of arguments the operation takes.
+    pub arity: usize,
+    /// A description of the operation for `--list`.
+    pub help: &'static str,
+    /// The function computing the result of the operation.
+    pub apply: fn(&[f64]) -> Result<f64, String>,
+}
+
+set_declare!(operation, Operation, keyed, self_test);
+
+const USAGE: &str = "\
+usage: calculator OPERATION ARGUMENT...
+       calculator --list | --check | --sets";
+
+/// Check an operation by applying it to arguments of one.
+pub fn applies(op: &Operation) -> Result<(), String> {
+    let x = (op.apply)(&vec![1.0; op.arity])?;
+    if x.is_finite() {
+        Ok(())
+    } else {
+        Err(format!("yields {} given ones", x))
+    }
+}
+
+#[set_invariant(operation)]
+fn takes_arguments(all: &LinkerSet<Operation>) -> Result<(), String> {
+    match all.iter().find(|op| op.arity == 0) {
+        Some(op) => Err(format!("{:?} takes no arguments", op.help)),
+        None => Ok(()),
+    }
+}
+
+fn calculate(name: &str, args: &[String]) -> Result<f64, String> {
+    let Some(op) = operation::lookup(name) else {
+        return Err(match operation::suggest(name) {
+            Some(key) => {
+                format!("no operation {}; did you mean {}?", name, key)
+            }
+            None => format!("no operation {}", name),
+        });
+    };
+    if args.len() != op.arity {
+        let n = args.len();
+        return Err(format!(
+            "{} takes {} arguments, not {}",
+            name, op.arity, n
+        ));
+    }
+    let args = args
+        .iter()
+        .map(|a| a.parse().map_err(|e| format!("{}: {}", a, e)))
+        .collect::<Result<Vec<_>, String>>()?;
+    (op.apply)(&args)
+}
+
+fn list() -> Result<(), String> {
+    for (name, op) in operation::resolved() {
+        println!("{:<8} {}", name, op.help);
+    }
+    Ok(())
+}
+
+fn check() -> Result<(), String> {
+    let mut failures =
+        set!(operation).run_self_tests().err().unwrap_or_default();
+    failures.extend(invariant::validate_all().err().unwrap_or_default());
+    for (name, error) in &failures {
+        eprintln!("calculator: {} failed: {}", name, error);
+    }
+    if !failures.is_empty() {
+        return Err(format!("{} checks failed", failures.len()));
+    }
+    println!("{} operations pass", set!(operation).len());
+    Ok(())
+}
+
+fn sets() -> Result<(), String> {
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let data = std::fs::read(&exe).map_err(|e| e.to_string())?;
+    let elf = Elf::parse(&data).map_err(|e| format!("{:?}: {}", exe, e))?;
+    for set in inspect::sets(&elf) {
+        if !set.name.ends_with("operation") || set.size == 0 {
+            continue;
+        }
+        println!("{:<24} {:>6} bytes at {:#x}", set.name, set.size, set.addr);
+        for entry in inspect::entries(&elf, &set).map_err(|e| e.to_string())? {
+            println!("  {:#x} {}", entry.value, inspect::demangle(&entry.name));
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let result = match args.first().map(String::as_str) {
+        None => Err(USAGE.to_string()),
+        Some("--list") => list(),
+        Some("--check") => check(),
+        Some("--sets") => sets(),
+        Some(name) => calculate(name, &args[1..]).map(|x| println!("{}", x)),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("calculator: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
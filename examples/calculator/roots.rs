@@ -0,0 +1,21 @@
+//! The operations of roots, which reject numbers that have no real root.
+
+use crate::{applies, operation, Operation};
+use linker_set::*;
+
+#[set_entry(operation, key = "sqrt", priority = 10, self_test = applies)]
+static SQRT: Operation = Operation {
+    arity: 1,
+    help: "the square root of a number that is not negative",
+    apply: |args| match args[0] {
+        x if x < 0.0 => Err(format!("{} has no real square root", x)),
+        x => Ok(x.sqrt()),
+    },
+};
+
+#[set_entry(operation, key = "cbrt", self_test = applies)]
+static CBRT: Operation = Operation {
+    arity: 1,
+    help: "the cube root of a number",
+    apply: |args| Ok(args[0].cbrt()),
+};
@@ -0,0 +1,28 @@
+use std::env;
+use std::process::Command;
+
+/// Returns the minor version of the compiler building the crate.
+fn rustc_minor() -> Option<u32> {
+    let rustc = env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let out = Command::new(rustc).arg("--version").output().ok()?;
+    let version = String::from_utf8(out.stdout).ok()?;
+    let version = version.split_whitespace().nth(1)?;
+    version.split('.').nth(1)?.parse().ok()
+}
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=RUSTC");
+    println!("cargo:rustc-check-cfg=cfg(unsafe_attributes)");
+    println!("cargo:rustc-check-cfg=cfg(const_static_refs)");
+    let minor = rustc_minor().unwrap_or(u32::MAX);
+    // #[unsafe(link_section)] is accepted from 1.82 and required in
+    // edition 2024; older compilers only understand the bare form
+    if minor >= 82 {
+        println!("cargo:rustc-cfg=unsafe_attributes");
+    }
+    // a const may refer to any static from 1.90, including one with
+    // interior mutability, as the slot of an entry of a sealed set does
+    if minor >= 90 {
+        println!("cargo:rustc-cfg=const_static_refs");
+    }
+}
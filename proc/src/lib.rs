@@ -3,50 +3,1515 @@
 //! Procedural macro crate to accompany the linker-set crate.
 
 use proc_macro::TokenStream;
-use quote::{format_ident, quote};
+use quote::{format_ident, quote, quote_spanned};
 use syn::parse::*;
-use syn::punctuated::*;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
 use syn::*;
 
-struct Name(String);
+/* The text of these diagnostics is part of the interface of the crate, so
+ * that users can write compile-fail tests against it.  It is repeated in
+ * linker_set::diagnostics, whose tests check that the two agree. */
+const ENTRY_ARGUMENT: &str =
+    "expected `key`, `priority`, `self_test`, `sub`, or `target_feature`";
+const PRIORITY_WITHOUT_KEY: &str = "`priority` requires `key`";
+const SUB_EXCLUSIVE: &str =
+    "`sub` cannot be used with `key`, `self_test`, or `target_feature`";
+const ENTRY_ITEM: &str = "set_entry must be used on a static or a const";
+const ENTRY_TOO_LARGE: &str = "is larger than the max_size of its set";
+const IMPL_ARGUMENT: &str = "expected `ctor`";
+const IMPL_GENERIC: &str = "register_impl does not support generic impls";
+const IMPL_NOT_TRAIT: &str = "register_impl must be used on an impl of a trait";
+const CONST_GENERIC: &str = "register_const does not support generic impls";
+const SHUTDOWN_ARGUMENT: &str = "expected `phase`";
+const SHUTDOWN_ITEM: &str = "shutdown must be used on a fn";
+const INVARIANT_ITEM: &str = "set_invariant must be used on a fn";
+const STAGE_ITEM: &str = "stage_init must be used on a fn";
+const ABI_ITEM: &str = "SetAbi can only be derived for a struct";
+const ABI_GENERIC: &str = "SetAbi does not support generic structs";
+const ABI_NOT_REPR_C: &str = "SetAbi requires #[repr(C)]";
+const CONST_NOT_FOUND: &str =
+    "expected `Self::NAME` naming an associated const of the impl";
+const NAMESPACE_MISMATCH: &str =
+    "a set must be named by a path beginning with its crate \
+     if and only if it is namespaced";
+const SELF_TEST_UNDECLARED: &str =
+    "a set must be declared with `self_test` to take entries with self tests";
+const FEATURES_UNDECLARED: &str =
+    "a set must be declared with `target_feature` to take entries requiring \
+     CPU features";
+const TEST_ENTRY_UNDECLARED: &str =
+    "a set must be declared with `test_entries` to take test entries";
+const NAMESPACE_WITHOUT_CARGO: &str =
+    "a namespaced set must be named in a crate built by Cargo";
+const SEALED_TOO_LARGE: &str =
+    "a sealed set holds at most 64 entries in its const array";
+const SEALED_PATH: &str =
+    "a sealed set must be named by a path beginning with `crate`, `self`, \
+     or `super`";
 
-impl Parse for Name {
+/// A set named by an attribute, by a bare name, or by a path whose first
+/// segment names the crate that declared it as namespaced.
+struct SetPath {
+    path: Path,
+    name: String,
+    namespace: String,
+}
+
+impl SetPath {
+    fn new(path: Path) -> Result<Self> {
+        let segments = &path.segments;
+        let name = segments.last().unwrap().ident.to_string();
+        let namespace = if segments.len() == 1 {
+            String::new()
+        } else {
+            let krate = segments.first().unwrap().ident.to_string();
+            let krate = match krate.as_str() {
+                "crate" | "self" | "super" => {
+                    match std::env::var("CARGO_CRATE_NAME") {
+                        Ok(krate) => krate,
+                        Err(_) => {
+                            let msg = NAMESPACE_WITHOUT_CARGO;
+                            return Err(Error::new_spanned(&path, msg));
+                        }
+                    }
+                }
+                _ => krate,
+            };
+            format!("{}__", krate)
+        };
+        Ok(Self {
+            path,
+            name,
+            namespace,
+        })
+    }
+
+    /// Returns the name of the section of the set holding entries of a
+    /// kind, such as `_meta_`, or its entries themselves given "".
+    fn section(&self, kind: &str) -> String {
+        format!("set_{}{}{}", self.namespace, kind, self.name)
+    }
+}
+
+impl Parse for SetPath {
     fn parse(input: ParseStream) -> Result<Self> {
-        let name = Punctuated::<Ident, Token![,]>::parse_terminated(input)?;
-        assert!(name.len() == 1, "set_entry macro takes one argument");
-        Ok(Self(name[0].to_string()))
+        Self::new(Path::parse_mod_style(input)?)
     }
 }
 
-/// Attribute macro that puts an item into a linker set.
-#[proc_macro_attribute]
-pub fn set_entry(meta: TokenStream, decl: TokenStream) -> TokenStream {
-    let meta = parse_macro_input!(meta as Name);
-    let decl = parse_macro_input!(decl as ItemStatic);
-
-    let set = meta.0;
-    let set_section = format!("set_{}", set);
-    let set_ident = format_ident!("{}", set);
-    let start_set = format_ident!("__start_set_{}", set);
+struct EntryArgs {
+    set: SetPath,
+    key: Option<LitStr>,
+    priority: Option<Expr>,
+    self_test: Option<Path>,
+    sub: Option<Ident>,
+    target_feature: Option<LitStr>,
+}
+
+impl Parse for EntryArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let set = input.parse()?;
+        let (mut key, mut priority) = (None, None);
+        let (mut self_test, mut sub, mut target_feature) = (None, None, None);
+        while input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty()
+        {
+            let name = input.parse::<Ident>()?;
+            input.parse::<Token![=]>()?;
+            if name == "key" {
+                key = Some(input.parse()?);
+            } else if name == "priority" {
+                priority = Some(input.parse()?);
+            } else if name == "self_test" {
+                self_test = Some(input.parse()?);
+            } else if name == "sub" {
+                sub = Some(input.parse::<Ident>()?);
+            } else if name == "target_feature" {
+                target_feature = Some(input.parse()?);
+            } else {
+                return Err(Error::new_spanned(name, ENTRY_ARGUMENT));
+            }
+        }
+        if let (None, Some(priority)) = (&key, &priority) {
+            return Err(Error::new_spanned(priority, PRIORITY_WITHOUT_KEY));
+        }
+        if let Some(sub) = &sub {
+            if key.is_some() || self_test.is_some() || target_feature.is_some()
+            {
+                return Err(Error::new_spanned(sub, SUB_EXCLUSIVE));
+            }
+        }
+        Ok(Self {
+            set,
+            key,
+            priority,
+            self_test,
+            sub,
+            target_feature,
+        })
+    }
+}
+
+/// Returns the name by which Mach-O knows a section, which must fit in 16
+/// bytes.
+///
+/// A longer name is cut to at most 8 bytes and followed by the FNV-1a hash
+/// of the whole name in hex, so that sets whose names share a prefix still
+/// get sections of their own.  The readable name of the set is kept in its
+/// declaration, for reflection.
+fn mach_o(section: &str) -> String {
+    if section.len() <= 16 {
+        return section.to_string();
+    }
+    let hash = section.bytes().fold(0x811c9dc5u32, |h, b| {
+        (h ^ u32::from(b)).wrapping_mul(0x01000193)
+    });
+    let cut = (0..=8).rev().find(|&i| section.is_char_boundary(i));
+    format!("{}{:08x}", &section[..cut.unwrap()], hash)
+}
+
+/// Returns a name usable unquoted in assembly for a section name, which
+/// may hold any character of an identifier.
+fn asm_name(section: &str) -> String {
+    section
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => c.to_string(),
+            _ => format!("${:x}$", u32::from(c)),
+        })
+        .collect()
+}
+
+/// Returns the assembly defining a symbol named for the Mach-O name of a
+/// section, in a COMDAT group named for the whole name.
+///
+/// The declarations of a section, in any crate, are merged into one group,
+/// so that the symbol is defined once, but those of two sections sharing
+/// a Mach-O name define it twice, and the link fails, naming the symbol
+/// and both sections.  Only ELF has COMDAT groups, but the Mach-O name is
+/// the same on every target, so a build for ELF finds such sets before
+/// they are built for Mach-O.
+fn mach_o_guard(section: &str) -> String {
+    let group = format!("linker_set.section.{}", asm_name(section));
+    let symbol = format!("linker_set.mach_o.{}", asm_name(&mach_o(section)));
+    /* a section declared twice in one object is one group already */
+    [
+        format!(
+            ".pushsection .linker_set_guard,\"G\",%progbits,{},comdat",
+            group
+        ),
+        format!(".ifndef {}", group),
+        format!("{}:", group),
+        format!(".globl {}", symbol),
+        format!("{}:", symbol),
+        ".endif".to_string(),
+        ".popsection".to_string(),
+    ]
+    .join("\n")
+}
+
+/// Returns the `link_section` argument placing an item into a section on
+/// Mach-O, where sections belong to a segment.
+fn mach_o_section(section: &str) -> String {
+    format!("__DATA,{},regular,no_dead_strip", mach_o(section))
+}
+
+/// Returns the attributes placing an item into a section, on ELF or on
+/// Mach-O as the target requires, in the syntax understood by the compiler
+/// building this crate.
+fn link_section(section: &str) -> proc_macro2::TokenStream {
+    let mach_o = mach_o_section(section);
+    if cfg!(unsafe_attributes) {
+        quote! {
+            #[cfg_attr(
+                not(target_vendor = "apple"),
+                unsafe(link_section = #section)
+            )]
+            #[cfg_attr(target_vendor = "apple", unsafe(link_section = #mach_o))]
+        }
+    } else {
+        quote! {
+            #[cfg_attr(not(target_vendor = "apple"), link_section = #section)]
+            #[cfg_attr(target_vendor = "apple", link_section = #mach_o)]
+        }
+    }
+}
+
+/// Returns the value of an argument of `concat!`, which the declarative
+/// macros of the linker-set crate build from literals, `concat!`,
+/// `stringify!`, and `env!`.
+fn concatenated(expr: &Expr) -> Result<String> {
+    match expr {
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(s), ..
+        }) => Ok(s.value()),
+        Expr::Group(group) => concatenated(&group.expr),
+        Expr::Macro(m) if m.mac.path.is_ident("concat") => {
+            let args = m.mac.parse_body_with(
+                Punctuated::<Expr, Token![,]>::parse_terminated,
+            )?;
+            args.iter().map(concatenated).collect()
+        }
+        Expr::Macro(m) if m.mac.path.is_ident("stringify") => {
+            Ok(m.mac.tokens.to_string())
+        }
+        Expr::Macro(m) if m.mac.path.is_ident("env") => {
+            let var = m.mac.parse_body::<LitStr>()?;
+            std::env::var(var.value()).map_err(|_| {
+                Error::new_spanned(&var, "environment variable not defined")
+            })
+        }
+        _ => Err(Error::new_spanned(
+            expr,
+            "expected a string literal, concat!, stringify!, or env!",
+        )),
+    }
+}
+
+/// Returns the name of a section given as the arguments of `concat!`.
+fn section_name(input: ParseStream) -> Result<String> {
+    let args = Punctuated::<Expr, Token![,]>::parse_terminated(input)?;
+    args.iter().map(concatenated).collect()
+}
+
+/// Returns the `cfg` attributes of an item, which must also be applied to
+/// the items generated alongside it.
+fn cfgs(attrs: &[Attribute]) -> Vec<&Attribute> {
+    attrs.iter().filter(|a| a.path().is_ident("cfg")).collect()
+}
+
+/// Returns the doc attributes of an item.
+fn docs(attrs: &[Attribute]) -> Vec<&Attribute> {
+    attrs
+        .iter()
+        .filter(|a| a.path().is_ident("doc"))
+        .filter(|a| matches!(a.meta, Meta::NameValue(_)))
+        .collect()
+}
+
+/// Returns the doc comment of an item, as rustdoc would read it.
+#[cfg(feature = "metadata")]
+fn doc(attrs: &[Attribute]) -> String {
+    let lines = docs(attrs).into_iter().filter_map(|a| match &a.meta {
+        Meta::NameValue(MetaNameValue {
+            value:
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(s), ..
+                }),
+            ..
+        }) => Some(s.value()),
+        _ => None,
+    });
+    let lines = lines
+        .map(|l| l.strip_prefix(' ').map(str::to_string).unwrap_or(l))
+        .collect::<Vec<_>>();
+    lines.join("\n")
+}
+
+#[cfg(feature = "metadata")]
+fn metadata(
+    set: &SetPath, decl: &ItemStatic, name: &str,
+) -> proc_macro2::TokenStream {
+    let section = set.section("_meta_");
+    let ident = &decl.ident;
+    let meta_ident = format_ident!("__SET_META_{}", name.to_uppercase());
+    let doc = doc(&decl.attrs);
+    let ty = &decl.ty;
+    let link_section = link_section(&section);
+    let cfgs = cfgs(&decl.attrs);
+    quote! {
+        #(#cfgs)*
+        #link_section
+        #[used]
+        static #meta_ident: ::linker_set::EntryMeta<#ty> =
+            ::linker_set::EntryMeta::new(#name, module_path!(), #doc, &#ident);
+    }
+}
+
+#[cfg(not(feature = "metadata"))]
+fn metadata(_: &SetPath, _: &ItemStatic, _: &str) -> proc_macro2::TokenStream {
+    proc_macro2::TokenStream::new()
+}
+
+/// Records a line, for the crate being compiled, in the manifest of
+/// registrations kept in the directory named by `LINKER_SET_MANIFEST_DIR`,
+/// if it is set.
+///
+/// Each compilation of a crate appends to a file of its own, one line per
+/// declaration of a set or placement of an entry, with the fields of the
+/// line separated by tabs.  Failing to write is not an error, since the
+/// manifest only describes the build.
+#[cfg(feature = "manifest")]
+fn append(fields: impl FnOnce(&str) -> String) {
+    use std::io::Write;
+
+    let Some(dir) = std::env::var_os("LINKER_SET_MANIFEST_DIR") else {
+        return;
+    };
+    let krate = std::env::var("CARGO_CRATE_NAME").unwrap_or("?".to_string());
+    let dir = std::path::Path::new(&dir).join(&krate);
+    let file = dir.join(format!("{}.tsv", std::process::id()));
+    let line = fields(&krate) + "\n";
+    let _ = std::fs::create_dir_all(&dir).and_then(|_| {
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&file)?
+            .write_all(line.as_bytes())
+    });
+}
+
+/// Records the placement of an entry into a set, as `entry`, the set, the
+/// crate, the entry, and the file and line of the entry.
+#[cfg(feature = "manifest")]
+#[clippy::msrv = "1.88"]
+fn record(set: &SetPath, decl: &ItemStatic, name: &str) {
+    let span = decl.ident.span().unwrap();
+    append(|krate| {
+        format!(
+            "entry\t{}\t{}\t{}\t{}\t{}",
+            set.name,
+            krate,
+            name,
+            span.file(),
+            span.line()
+        )
+    });
+}
+
+#[cfg(not(feature = "manifest"))]
+fn record(_: &SetPath, _: &ItemStatic, _: &str) {}
+
+/// Records the declaration of a set, as `set`, the set, the crate, and the
+/// file and line of the declaration.
+#[cfg(feature = "manifest")]
+#[clippy::msrv = "1.88"]
+fn record_set(set: &Ident) {
+    let span = set.span().unwrap();
+    append(|krate| {
+        format!("set\t{}\t{}\t{}\t{}", set, krate, span.file(), span.line())
+    });
+}
+
+#[cfg(not(feature = "manifest"))]
+fn record_set(_: &Ident) {}
+
+/// Returns a function that does nothing, named predictably for the set
+/// and the entry, such as `__linker_set_entry_stuff_FOO`, so that tools
+/// that search source or index symbols can find every entry of a set.
+#[cfg(feature = "markers")]
+fn marker(
+    set: &SetPath, decl: &ItemStatic, name: &str,
+) -> proc_macro2::TokenStream {
+    let ident = &decl.ident;
+    let marker_ident =
+        format_ident!("__linker_set_entry_{}_{}", set.name, name);
+    let cfgs = cfgs(&decl.attrs);
+    quote! {
+        #(#cfgs)*
+        #[doc(hidden)]
+        #[allow(dead_code, non_snake_case)]
+        fn #marker_ident() {
+            let _ = &#ident;
+        }
+    }
+}
+
+#[cfg(not(feature = "markers"))]
+fn marker(_: &SetPath, _: &ItemStatic, _: &str) -> proc_macro2::TokenStream {
+    proc_macro2::TokenStream::new()
+}
+
+/// Returns the slot of an entry of a set named by a path beginning with
+/// `crate`, `self`, or `super`, which the set probes for to count the
+/// entries placed within its crate and, from Rust 1.90, to gather
+/// references to them into a const array.  The entries of a set are
+/// numbered in the order in which they are expanded, which is fixed
+/// within one invocation of the compiler.  A sealed set named by another
+/// path fails to compile, lest the entry be left out of its slots.
+fn slot(set: &SetPath, decl: &ItemStatic) -> proc_macro2::TokenStream {
+    static SLOTS: std::sync::Mutex<Vec<(String, usize)>> =
+        std::sync::Mutex::new(Vec::new());
+
+    let path = &set.path;
+    let cfgs = cfgs(&decl.attrs);
+    let first = path.segments.first().unwrap().ident.to_string();
+    if path.segments.len() == 1 {
+        return proc_macro2::TokenStream::new();
+    } else if !matches!(first.as_str(), "crate" | "self" | "super") {
+        return quote_spanned! {path.span()=>
+            #(#cfgs)*
+            const _: () = assert!(#path::__SLOTS == usize::MAX, #SEALED_PATH);
+        };
+    }
+    let section = set.section("");
+    let mut slots = SLOTS.lock().unwrap();
+    let index = match slots.iter_mut().find(|(s, _)| *s == section) {
+        Some((_, next)) => {
+            *next += 1;
+            *next - 1
+        }
+        None => {
+            slots.push((section, 1));
+            0
+        }
+    };
+    let ident = &decl.ident;
+    let entry = match cfg!(const_static_refs) {
+        true => quote! {
+            pub const ENTRY: ::std::option::Option<&'static #path::__Type> =
+                ::std::option::Option::Some(&#ident);
+        },
+        false => quote!(),
+    };
+    quote! {
+        #(#cfgs)*
+        const _: () = {
+            assert!(#index < #path::__SLOTS, #SEALED_TOO_LARGE);
+
+            #[allow(dead_code)]
+            impl #path::__Slot<#index> {
+                pub const PRESENT: bool = true;
+                #entry
+            }
+        };
+    }
+}
+
+fn keyed(args: &EntryArgs, decl: &ItemStatic) -> proc_macro2::TokenStream {
+    let Some(key) = &args.key else {
+        return proc_macro2::TokenStream::new();
+    };
+    let priority = match &args.priority {
+        Some(priority) => quote!(#priority),
+        None => quote!(0),
+    };
+    let section = args.set.section("_keyed_");
+    let ident = &decl.ident;
+    let keyed_ident = format_ident!("__SET_KEYED_{}", ident);
+    let ty = &decl.ty;
+    let link_section = link_section(&section);
+    let cfgs = cfgs(&decl.attrs);
+    quote! {
+        #(#cfgs)*
+        #link_section
+        #[used]
+        #[allow(non_upper_case_globals)]
+        static #keyed_ident: ::linker_set::Keyed<#ty> =
+            ::linker_set::Keyed::new(#key, #priority, &#ident);
+    }
+}
+
+fn self_test(
+    args: &EntryArgs, decl: &ItemStatic, name: &str,
+) -> proc_macro2::TokenStream {
+    let Some(check) = &args.self_test else {
+        return proc_macro2::TokenStream::new();
+    };
+    let section = args.set.section("_selftest_");
+    let ident = &decl.ident;
+    let test_ident = format_ident!("__SET_SELF_TEST_{}", ident);
+    let ty = &decl.ty;
+    let link_section = link_section(&section);
+    let cfgs = cfgs(&decl.attrs);
+    let declared =
+        declared(&args.set, "__SELF_TESTS", SELF_TEST_UNDECLARED, decl);
+    quote! {
+        #declared
+
+        #(#cfgs)*
+        #link_section
+        #[used]
+        #[allow(non_upper_case_globals)]
+        static #test_ident: ::linker_set::SelfTest<#ty> =
+            ::linker_set::SelfTest::new(#name, &#ident, #check);
+    }
+}
+
+fn features(args: &EntryArgs, decl: &ItemStatic) -> proc_macro2::TokenStream {
+    let Some(features) = &args.target_feature else {
+        return proc_macro2::TokenStream::new();
+    };
+    let names = features.value();
+    let names = names.split(',').map(str::trim).collect::<Vec<_>>();
+    let section = args.set.section("_features_");
+    let ident = &decl.ident;
+    let features_ident = format_ident!("__SET_FEATURES_{}", ident);
+    let ty = &decl.ty;
+    let link_section = link_section(&section);
+    let cfgs = cfgs(&decl.attrs);
+    let x86 = quote!(any(target_arch = "x86", target_arch = "x86_64"));
+    let arm = quote!(target_arch = "aarch64");
+    let declared =
+        declared(&args.set, "__TARGET_FEATURES", FEATURES_UNDECLARED, decl);
+    quote! {
+        #declared
+
+        #(#cfgs)*
+        #link_section
+        #[used]
+        #[allow(non_upper_case_globals)]
+        static #features_ident: ::linker_set::Featured<#ty> = {
+            #[cfg(#x86)]
+            fn detect() -> bool {
+                true #(&& ::std::is_x86_feature_detected!(#names))*
+            }
+            #[cfg(#arm)]
+            fn detect() -> bool {
+                true #(&& ::std::arch::is_aarch64_feature_detected!(#names))*
+            }
+            #[cfg(not(any(#x86, #arm)))]
+            fn detect() -> bool {
+                true #(&& cfg!(target_feature = #names))*
+            }
+            ::linker_set::Featured::new(#features, detect, &#ident)
+        };
+    }
+}
+
+/// Returns a check that a set was declared with the option that an entry
+/// of a kind requires, which opts the set into the section of the kind.
+fn declared(
+    set: &SetPath, flag: &str, message: &str, decl: &ItemStatic,
+) -> proc_macro2::TokenStream {
+    let path = &set.path;
+    let flag = format_ident!("{}", flag);
+    let cfgs = cfgs(&decl.attrs);
+    quote_spanned! {path.span()=>
+        #(#cfgs)*
+        const _: () = assert!(#path::#flag, #message);
+    }
+}
+
+/// Returns a static placed into a section, and a function checking that
+/// its type is the element type of a set.
+fn placed(
+    set: &SetPath, section: &str, decl: &ItemStatic,
+) -> proc_macro2::TokenStream {
+    let path = &set.path;
+    let namespace = &set.namespace;
     let fn_name = format_ident!(
         "__set_{}_typecheck_{}",
-        set,
+        set.name,
         decl.ident.to_string().to_lowercase()
     );
-    let expr = decl.expr.clone();
+    let ident = &decl.ident;
+    let link_section = link_section(section);
+    let cfgs = cfgs(&decl.attrs);
+    // spanned so that a mismatch reads as an ordinary error in the static
+    let expected = quote_spanned! {decl.ty.span()=> &#path::__Type};
+    let value = Ident::new(&ident.to_string(), decl.expr.span());
+    let found = quote_spanned! {decl.expr.span()=> &#value};
+    let ty = &decl.ty;
+    let too_large = quote_spanned! {ident.span()=>
+        const _: () = assert!(
+            ::linker_set::__fits(
+                ::std::mem::size_of::<#ty>(),
+                #path::__MAX_SIZE
+            ),
+            concat!(stringify!(#ident), " ", #ENTRY_TOO_LARGE)
+        );
+    };
 
-    let gen = quote! {
-        #[link_section = #set_section]
+    quote! {
+        #link_section
         #[used]
         #decl
 
+        #(#cfgs)*
+        const _: () = assert!(
+            ::linker_set::__same_str(#path::__NAMESPACE, #namespace),
+            #NAMESPACE_MISMATCH
+        );
+
+        #(#cfgs)*
+        #too_large
+
+        #(#cfgs)*
         #[cfg(any(debug_assertions, test))]
         #[allow(unused)]
-        fn #fn_name() -> bool {
+        fn #fn_name() {
             // for typechecking
-            #[allow(clippy::fn_address_comparisons)]
-            unsafe { #set_ident::#start_set == #expr }
+            let _: #expected = #found;
+        }
+    }
+}
+
+fn entry(
+    set: &SetPath, decl: &ItemStatic, name: &str,
+) -> proc_macro2::TokenStream {
+    let placed = placed(set, &set.section(""), decl);
+    let meta = metadata(set, decl, name);
+    let marker = marker(set, decl, name);
+    let slot = slot(set, decl);
+    record(set, decl, name);
+    quote! {
+        #placed
+        #meta
+        #marker
+        #slot
+    }
+}
+
+/// Returns an entry of a sub-set, which leaves the static out of the
+/// section of the parent set and places a reference to it there instead.
+fn sub_entry(
+    set: &SetPath, sub: &Ident, decl: &ItemStatic, name: &str,
+) -> proc_macro2::TokenStream {
+    let ident = &decl.ident;
+    let sub_ident = format_ident!("__SET_SUB_{}", ident);
+    let ty = &decl.ty;
+    let cfgs = cfgs(&decl.attrs);
+    let docs = docs(&decl.attrs);
+    let wrapper: ItemStatic = parse_quote! {
+        #(#cfgs)*
+        #(#docs)*
+        #[doc(hidden)]
+        #[allow(non_upper_case_globals)]
+        static #sub_ident: ::linker_set::Sub<#ty> =
+            ::linker_set::Sub::new(&#sub::ID, &#ident);
+    };
+    let entry = entry(set, &wrapper, name);
+    quote! {
+        #decl
+        #entry
+    }
+}
+
+/// Returns the items placing a static into its set, and into the helper
+/// sections named by the arguments.
+fn entries(
+    args: &EntryArgs, decl: &ItemStatic, name: &str,
+) -> proc_macro2::TokenStream {
+    if let Some(sub) = &args.sub {
+        return sub_entry(&args.set, sub, decl, name);
+    }
+    let entry = entry(&args.set, decl, name);
+    let keyed = keyed(args, decl);
+    let self_test = self_test(args, decl, name);
+    let features = features(args, decl);
+    quote! {
+        #entry
+        #keyed
+        #self_test
+        #features
+    }
+}
+
+/// Returns a hidden static initialized from a const, to be placed into a
+/// set in place of the const.
+fn const_static(item: &ItemConst) -> ItemStatic {
+    let ident = &item.ident;
+    let ty = &item.ty;
+    let static_ident = format_ident!("__SET_CONST_{}", ident);
+    let cfgs = cfgs(&item.attrs);
+    let docs = docs(&item.attrs);
+    parse_quote! {
+        #(#cfgs)*
+        #(#docs)*
+        #[doc(hidden)]
+        #[allow(non_upper_case_globals)]
+        static #static_ident: #ty = #ident;
+    }
+}
+
+fn const_entry(args: &EntryArgs, item: &ItemConst) -> proc_macro2::TokenStream {
+    let decl = const_static(item);
+    let entries = entries(args, &decl, &item.ident.to_string());
+    quote! {
+        #item
+        #entries
+    }
+}
+
+/// Returns true if an attribute gives an item an unmangled symbol name.
+fn exports(attr: &Attribute) -> bool {
+    let exporting = |path: &Path| {
+        path.is_ident("no_mangle") || path.is_ident("export_name")
+    };
+    if attr.path().is_ident("unsafe") {
+        attr.parse_args_with(Meta::parse)
+            .is_ok_and(|meta| exporting(meta.path()))
+    } else {
+        exporting(attr.path())
+    }
+}
+
+/// Attribute macro that puts an item into a linker set.
+///
+/// The item may be a static, or a const, in which case a hidden static
+/// initialized from the const is placed into the set.
+///
+/// A set declared as namespaced is named by a path beginning with the
+/// crate that declared it, as `#[set_entry(krate::name)]`, or with
+/// `crate`, `self`, or `super` within that crate.
+///
+/// Given `key = "..."` and optionally `priority = n`, where the priority
+/// is an `i32` defaulting to zero, the attribute also records the entry
+/// under the key for a set declared with the `keyed` option.
+///
+/// Given `self_test = path`, where the path names a `fn(&T) -> Result<(),
+/// String>`, the attribute records the function as a check of the entry,
+/// run by `LinkerSet::run_self_tests`.
+///
+/// Given `target_feature = "..."`, naming CPU features separated by
+/// commas, the attribute records that the entry requires them, so that
+/// `LinkerSet::for_cpu` and the `best_for_cpu` function of the set choose
+/// among entries by the features of the running CPU.  Features specific to
+/// an architecture must be named only where the entry is compiled for it,
+/// as under `#[cfg(target_arch = "x86_64")]`.
+///
+/// Given `sub = child`, where the child was declared with
+/// `set_declare_sub!`, the static is placed into that sub-set of the set,
+/// which must have the element type `Sub<T>`.  A sub-set entry cannot
+/// have a key, a self test, or target features.
+///
+/// Other attributes of the item are kept in order.  A `cfg` attribute
+/// following this one also applies to the items generated for the entry,
+/// so an entry can be compiled out.
+///
+/// The symbol name of an entry is always mangled: `#[no_mangle]` and
+/// `#[export_name]` are removed from it, so that entries of the same name
+/// in different crates, such as staticlibs linked together, never collide.
+#[proc_macro_attribute]
+pub fn set_entry(meta: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(meta as EntryArgs);
+    let item = parse_macro_input!(item as Item);
+    let gen = match item {
+        Item::Static(mut decl) => {
+            decl.attrs.retain(|a| !exports(a));
+            entries(&args, &decl, &decl.ident.to_string())
+        }
+        Item::Const(item) => const_entry(&args, &item),
+        item => Error::new_spanned(item, ENTRY_ITEM).to_compile_error(),
+    };
+    TokenStream::from(gen)
+}
+
+/// Attribute macro that puts an item into the test entries of a linker
+/// set, which only `set_test!` yields.
+///
+/// The item may be a static or a const, as for `set_entry`.  Test entries
+/// are placed into a section of their own, apart from the entries of the
+/// set, so that an entry meant only for tests never appears in the set,
+/// even if it is compiled into a release build by mistake.  Such entries
+/// should still be guarded by `#[cfg(test)]`, and `cargo linker-set
+/// release FILE` reports any that a build contains.
+#[proc_macro_attribute]
+pub fn test_entry(meta: TokenStream, item: TokenStream) -> TokenStream {
+    let set = parse_macro_input!(meta as SetPath);
+    let item = parse_macro_input!(item as Item);
+    let section = set.section("_test_");
+    let gen = match item {
+        Item::Static(mut decl) => {
+            decl.attrs.retain(|a| !exports(a));
+            let declared = test_declared(&set, &decl);
+            let placed = placed(&set, &section, &decl);
+            quote! {
+                #declared
+                #placed
+            }
+        }
+        Item::Const(item) => {
+            let decl = const_static(&item);
+            let declared = test_declared(&set, &decl);
+            let placed = placed(&set, &section, &decl);
+            quote! {
+                #item
+                #declared
+                #placed
+            }
         }
+        item => Error::new_spanned(item, ENTRY_ITEM).to_compile_error(),
     };
     TokenStream::from(gen)
 }
+
+fn test_declared(set: &SetPath, decl: &ItemStatic) -> proc_macro2::TokenStream {
+    declared(set, "__TEST_ENTRIES", TEST_ENTRY_UNDECLARED, decl)
+}
+
+struct ImplArgs {
+    set: SetPath,
+    ctor: Option<Path>,
+}
+
+impl Parse for ImplArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let set = input.parse()?;
+        let mut ctor = None;
+        if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let key = input.parse::<Ident>()?;
+            if key != "ctor" {
+                return Err(Error::new_spanned(key, IMPL_ARGUMENT));
+            }
+            input.parse::<Token![=]>()?;
+            ctor = Some(input.parse()?);
+            input.parse::<Option<Token![,]>>()?;
+        }
+        Ok(Self { set, ctor })
+    }
+}
+
+fn symbol_part<T: quote::ToTokens>(t: &T) -> String {
+    let s = quote!(#t).to_string();
+    let mut out = String::new();
+    for c in s.chars() {
+        if c.is_ascii_alphanumeric() {
+            out.push(c.to_ascii_uppercase());
+        } else if !out.ends_with('_') {
+            out.push('_');
+        }
+    }
+    out.trim_matches('_').to_string()
+}
+
+fn register(
+    args: &ImplArgs, imp: &ItemImpl,
+) -> Result<proc_macro2::TokenStream> {
+    if !imp.generics.params.is_empty() {
+        return Err(Error::new_spanned(&imp.generics, IMPL_GENERIC));
+    }
+    let Some((_, trait_path, _)) = &imp.trait_ else {
+        return Err(Error::new_spanned(&imp.self_ty, IMPL_NOT_TRAIT));
+    };
+    let self_ty = &imp.self_ty;
+    let value = match &args.ctor {
+        Some(ctor) => quote!(#ctor()),
+        None => quote!(#self_ty),
+    };
+    let name =
+        format!("{}_FOR_{}", symbol_part(trait_path), symbol_part(self_ty));
+    let ident = format_ident!("__SET_IMPL_{}", name);
+    let value_ident = format_ident!("__SET_IMPL_VALUE_{}", name);
+    let cfgs = cfgs(&imp.attrs);
+    let decl: ItemStatic = parse_quote! {
+        #(#cfgs)*
+        #[doc(hidden)]
+        static #ident: &'static dyn #trait_path =
+            &#value_ident as &'static dyn #trait_path;
+    };
+    let entry = entry(&args.set, &decl, &ident.to_string());
+    Ok(quote! {
+        #imp
+
+        #(#cfgs)*
+        #[doc(hidden)]
+        static #value_ident: #self_ty = #value;
+
+        #entry
+    })
+}
+
+/// Attribute macro that puts a trait object for an impl into a linker set.
+///
+/// The linker set must be declared with an element type of `&'static dyn
+/// Trait`, and the trait must have `Sync` as a supertrait.  The trait
+/// object refers to the implementing type, which must be a unit struct, or
+/// to the value returned by a `const fn` given as `ctor = path`.
+#[proc_macro_attribute]
+pub fn register_impl(meta: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(meta as ImplArgs);
+    let imp = parse_macro_input!(item as ItemImpl);
+    match register(&args, &imp) {
+        Ok(gen) => TokenStream::from(gen),
+        Err(e) => TokenStream::from(e.to_compile_error()),
+    }
+}
+
+struct ConstArgs {
+    set: SetPath,
+    path: Path,
+}
+
+impl Parse for ConstArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let set = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let path = input.parse()?;
+        input.parse::<Option<Token![,]>>()?;
+        Ok(Self { set, path })
+    }
+}
+
+/// Returns tokens with each `Self` replaced by a type, so that the type of
+/// an associated const can be written outside of its impl.
+fn replace_self(
+    tokens: proc_macro2::TokenStream, self_ty: &Type,
+) -> proc_macro2::TokenStream {
+    use proc_macro2::{Group, TokenTree};
+    tokens
+        .into_iter()
+        .flat_map(|t| match t {
+            TokenTree::Ident(i) if i == "Self" => quote!(#self_ty),
+            TokenTree::Group(g) => {
+                let stream = replace_self(g.stream(), self_ty);
+                let mut group = Group::new(g.delimiter(), stream);
+                group.set_span(g.span());
+                quote!(#group)
+            }
+            t => quote!(#t),
+        })
+        .collect()
+}
+
+fn register_assoc(
+    args: &ConstArgs, imp: &ItemImpl,
+) -> Result<proc_macro2::TokenStream> {
+    if !imp.generics.params.is_empty() {
+        return Err(Error::new_spanned(&imp.generics, CONST_GENERIC));
+    }
+    let segments = &args.path.segments;
+    let name = match (segments.len(), segments.first(), segments.last()) {
+        (2, Some(s), Some(name)) if s.ident == "Self" => &name.ident,
+        _ => return Err(Error::new_spanned(&args.path, CONST_NOT_FOUND)),
+    };
+    let item = imp.items.iter().find_map(|i| match i {
+        ImplItem::Const(c) if c.ident == *name => Some(c),
+        _ => None,
+    });
+    let Some(item) = item else {
+        return Err(Error::new_spanned(&args.path, CONST_NOT_FOUND));
+    };
+    let self_ty = &imp.self_ty;
+    let ty = &item.ty;
+    let ty = replace_self(quote!(#ty), self_ty);
+    let ident = format_ident!("__SET_CONST_{}_{}", symbol_part(self_ty), name);
+    let mut cfgs = cfgs(&imp.attrs);
+    cfgs.extend(self::cfgs(&item.attrs));
+    let docs = docs(&item.attrs);
+    let decl: ItemStatic = parse_quote! {
+        #(#cfgs)*
+        #(#docs)*
+        #[doc(hidden)]
+        #[allow(non_upper_case_globals)]
+        static #ident: #ty = <#self_ty>::#name;
+    };
+    let entry = entry(&args.set, &decl, &ident.to_string());
+    Ok(quote! {
+        #imp
+
+        #entry
+    })
+}
+
+/// Attribute macro that puts the value of an associated const into a
+/// linker set.
+///
+/// Applied to an impl as `#[register_const(set, Self::NAME)]`, where the
+/// impl defines the const `NAME`, the attribute places a hidden static
+/// initialized from the const into the set, so that constants kept with
+/// the types they tune need not be copied into free statics.  The
+/// attribute may be repeated to register several consts of an impl.
+#[proc_macro_attribute]
+pub fn register_const(meta: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(meta as ConstArgs);
+    let imp = parse_macro_input!(item as ItemImpl);
+    match register_assoc(&args, &imp) {
+        Ok(gen) => TokenStream::from(gen),
+        Err(e) => TokenStream::from(e.to_compile_error()),
+    }
+}
+
+struct ShutdownArgs {
+    phase: Option<Expr>,
+}
+
+impl Parse for ShutdownArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut phase = None;
+        if !input.is_empty() {
+            let name = input.parse::<Ident>()?;
+            if name != "phase" {
+                return Err(Error::new_spanned(name, SHUTDOWN_ARGUMENT));
+            }
+            input.parse::<Token![=]>()?;
+            phase = Some(input.parse()?);
+            input.parse::<Option<Token![,]>>()?;
+        }
+        Ok(Self { phase })
+    }
+}
+
+/// Attribute macro that registers a function as a shutdown hook.
+///
+/// Applied to a `fn() -> Result<(), String>` as `#[shutdown(phase = n)]`,
+/// where the phase is a `u32` defaulting to zero, the attribute places a
+/// hook calling the function into the set of `linker_set::shutdown`,
+/// whose `run_all` calls the hooks in the reverse order of their phases.
+#[proc_macro_attribute]
+pub fn shutdown(meta: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(meta as ShutdownArgs);
+    let item = match parse::<ItemFn>(item) {
+        Ok(item) => item,
+        Err(e) => {
+            let e = Error::new(e.span(), SHUTDOWN_ITEM);
+            return TokenStream::from(e.to_compile_error());
+        }
+    };
+    let ident = &item.sig.ident;
+    let phase = match &args.phase {
+        Some(phase) => quote!(#phase),
+        None => quote!(0),
+    };
+    let hook_ident = format_ident!("__SET_SHUTDOWN_{}", ident);
+    let cfgs = cfgs(&item.attrs);
+    let docs = docs(&item.attrs);
+    let decl: ItemStatic = parse_quote! {
+        #(#docs)*
+        #[doc(hidden)]
+        #[allow(non_upper_case_globals)]
+        static #hook_ident: ::linker_set::shutdown::Hook =
+            ::linker_set::shutdown::Hook::new(
+                concat!(module_path!(), "::", stringify!(#ident)),
+                #phase,
+                #ident,
+            );
+    };
+    let set = SetPath::new(parse_quote!(::linker_set::shutdown::shutdown_hook))
+        .unwrap();
+    let entry = entry(&set, &decl, &hook_ident.to_string());
+    TokenStream::from(quote! {
+        #item
+
+        #(#cfgs)*
+        const _: () = {
+            #entry
+        };
+    })
+}
+
+/// Attribute macro that registers a function as an invariant of a linker
+/// set.
+///
+/// Applied to a `fn(&LinkerSet<T>) -> Result<(), String>` as
+/// `#[set_invariant(set)]`, where the set has the element type `T` and is
+/// in scope, the attribute places an invariant calling the function on
+/// the set into the set of `linker_set::invariant`, whose `validate_all`
+/// checks every invariant.
+#[proc_macro_attribute]
+pub fn set_invariant(meta: TokenStream, item: TokenStream) -> TokenStream {
+    let set = parse_macro_input!(meta as Ident);
+    let item = match parse::<ItemFn>(item) {
+        Ok(item) => item,
+        Err(e) => {
+            let e = Error::new(e.span(), INVARIANT_ITEM);
+            return TokenStream::from(e.to_compile_error());
+        }
+    };
+    let ident = &item.sig.ident;
+    let invariant_ident = format_ident!("__SET_INVARIANT_{}", ident);
+    let cfgs = cfgs(&item.attrs);
+    let docs = docs(&item.attrs);
+    let decl: ItemStatic = parse_quote! {
+        #(#docs)*
+        #[doc(hidden)]
+        #[allow(non_upper_case_globals)]
+        static #invariant_ident: ::linker_set::invariant::Invariant =
+            ::linker_set::invariant::Invariant::new(
+                concat!(module_path!(), "::", stringify!(#ident)),
+                stringify!(#set),
+                || #ident(&::linker_set::set!(#set)),
+            );
+    };
+    let path =
+        SetPath::new(parse_quote!(::linker_set::invariant::invariant)).unwrap();
+    let entry = entry(&path, &decl, &invariant_ident.to_string());
+    TokenStream::from(quote! {
+        #item
+
+        #(#cfgs)*
+        const _: () = {
+            #entry
+        };
+    })
+}
+
+/// Attribute macro that registers a function as an initializer of a boot
+/// stage.
+///
+/// Applied to a `fn()` as `#[stage_init("name")]`, the attribute places
+/// an initializer calling the function into the set of
+/// `linker_set::stage`, whose `advance` calls the initializers of a stage
+/// when the stage is reached.
+#[proc_macro_attribute]
+pub fn stage_init(meta: TokenStream, item: TokenStream) -> TokenStream {
+    let stage = parse_macro_input!(meta as LitStr);
+    let item = match parse::<ItemFn>(item) {
+        Ok(item) => item,
+        Err(e) => {
+            let e = Error::new(e.span(), STAGE_ITEM);
+            return TokenStream::from(e.to_compile_error());
+        }
+    };
+    let ident = &item.sig.ident;
+    let init_ident = format_ident!("__SET_STAGE_INIT_{}", ident);
+    let cfgs = cfgs(&item.attrs);
+    let docs = docs(&item.attrs);
+    let decl: ItemStatic = parse_quote! {
+        #(#docs)*
+        #[doc(hidden)]
+        #[allow(non_upper_case_globals)]
+        static #init_ident: ::linker_set::stage::Init =
+            ::linker_set::stage::Init::new(
+                concat!(module_path!(), "::", stringify!(#ident)),
+                #stage,
+                #ident,
+            );
+    };
+    let set =
+        SetPath::new(parse_quote!(::linker_set::stage::stage_init)).unwrap();
+    let entry = entry(&set, &decl, &init_ident.to_string());
+    TokenStream::from(quote! {
+        #item
+
+        #(#cfgs)*
+        const _: () = {
+            #entry
+        };
+    })
+}
+
+/// Returns true if an attribute is a `repr` including `C`.
+fn repr_c(attr: &Attribute) -> bool {
+    let Meta::List(list) = &attr.meta else {
+        return false;
+    };
+    list.path.is_ident("repr")
+        && list.tokens.clone().into_iter().any(|t| match t {
+            proc_macro2::TokenTree::Ident(i) => i == "C",
+            _ => false,
+        })
+}
+
+fn set_abi(item: &DeriveInput) -> Result<proc_macro2::TokenStream> {
+    let Data::Struct(data) = &item.data else {
+        return Err(Error::new_spanned(&item.ident, ABI_ITEM));
+    };
+    if !item.generics.params.is_empty() {
+        return Err(Error::new_spanned(&item.generics, ABI_GENERIC));
+    }
+    if !item.attrs.iter().any(repr_c) {
+        return Err(Error::new_spanned(&item.ident, ABI_NOT_REPR_C));
+    }
+    let ident = &item.ident;
+    let members = data.fields.members().collect::<Vec<_>>();
+    let types = data.fields.iter().map(|f| &f.ty).collect::<Vec<_>>();
+    let read = match &data.fields {
+        Fields::Named(_) => quote! {
+            Self {
+                #(#members: ::linker_set::replay::Snapshot::read(input)?),*
+            }
+        },
+        Fields::Unnamed(_) => quote! {
+            Self(#(<#types as ::linker_set::replay::Snapshot>::read(input)?),*)
+        },
+        Fields::Unit => quote!(Self),
+    };
+    Ok(quote! {
+        impl ::linker_set::replay::Snapshot for #ident {
+            fn write(&self, out: &mut ::std::vec::Vec<u8>) {
+                #(::linker_set::replay::Snapshot::write(&self.#members, out);)*
+            }
+
+            fn read(
+                input: &mut &[u8],
+            ) -> ::std::result::Result<Self, ::linker_set::replay::Error> {
+                ::std::result::Result::Ok(#read)
+            }
+        }
+
+        impl ::linker_set::abi::SetAbi for #ident {
+            const FINGERPRINT: u64 = {
+                use ::linker_set::abi::{__hash_str, __hash_usize};
+                let hash = ::linker_set::abi::__BASIS;
+                let hash = __hash_str(hash, stringify!(#ident));
+                let hash = __hash_usize(hash, ::std::mem::size_of::<Self>());
+                let hash = __hash_usize(hash, ::std::mem::align_of::<Self>());
+                #(
+                    let hash = __hash_str(hash, stringify!(#members));
+                    let hash = __hash_str(hash, stringify!(#types));
+                    let hash = __hash_usize(
+                        hash,
+                        ::std::mem::offset_of!(Self, #members),
+                    );
+                )*
+                hash
+            };
+        }
+    })
+}
+
+/// Derive macro that makes a struct an element type that can be shared
+/// with separately built code, such as plugins.
+///
+/// The struct must be `#[repr(C)]` and not generic.  The derive
+/// implements `linker_set::abi::SetAbi`, whose fingerprint hashes the
+/// name, size, and alignment of the struct and the name, type, and offset
+/// of each field, and `linker_set::replay::Snapshot`, encoding the fields
+/// in order, each of which must implement it.
+#[proc_macro_derive(SetAbi)]
+pub fn derive_set_abi(item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as DeriveInput);
+    match set_abi(&item) {
+        Ok(gen) => TokenStream::from(gen),
+        Err(e) => TokenStream::from(e.to_compile_error()),
+    }
+}
+
+/// Compress text with LZ77, as `linker_set::compressed::decompress`
+/// expands it: each run of up to 128 bytes that cannot be found in the
+/// previous 64 KiB of the text is a byte less than 128, one less than the
+/// length of the run, followed by the run, and each run that can is a
+/// byte of 128 plus three less than its length, up to 130, followed by
+/// its distance back as two bytes, least significant first.
+#[cfg(feature = "compress")]
+fn lz77(text: &[u8]) -> Vec<u8> {
+    use std::collections::HashMap;
+
+    const MAX_RUN: usize = 128;
+    const MIN_MATCH: usize = 3;
+    const MAX_MATCH: usize = 130;
+    const MAX_DISTANCE: usize = 65535;
+    const CANDIDATES: usize = 64;
+
+    fn flush(out: &mut Vec<u8>, run: &mut Vec<u8>) {
+        if !run.is_empty() {
+            out.push((run.len() - 1) as u8);
+            out.append(run);
+        }
+    }
+
+    let mut out = Vec::new();
+    let mut run = Vec::new();
+    let mut seen = HashMap::<&[u8], Vec<usize>>::new();
+    let mut i = 0;
+    while i < text.len() {
+        let mut best = (0, 0);
+        if let Some(starts) =
+            text.get(i..i + MIN_MATCH).and_then(|k| seen.get(k))
+        {
+            for &start in starts.iter().rev().take(CANDIDATES) {
+                if i - start > MAX_DISTANCE {
+                    break;
+                }
+                let len = (0..MAX_MATCH.min(text.len() - i))
+                    .take_while(|&k| text[start + k] == text[i + k])
+                    .count();
+                if len > best.0 {
+                    best = (len, i - start);
+                }
+            }
+        }
+        let len = if best.0 >= MIN_MATCH {
+            flush(&mut out, &mut run);
+            out.push((128 + best.0 - MIN_MATCH) as u8);
+            out.extend_from_slice(&(best.1 as u16).to_le_bytes());
+            best.0
+        } else {
+            run.push(text[i]);
+            if run.len() == MAX_RUN {
+                flush(&mut out, &mut run);
+            }
+            1
+        };
+        for k in i..i + len {
+            if let Some(key) = text.get(k..k + MIN_MATCH) {
+                seen.entry(key).or_default().push(k);
+            }
+        }
+        i += len;
+    }
+    flush(&mut out, &mut run);
+    out
+}
+
+/// Compress a string literal at compile time into a
+/// `linker_set::Compressed`, which expands it the first time it is read.
+///
+/// ```ignore
+/// #[set_entry(messages)]
+/// static LICENSE: Compressed = compress!("...");
+/// ```
+#[cfg(feature = "compress")]
+#[proc_macro]
+pub fn compress(item: TokenStream) -> TokenStream {
+    let text = parse_macro_input!(item as LitStr).value();
+    let data = lz77(text.as_bytes());
+    let len = text.len();
+    TokenStream::from(quote! {
+        ::linker_set::Compressed::__new(&[#(#data),*], #len)
+    })
+}
+
+/// Check and record the declaration of a set by `set_declare!`.
+///
+/// The element type must have values, so that a set declared with a type
+/// such as an enum with no variants, or a struct holding one, fails to
+/// compile.  No constant expression can tell whether a type has values,
+/// but a pattern is irrefutable when the other variant of a `Result`
+/// cannot exist, which the `irrefutable_let_patterns` lint reports.  The
+/// compiler does not report lints in code expanded from the macros of
+/// other crates, so the check is given the span of the name of the set.
+#[doc(hidden)]
+#[proc_macro]
+pub fn __set_declared(item: TokenStream) -> TokenStream {
+    struct Args {
+        set: Ident,
+        ty: Type,
+    }
+
+    impl Parse for Args {
+        fn parse(input: ParseStream) -> Result<Self> {
+            let set = input.parse()?;
+            input.parse::<Token![,]>()?;
+            let ty = input.parse()?;
+            Ok(Self { set, ty })
+        }
+    }
+
+    let Args { set, ty } = parse_macro_input!(item as Args);
+    record_set(&set);
+    TokenStream::from(quote_spanned! {set.span()=>
+        #[deny(irrefutable_let_patterns)]
+        #[allow(dead_code)]
+        fn __linker_set_elements_must_not_be_uninhabited(
+            element: ::std::result::Result<#ty, ()>,
+        ) {
+            let ::std::result::Result::Err(()) = element else {
+                return;
+            };
+        }
+    })
+}
+
+struct ReserveArgs {
+    set: SetPath,
+    count: LitInt,
+}
+
+impl Parse for ReserveArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let set = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let count = input.parse()?;
+        input.parse::<Option<Token![,]>>()?;
+        Ok(Self { set, count })
+    }
+}
+
+/// Macro that reserves entries in a linker set, to be patched early in the
+/// program.
+///
+/// `set_reserve!(name, n)` places `n` entries into the set, each holding
+/// the `DEFAULT` of the element type, which must implement
+/// `linker_set::ConstDefault`.  The section of the set thus has room for
+/// `n` more entries than are registered, however many those are, so that
+/// a firmware image whose tables must be of a fixed size can fill them in
+/// at boot.
+#[proc_macro]
+pub fn set_reserve(item: TokenStream) -> TokenStream {
+    let ReserveArgs { set, count } = parse_macro_input!(item as ReserveArgs);
+    let count = match count.base10_parse::<usize>() {
+        Ok(count) => count,
+        Err(e) => return TokenStream::from(e.to_compile_error()),
+    };
+    let path = &set.path;
+    let entries = (0..count).map(|i| {
+        let ident = format_ident!("__SET_RESERVED_{}", i);
+        let decl: ItemStatic = parse_quote! {
+            #[doc(hidden)]
+            static #ident: #path::__Type =
+                <#path::__Type as ::linker_set::ConstDefault>::DEFAULT;
+        };
+        entry(&set, &decl, &format!("RESERVED_{}", i))
+    });
+    TokenStream::from(quote! {
+        const _: () = {
+            #(#entries)*
+        };
+    })
+}
+
+/// Returns the name by which Mach-O knows a section, given its name on ELF
+/// as the arguments of `concat!`.
+#[doc(hidden)]
+#[proc_macro]
+pub fn __mach_o_name(item: TokenStream) -> TokenStream {
+    match section_name.parse(item) {
+        Ok(section) => {
+            let name = mach_o(&section);
+            TokenStream::from(quote!(#name))
+        }
+        Err(e) => TokenStream::from(e.to_compile_error()),
+    }
+}
+
+/// Guard against two sections whose names differ but whose Mach-O names
+/// are the same, given the name of a section on ELF as the arguments of
+/// `concat!`, by failing the link of a program declaring both on ELF.
+#[doc(hidden)]
+#[proc_macro]
+pub fn __mach_o_guard(item: TokenStream) -> TokenStream {
+    match section_name.parse(item) {
+        Ok(section) => {
+            let asm = mach_o_guard(&section);
+            TokenStream::from(quote! {
+                #[cfg(not(target_vendor = "apple"))]
+                ::core::arch::global_asm!(#asm);
+            })
+        }
+        Err(e) => TokenStream::from(e.to_compile_error()),
+    }
+}
+
+/// Returns the symbol that the Mach-O linker defines at the `start` or
+/// `stop` of a section, given the name of the section on ELF as the
+/// arguments of `concat!`, following the bound.
+#[doc(hidden)]
+#[proc_macro]
+pub fn __mach_o_symbol(item: TokenStream) -> TokenStream {
+    let parser = |input: ParseStream| {
+        let bound = input.parse::<Ident>()?;
+        let bound = match bound.to_string().as_str() {
+            "start" => "start",
+            "stop" => "end",
+            _ => {
+                return Err(Error::new_spanned(
+                    bound,
+                    "expected `start` or `stop`",
+                ))
+            }
+        };
+        input.parse::<Token![,]>()?;
+        Ok((bound, section_name(input)?))
+    };
+    match parser.parse(item) {
+        Ok((bound, section)) => {
+            let symbol =
+                format!("\x01section${}$__DATA${}", bound, mach_o(&section));
+            TokenStream::from(quote!(#symbol))
+        }
+        Err(e) => TokenStream::from(e.to_compile_error()),
+    }
+}
+
+/// Returns the arguments of the compiler building the calling crate, as a
+/// slice of strings, so that a test can build code against exactly the
+/// libraries and features of its own build.
+#[doc(hidden)]
+#[proc_macro]
+pub fn __rustc_args(item: TokenStream) -> TokenStream {
+    parse_macro_input!(item as Nothing);
+    /* cargo passes the arguments in a file if they are too long */
+    let args =
+        std::env::args()
+            .skip(1)
+            .flat_map(|arg| match arg.strip_prefix('@') {
+                Some(file) => std::fs::read_to_string(file)
+                    .map(|s| s.lines().map(String::from).collect())
+                    .unwrap_or_default(),
+                None => vec![arg],
+            });
+    TokenStream::from(quote!(&[#(#args),*]))
+}
@@ -0,0 +1,179 @@
+//! Element types shared with separately built code.
+//!
+//! The entries of a set may come from code built apart from the program,
+//! such as plugins loaded at run time, which must agree with it on the
+//! layout of the element type.  Deriving [SetAbi](macro@crate::SetAbi)
+//! for a `#[repr(C)]` struct gives it a fingerprint of that layout, which
+//! a loader compares with [verify] against the one the plugin reports for
+//! its entries, before merging them, and an encoding by [Snapshot],
+//! through which `LinkerSet::export` and the inspection tooling read its
+//! elements:
+//!
+//! ```
+//! use linker_set::*;
+//! use linker_set::abi;
+//!
+//! #[derive(SetAbi)]
+//! #[repr(C)]
+//! pub struct Codec {
+//!     id: u32,
+//!     name: &'static str,
+//! }
+//!
+//! set_declare!(codecs, Codec);
+//!
+//! #[set_entry(codecs)]
+//! static GZIP: Codec = Codec { id: 1, name: "gzip" };
+//!
+//! # fn main() {
+//! // the fingerprint that a plugin built with this definition reports
+//! let reported = <Codec as abi::SetAbi>::FINGERPRINT;
+//! abi::verify::<Codec>(reported).unwrap();
+//! assert!(abi::verify::<Codec>(reported ^ 1).is_err());
+//! assert!(!set!(codecs).export().is_empty());
+//! # }
+//! ```
+//!
+//! The fingerprint covers the name, size, and alignment of the struct and
+//! the name, type as written, and offset of each field.  It does not look
+//! into the types of the fields, so a change to a struct nested within
+//! another changes the fingerprint of the outer one only if it changes its
+//! size or the offsets of its fields.
+
+use crate::replay::Snapshot;
+use std::fmt;
+
+/// An element type whose layout is fixed and fingerprinted, derived with
+/// [SetAbi](macro@crate::SetAbi).
+pub trait SetAbi: Snapshot {
+    /// A hash of the layout of the type.
+    const FINGERPRINT: u64;
+}
+
+/// The fingerprint of an element type differs from that expected.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Mismatch {
+    /// The name of the element type.
+    pub type_name: &'static str,
+    /// The fingerprint of the element type in this program.
+    pub expected: u64,
+    /// The fingerprint given, such as by a plugin.
+    pub found: u64,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "layout of {} has fingerprint {:016x}, but {:016x} was given",
+            self.type_name, self.expected, self.found
+        )
+    }
+}
+
+impl std::error::Error for Mismatch {}
+
+/// Check that a fingerprint, such as one reported by a plugin for the
+/// element type of its entries, matches that of `T` in this program.
+pub fn verify<T>(fingerprint: u64) -> Result<(), Mismatch>
+where
+    T: SetAbi,
+{
+    if fingerprint == T::FINGERPRINT {
+        Ok(())
+    } else {
+        Err(Mismatch {
+            type_name: std::any::type_name::<T>(),
+            expected: T::FINGERPRINT,
+            found: fingerprint,
+        })
+    }
+}
+
+#[doc(hidden)]
+pub const __BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Returns an FNV-1a hash continued over some bytes.
+const fn hash(mut hash: u64, bytes: &[u8]) -> u64 {
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u64;
+        hash = hash.wrapping_mul(PRIME);
+        i += 1;
+    }
+    hash
+}
+
+#[doc(hidden)]
+pub const fn __hash_str(h: u64, s: &str) -> u64 {
+    /* terminate the string, so that adjacent strings hash apart */
+    hash(hash(h, s.as_bytes()), &[0])
+}
+
+#[doc(hidden)]
+pub const fn __hash_usize(h: u64, n: usize) -> u64 {
+    hash(h, &(n as u64).to_le_bytes())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::replay::ReplaySet;
+    use crate::*;
+
+    #[derive(Debug, PartialEq, SetAbi)]
+    #[repr(C)]
+    pub struct Wide {
+        id: u64,
+        tag: u8,
+    }
+
+    mod narrow {
+        use super::*;
+
+        /* the same name and fields as the other, laid out differently */
+        #[derive(SetAbi)]
+        #[repr(C)]
+        pub struct Wide {
+            id: u32,
+            tag: u8,
+        }
+    }
+
+    #[derive(Debug, PartialEq, SetAbi)]
+    #[repr(C, align(8))]
+    pub struct Pair(u16, Option<u32>);
+
+    set_declare!(pairs, Pair);
+
+    #[set_entry(pairs)]
+    static PAIR: Pair = Pair(7, Some(9));
+
+    #[test]
+    fn test_fingerprint() {
+        assert_ne!(Wide::FINGERPRINT, narrow::Wide::FINGERPRINT);
+        assert_ne!(Wide::FINGERPRINT, Pair::FINGERPRINT);
+        assert_eq!(__hash_str(__BASIS, ""), hash(__BASIS, &[0]));
+        assert_ne!(
+            __hash_str(__hash_str(__BASIS, "ab"), "c"),
+            __hash_str(__hash_str(__BASIS, "a"), "bc")
+        );
+        verify::<Pair>(Pair::FINGERPRINT).unwrap();
+        let e = verify::<Wide>(narrow::Wide::FINGERPRINT).unwrap_err();
+        assert_eq!(e.expected, Wide::FINGERPRINT);
+        assert!(e.to_string().starts_with("layout of linker_set::abi::test"));
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let replay = ReplaySet::<Pair>::import(&set!(pairs).export()).unwrap();
+        assert_eq!(replay.iter().collect::<Vec<_>>(), [&PAIR]);
+        let wide = Wide { id: 1, tag: 2 };
+        let mut bytes = Vec::new();
+        wide.write(&mut bytes);
+        assert_eq!(bytes.len(), 9);
+        assert_eq!(Wide::read(&mut &bytes[..]), Ok(wide));
+    }
+}
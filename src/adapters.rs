@@ -0,0 +1,135 @@
+//! Iterator adapters that keep the `'static` lifetime of set elements.
+
+use crate::{LinkerSet, LinkerSetIter};
+use std::iter::{Filter, FilterMap, Map};
+
+/// Adapters for iterators over `'static` references, such as those over
+/// linker sets, that keep the references `'static`.
+///
+/// The closures passed to these adapters are declared to take and return
+/// `'static` references, so a projection into an element, like
+/// `|h| &h.name`, yields a reference that can be stored anywhere without
+/// cloning, where the compiler might otherwise infer a shorter lifetime
+/// for the closure.
+pub trait StaticIterator<T>: Iterator<Item = &'static T> + Sized
+where
+    T: 'static,
+{
+    /// Map each element to a reference into it, or to any other `'static`
+    /// reference.
+    fn map_static<U, F>(self, f: F) -> Map<Self, F>
+    where
+        U: ?Sized + 'static,
+        F: FnMut(&'static T) -> &'static U,
+    {
+        self.map(f)
+    }
+
+    /// Keep the elements for which a predicate holds.
+    fn filter_static<P>(self, p: P) -> Filter<Self, P>
+    where
+        P: FnMut(&&'static T) -> bool,
+    {
+        self.filter(p)
+    }
+
+    /// Map each element to an optional `'static` reference, keeping those
+    /// that are present.
+    fn filter_map_static<U, F>(self, f: F) -> FilterMap<Self, F>
+    where
+        U: ?Sized + 'static,
+        F: FnMut(&'static T) -> Option<&'static U>,
+    {
+        self.filter_map(f)
+    }
+}
+
+impl<T, I> StaticIterator<T> for I
+where
+    T: 'static,
+    I: Iterator<Item = &'static T>,
+{
+}
+
+impl<T> LinkerSet<T>
+where
+    T: 'static,
+{
+    /// Returns an iterator mapping each item in the linker set to a
+    /// `'static` reference, as by [StaticIterator::map_static].
+    pub fn map_static<U, F>(&self, f: F) -> Map<LinkerSetIter<T>, F>
+    where
+        U: ?Sized + 'static,
+        F: FnMut(&'static T) -> &'static U,
+    {
+        self.iter().map_static(f)
+    }
+
+    /// Returns an iterator over the items in the linker set for which a
+    /// predicate holds, as by [StaticIterator::filter_static].
+    pub fn filter_static<P>(&self, p: P) -> Filter<LinkerSetIter<T>, P>
+    where
+        P: FnMut(&&'static T) -> bool,
+    {
+        self.iter().filter_static(p)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+    use std::sync::OnceLock;
+
+    pub struct Route {
+        path: &'static str,
+        methods: &'static [&'static str],
+        hidden: bool,
+        weight: u32,
+    }
+
+    set_declare!(static_routes, Route);
+
+    #[set_entry(static_routes)]
+    static INDEX: Route = Route {
+        path: "/",
+        methods: &["GET", "HEAD"],
+        hidden: false,
+        weight: 1,
+    };
+    #[set_entry(static_routes)]
+    static ADMIN: Route = Route {
+        path: "/admin",
+        methods: &["POST"],
+        hidden: true,
+        weight: 2,
+    };
+
+    static VISIBLE: OnceLock<Vec<&'static u32>> = OnceLock::new();
+    static PATHS: OnceLock<Vec<&'static str>> = OnceLock::new();
+    static POSTS: OnceLock<Vec<&'static [&'static str]>> = OnceLock::new();
+
+    #[test]
+    fn test_static_adapters() {
+        let visible = set!(static_routes)
+            .iter()
+            .filter_static(|r| !r.hidden)
+            .map_static(|r| &r.weight)
+            .collect();
+        assert_eq!(*VISIBLE.get_or_init(|| visible), [&1]);
+
+        let mut paths = set!(static_routes)
+            .map_static(|r| r.path)
+            .collect::<Vec<_>>();
+        paths.sort();
+        assert_eq!(*PATHS.get_or_init(|| paths), ["/", "/admin"]);
+        assert_eq!(set!(static_routes).filter_static(|r| r.hidden).count(), 1);
+
+        let posts = set!(static_routes)
+            .iter()
+            .filter_map_static(|r| {
+                r.methods.contains(&"POST").then_some(r.methods)
+            })
+            .collect();
+        assert_eq!(*POSTS.get_or_init(|| posts), [&["POST"]]);
+    }
+}
@@ -0,0 +1,323 @@
+//! Cargo subcommand for inspecting the linker sets in compiled artifacts.
+
+use linker_set::deny::DenyList;
+use linker_set::inspect::generate;
+use linker_set::inspect::query::{self, Filter, Layout};
+use linker_set::inspect::{self, Elf, EntryChange};
+use linker_set::manifest;
+use std::collections::BTreeMap;
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+usage: cargo linker-set sizes [--budget SET=BYTES]... FILE
+       cargo linker-set diff OLD NEW
+       cargo linker-set schema FILE
+       cargo linker-set strings FILE
+       cargo linker-set query FILE --set SET [--layout FILE] [--where FILTER]
+                              [--format text|json]
+       cargo linker-set generate FILE --set SET [--layout FILE]
+                                 [--dispatch FIELD --type TYPE]
+       cargo linker-set release FILE
+       cargo linker-set deny FILE --list LIST
+       cargo linker-set layout FILE
+       cargo linker-set manifest DIR
+       cargo linker-set lint DIR";
+
+fn read(file: &str) -> Result<Vec<u8>, String> {
+    std::fs::read(file).map_err(|e| format!("{}: {}", file, e))
+}
+
+fn parse<'a>(file: &str, data: &'a [u8]) -> Result<Elf<'a>, String> {
+    Elf::parse(data).map_err(|e| format!("{}: {}", file, e))
+}
+
+fn sizes(args: &[String]) -> Result<ExitCode, String> {
+    let mut budgets = BTreeMap::new();
+    let mut file = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--budget" {
+            let budget = args.next().ok_or(USAGE)?;
+            let bytes = budget
+                .split_once('=')
+                .and_then(|(set, bytes)| Some((set, bytes.parse().ok()?)));
+            let Some((set, bytes)) = bytes else {
+                return Err(format!("bad budget: {}", budget));
+            };
+            budgets.insert(set.to_string(), bytes);
+        } else if file.is_none() {
+            file = Some(arg);
+        } else {
+            return Err(USAGE.to_string());
+        }
+    }
+    let file = file.ok_or(USAGE)?;
+    let data = read(file)?;
+    let elf = parse(file, &data)?;
+
+    let mut over = false;
+    for set in inspect::sets(&elf) {
+        let mark = match budgets.remove(&set.name) {
+            Some(b) if set.size > b => {
+                over = true;
+                format!("  OVER BUDGET ({} bytes)", b)
+            }
+            _ => String::new(),
+        };
+        println!("{:<32} {:>10}{}", set.name, set.size, mark);
+        let crates = inspect::crate_sizes(&elf, &set)
+            .map_err(|e| format!("{}: {}", file, e))?;
+        for (name, size) in crates {
+            println!("  {:<30} {:>10}", name, size);
+        }
+    }
+    for set in budgets.keys() {
+        eprintln!("cargo-linker-set: no such set: {}", set);
+    }
+    Ok(if over {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+fn size(size: Option<u64>) -> String {
+    size.map_or("-".to_string(), |s| s.to_string())
+}
+
+fn diff(args: &[String]) -> Result<ExitCode, String> {
+    let [old, new] = args else {
+        return Err(USAGE.to_string());
+    };
+    let (old_data, new_data) = (read(old)?, read(new)?);
+    let old_elf = parse(old, &old_data)?;
+    let new_elf = parse(new, &new_data)?;
+    let diffs = inspect::diff(&old_elf, &new_elf).map_err(|e| e.to_string())?;
+    for d in &diffs {
+        let (old, new) = (size(d.old_size), size(d.new_size));
+        println!("{}: {} -> {} bytes", d.name, old, new);
+        for change in &d.changes {
+            match change {
+                EntryChange::Added(name, size) => {
+                    println!("  + {} ({})", name, size)
+                }
+                EntryChange::Removed(name, size) => {
+                    println!("  - {} ({})", name, size)
+                }
+                EntryChange::Resized(name, old, new) => {
+                    println!("  ~ {} ({} -> {})", name, old, new)
+                }
+            }
+        }
+    }
+    Ok(if diffs.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+type Lines = for<'a> fn(&Elf<'a>) -> Result<Vec<&'a str>, inspect::Error>;
+
+fn lines(args: &[String], f: Lines) -> Result<ExitCode, String> {
+    let [file] = args else {
+        return Err(USAGE.to_string());
+    };
+    let data = read(file)?;
+    let elf = parse(file, &data)?;
+    let lines = f(&elf).map_err(|e| format!("{}: {}", file, e))?;
+    for line in lines {
+        println!("{}", line);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn read_layout(path: &str) -> Result<Layout, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("{}: {}", path, e))?;
+    Layout::parse(&text).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn query(args: &[String]) -> Result<ExitCode, String> {
+    let (mut file, mut set, mut layout) = (None, None, Layout::default());
+    let (mut filter, mut json) = (Filter::All, false);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--set" => set = Some(args.next().ok_or(USAGE)?),
+            "--layout" => layout = read_layout(args.next().ok_or(USAGE)?)?,
+            "--where" => {
+                let expr = args.next().ok_or(USAGE)?;
+                filter = Filter::parse(expr).map_err(|e| e.to_string())?;
+            }
+            "--format" => match args.next().map(String::as_str) {
+                Some("text") => json = false,
+                Some("json") => json = true,
+                _ => return Err(USAGE.to_string()),
+            },
+            _ if file.is_none() => file = Some(arg),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    let (file, set) = (file.ok_or(USAGE)?, set.ok_or(USAGE)?);
+    let data = read(file)?;
+    let elf = parse(file, &data)?;
+    let records = query::records(&elf, set, &layout)
+        .map_err(|e| format!("{}: {}", file, e))?;
+    for record in records.iter().filter(|r| filter.matches(r)) {
+        if json {
+            println!("{}", record.to_json());
+        } else {
+            println!("{}", record);
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn generate(args: &[String]) -> Result<ExitCode, String> {
+    let (mut file, mut set, mut layout) = (None, None, Layout::default());
+    let (mut field, mut element) = (None, None);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--set" => set = Some(args.next().ok_or(USAGE)?),
+            "--layout" => layout = read_layout(args.next().ok_or(USAGE)?)?,
+            "--dispatch" => field = Some(args.next().ok_or(USAGE)?),
+            "--type" => element = Some(args.next().ok_or(USAGE)?),
+            _ if file.is_none() => file = Some(arg),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    let (file, set) = (file.ok_or(USAGE)?, set.ok_or(USAGE)?);
+    let data = read(file)?;
+    let elf = parse(file, &data)?;
+    let source = match (field, element) {
+        (None, None) => generate::rust(&elf, set, &layout),
+        (Some(field), Some(element)) => {
+            generate::dispatch(&elf, set, &layout, field, element)
+        }
+        _ => return Err(USAGE.to_string()),
+    }
+    .map_err(|e| format!("{}: {}", file, e))?;
+    print!("{}", source);
+    Ok(ExitCode::SUCCESS)
+}
+
+fn release(args: &[String]) -> Result<ExitCode, String> {
+    let [file] = args else {
+        return Err(USAGE.to_string());
+    };
+    let data = read(file)?;
+    let elf = parse(file, &data)?;
+    let sets = inspect::sets_with_test_entries(&elf);
+    for set in &sets {
+        println!("{}: {} bytes of test entries", set.name, set.size);
+        let entries = inspect::entries(&elf, set)
+            .map_err(|e| format!("{}: {}", file, e))?;
+        for entry in entries {
+            println!("  {}", inspect::demangle(&entry.name));
+        }
+    }
+    Ok(if sets.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+fn deny(args: &[String]) -> Result<ExitCode, String> {
+    let [file, flag, list] = args else {
+        return Err(USAGE.to_string());
+    };
+    if flag != "--list" {
+        return Err(USAGE.to_string());
+    }
+    let text = std::fs::read_to_string(list)
+        .map_err(|e| format!("{}: {}", list, e))?;
+    let list =
+        DenyList::parse(&text).map_err(|e| format!("{}: {}", list, e))?;
+    let data = read(file)?;
+    let elf = parse(file, &data)?;
+    let denied =
+        inspect::denied(&elf, &list).map_err(|e| format!("{}: {}", file, e))?;
+    for d in &denied {
+        println!("{}: {} from denied crate {}", d.set, d.entry, d.krate);
+    }
+    Ok(if denied.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+fn layout(args: &[String]) -> Result<ExitCode, String> {
+    let [file] = args else {
+        return Err(USAGE.to_string());
+    };
+    let data = read(file)?;
+    let elf = parse(file, &data)?;
+    for set in inspect::sets(&elf) {
+        let report = inspect::layout(&elf, &set)
+            .map_err(|e| format!("{}: {}", file, e))?;
+        let Some(report) = report else {
+            continue;
+        };
+        println!("{:<32} {}", set.name, report);
+        for suggestion in report.suggestions() {
+            println!("  {}", suggestion);
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn manifest(args: &[String]) -> Result<ExitCode, String> {
+    let [dir] = args else {
+        return Err(USAGE.to_string());
+    };
+    let all = manifest::read(dir).map_err(|e| format!("{}: {}", dir, e))?;
+    print!("{}", manifest::to_json(&all));
+    Ok(ExitCode::SUCCESS)
+}
+
+fn lint(args: &[String]) -> Result<ExitCode, String> {
+    let [dir] = args else {
+        return Err(USAGE.to_string());
+    };
+    let error = |e: std::io::Error| format!("{}: {}", dir, e);
+    let declarations = manifest::declarations(dir).map_err(error)?;
+    let registrations = manifest::read(dir).map_err(error)?;
+    let findings = manifest::lint(&declarations, &registrations);
+    for f in &findings {
+        println!("{}", f);
+    }
+    Ok(if findings.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.first().map(String::as_str) == Some("linker-set") {
+        args.remove(0); // invoked as a cargo subcommand
+    }
+    let result = match args.split_first() {
+        Some((cmd, rest)) if cmd == "sizes" => sizes(rest),
+        Some((cmd, rest)) if cmd == "diff" => diff(rest),
+        Some((cmd, rest)) if cmd == "schema" => lines(rest, inspect::schema),
+        Some((cmd, rest)) if cmd == "strings" => lines(rest, inspect::interned),
+        Some((cmd, rest)) if cmd == "query" => query(rest),
+        Some((cmd, rest)) if cmd == "generate" => generate(rest),
+        Some((cmd, rest)) if cmd == "release" => release(rest),
+        Some((cmd, rest)) if cmd == "deny" => deny(rest),
+        Some((cmd, rest)) if cmd == "layout" => layout(rest),
+        Some((cmd, rest)) if cmd == "manifest" => manifest(rest),
+        Some((cmd, rest)) if cmd == "lint" => lint(rest),
+        _ => Err(USAGE.to_string()),
+    };
+    result.unwrap_or_else(|msg| {
+        eprintln!("cargo-linker-set: {}", msg);
+        ExitCode::from(2)
+    })
+}
@@ -0,0 +1,117 @@
+//! Records of the versions of the crates built into a program.
+//!
+//! A program assembled from many crates, such as one whose plugins are
+//! each a crate of their own, is hard to diagnose without knowing exactly
+//! which version of each it was built with.  Each crate calls
+//! [register_build_info!](crate::register_build_info) once, which places
+//! a [BuildInfo] with its name, its version, and the hash of the commit it
+//! was built from into the [build_stamp] linker set, and [build_info()]
+//! returns the records of every crate, such as to print for `--version`.
+//!
+//! ```
+//! use linker_set::*;
+//!
+//! register_build_info!(git_hash = "0123abc");
+//!
+//! # fn main() {
+//! for info in build_info() {
+//!     println!("{}", info);
+//! }
+//! let ours = build_info().into_iter().find(|i| i.git_hash.is_some());
+//! assert_eq!(ours.unwrap().version, env!("CARGO_PKG_VERSION"));
+//! # }
+//! ```
+//!
+//! Without `git_hash`, the hash is read when the crate is compiled from
+//! the environment variable `GIT_HASH`, which a build script can set with
+//! `cargo:rustc-env=GIT_HASH=...`, and is `None` if that is not set.
+
+use crate::*;
+use std::fmt;
+
+set_declare!(build_stamp, BuildInfo, namespaced);
+
+/// The record of a crate built into the program.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct BuildInfo {
+    /// The name of the crate.
+    pub name: &'static str,
+    /// The version of the crate.
+    pub version: &'static str,
+    /// The hash of the commit the crate was built from, if known.
+    pub git_hash: Option<&'static str>,
+}
+
+impl fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.name, self.version)?;
+        match self.git_hash {
+            Some(hash) => write!(f, " ({})", hash),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Returns the records of the crates built into the program, in order of
+/// name and version.
+pub fn build_info() -> Vec<&'static BuildInfo> {
+    let mut all = set!(build_stamp).iter().collect::<Vec<_>>();
+    all.sort();
+    all
+}
+
+/// Register the name, version, and commit of the calling crate in the
+/// [build_stamp](crate::build_info::build_stamp) linker set.
+///
+/// `register_build_info!()` takes the hash of the commit from the
+/// environment variable `GIT_HASH` at compile time, and
+/// `register_build_info!(git_hash = expr)` from a `&'static str`, such as
+/// `env!("MY_HASH")`.
+#[macro_export]
+macro_rules! register_build_info {
+    () => {
+        $crate::register_build_info!(@hash option_env!("GIT_HASH"));
+    };
+    (git_hash = $hash:expr) => {
+        $crate::register_build_info!(@hash Some($hash));
+    };
+    (@hash $hash:expr) => {
+        const _: () = {
+            use $crate::build_info::BuildInfo;
+
+            #[$crate::set_entry(::linker_set::build_info::build_stamp)]
+            static BUILD_INFO: BuildInfo = BuildInfo {
+                name: env!("CARGO_PKG_NAME"),
+                version: env!("CARGO_PKG_VERSION"),
+                git_hash: $hash,
+            };
+        };
+    };
+}
+
+register_build_info!();
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_build_info() {
+        let all = build_info();
+        let ours = all.iter().find(|i| i.name == "linker-set").unwrap();
+        assert_eq!(ours.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(ours.git_hash, option_env!("GIT_HASH"));
+
+        let info = BuildInfo {
+            name: "plugin-audio",
+            version: "2.1.0",
+            git_hash: Some("9f86d08"),
+        };
+        assert_eq!(info.to_string(), "plugin-audio 2.1.0 (9f86d08)");
+        let info = BuildInfo {
+            git_hash: None,
+            ..info
+        };
+        assert_eq!(info.to_string(), "plugin-audio 2.1.0");
+    }
+}
@@ -0,0 +1,204 @@
+//! C headers for linker sets shared with C code.
+//!
+//! In a program mixing Rust and C, translation units in C can contribute
+//! entries to a set declared with [set_declare!](crate::set_declare) and
+//! iterate over it, given the name of its section and a C definition of
+//! its element type.  A build script can write a header providing both
+//! with [Header]:
+//!
+//! ```no_run
+//! // build.rs
+//! use linker_set::c_header::Header;
+//!
+//! let out = std::env::var("OUT_DIR").unwrap();
+//! Header::new("APP_SETS_H")
+//!     .include("handler.h")
+//!     .set("handlers", "struct handler")
+//!     .write(format!("{}/sets.h", out))
+//!     .unwrap();
+//! ```
+//!
+//! The element type, here defined in `handler.h`, must have the same
+//! layout as the Rust type, which should be `#[repr(C)]`.  C code then
+//! uses the macros of the header, modeled on those of FreeBSD:
+//!
+//! ```c
+//! #include "sets.h"
+//!
+//! LINKER_SET_ENTRY(handlers, struct handler, my_handler) = { ... };
+//!
+//! void run(void) {
+//!     struct handler *h;
+//!     LINKER_SET_FOREACH(h, handlers)
+//!         h->run();
+//! }
+//! ```
+//!
+//! Each entry is given the alignment of its type explicitly, since a C
+//! compiler may otherwise align large variables more strictly, leaving
+//! gaps between the elements of the set.
+//!
+//! C code that only walks a set built by Rust can instead call a function
+//! defined with [set_export_c!](crate::set_export_c), declared in the
+//! header with [Header::accessor], without relying on the symbols of the
+//! linker.
+
+use std::fmt;
+
+/// A C header declaring linker sets.
+pub struct Header {
+    guard: String,
+    includes: Vec<String>,
+    sets: Vec<(String, String)>,
+    accessors: Vec<(String, String)>,
+}
+
+impl Header {
+    /// Create a header with the given include guard.
+    pub fn new(guard: &str) -> Self {
+        Self {
+            guard: guard.to_string(),
+            includes: Vec::new(),
+            sets: Vec::new(),
+            accessors: Vec::new(),
+        }
+    }
+
+    /// Include a header, such as one defining the element types of sets.
+    pub fn include(mut self, header: &str) -> Self {
+        self.includes.push(header.to_string());
+        self
+    }
+
+    /// Declare a set with a C element type, such as `struct handler`.
+    pub fn set(mut self, name: &str, c_type: &str) -> Self {
+        self.sets.push((name.to_string(), c_type.to_string()));
+        self
+    }
+
+    /// Declare a function defined by [set_export_c!](crate::set_export_c)
+    /// returning the elements of a set of a C element type.
+    pub fn accessor(mut self, function: &str, c_type: &str) -> Self {
+        self.accessors
+            .push((function.to_string(), c_type.to_string()));
+        self
+    }
+
+    /// Write the header to a file, leaving the file untouched if it is
+    /// already up to date, so that C code is not rebuilt needlessly.
+    pub fn write(
+        &self, path: impl AsRef<std::path::Path>,
+    ) -> std::io::Result<()> {
+        let text = self.to_string();
+        if std::fs::read_to_string(&path).is_ok_and(|old| old == text) {
+            return Ok(());
+        }
+        std::fs::write(path, text)
+    }
+}
+
+const MACROS: &str = r#"#define LINKER_SET_SECTION(set) "set_" #set
+
+#define LINKER_SET_ENTRY(set, type, name) \
+    static type name \
+    __attribute__((section(LINKER_SET_SECTION(set)), used, \
+                   aligned(__alignof__(type))))
+
+#define LINKER_SET_BEGIN(set) (__start_set_##set)
+#define LINKER_SET_LIMIT(set) (__stop_set_##set)
+#define LINKER_SET_COUNT(set) \
+    ((size_t)(LINKER_SET_LIMIT(set) - LINKER_SET_BEGIN(set)))
+#define LINKER_SET_FOREACH(var, set) \
+    for ((var) = LINKER_SET_BEGIN(set); (var) < LINKER_SET_LIMIT(set); \
+         (var)++)
+"#;
+
+impl fmt::Display for Header {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "/* Generated by linker-set.  Do not edit. */")?;
+        writeln!(f)?;
+        writeln!(f, "#ifndef {}", self.guard)?;
+        writeln!(f, "#define {}", self.guard)?;
+        writeln!(f)?;
+        writeln!(f, "#include <stddef.h>")?;
+        for include in &self.includes {
+            writeln!(f, "#include \"{}\"", include)?;
+        }
+        writeln!(f)?;
+        write!(f, "{}", MACROS)?;
+        for (name, c_type) in &self.sets {
+            writeln!(f)?;
+            writeln!(f, "extern {} __start_set_{}[];", c_type, name)?;
+            writeln!(f, "extern {} __stop_set_{}[];", c_type, name)?;
+        }
+        if !self.accessors.is_empty() {
+            writeln!(f)?;
+        }
+        for (function, c_type) in &self.accessors {
+            writeln!(f, "const {} *{}(size_t *len);", c_type, function)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "#endif /* {} */", self.guard)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::inspect::Elf;
+    use std::process::Command;
+
+    #[test]
+    fn test_header() {
+        let dir = std::env::temp_dir()
+            .join(format!("linker-set-c-header-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("handler.h"),
+            "struct handler { int id; char name[32]; };\n",
+        )
+        .unwrap();
+        Header::new("SETS_H")
+            .include("handler.h")
+            .set("handlers", "struct handler")
+            .accessor("app_handlers", "struct handler")
+            .write(dir.join("sets.h"))
+            .unwrap();
+        std::fs::write(
+            dir.join("entries.c"),
+            r#"#include "sets.h"
+LINKER_SET_ENTRY(handlers, struct handler, a) = { 1, "a" };
+LINKER_SET_ENTRY(handlers, struct handler, b) = { 2, "b" };
+int total(void) {
+    struct handler *h;
+    int n = 0;
+    LINKER_SET_FOREACH(h, handlers)
+        n += h->id;
+    return n + (int)LINKER_SET_COUNT(handlers);
+}
+int first(void) {
+    size_t len;
+    const struct handler *h = app_handlers(&len);
+    return len ? h->id : -1;
+}
+"#,
+        )
+        .unwrap();
+
+        let object = dir.join("entries.o");
+        let status = Command::new("cc")
+            .args(["-O2", "-Wall", "-Werror", "-c", "-o"])
+            .arg(&object)
+            .arg(dir.join("entries.c"))
+            .status();
+        let Ok(status) = status else {
+            return; // no C compiler
+        };
+        assert!(status.success());
+        let data = std::fs::read(&object).unwrap();
+        let elf = Elf::parse(&data).unwrap();
+        let section = elf.section("set_handlers").unwrap();
+        assert_eq!(section.size, 72); // no padding between entries
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
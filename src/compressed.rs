@@ -0,0 +1,197 @@
+//! Text stored compressed in a linker set and expanded when first read.
+//!
+//! A large table of text, such as the messages of a program translated
+//! into many languages, can make up much of the size of a binary.  With
+//! the `compress` feature enabled, the [compress](crate::compress) macro
+//! compresses a string literal at compile time into a [Compressed], which
+//! holds only the compressed bytes in the binary, and expands them into
+//! memory leaked for the rest of the program the first time it is read.
+//! A [Compressed] dereferences to `str`, so that a set of them, or of
+//! entries holding them, is iterated and read as any other.
+//!
+//! ```
+//! use linker_set::*;
+//!
+//! pub struct Message {
+//!     id: u32,
+//!     text: Compressed,
+//! }
+//!
+//! set_declare!(messages, Message);
+//!
+//! #[set_entry(messages)]
+//! static GREETING: Message = Message {
+//!     id: 1,
+//!     text: compress!("Hello, hello, hello, is there anybody out there?"),
+//! };
+//!
+//! # fn main() {
+//! let greeting = set!(messages).iter().find(|m| m.id == 1).unwrap();
+//! assert!(greeting.text.starts_with("Hello, hello"));
+//! assert!(greeting.text.compressed_len() < greeting.text.len());
+//! # }
+//! ```
+//!
+//! Text too short or too varied to compress grows by less than one byte
+//! in a hundred.
+
+use std::fmt;
+use std::ops::Deref;
+use std::sync::OnceLock;
+
+/// Text stored compressed, made by the [compress](crate::compress) macro.
+pub struct Compressed {
+    data: &'static [u8],
+    len: usize,
+    text: OnceLock<&'static str>,
+}
+
+impl Compressed {
+    /// Create text from its compressed bytes and its length once expanded.
+    ///
+    /// Users should call the [compress](crate::compress) macro instead of
+    /// this function.
+    #[doc(hidden)]
+    pub const fn __new(data: &'static [u8], len: usize) -> Self {
+        Self {
+            data,
+            len,
+            text: OnceLock::new(),
+        }
+    }
+
+    /// Returns the text, expanding it if this is the first time it is read.
+    pub fn as_str(&self) -> &'static str {
+        self.text.get_or_init(|| {
+            let text = String::from_utf8(decompress(self.data, self.len))
+                .expect("compressed text is not UTF-8");
+            Box::leak(text.into_boxed_str())
+        })
+    }
+
+    /// Returns the length of the text in bytes, without expanding it.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the text is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of bytes of the text as compressed.
+    pub fn compressed_len(&self) -> usize {
+        self.data.len()
+    }
+}
+
+impl Deref for Compressed {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for Compressed {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Display for Compressed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for Compressed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+/// Expand bytes compressed by the [compress](crate::compress) macro into
+/// `len` bytes.
+///
+/// The bytes are a sequence of runs.  A byte less than 128 is followed by
+/// that many bytes, plus one, to be copied.  A byte of 128 or more is
+/// followed by a distance of two bytes, least significant first, and
+/// repeats that byte, less 125, bytes of the text expanded so far,
+/// starting that distance back from its end.
+///
+/// # Panics
+/// Panics if the bytes were not compressed to `len` bytes this way.
+pub fn decompress(data: &[u8], len: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(len);
+    let mut i = 0;
+    while i < data.len() {
+        let op = data[i] as usize;
+        i += 1;
+        if op < 128 {
+            out.extend_from_slice(&data[i..i + op + 1]);
+            i += op + 1;
+        } else {
+            let distance = u16::from_le_bytes([data[i], data[i + 1]]);
+            i += 2;
+            let start = out.len() - distance as usize;
+            for k in start..start + op - 125 {
+                out.push(out[k]);
+            }
+        }
+    }
+    assert_eq!(out.len(), len, "compressed text has the wrong length");
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::*;
+
+    set_declare!(catalog, Compressed);
+
+    #[set_entry(catalog)]
+    static EMPTY: Compressed = compress!("");
+    #[set_entry(catalog)]
+    static FARBE: Compressed = compress!("Farbe, Färbung, färben, Farben");
+    #[set_entry(catalog)]
+    static LICENSE: Compressed = compress!(
+        "Redistribution and use in source and binary forms, with or without \
+         modification, are permitted provided that the following conditions \
+         are met: Redistributions of source code must retain the above \
+         copyright notice, this list of conditions and the following \
+         disclaimer.  Redistributions in binary form must reproduce the \
+         above copyright notice, this list of conditions and the following \
+         disclaimer in the documentation and/or other materials provided \
+         with the distribution."
+    );
+
+    #[test]
+    fn test_decompress() {
+        /* "abc" then three back for seven, overlapping what it copies */
+        let data = [2, b'a', b'b', b'c', 128 + 4, 3, 0, 0, b'!'];
+        assert_eq!(decompress(&data, 11), b"abcabcabca!");
+        assert_eq!(decompress(&[], 0), b"");
+        assert!(std::panic::catch_unwind(|| decompress(&data, 5)).is_err());
+    }
+
+    #[test]
+    fn test_compressed() {
+        assert!(EMPTY.is_empty());
+        assert_eq!(EMPTY.as_str(), "");
+        assert_eq!(FARBE.len(), "Farbe, Färbung, färben, Farben".len());
+        assert_eq!(&*FARBE, "Farbe, Färbung, färben, Farben");
+        assert!(LICENSE.compressed_len() < LICENSE.len());
+        assert!(LICENSE.ends_with("with the distribution."));
+        assert_eq!(LICENSE.matches("notice, this list").count(), 2);
+        assert!(std::ptr::eq(LICENSE.as_str(), LICENSE.as_str()));
+        assert_eq!(
+            format!("{:?}", FARBE),
+            "\"Farbe, Färbung, färben, Farben\""
+        );
+
+        let total = set!(catalog).iter().map(|c| c.len()).sum::<usize>();
+        assert_eq!(total, EMPTY.len() + FARBE.len() + LICENSE.len());
+    }
+}
@@ -0,0 +1,153 @@
+//! Linker sets of atomic counters.
+//!
+//! Each module of a program can register its statistics as atomic statics
+//! in a set, and the program can total them by walking the set, without a
+//! central list of every counter.
+//!
+//! ```
+//! use linker_set::*;
+//! use std::sync::atomic::{AtomicU64, Ordering};
+//!
+//! set_declare!(packets, AtomicU64);
+//!
+//! #[set_entry(packets)]
+//! static RECEIVED: AtomicU64 = AtomicU64::new(0);
+//! #[set_entry(packets)]
+//! static SENT: AtomicU64 = AtomicU64::new(0);
+//!
+//! # fn main() {
+//! RECEIVED.fetch_add(3, Ordering::Relaxed);
+//! SENT.fetch_add(1, Ordering::Relaxed);
+//! assert_eq!(set!(packets).sum(), 4);
+//! set!(packets).reset();
+//! assert_eq!(set!(packets).sum(), 0);
+//! # }
+//! ```
+//!
+//! # Soundness
+//!
+//! An element with interior mutability, such as an atomic, lives in a
+//! writable section, which Rust chooses because the type is not `Freeze`.
+//! All elements of a set, and the empty placeholder output by
+//! [set_declare!](crate::set_declare), have the same type, so the section
+//! never mixes writable and read-only data.  A [LinkerSet] yields only
+//! shared references to its elements, so they can be modified only through
+//! interior mutability, and a set may be shared or sent between threads
+//! only if its element type is `Sync`.
+//!
+//! The counters are read and written with relaxed ordering, one at a time,
+//! so a total taken while other threads count is not a consistent snapshot
+//! of every counter at one instant.
+
+use crate::LinkerSet;
+use std::sync::atomic::Ordering;
+
+/// An atomic integer that can be the element of a set of counters.
+pub trait Counter: Sync + 'static {
+    /// The integer held by the counter.
+    type Value: Copy + std::iter::Sum;
+
+    /// Returns the value of the counter.
+    fn get(&self) -> Self::Value;
+
+    /// Resets the counter to zero, returning its previous value.
+    fn take(&self) -> Self::Value;
+}
+
+macro_rules! counter {
+    ($atomic:ident, $value:ty) => {
+        impl Counter for std::sync::atomic::$atomic {
+            type Value = $value;
+
+            fn get(&self) -> $value {
+                self.load(Ordering::Relaxed)
+            }
+
+            fn take(&self) -> $value {
+                self.swap(0, Ordering::Relaxed)
+            }
+        }
+    };
+}
+
+counter!(AtomicU8, u8);
+counter!(AtomicU16, u16);
+counter!(AtomicU32, u32);
+counter!(AtomicUsize, usize);
+counter!(AtomicI8, i8);
+counter!(AtomicI16, i16);
+counter!(AtomicI32, i32);
+counter!(AtomicIsize, isize);
+#[cfg(target_has_atomic = "64")]
+counter!(AtomicU64, u64);
+#[cfg(target_has_atomic = "64")]
+counter!(AtomicI64, i64);
+
+impl<T> LinkerSet<T>
+where
+    T: Counter,
+{
+    /// Returns the total of the counters in the set.
+    pub fn sum(&self) -> T::Value {
+        self.iter().map(Counter::get).sum()
+    }
+
+    /// Returns the value of each counter in the set.
+    pub fn snapshot(&self) -> Vec<T::Value> {
+        self.iter().map(Counter::get).collect()
+    }
+
+    /// Resets every counter in the set to zero.
+    pub fn reset(&self) {
+        self.iter().for_each(|c| {
+            c.take();
+        });
+    }
+
+    /// Resets every counter in the set to zero, returning the value of
+    /// each beforehand.
+    pub fn take(&self) -> Vec<T::Value> {
+        self.iter().map(Counter::take).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    set_declare!(hits, AtomicU32);
+
+    #[set_entry(hits)]
+    static A: AtomicU32 = AtomicU32::new(0);
+    #[set_entry(hits)]
+    static B: AtomicU32 = AtomicU32::new(0);
+
+    #[test]
+    fn test_counters() {
+        let threads = (0..4)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    for _ in 0..100 {
+                        A.fetch_add(1, Ordering::Relaxed);
+                        B.fetch_add(2, Ordering::Relaxed);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        threads.into_iter().for_each(|t| t.join().unwrap());
+
+        let hits = set!(hits);
+        assert_eq!(hits.sum(), 1200);
+        let mut snapshot = hits.snapshot();
+        snapshot.sort();
+        assert_eq!(snapshot, [400, 800]);
+        let mut taken = hits.take();
+        taken.sort();
+        assert_eq!(taken, [400, 800]);
+        assert_eq!(hits.sum(), 0);
+        A.fetch_add(5, Ordering::Relaxed);
+        hits.reset();
+        assert_eq!(hits.snapshot(), [0, 0]);
+    }
+}
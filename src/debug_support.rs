@@ -0,0 +1,56 @@
+//! Support for examining linker sets in a debugger.
+//!
+//! The scripts in this module teach GDB and LLDB to print a
+//! [LinkerSet](crate::LinkerSet) as its elements rather than as two raw
+//! pointers.  Write one out and load it into the debugger:
+//!
+//! ```text
+//! (gdb) source gdb_linker_set.py
+//! (gdb) print set
+//! $1 = LinkerSet(len=2) = {4, 2}
+//! (gdb) linker-set stuff
+//! stuff: 2 x u64
+//! [0] = 4
+//! [1] = 2
+//! ```
+//!
+//! The GDB script also adds a `linker-set` command that lists the declared
+//! sets or prints the elements of one, even in a core dump.  It relies on
+//! the following, which are kept stable:
+//!
+//! - the section `set_<name>` holding the entries of a set,
+//! - the section `linker_set_schema` holding the description of each
+//!   set (see [schema](crate::schema)), and
+//! - the placeholder static `__EMPTY_SET` of type `[T; 0]` in the module
+//!   declared for the set, whose debug information gives the type of the
+//!   elements.
+//!
+//! The scripts are experimental.  No test runs them in a debugger, so
+//! they may break with a release of GDB, LLDB, or the compiler that
+//! changes how the standard library or debug information is laid out.
+
+/// A GDB script providing pretty-printers and the `linker-set` command.
+pub const GDB_SCRIPT: &str = include_str!("../etc/gdb_linker_set.py");
+
+/// An LLDB script providing a synthetic child provider for
+/// [LinkerSet](crate::LinkerSet).
+pub const LLDB_SCRIPT: &str = include_str!("../etc/lldb_linker_set.py");
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::inspect::{self, Elf};
+
+    #[test]
+    fn test_placeholder() {
+        let data = inspect::test::exe();
+        let elf = Elf::parse(&data).unwrap();
+        let symbols = elf.symbols().unwrap();
+        let name = "linker_set::test::stuff::__EMPTY_SET";
+        assert!(symbols.iter().any(|s| inspect::demangle(&s.name) == name));
+        if cfg!(feature = "schema") {
+            assert!(elf.section("linker_set_schema").is_some());
+        }
+        assert!(GDB_SCRIPT.contains("__EMPTY_SET"));
+    }
+}
@@ -0,0 +1,203 @@
+//! Lists of crates forbidden to contribute entries to linker sets.
+//!
+//! Any crate linked into a program can place entries into its sets,
+//! including a transitive dependency that has been compromised, which
+//! could register a handler into a dispatch table without anything in
+//! the program naming it.  A [DenyList] names the crates that must not
+//! contribute entries, to every set or to particular ones.  It is written
+//! as text, one rule to a line:
+//!
+//! ```text
+//! # no entries from this crate in any set
+//! vendored_parser
+//! # nor from this one in the commands set
+//! untrusted_plugin commands
+//! ```
+//!
+//! The list is checked twice.  At build time, `cargo linker-set deny FILE
+//! --list LIST` attributes each entry in an artifact to the crate whose
+//! symbol holds it, and fails if any crate contributes where it is
+//! denied.  At run time, with the `metadata` feature, `LinkerSet::denied`
+//! returns the entries contributed by denied crates, and
+//! `LinkerSet::iter_allowed` iterates over a set without them:
+//!
+//! ```
+//! use linker_set::*;
+//! use linker_set::deny::DenyList;
+//!
+//! set_declare!(commands, &'static str);
+//!
+//! #[set_entry(commands)]
+//! static LIST: &str = "list";
+//!
+//! # fn main() {
+//! let list = DenyList::parse("rust_out commands").unwrap();
+//! assert!(list.denies("commands", "rust_out"));
+//! assert!(!list.denies("other", "rust_out"));
+//! # #[cfg(feature = "metadata")]
+//! assert_eq!(set!(commands).iter_allowed(&list).count(), 0);
+//! # }
+//! ```
+//!
+//! Entries that cannot be attributed to a crate, such as those placed by
+//! C code, are attributed to the crate named `?`, which a list can deny
+//! like any other.
+
+use std::fmt;
+
+/// Crates forbidden to contribute entries to linker sets.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DenyList {
+    rules: Vec<(String, Option<String>)>,
+}
+
+/// An error in the text of a [DenyList].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Error {
+    /// The number of the line, counting from one.
+    pub line: usize,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: expected CRATE [SET]", self.line)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl DenyList {
+    /// Parse a list, ignoring blank lines and comments starting with `#`.
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let mut list = Self::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap();
+            match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [] => (),
+                [krate] => list = list.deny(krate, None),
+                [krate, set] => list = list.deny(krate, Some(set)),
+                _ => return Err(Error { line: i + 1 }),
+            }
+        }
+        Ok(list)
+    }
+
+    /// Forbid a crate to contribute entries to a set, or to every set
+    /// given `None`.
+    pub fn deny(mut self, krate: &str, set: Option<&str>) -> Self {
+        self.rules
+            .push((krate.to_string(), set.map(|s| s.to_string())));
+        self
+    }
+
+    /// Returns true if the list forbids a crate to contribute entries to
+    /// a set.
+    pub fn denies(&self, set: &str, krate: &str) -> bool {
+        self.rules
+            .iter()
+            .any(|(k, s)| k == krate && s.as_deref().map_or(true, |s| s == set))
+    }
+
+    /// Returns true if the list forbids nothing.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+}
+
+#[cfg(feature = "metadata")]
+impl<T> crate::LinkerSet<T>
+where
+    T: 'static,
+{
+    /// Returns the crate that contributed each entry, with its entry.
+    fn provenance(&self) -> impl Iterator<Item = (&'static str, &'static T)> {
+        let meta = self.meta;
+        self.iter().map(move |e| {
+            match meta.iter().find(|m| std::ptr::eq(m.entry(), e)) {
+                Some(m) => (m.crate_name(), e),
+                None => (crate::inspect::UNKNOWN_CRATE, e),
+            }
+        })
+    }
+
+    /// Returns a description of each entry contributed by a crate that a
+    /// list forbids to contribute to the set, as `LinkerSet::entry_names`
+    /// describes it.
+    ///
+    /// A set made other than by the [set!](crate::set) macro has no name,
+    /// so only the rules of the list for every set apply to it.
+    pub fn denied(&self, list: &DenyList) -> Vec<String> {
+        let set = self.name().unwrap_or_default();
+        self.provenance()
+            .filter(|(krate, _)| list.denies(set, krate))
+            .map(|(_, e)| self.describe(e))
+            .collect()
+    }
+
+    /// Returns an iterator over the entries of the set, skipping those
+    /// contributed by a crate that a list forbids to contribute to it.
+    pub fn iter_allowed<'a>(
+        &'a self, list: &'a DenyList,
+    ) -> impl Iterator<Item = &'static T> + 'a {
+        let set = self.name().unwrap_or_default();
+        self.provenance()
+            .filter(move |(krate, _)| !list.denies(set, krate))
+            .map(|(_, e)| e)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let text = "# comment\n\nevil\nshady commands # trailing\n";
+        let list = DenyList::parse(text).unwrap();
+        assert_eq!(
+            list,
+            DenyList::default()
+                .deny("evil", None)
+                .deny("shady", Some("commands"))
+        );
+        assert!(list.denies("anything", "evil"));
+        assert!(list.denies("commands", "shady"));
+        assert!(!list.denies("routes", "shady"));
+        assert!(!list.denies("commands", "fine"));
+        let e = DenyList::parse("ok\na b c\n").unwrap_err();
+        assert_eq!(e.to_string(), "line 2: expected CRATE [SET]");
+        assert!(DenyList::parse("").unwrap().is_empty());
+    }
+
+    #[cfg(feature = "metadata")]
+    mod runtime {
+        use super::*;
+        use crate::*;
+
+        set_declare!(handlers, u32);
+
+        #[set_entry(handlers)]
+        static ONE: u32 = 1;
+        #[set_entry(handlers)]
+        static TWO: u32 = 2;
+
+        #[test]
+        fn test_denied() {
+            let set = set!(handlers);
+            let list = DenyList::default().deny("linker_set", Some("other"));
+            assert!(set.denied(&list).is_empty());
+            assert_eq!(set.iter_allowed(&list).count(), 2);
+
+            let list = list.deny("linker_set", Some("handlers"));
+            let mut denied = set.denied(&list);
+            denied.sort();
+            let module = "linker_set::deny::test::runtime";
+            assert_eq!(
+                denied,
+                [format!("{}::ONE", module), format!("{}::TWO", module)]
+            );
+            assert_eq!(set.iter_allowed(&list).count(), 0);
+            assert_eq!(ONE + TWO, 3);
+        }
+    }
+}
@@ -0,0 +1,411 @@
+//! Dependency injection from registered constructors.
+//!
+//! Each [Provider] registered into the [provider] linker set constructs
+//! one value and stores it in a [Container], possibly using values that
+//! other providers have already stored.  [build] orders the providers by
+//! what they require and provide and runs them.  The dependency graph can
+//! be rendered with [dot] or listed in order with [listing].
+//!
+//! ```
+//! use linker_set::*;
+//! use linker_set::di::{self, Key, Provider};
+//!
+//! struct Config(u32);
+//! struct Server(u32);
+//!
+//! #[set_entry(linker_set::di::provider)]
+//! static CONFIG: Provider = Provider {
+//!     name: "config",
+//!     provides: Key::of::<Config>(),
+//!     requires: &[],
+//!     construct: |c| {
+//!         c.insert(Config(8080));
+//!         Ok(())
+//!     },
+//! };
+//!
+//! #[set_entry(linker_set::di::provider)]
+//! static SERVER: Provider = Provider {
+//!     name: "server",
+//!     provides: Key::of::<Server>(),
+//!     requires: &[Key::of::<Config>()],
+//!     construct: |c| {
+//!         let port = c.require::<Config>()?.0;
+//!         c.insert(Server(port));
+//!         Ok(())
+//!     },
+//! };
+//!
+//! # fn main() {
+//! let container = di::build().unwrap();
+//! assert_eq!(container.get::<Server>().unwrap().0, 8080);
+//! # }
+//! ```
+
+use crate::*;
+use std::any::{Any, TypeId};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Write};
+
+set_declare!(provider, Provider, namespaced);
+
+/// A boxed error returned by a constructor.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Identifies the type of a value in a [Container].
+#[derive(Clone, Copy)]
+pub struct Key {
+    id: fn() -> TypeId,
+    name: fn() -> &'static str,
+}
+
+impl Key {
+    /// Returns the key for a type.
+    pub const fn of<T>() -> Self
+    where
+        T: 'static,
+    {
+        Self {
+            id: TypeId::of::<T>,
+            name: std::any::type_name::<T>,
+        }
+    }
+
+    /// Returns the [TypeId] of the type.
+    pub fn id(&self) -> TypeId {
+        (self.id)()
+    }
+
+    /// Returns the name of the type.
+    pub fn name(&self) -> &'static str {
+        (self.name)()
+    }
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A registered constructor of a value.
+pub struct Provider {
+    /// Name of the provider, used in errors.
+    pub name: &'static str,
+    /// The type of value that the provider stores in the container.
+    pub provides: Key,
+    /// The types of value that must be in the container beforehand.
+    pub requires: &'static [Key],
+    /// Construct the value and insert it into the container.
+    pub construct: fn(&mut Container) -> Result<(), BoxError>,
+}
+
+/// A container of values, at most one of each type.
+#[derive(Default)]
+pub struct Container {
+    values: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl Container {
+    /// Create an empty container.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store a value, replacing any other value of the same type.
+    pub fn insert<T>(&mut self, value: T)
+    where
+        T: Send + Sync + 'static,
+    {
+        self.values.insert(TypeId::of::<T>(), Box::new(value));
+    }
+
+    /// Returns the value of a type, if present.
+    pub fn get<T>(&self) -> Option<&T>
+    where
+        T: 'static,
+    {
+        self.values.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+
+    /// Returns the value of a type or an error suitable for returning from
+    /// a constructor.
+    pub fn require<T>(&self) -> Result<&T, BoxError>
+    where
+        T: 'static,
+    {
+        self.get().ok_or_else(|| {
+            format!("missing {}", std::any::type_name::<T>()).into()
+        })
+    }
+
+    /// Returns true if a value of the type with the given id is present.
+    pub fn contains(&self, id: TypeId) -> bool {
+        self.values.contains_key(&id)
+    }
+
+    /// Returns the number of values in the container.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns true if the container holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// An error building a container.
+#[derive(Debug)]
+pub enum Error {
+    /// Two providers provide the same type.
+    Duplicate(&'static str, &'static str),
+    /// A provider requires a type that no provider provides.
+    Missing(&'static str, &'static str),
+    /// The named providers require each other.
+    Cycle(Vec<&'static str>),
+    /// A provider failed or did not insert what it provides.
+    Failed(&'static str, BoxError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Duplicate(a, b) => {
+                write!(f, "providers {} and {} provide the same type", a, b)
+            }
+            Self::Missing(p, t) => {
+                write!(
+                    f,
+                    "provider {} requires {}, which is not provided",
+                    p, t
+                )
+            }
+            Self::Cycle(names) => {
+                write!(f, "providers form a cycle: {}", names.join(", "))
+            }
+            Self::Failed(p, e) => write!(f, "provider {} failed: {}", p, e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Order providers so that each comes after those whose values it needs.
+pub fn order<'a, I>(providers: I) -> Result<Vec<&'a Provider>, Error>
+where
+    I: IntoIterator<Item = &'a Provider>,
+{
+    let providers = providers.into_iter().collect::<Vec<_>>();
+    let mut by_type = HashMap::new();
+    for (i, p) in providers.iter().enumerate() {
+        if let Some(j) = by_type.insert(p.provides.id(), i) {
+            return Err(Error::Duplicate(providers[j].name, p.name));
+        }
+    }
+
+    // Kahn's algorithm; ties are broken by name for a stable order
+    let mut waiting = vec![0; providers.len()];
+    let mut users = vec![Vec::new(); providers.len()];
+    for (i, p) in providers.iter().enumerate() {
+        for req in p.requires {
+            let Some(&j) = by_type.get(&req.id()) else {
+                return Err(Error::Missing(p.name, req.name()));
+            };
+            waiting[i] += 1;
+            users[j].push(i);
+        }
+    }
+    let mut ready = BTreeMap::new();
+    for (i, p) in providers.iter().enumerate() {
+        if waiting[i] == 0 {
+            ready.insert((p.name, i), i);
+        }
+    }
+    let mut ordered = Vec::with_capacity(providers.len());
+    while let Some((_, i)) = ready.pop_first() {
+        ordered.push(providers[i]);
+        for &u in &users[i] {
+            waiting[u] -= 1;
+            if waiting[u] == 0 {
+                ready.insert((providers[u].name, u), u);
+            }
+        }
+    }
+    if ordered.len() < providers.len() {
+        let mut cycle = (0..providers.len())
+            .filter(|&i| waiting[i] > 0)
+            .map(|i| providers[i].name)
+            .collect::<Vec<_>>();
+        cycle.sort();
+        return Err(Error::Cycle(cycle));
+    }
+    Ok(ordered)
+}
+
+/// Build a container by running the given providers.
+pub fn build_from<'a, I>(providers: I) -> Result<Container, Error>
+where
+    I: IntoIterator<Item = &'a Provider>,
+{
+    let mut container = Container::new();
+    for p in order(providers)? {
+        (p.construct)(&mut container).map_err(|e| Error::Failed(p.name, e))?;
+        if !container.contains(p.provides.id()) {
+            let msg = format!("did not insert {}", p.provides.name());
+            return Err(Error::Failed(p.name, msg.into()));
+        }
+    }
+    Ok(container)
+}
+
+/// Build a container by running all providers in the [provider] set.
+pub fn build() -> Result<Container, Error> {
+    build_from(set!(provider))
+}
+
+fn dot_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        if c == '"' || c == '\\' {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push('"');
+}
+
+/// Render the dependency graph of providers in Graphviz DOT format.
+///
+/// Each provider is a node, and each edge runs from a provider to one that
+/// requires its value, labelled with the type of the value.
+pub fn dot<'a, I>(providers: I) -> String
+where
+    I: IntoIterator<Item = &'a Provider>,
+{
+    let providers = providers.into_iter().collect::<Vec<_>>();
+    let mut out = String::from("digraph providers {\n");
+    for p in &providers {
+        out.push_str("    ");
+        dot_string(&mut out, p.name);
+        out.push_str(";\n");
+    }
+    for p in &providers {
+        for req in p.requires {
+            let from = providers.iter().find(|q| q.provides.id() == req.id());
+            out.push_str("    ");
+            dot_string(&mut out, from.map_or("?", |q| q.name));
+            out.push_str(" -> ");
+            dot_string(&mut out, p.name);
+            out.push_str(" [label=");
+            dot_string(&mut out, req.name());
+            out.push_str("];\n");
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// List providers in the order in which they would run, one per line.
+pub fn listing<'a, I>(providers: I) -> Result<String, Error>
+where
+    I: IntoIterator<Item = &'a Provider>,
+{
+    let mut out = String::new();
+    for (i, p) in order(providers)?.into_iter().enumerate() {
+        writeln!(out, "{}. {} ({})", i + 1, p.name, p.provides.name()).unwrap();
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct A(u32);
+    struct B(u32);
+    struct C(u32);
+
+    #[set_entry(crate::di::provider)]
+    static PROVIDE_C: Provider = Provider {
+        name: "c",
+        provides: Key::of::<C>(),
+        requires: &[Key::of::<A>(), Key::of::<B>()],
+        construct: |c| {
+            let sum = c.require::<A>()?.0 + c.require::<B>()?.0;
+            c.insert(C(sum));
+            Ok(())
+        },
+    };
+
+    #[set_entry(crate::di::provider)]
+    static PROVIDE_B: Provider = Provider {
+        name: "b",
+        provides: Key::of::<B>(),
+        requires: &[Key::of::<A>()],
+        construct: |c| {
+            let b = c.require::<A>()?.0 * 10;
+            c.insert(B(b));
+            Ok(())
+        },
+    };
+
+    #[set_entry(crate::di::provider)]
+    static PROVIDE_A: Provider = Provider {
+        name: "a",
+        provides: Key::of::<A>(),
+        requires: &[],
+        construct: |c| {
+            c.insert(A(1));
+            Ok(())
+        },
+    };
+
+    #[test]
+    fn test_build() {
+        let container = build().unwrap();
+        assert_eq!(container.len(), 3);
+        assert_eq!(container.get::<C>().unwrap().0, 11);
+    }
+
+    #[test]
+    fn test_export() {
+        let providers = [&PROVIDE_C, &PROVIDE_A, &PROVIDE_B];
+        let expect = concat!(
+            "1. a (linker_set::di::test::A)\n",
+            "2. b (linker_set::di::test::B)\n",
+            "3. c (linker_set::di::test::C)\n",
+        );
+        assert_eq!(listing(providers).unwrap(), expect);
+        let expect = concat!(
+            "digraph providers {\n",
+            "    \"c\";\n",
+            "    \"a\";\n",
+            "    \"b\";\n",
+            "    \"a\" -> \"c\" [label=\"linker_set::di::test::A\"];\n",
+            "    \"b\" -> \"c\" [label=\"linker_set::di::test::B\"];\n",
+            "    \"a\" -> \"b\" [label=\"linker_set::di::test::A\"];\n",
+            "}\n",
+        );
+        assert_eq!(dot(providers), expect);
+    }
+
+    static LAZY: Provider = Provider {
+        name: "lazy",
+        provides: Key::of::<A>(),
+        requires: &[Key::of::<B>()],
+        construct: |_| Ok(()),
+    };
+
+    #[test]
+    fn test_errors() {
+        let e = build_from([&PROVIDE_A, &LAZY]).err().unwrap();
+        assert!(matches!(e, Error::Duplicate("a", "lazy")));
+        let e = build_from([&LAZY]).err().unwrap();
+        assert!(matches!(e, Error::Missing("lazy", _)));
+        let e = build_from([&LAZY, &PROVIDE_B]).err().unwrap();
+        assert!(matches!(e, Error::Cycle(ref v) if v == &["b", "lazy"]));
+        let e = build_from([&PROVIDE_A, &PROVIDE_B, &PROVIDE_C, &LAZY]);
+        assert!(matches!(e.err().unwrap(), Error::Duplicate(..)));
+    }
+}
@@ -0,0 +1,583 @@
+//! Diagnostics of the attribute macros, and a helper to test for them.
+//!
+//! The messages below are part of the interface of this crate: the
+//! [set_entry](crate::set_entry), [test_entry](crate::test_entry),
+//! [register_impl](crate::register_impl),
+//! [register_const](crate::register_const),
+//! [shutdown](macro@crate::shutdown), and
+//! [set_invariant](crate::set_invariant) attributes, and the
+//! [SetAbi](macro@crate::SetAbi) derive, report errors in their arguments
+//! or items with exactly this text, pointing at the offending tokens, as
+//! do [set_declare!](crate::set_declare) and [set_test!](crate::set_test),
+//! and the text will change only in a release that is not
+//! semver-compatible.
+//! An entry whose type does not match the element type of its set is
+//! reported by the compiler as `mismatched types`, pointing at the
+//! initializer of the entry, as though the type of the static had been
+//! written as the type of the set.  A set whose element type has no
+//! values, such as an enum with no variants or a struct holding one, is
+//! reported as an irrefutable `let...else` pattern, or by later compilers
+//! as an unreachable `else` clause, pointing at the name of the set.
+//!
+//! A crate whose own macros expand to uses of these attributes can check
+//! that misuse yields the intended diagnostics with [CompileFail], which
+//! compiles a snippet of code against the crates of the running test:
+//!
+//! ```no_run
+//! use linker_set::diagnostics::{self, CompileFail};
+//!
+//! CompileFail::new(
+//!     "use linker_set::*;
+//!      set_declare!(stuff, u8);
+//!      #[set_entry(stuff, priority = 1)]
+//!      static X: u8 = 1;",
+//! )
+//! .expect(diagnostics::PRIORITY_WITHOUT_KEY)
+//! .check()
+//! .unwrap();
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// An argument of [set_entry](crate::set_entry) other than `key`,
+/// `priority`, `self_test`, `sub`, or `target_feature`.
+pub const ENTRY_ARGUMENT: &str =
+    "expected `key`, `priority`, `self_test`, `sub`, or `target_feature`";
+
+/// A `priority` given to [set_entry](crate::set_entry) without a `key`.
+pub const PRIORITY_WITHOUT_KEY: &str = "`priority` requires `key`";
+
+/// A `sub` given to [set_entry](crate::set_entry) with a `key`, a
+/// `self_test`, or a `target_feature`.
+pub const SUB_EXCLUSIVE: &str =
+    "`sub` cannot be used with `key`, `self_test`, or `target_feature`";
+
+/// [set_entry](crate::set_entry) applied to an item other than a static or
+/// a const.
+pub const ENTRY_ITEM: &str = "set_entry must be used on a static or a const";
+
+/// An entry larger than the `max_size` declared for its set, following the
+/// name of the static.
+pub const ENTRY_TOO_LARGE: &str = "is larger than the max_size of its set";
+
+/// An argument of [register_impl](crate::register_impl) other than `ctor`.
+pub const IMPL_ARGUMENT: &str = "expected `ctor`";
+
+/// [register_impl](crate::register_impl) applied to a generic impl.
+pub const IMPL_GENERIC: &str = "register_impl does not support generic impls";
+
+/// [register_impl](crate::register_impl) applied to an inherent impl.
+pub const IMPL_NOT_TRAIT: &str =
+    "register_impl must be used on an impl of a trait";
+
+/// [register_const](crate::register_const) applied to a generic impl.
+pub const CONST_GENERIC: &str = "register_const does not support generic impls";
+
+/// A const given to [register_const](crate::register_const) other than
+/// one defined in the impl, named as `Self::NAME`.
+pub const CONST_NOT_FOUND: &str =
+    "expected `Self::NAME` naming an associated const of the impl";
+
+/// A set named by a bare name when it is namespaced, or by a path when it
+/// is not, or by a path not beginning with the crate that declared it.
+/// The compiler reports this message when it evaluates the check of the
+/// entry, pointing at the attribute.
+pub const NAMESPACE_MISMATCH: &str =
+    "a set must be named by a path beginning with its crate \
+     if and only if it is namespaced";
+
+/// A set named by a path beginning with `crate`, `self`, or `super` in a
+/// crate built other than by Cargo, which names the crate.
+pub const NAMESPACE_WITHOUT_CARGO: &str =
+    "a namespaced set must be named in a crate built by Cargo";
+
+/// An entry of a sealed set beyond the 64 that its const array can
+/// hold.  The compiler reports this message when it evaluates the check
+/// of the entry.
+pub const SEALED_TOO_LARGE: &str =
+    "a sealed set holds at most 64 entries in its const array";
+
+/// A sealed set named by a path beginning with the name of its crate,
+/// rather than with `crate`, `self`, or `super`, which is how an entry
+/// tells that it is placed within the crate of its set.
+pub const SEALED_PATH: &str =
+    "a sealed set must be named by a path beginning with `crate`, `self`, \
+     or `super`";
+
+/// An argument of [shutdown](macro@crate::shutdown) other than `phase`.
+pub const SHUTDOWN_ARGUMENT: &str = "expected `phase`";
+
+/// [shutdown](macro@crate::shutdown) applied to an item other than a fn.
+pub const SHUTDOWN_ITEM: &str = "shutdown must be used on a fn";
+
+/// [set_invariant](crate::set_invariant) applied to an item other than a
+/// fn.
+pub const INVARIANT_ITEM: &str = "set_invariant must be used on a fn";
+
+/// [stage_init](crate::stage_init) applied to an item other than a fn.
+pub const STAGE_ITEM: &str = "stage_init must be used on a fn";
+
+/// [SetAbi](macro@crate::SetAbi) derived for an enum or a union.
+pub const ABI_ITEM: &str = "SetAbi can only be derived for a struct";
+
+/// [SetAbi](macro@crate::SetAbi) derived for a generic struct.
+pub const ABI_GENERIC: &str = "SetAbi does not support generic structs";
+
+/// [SetAbi](macro@crate::SetAbi) derived for a struct without
+/// `#[repr(C)]`.
+pub const ABI_NOT_REPR_C: &str = "SetAbi requires #[repr(C)]";
+
+/// An `on_duplicate` policy given to [set_declare!](crate::set_declare)
+/// other than those of [OnDuplicate](crate::OnDuplicate).
+pub const DUPLICATE_POLICY: &str =
+    "expected `panic`, `first_wins`, `last_wins`, or `priority`";
+
+/// An option given to [set_declare!](crate::set_declare) that it does not
+/// take.
+pub const DECLARE_OPTION: &str =
+    "expected `sealed`, `namespaced`, `singleton`, `keyed`, \
+     `on_duplicate`, `lookup`, `cfg`, `on_first_access`, `max_size`, \
+     `stage`, `self_test`, `target_feature`, or `test_entries`";
+
+/// A set declared both `singleton` and `keyed`, or either twice.
+pub const DECLARE_KIND: &str =
+    "`singleton` and `keyed` cannot be combined or repeated";
+
+/// A set declared with `on_duplicate` or `lookup` but not `keyed`.
+pub const DECLARE_WITHOUT_KEYED: &str =
+    "`on_duplicate` and `lookup` require `keyed`";
+
+/// An entry given `self_test` in a set not declared with `self_test`.
+pub const SELF_TEST_UNDECLARED: &str =
+    "a set must be declared with `self_test` to take entries with self tests";
+
+/// An entry given `target_feature` in a set not declared with
+/// `target_feature`.
+pub const FEATURES_UNDECLARED: &str =
+    "a set must be declared with `target_feature` to take entries requiring \
+     CPU features";
+
+/// A [test_entry](crate::test_entry), or a call to
+/// [set_test!](crate::set_test), naming a set not declared with
+/// `test_entries`.
+pub const TEST_ENTRY_UNDECLARED: &str =
+    "a set must be declared with `test_entries` to take test entries";
+
+/// A snippet of code that must fail to compile with given messages.
+///
+/// The snippet is compiled as a library with `rustc`, or the compiler
+/// named by `RUSTC`, against the crates built alongside the running
+/// test, so it should be checked from a test of a crate that depends on
+/// this one.  Each crate is the library of its name built most recently,
+/// unless its path is given with [with_extern](CompileFail::with_extern),
+/// which a test built with more than one set of features should do.
+pub struct CompileFail {
+    source: String,
+    crates: Vec<(String, Option<PathBuf>)>,
+    messages: Vec<String>,
+}
+
+impl CompileFail {
+    /// Create a test of a snippet of code, which can use this crate.
+    pub fn new(source: impl Into<String>) -> Self {
+        Self {
+            source: source.into(),
+            crates: vec![("linker_set".to_string(), None)],
+            messages: Vec::new(),
+        }
+    }
+
+    /// Make another crate built alongside the test usable by the snippet.
+    pub fn with_crate(mut self, name: &str) -> Self {
+        self.crates.push((name.replace('-', "_"), None));
+        self
+    }
+
+    /// Make a crate usable by the snippet from the library at a path, in
+    /// place of any library of the crate found alongside the test.
+    pub fn with_extern(mut self, name: &str, path: impl Into<PathBuf>) -> Self {
+        let name = name.replace('-', "_");
+        self.crates.retain(|(n, _)| *n != name);
+        self.crates.push((name, Some(path.into())));
+        self
+    }
+
+    /// Require the compiler to report a message.
+    pub fn expect(mut self, message: &str) -> Self {
+        self.messages.push(message.to_string());
+        self
+    }
+
+    /// Compile the snippet, and return a description of the failure if it
+    /// compiles or lacks an expected message.
+    pub fn check(&self) -> Result<(), String> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+        let deps = deps()?;
+        let dir = std::env::temp_dir().join(format!(
+            "linker-set-compile-fail-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let file = dir.join("snippet.rs");
+        std::fs::write(&file, &self.source).map_err(|e| e.to_string())?;
+
+        let rustc = std::env::var("RUSTC").unwrap_or("rustc".to_string());
+        let mut cmd = Command::new(rustc);
+        cmd.args(["--edition", "2021", "--crate-type", "lib"])
+            .args(["--emit", "metadata", "--crate-name", "snippet"])
+            .env("CARGO_CRATE_NAME", "snippet")
+            .arg("--out-dir")
+            .arg(&dir)
+            .arg("-L")
+            .arg(format!("dependency={}", deps.display()));
+        for (name, path) in &self.crates {
+            let lib = match path {
+                Some(path) => path.clone(),
+                None => library(&deps, name)?,
+            };
+            cmd.arg("--extern")
+                .arg(format!("{}={}", name, lib.display()));
+        }
+        let output = cmd.arg(&file).output();
+        let _ = std::fs::remove_dir_all(&dir);
+        let output = output.map_err(|e| e.to_string())?;
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if output.status.success() {
+            return Err("snippet compiled successfully".to_string());
+        }
+        match self.messages.iter().find(|m| !stderr.contains(m.as_str())) {
+            Some(m) => Err(format!("expected {:?} in:\n{}", m, stderr)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Returns the directory holding the dependencies of the running test.
+pub(crate) fn deps() -> Result<PathBuf, String> {
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let dir = exe.parent().ok_or("no directory of test")?;
+    Ok(if dir.ends_with("deps") {
+        dir.to_path_buf()
+    } else {
+        dir.join("deps")
+    })
+}
+
+/// Returns the most recently built library of a crate.
+pub(crate) fn library(deps: &Path, name: &str) -> Result<PathBuf, String> {
+    let prefix = format!("lib{}-", name);
+    let dylib = std::env::consts::DLL_SUFFIX;
+    let entries = std::fs::read_dir(deps).map_err(|e| e.to_string())?;
+    entries
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            let file = e.file_name();
+            let file = file.to_string_lossy();
+            file.starts_with(&prefix)
+                && (file.ends_with(".rlib") || file.ends_with(dylib))
+        })
+        .max_by_key(|e| e.metadata().and_then(|m| m.modified()).ok())
+        .map(|e| e.path())
+        .ok_or(format!(
+            "no library of {} in {}; build one, such as by running the \
+             tests without --lib, or give its path with \
+             CompileFail::with_extern",
+            name,
+            deps.display()
+        ))
+}
+
+/// Returns the arguments with which the compiler built the running test.
+#[cfg(test)]
+fn rustc_args() -> &'static [&'static str] {
+    linker_set_proc::__rustc_args!()
+}
+
+/// Returns the path of the library of a crate given to the compiler that
+/// built the running test.
+#[cfg(test)]
+pub(crate) fn extern_path(name: &str) -> Result<PathBuf, String> {
+    let prefix = format!("{}=", name);
+    rustc_args()
+        .windows(2)
+        .filter(|w| w[0] == "--extern")
+        .find_map(|w| w[1].strip_prefix(&prefix))
+        .map(PathBuf::from)
+        .ok_or(format!("the test was not built with --extern {}", name))
+}
+
+/// Returns the library of this crate, built with the features and the
+/// procedural macros of the running test, so that snippets are checked
+/// against exactly the code under test.
+#[cfg(test)]
+pub(crate) fn this_crate() -> Result<PathBuf, String> {
+    static LIBRARY: std::sync::OnceLock<Result<PathBuf, String>> =
+        std::sync::OnceLock::new();
+    let build = || {
+        let proc = extern_path("linker_set_proc")?;
+        /* named for the macros, whose file name differs with the
+         * compiler and the features, so that tests built differently do
+         * not share a directory */
+        let stem = proc.file_stem().ok_or("no name of macros")?;
+        let dir = std::env::temp_dir().join(stem);
+        std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let features = rustc_args()
+            .windows(2)
+            .filter(|w| w[0] == "--cfg" && w[1].starts_with("feature="))
+            .flat_map(|w| ["--cfg", w[1]]);
+        // the macros, and whatever the features depend on
+        let externs = rustc_args()
+            .windows(2)
+            .filter(|w| w[0] == "--extern")
+            .flat_map(|w| ["--extern", w[1]]);
+        let rustc = std::env::var("RUSTC").unwrap_or("rustc".to_string());
+        let output = Command::new(rustc)
+            .args(["--edition", "2021", "--crate-type", "rlib"])
+            .args(["--emit", "metadata,link", "--crate-name", "linker_set"])
+            .args(features)
+            .env("CARGO_CRATE_NAME", "linker_set")
+            .arg("--out-dir")
+            .arg(&dir)
+            .arg("-L")
+            .arg(format!("dependency={}", deps()?.display()))
+            .args(externs)
+            .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/src/lib.rs"))
+            .output()
+            .map_err(|e| e.to_string())?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("cannot build linker_set:\n{}", stderr));
+        }
+        Ok(dir.join("liblinker_set.rlib"))
+    };
+    LIBRARY.get_or_init(build).clone()
+}
+
+/// Build a program against [this_crate], returning what the compiler or
+/// the linker reported if it fails.
+#[cfg(test)]
+pub(crate) fn link(source: &str) -> Result<(), String> {
+    build(source, false)
+}
+
+/// Build a program against [this_crate] and run it, returning what the
+/// compiler, the linker, or the program reported if any of them fails.
+#[cfg(test)]
+pub(crate) fn run(source: &str) -> Result<(), String> {
+    build(source, true)
+}
+
+#[cfg(test)]
+fn build(source: &str, run: bool) -> Result<(), String> {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+    let lib = this_crate()?;
+    let dir = std::env::temp_dir().join(format!(
+        "linker-set-link-{}-{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let file = dir.join("main.rs");
+    std::fs::write(&file, source).map_err(|e| e.to_string())?;
+    let rustc = std::env::var("RUSTC").unwrap_or("rustc".to_string());
+    let output = Command::new(rustc)
+        .args(["--edition", "2021", "--crate-name", "main"])
+        .env("CARGO_CRATE_NAME", "main")
+        .arg("--out-dir")
+        .arg(&dir)
+        .arg("-L")
+        .arg(format!("dependency={}", deps()?.display()))
+        .arg("--extern")
+        .arg(format!("linker_set={}", lib.display()))
+        .arg(&file)
+        .output();
+    let output = match output {
+        Ok(output) if output.status.success() && run => {
+            Command::new(dir.join("main")).output()
+        }
+        output => output,
+    };
+    let _ = std::fs::remove_dir_all(&dir);
+    let output = output.map_err(|e| e.to_string())?;
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(String::from_utf8_lossy(&output.stderr).into_owned()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const DECLARE: &str = "
+        use linker_set::*;
+        pub trait Tr: Sync {}
+        set_declare!(stuff, u8);
+        set_declare!(traits, &'static dyn Tr);
+    ";
+
+    fn snippet(source: String) -> CompileFail {
+        match this_crate() {
+            Ok(lib) => CompileFail::new(source).with_extern("linker_set", lib),
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    fn check(fail: CompileFail) {
+        if let Err(e) = fail.check() {
+            panic!("{}", e);
+        }
+    }
+
+    fn fails(source: &str, message: &str) {
+        check(snippet(format!("{}{}", DECLARE, source)).expect(message));
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        fails(
+            "#[set_entry(stuff, color = 1)] static X: u8 = 1;",
+            ENTRY_ARGUMENT,
+        );
+        fails(
+            "#[set_entry(stuff, priority = 1)] static X: u8 = 1;",
+            PRIORITY_WITHOUT_KEY,
+        );
+        fails(
+            "#[set_entry(stuff, key = \"x\", sub = y)] static X: u8 = 1;",
+            SUB_EXCLUSIVE,
+        );
+        fails("#[set_entry(stuff)] fn f() {}", ENTRY_ITEM);
+        fails(
+            "set_declare!(small, u16, max_size = 2);
+             #[set_entry(small)] static BIG: u32 = 1;",
+            &format!("BIG {}", ENTRY_TOO_LARGE),
+        );
+        fails(
+            "set_declare!(tiny, u32, max_size = 2);",
+            "linker set elements must not be larger than max_size",
+        );
+        fails(
+            "set_declare!(nothing, ());",
+            "linker set elements must not be zero-sized",
+        );
+        /* the lint was renamed by later compilers */
+        let never = format!(
+            "{}pub enum Never {{}} pub struct S(u32, Never); \
+             set_declare!(never, S);",
+            DECLARE
+        );
+        if cfg!(uninhabited_patterns) {
+            let old = snippet(never.clone())
+                .expect("irrefutable `let...else` pattern")
+                .check();
+            let new =
+                snippet(never).expect("unreachable `else` clause").check();
+            assert!(old.is_ok() || new.is_ok(), "{}", old.unwrap_err());
+        }
+        check(
+            snippet(format!(
+                "{}#[set_entry(stuff)] static X: u16 = 1;",
+                DECLARE
+            ))
+            .expect("mismatched types")
+            .expect("expected `&u8`, found `&u16`")
+            .expect("expected due to this"),
+        );
+        fails(
+            "pub struct S; #[register_impl(traits, init = S)] impl Tr for S {}",
+            IMPL_ARGUMENT,
+        );
+        fails(
+            "pub struct S<T>(T); #[register_impl(traits)] \
+             impl<T: Sync> Tr for S<T> {}",
+            IMPL_GENERIC,
+        );
+        fails(
+            "pub struct S; #[register_impl(traits)] impl S {}",
+            IMPL_NOT_TRAIT,
+        );
+        fails(
+            "pub struct S<T>(T); #[register_const(stuff, Self::X)] \
+             impl<T> S<T> { const X: u8 = 1; }",
+            CONST_GENERIC,
+        );
+        fails(
+            "pub struct S; #[register_const(stuff, Self::Y)] \
+             impl S { const X: u8 = 1; }",
+            CONST_NOT_FOUND,
+        );
+        fails(
+            "#[shutdown(order = 1)] fn f() -> Result<(), String> { Ok(()) }",
+            SHUTDOWN_ARGUMENT,
+        );
+        fails("#[shutdown] static X: u8 = 1;", SHUTDOWN_ITEM);
+        fails("#[set_invariant(stuff)] static X: u8 = 1;", INVARIANT_ITEM);
+        fails("#[stage_init(\"smp\")] static X: u8 = 1;", STAGE_ITEM);
+        fails("#[derive(SetAbi)] #[repr(C)] pub enum E { A }", ABI_ITEM);
+        fails(
+            "#[derive(SetAbi)] #[repr(C)] pub struct S<T>(T);",
+            ABI_GENERIC,
+        );
+        fails("#[derive(SetAbi)] pub struct S(u8);", ABI_NOT_REPR_C);
+        fails(
+            "set_declare!(named, u8, keyed, on_duplicate = newest);",
+            DUPLICATE_POLICY,
+        );
+        fails("set_declare!(named, u8, ordered);", DECLARE_OPTION);
+        fails(
+            "fn even(_: &u8) -> Result<(), String> { Ok(()) } \
+             #[set_entry(stuff, self_test = even)] static X: u8 = 1;",
+            SELF_TEST_UNDECLARED,
+        );
+        fails(
+            "#[set_entry(stuff, target_feature = \"avx2\")] static X: u8 = 1;",
+            FEATURES_UNDECLARED,
+        );
+        fails(
+            "#[test_entry(stuff)] static X: u8 = 1;",
+            TEST_ENTRY_UNDECLARED,
+        );
+        fails("fn f() { set_test!(stuff); }", TEST_ENTRY_UNDECLARED);
+        fails("set_declare!(named, u8, keyed, singleton);", DECLARE_KIND);
+        fails(
+            "set_declare!(named, u8, sealed, lookup = fx);",
+            DECLARE_WITHOUT_KEYED,
+        );
+        fails(
+            "set_declare!(scoped, u8, namespaced); \
+             #[set_entry(scoped)] static X: u8 = 1;",
+            NAMESPACE_MISMATCH,
+        );
+        fails(
+            "#[set_entry(crate::stuff)] static X: u8 = 1;",
+            NAMESPACE_MISMATCH,
+        );
+        let entries = (0..65)
+            .map(|i| format!("#[set_entry(crate::few)] static X{i}: u8 = 1;"))
+            .collect::<String>();
+        fails(
+            &format!("set_declare!(few, u8, sealed); {}", entries),
+            SEALED_TOO_LARGE,
+        );
+        fails(
+            "extern crate self as snippet; \
+             set_declare!(few, u8, sealed); \
+             #[set_entry(snippet::few)] static X: u8 = 1;",
+            SEALED_PATH,
+        );
+    }
+
+    #[test]
+    fn test_compiles() {
+        let ok = format!("{}#[set_entry(stuff)] static X: u8 = 1;", DECLARE);
+        let err = snippet(ok).check().unwrap_err();
+        assert_eq!(err, "snippet compiled successfully");
+    }
+}
@@ -0,0 +1,149 @@
+//! Dispatch on the opcodes of the entries of a linker set.
+//!
+//! An interpreter or protocol handler whose opcodes are registered in a
+//! linker set, each entry carrying its opcode and handler, looks up the
+//! entry for every opcode it executes.  A [Table] indexes the entries by
+//! opcode at startup, for a lookup costing one bounds check and one load:
+//!
+//! ```
+//! use linker_set::*;
+//! use linker_set::dispatch::Table;
+//! use std::sync::OnceLock;
+//!
+//! pub struct Op {
+//!     code: u8,
+//!     run: fn(i64, i64) -> i64,
+//! }
+//!
+//! set_declare!(ops, Op);
+//!
+//! #[set_entry(ops)]
+//! pub static ADD: Op = Op { code: 1, run: |a, b| a + b };
+//! #[set_entry(ops)]
+//! pub static MUL: Op = Op { code: 2, run: |a, b| a * b };
+//!
+//! fn fallback(code: u8) -> Option<&'static Op> {
+//!     static TABLE: OnceLock<Table<Op>> = OnceLock::new();
+//!     let table = TABLE.get_or_init(|| {
+//!         Table::new(set!(ops), |op| op.code as usize).unwrap()
+//!     });
+//!     table.get(code as usize)
+//! }
+//!
+//! # fn main() {
+//! assert_eq!((fallback(2).unwrap().run)(6, 7), 42);
+//! assert!(fallback(3).is_none());
+//! # }
+//! ```
+//!
+//! A call through the handler of an entry found in a table is indirect.
+//! On a hot path, [generate::dispatch](crate::inspect::generate::dispatch)
+//! writes, from an artifact built with the table, a `lookup` function
+//! matching each opcode to its static, through which the compiler can
+//! call, or inline, each handler directly.  A build script including that
+//! function where it exists, and the table where it does not, gets the
+//! match when the step is enabled and the table otherwise; either way, an
+//! opcode missing from the match, such as that of an entry registered
+//! after it was generated, falls back to the table:
+//!
+//! ```ignore
+//! #[cfg(dispatch_codegen)]
+//! include!(concat!(env!("OUT_DIR"), "/ops.rs"));
+//!
+//! #[cfg(not(dispatch_codegen))]
+//! fn lookup(code: u8) -> Option<&'static Op> {
+//!     fallback(code)
+//! }
+//! ```
+
+use crate::LinkerSet;
+
+/// The entries of a linker set, indexed by opcode.
+pub struct Table<T>
+where
+    T: 'static,
+{
+    entries: Vec<Option<&'static T>>,
+}
+
+impl<T> Table<T>
+where
+    T: 'static,
+{
+    /// Index the entries of a linker set by the opcode of each.
+    ///
+    /// The table has a slot for every opcode up to the largest, so the
+    /// opcodes should be small and dense.  Returns the first opcode that
+    /// two entries share as an error.
+    pub fn new(
+        set: LinkerSet<T>, opcode: fn(&T) -> usize,
+    ) -> Result<Self, usize> {
+        let len = set.iter().map(|e| opcode(e) + 1).max().unwrap_or(0);
+        let mut entries = vec![None; len];
+        for entry in set {
+            let slot = &mut entries[opcode(entry)];
+            if slot.is_some() {
+                return Err(opcode(entry));
+            }
+            *slot = Some(entry);
+        }
+        Ok(Self { entries })
+    }
+
+    /// Returns the entry with an opcode, if any.
+    pub fn get(&self, opcode: usize) -> Option<&'static T> {
+        self.entries.get(opcode).copied().flatten()
+    }
+
+    /// Returns the number of slots in the table, one more than the largest
+    /// opcode.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if the table has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::*;
+
+    pub struct Op {
+        code: usize,
+        name: &'static str,
+    }
+
+    set_declare!(ops, Op);
+
+    #[set_entry(ops)]
+    static NOP: Op = Op {
+        code: 0,
+        name: "nop",
+    };
+    #[set_entry(ops)]
+    static JUMP: Op = Op {
+        code: 4,
+        name: "jump",
+    };
+    #[set_entry(ops)]
+    static HALT: Op = Op {
+        code: 9,
+        name: "halt",
+    };
+
+    #[test]
+    fn test_table() {
+        let table = Table::new(set!(ops), |op| op.code).unwrap();
+        assert_eq!(table.len(), 10);
+        assert_eq!(table.get(4).map(|op| op.name), Some("jump"));
+        assert!(table.get(3).is_none() && table.get(10).is_none());
+        assert!(std::ptr::eq(table.get(0).unwrap(), &NOP));
+        assert_eq!(Table::new(set!(ops), |op| op.code % 5).err(), Some(4));
+        assert_eq!(HALT.name, "halt");
+        assert_eq!(JUMP.code, 4);
+    }
+}
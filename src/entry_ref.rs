@@ -0,0 +1,259 @@
+//! Handles to the entries of linker sets.
+
+use crate::abi::{__hash_str, __BASIS};
+use crate::LinkerSet;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// A handle to an entry of a linker set, by the id of the set and the
+/// index of the entry within it, for use inside other entries, such as to
+/// link the nodes of a graph registered as entries.
+///
+/// Unlike a reference, a handle holds no address, so it reads the same in
+/// every run of a binary, and tools reading a set from an artifact or a
+/// snapshot can follow it.  It is resolved against the set at run time,
+/// which checks that the set is the one it names and that the index is
+/// within the set.
+///
+/// ```
+/// use linker_set::*;
+///
+/// pub struct Node {
+///     name: &'static str,
+/// }
+///
+/// set_declare!(nodes, Node);
+///
+/// #[set_entry(nodes)]
+/// static ROOT: Node = Node { name: "root" };
+///
+/// # fn main() {
+/// let nodes = set!(nodes);
+/// let root = nodes.entry_ref(&ROOT).unwrap();
+/// assert_eq!(root.resolve(&nodes).unwrap().name, "root");
+/// let beyond: EntryRef<Node> = entry_ref!(nodes, 1);
+/// assert!(beyond.resolve(&nodes).is_err());
+/// # }
+/// ```
+///
+/// The index of an entry is fixed within a build of a program, but not
+/// from one build to the next, as with the index of an error kind in
+/// [errors](crate::errors).  A handle written into a static by
+/// [entry_ref!](crate::entry_ref) should index a set whose order is
+/// known, such as a set of one entry, or be generated by a build step
+/// from a previous link, and a [set_invariant](crate::set_invariant)
+/// resolving every handle in a set catches one that has gone stale.
+/// Handles made at run time by `LinkerSet::entry_ref` are always current.
+#[repr(C)]
+pub struct EntryRef<T>
+where
+    T: 'static,
+{
+    set: u32,
+    index: u32,
+    _type: PhantomData<fn() -> &'static T>,
+}
+
+impl<T> EntryRef<T>
+where
+    T: 'static,
+{
+    /// Create a handle to the entry at an index into the set of a name.
+    ///
+    /// Users should call the [entry_ref!](crate::entry_ref) macro instead
+    /// of this function, which checks the type of the set.
+    pub const fn new(set: &str, index: u32) -> Self {
+        Self {
+            set: id(set),
+            index,
+            _type: PhantomData,
+        }
+    }
+
+    /// Returns the id of the set, a hash of its name.
+    pub fn set_id(&self) -> u32 {
+        self.set
+    }
+
+    /// Returns the index of the entry within the set.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Returns the entry, if the handle refers to one in a set.
+    ///
+    /// A set made other than by the [set!](crate::set) macro has no name,
+    /// and the handle is taken to name it.
+    pub fn resolve(&self, set: &LinkerSet<T>) -> Result<&'static T, RefError> {
+        if let Some(name) = set.name() {
+            if id(name) != self.set {
+                return Err(RefError::WrongSet {
+                    expected: self.set,
+                    found: name,
+                });
+            }
+        }
+        set.slice
+            .get(self.index as usize)
+            .ok_or(RefError::OutOfRange {
+                index: self.index,
+                len: set.len(),
+            })
+    }
+}
+
+impl<T> Clone for EntryRef<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for EntryRef<T> {}
+
+impl<T> PartialEq for EntryRef<T> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.set, self.index) == (other.set, other.index)
+    }
+}
+
+impl<T> Eq for EntryRef<T> {}
+
+impl<T> fmt::Debug for EntryRef<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "EntryRef({:08x}, {})", self.set, self.index)
+    }
+}
+
+/// Returns the id of the set of a name, as a handle records it.
+const fn id(name: &str) -> u32 {
+    __hash_str(__BASIS, name) as u32
+}
+
+/// A handle that does not refer to an entry of a set.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RefError {
+    /// The handle names another set.
+    WrongSet {
+        /// The id of the set that the handle names.
+        expected: u32,
+        /// The name of the set that the handle was resolved against.
+        found: &'static str,
+    },
+    /// The index is not within the set.
+    OutOfRange {
+        /// The index of the handle.
+        index: u32,
+        /// The number of entries in the set.
+        len: usize,
+    },
+}
+
+impl fmt::Display for RefError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WrongSet { expected, found } => write!(
+                f,
+                "handle to set {:08x} resolved against set {}",
+                expected, found
+            ),
+            Self::OutOfRange { index, len } => write!(
+                f,
+                "handle to entry {} resolved against set of {} entries",
+                index, len
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RefError {}
+
+impl<T> LinkerSet<T>
+where
+    T: 'static,
+{
+    /// Returns a handle to an entry of the linker set, or `None` if the
+    /// entry is not in the set or the set has no name.
+    pub fn entry_ref(&self, entry: &'static T) -> Option<EntryRef<T>> {
+        let name = self.name()?;
+        let start = self.slice.as_ptr() as usize;
+        let offset = (entry as *const T as usize).wrapping_sub(start);
+        let index = offset / std::mem::size_of::<T>();
+        (index < self.len() && std::ptr::eq(&self.slice[index], entry))
+            .then(|| EntryRef::new(name, index as u32))
+    }
+}
+
+/// Make a handle to the entry at an index into a linker set.
+///
+/// See [EntryRef].
+#[macro_export]
+macro_rules! entry_ref {
+    ($set:ident, $index:expr) => {
+        $crate::EntryRef::<$set::__Type>::new(stringify!($set), $index)
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::*;
+
+    pub struct Node {
+        name: &'static str,
+        next: Option<EntryRef<Node>>,
+    }
+
+    set_declare!(ring, Node);
+
+    /* a set of one entry, whose index is known */
+    #[set_entry(ring)]
+    static ONLY: Node = Node {
+        name: "only",
+        next: Some(entry_ref!(ring, 0)),
+    };
+
+    set_declare!(line, Node);
+
+    #[set_entry(line)]
+    static FIRST: Node = Node {
+        name: "first",
+        next: None,
+    };
+    #[set_entry(line)]
+    static SECOND: Node = Node {
+        name: "second",
+        next: None,
+    };
+
+    #[test]
+    fn test_entry_ref() {
+        let ring = set!(ring);
+        let next = ONLY.next.unwrap().resolve(&ring).unwrap();
+        assert!(std::ptr::eq(next, &ONLY));
+        assert_eq!(ring.entry_ref(&ONLY), ONLY.next);
+
+        let line = set!(line);
+        let second = line.entry_ref(&SECOND).unwrap();
+        assert_eq!(second.resolve(&line).unwrap().name, "second");
+        assert_eq!(second.set_id(), id("line"));
+        assert!(line.entry_ref(&ONLY).is_none());
+        assert_eq!(
+            second.resolve(&ring).err(),
+            Some(RefError::WrongSet {
+                expected: id("line"),
+                found: "ring",
+            })
+        );
+        let beyond: EntryRef<Node> = entry_ref!(line, 2);
+        let e = beyond.resolve(&line).err().unwrap();
+        assert_eq!(e, RefError::OutOfRange { index: 2, len: 2 });
+        assert_eq!(
+            e.to_string(),
+            "handle to entry 2 resolved against set of 2 entries"
+        );
+        let unnamed = LinkerSet::from_slice(line.slice);
+        assert_eq!(second.resolve(&unnamed).unwrap().name, "second");
+        assert!(unnamed.entry_ref(&FIRST).is_none());
+        assert!(FIRST.next.is_none());
+    }
+}
@@ -0,0 +1,222 @@
+//! Registration of error kinds, for error values that need no allocation.
+//!
+//! Each kind of error that a program can report is described by an
+//! [ErrorKind] registered into the [error_kind] linker set, wherever in
+//! the program it is defined.  A [SetError] refers to its kind by index
+//! into the set, so it is four bytes, needs no allocation, and can be
+//! passed around as freely as an integer on embedded devices, while the
+//! code and message of its kind stay available for display.
+//!
+//! ```
+//! use linker_set::*;
+//! use linker_set::errors::{ErrorKind, SetError, Severity};
+//!
+//! #[set_entry(linker_set::errors::error_kind)]
+//! static SENSOR_TIMEOUT: ErrorKind = ErrorKind {
+//!     code: 1042,
+//!     message: "sensor did not respond",
+//!     severity: Severity::Warning,
+//! };
+//!
+//! fn read_sensor() -> Result<u16, SetError> {
+//!     Err(SetError::new(&SENSOR_TIMEOUT))
+//! }
+//!
+//! # fn main() {
+//! let e = read_sensor().unwrap_err();
+//! assert!(e.is(&SENSOR_TIMEOUT));
+//! assert_eq!(e.to_string(), "sensor did not respond (error 1042)");
+//! # }
+//! ```
+//!
+//! The index of a kind is fixed within a build of a program, but not from
+//! one build to the next, so an error that leaves the program, such as in
+//! a log, should be identified by its code.
+
+use crate::*;
+use std::fmt;
+
+set_declare!(error_kind, ErrorKind, namespaced);
+
+/// How serious an error is.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Severity {
+    /// Worth noting, but not a failure.
+    Info,
+    /// A failure that the program can recover from.
+    Warning,
+    /// A failure of the operation that encountered it.
+    Error,
+    /// A failure from which the program cannot continue.
+    Fatal,
+}
+
+/// The description of a kind of error.
+#[derive(Debug)]
+pub struct ErrorKind {
+    /// The code identifying the kind of error, which should be unique
+    /// within the program.
+    pub code: u32,
+    /// A message describing the error.
+    pub message: &'static str,
+    /// How serious the error is.
+    pub severity: Severity,
+}
+
+impl ErrorKind {
+    /// Returns true if an error is a [SetError] of this kind, so that a
+    /// kind can be tested for through a `dyn Error`.
+    pub fn matches(
+        &'static self, e: &(dyn std::error::Error + 'static),
+    ) -> bool {
+        e.downcast_ref::<SetError>().is_some_and(|e| e.is(self))
+    }
+}
+
+/// An error, referring to the registered description of its kind.
+#[derive(Clone, Copy, Eq, Hash, PartialEq)]
+pub struct SetError {
+    index: u32,
+}
+
+impl SetError {
+    /// Create an error of a kind.
+    ///
+    /// # Panics
+    /// Panics if the kind is not in the [error_kind] set.
+    pub fn new(kind: &'static ErrorKind) -> Self {
+        let kinds = set!(error_kind);
+        let start = kinds.slice.as_ptr() as usize;
+        let offset = (kind as *const ErrorKind as usize).wrapping_sub(start);
+        let index = offset / std::mem::size_of::<ErrorKind>();
+        assert!(
+            index < kinds.len() && std::ptr::eq(&kinds[index], kind),
+            "error kind {} is not registered",
+            kind.code
+        );
+        Self {
+            index: index as u32,
+        }
+    }
+
+    /// Returns the error of the kind at an index into the [error_kind]
+    /// set, if there is one.
+    pub fn from_index(index: u32) -> Option<Self> {
+        ((index as usize) < set!(error_kind).len()).then_some(Self { index })
+    }
+
+    /// Returns an error of the kind with a code, if there is one.
+    pub fn from_code(code: u32) -> Option<Self> {
+        let kinds = set!(error_kind);
+        let index = kinds.slice.iter().position(|k| k.code == code)?;
+        Some(Self {
+            index: index as u32,
+        })
+    }
+
+    /// Returns the index of the kind of the error in the [error_kind] set.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Returns the kind of the error.
+    pub fn kind(&self) -> &'static ErrorKind {
+        &set!(error_kind).slice[self.index as usize]
+    }
+
+    /// Returns true if the error is of a kind.
+    pub fn is(&self, kind: &'static ErrorKind) -> bool {
+        std::ptr::eq(self.kind(), kind)
+    }
+
+    /// Returns the code of the kind of the error.
+    pub fn code(&self) -> u32 {
+        self.kind().code
+    }
+
+    /// Returns the message of the kind of the error.
+    pub fn message(&self) -> &'static str {
+        self.kind().message
+    }
+
+    /// Returns the severity of the kind of the error.
+    pub fn severity(&self) -> Severity {
+        self.kind().severity
+    }
+}
+
+impl fmt::Debug for SetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SetError")
+            .field("code", &self.code())
+            .field("message", &self.message())
+            .finish()
+    }
+}
+
+impl fmt::Display for SetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (error {})", self.message(), self.code())
+    }
+}
+
+impl std::error::Error for SetError {}
+
+/// Returns the first code shared by two registered kinds of error, if
+/// any, which a test or startup check can use to catch collisions.
+pub fn duplicate_code() -> Option<u32> {
+    let mut codes = set!(error_kind).iter().map(|k| k.code).collect::<Vec<_>>();
+    codes.sort();
+    codes.windows(2).find(|w| w[0] == w[1]).map(|w| w[0])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::error::Error;
+
+    #[set_entry(crate::errors::error_kind)]
+    static DISK_FULL: ErrorKind = ErrorKind {
+        code: 28,
+        message: "no space left on device",
+        severity: Severity::Error,
+    };
+
+    #[set_entry(crate::errors::error_kind)]
+    static LOW_BATTERY: ErrorKind = ErrorKind {
+        code: 7,
+        message: "battery is low",
+        severity: Severity::Info,
+    };
+
+    static UNREGISTERED: ErrorKind = ErrorKind {
+        code: 99,
+        message: "not in the set",
+        severity: Severity::Fatal,
+    };
+
+    #[test]
+    fn test_set_error() {
+        assert_eq!(std::mem::size_of::<SetError>(), 4);
+        let e = SetError::new(&DISK_FULL);
+        assert!(e.is(&DISK_FULL) && !e.is(&LOW_BATTERY));
+        assert_eq!(e.code(), 28);
+        assert_eq!(e.severity(), Severity::Error);
+        assert_eq!(e.to_string(), "no space left on device (error 28)");
+        assert_eq!(SetError::from_index(e.index()), Some(e));
+        assert_eq!(SetError::from_code(7), Some(SetError::new(&LOW_BATTERY)));
+        assert_eq!(SetError::from_code(99), None);
+        assert_eq!(duplicate_code(), None);
+
+        let boxed: Box<dyn Error> = Box::new(e);
+        assert!(DISK_FULL.matches(boxed.as_ref()));
+        assert!(!LOW_BATTERY.matches(boxed.as_ref()));
+        assert_eq!(boxed.downcast_ref::<SetError>(), Some(&e));
+    }
+
+    #[test]
+    #[should_panic(expected = "error kind 99 is not registered")]
+    fn test_unregistered() {
+        SetError::new(&UNREGISTERED);
+    }
+}
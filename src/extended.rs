@@ -0,0 +1,158 @@
+//! Linker sets extended at run time.
+
+use crate::{LinkerSet, LinkerSetIter};
+use std::iter::{Chain, Copied};
+
+/// The entries of a linker set followed by entries added at run time.
+///
+/// Some entries are known only when a program starts, such as those read
+/// from its configuration.  An extended set appends them to the entries
+/// placed by the linker, each leaked into an allocation of its own so
+/// that it is `'static` like them, and iterates over both alike, so that
+/// code consuming the set need not merge the two itself.
+///
+/// ```
+/// use linker_set::*;
+///
+/// set_declare!(mirrors, &'static str);
+///
+/// #[set_entry(mirrors)]
+/// static PRIMARY: &str = "primary.example.org";
+///
+/// # fn main() {
+/// let mut all = ExtendedSet::new(set!(mirrors));
+/// all.push("configured.example.org");
+/// assert_eq!(all.len(), 2);
+/// assert_eq!(*all.iter().last().unwrap(), "configured.example.org");
+/// # }
+/// ```
+///
+/// Entries are added before the set is shared, such as into a static
+/// `OnceLock`, so that it is read without locking.  Like patching a set,
+/// adding entries fails once the sets are [sealed](crate::seal).
+pub struct ExtendedSet<T>
+where
+    T: 'static,
+{
+    set: LinkerSet<T>,
+    extra: Vec<&'static T>,
+}
+
+impl<T> ExtendedSet<T>
+where
+    T: 'static,
+{
+    /// Create an extended set with the entries of a linker set and none
+    /// added.
+    pub fn new(set: LinkerSet<T>) -> Self {
+        Self {
+            set,
+            extra: Vec::new(),
+        }
+    }
+
+    /// Add an entry, which is leaked, returning it.
+    ///
+    /// # Panics
+    /// Panics if the linker sets have been sealed.
+    pub fn push(&mut self, entry: T) -> &'static T {
+        crate::seal::assert_unsealed("extend a linker set");
+        let entry = Box::leak(Box::new(entry));
+        self.extra.push(entry);
+        entry
+    }
+
+    /// Returns an iterator over the entries of the linker set, followed by
+    /// those added.
+    pub fn iter(&self) -> ExtendedSetIter<'_, T> {
+        self.set.iter().chain(self.extra.iter().copied())
+    }
+
+    /// Returns the entry at an index, counting those of the linker set
+    /// first.
+    pub fn get(&self, i: usize) -> Option<&'static T> {
+        match i.checked_sub(self.set.len()) {
+            None => Some(&self.set.slice[i]),
+            Some(i) => self.extra.get(i).copied(),
+        }
+    }
+
+    /// Returns the linker set extended.
+    pub fn linker_set(&self) -> &LinkerSet<T> {
+        &self.set
+    }
+
+    /// Returns the entries added.
+    pub fn added(&self) -> &[&'static T] {
+        &self.extra
+    }
+
+    /// Returns the number of entries, including those added.
+    pub fn len(&self) -> usize {
+        self.set.len() + self.extra.len()
+    }
+
+    /// Returns true if there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> From<LinkerSet<T>> for ExtendedSet<T>
+where
+    T: 'static,
+{
+    fn from(set: LinkerSet<T>) -> Self {
+        Self::new(set)
+    }
+}
+
+/// An iterator over the entries of an [ExtendedSet].
+pub type ExtendedSetIter<'a, T> =
+    Chain<LinkerSetIter<T>, Copied<std::slice::Iter<'a, &'static T>>>;
+
+impl<'a, T> IntoIterator for &'a ExtendedSet<T>
+where
+    T: 'static,
+{
+    type Item = &'static T;
+    type IntoIter = ExtendedSetIter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::*;
+
+    set_declare!(quotas, u32);
+
+    #[set_entry(quotas)]
+    static LOW: u32 = 10;
+    #[set_entry(quotas)]
+    static HIGH: u32 = 20;
+
+    #[test]
+    fn test_extended() {
+        let mut set = ExtendedSet::from(set!(quotas));
+        assert_eq!(set.len(), 2);
+        let added = set.push(30);
+        set.push(40);
+        assert_eq!(*added, 30);
+        assert_eq!(set.len(), 4);
+        assert!(!set.is_empty());
+
+        let mut all = set.iter().copied().collect::<Vec<_>>();
+        all[..2].sort();
+        assert_eq!(all, [LOW, HIGH, 30, 40]);
+        assert_eq!((&set).into_iter().count(), 4);
+        assert!(std::ptr::eq(set.get(2).unwrap(), added));
+        assert_eq!(set.get(3), Some(&40));
+        assert!(set.get(4).is_none());
+        assert_eq!(set.linker_set().len(), 2);
+        assert_eq!(set.added(), [&30, &40]);
+    }
+}
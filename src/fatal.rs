@@ -0,0 +1,208 @@
+//! Handlers run on a fatal error, before the process exits.
+//!
+//! A service that cannot continue should still flush its logs, leave a
+//! marker for whatever restarts it, and tell its watchdog, and each of
+//! these is set up by a different part of it.  A [Handler] registered into
+//! the [fatal_handler] linker set is called by [fatal()], with the exit code
+//! and the message of the error, before the process exits with the code.
+//! The handlers of the highest priority run first, and those of the same
+//! priority in order of name.
+//!
+//! ```no_run
+//! use linker_set::*;
+//! use linker_set::fatal::Handler;
+//! use std::time::Duration;
+//!
+//! fn write_marker(code: i32, msg: &str) -> Result<(), String> {
+//!     std::fs::write("/run/app.crashed", format!("{}: {}\n", code, msg))
+//!         .map_err(|e| e.to_string())
+//! }
+//!
+//! #[set_entry(linker_set::fatal::fatal_handler)]
+//! static MARKER: Handler =
+//!     Handler::new("marker", 10, Duration::from_millis(100), write_marker);
+//!
+//! # fn main() {
+//! linker_set::fatal(3, "configuration is unreadable");
+//! # }
+//! ```
+//!
+//! Each handler runs on a thread of its own, and is abandoned, left
+//! running, if it takes longer than its budget, so that one handler that
+//! hangs, such as on a full disk, does not keep the others from running or
+//! the process from exiting.
+
+use crate::shutdown::Failure;
+use crate::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::time::Duration;
+
+set_declare!(fatal_handler, Handler, namespaced);
+
+/// A function to call on a fatal error, with the exit code and the message
+/// of the error.
+pub struct Handler {
+    name: &'static str,
+    priority: u32,
+    budget: Duration,
+    run: fn(i32, &str) -> Result<(), String>,
+}
+
+impl Handler {
+    /// Create a handler from its name, its priority, how long it may run,
+    /// and the function to call.
+    pub const fn new(
+        name: &'static str, priority: u32, budget: Duration,
+        run: fn(i32, &str) -> Result<(), String>,
+    ) -> Self {
+        Self {
+            name,
+            priority,
+            budget,
+            run,
+        }
+    }
+
+    /// Returns the name of the handler.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the priority of the handler.
+    pub fn priority(&self) -> u32 {
+        self.priority
+    }
+
+    /// Returns how long the handler may run before it is abandoned.
+    pub fn budget(&self) -> Duration {
+        self.budget
+    }
+}
+
+fn call(
+    handler: &'static Handler, code: i32, msg: &str,
+) -> Result<(), Failure> {
+    let (tx, rx) = mpsc::channel();
+    let msg = msg.to_string();
+    std::thread::Builder::new()
+        .name(format!("fatal {}", handler.name))
+        .spawn(move || tx.send((handler.run)(code, &msg)))
+        .map_err(|e| Failure::Error(e.to_string()))?;
+    match rx.recv_timeout(handler.budget) {
+        Ok(result) => result.map_err(Failure::Error),
+        Err(mpsc::RecvTimeoutError::Timeout) => Err(Failure::TimedOut),
+        Err(mpsc::RecvTimeoutError::Disconnected) => Err(Failure::Panicked),
+    }
+}
+
+/// Run the given handlers, those of the highest priority first, and those
+/// of the same priority in order of name, returning the name of each that
+/// failed, and how, in the order run.
+pub fn run<I>(handlers: I, code: i32, msg: &str) -> Vec<(&'static str, Failure)>
+where
+    I: IntoIterator<Item = &'static Handler>,
+{
+    let mut handlers = handlers.into_iter().collect::<Vec<_>>();
+    handlers
+        .sort_by(|a, b| b.priority.cmp(&a.priority).then(a.name.cmp(b.name)));
+    handlers
+        .into_iter()
+        .filter_map(|h| call(h, code, msg).err().map(|f| (h.name, f)))
+        .collect()
+}
+
+static FATAL: AtomicBool = AtomicBool::new(false);
+
+/// Report a fatal error, run the handlers registered in the
+/// [fatal_handler] linker set, and exit the process with the code.
+///
+/// The error, and the failure of any handler, is written to standard
+/// error.  Only the first call runs the handlers; a later call, such as
+/// from another thread or from a handler, blocks until the first exits
+/// the process.
+pub fn fatal(code: i32, msg: &str) -> ! {
+    if FATAL.swap(true, Ordering::AcqRel) {
+        loop {
+            std::thread::park();
+        }
+    }
+    eprintln!("fatal error {}: {}", code, msg);
+    for (name, failure) in run(set!(fatal_handler), code, msg) {
+        eprintln!("fatal error handler {}: {}", name, failure);
+    }
+    std::process::exit(code)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Mutex;
+
+    static CALLS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    fn record(code: i32, msg: &str) -> Result<(), String> {
+        CALLS.lock().unwrap().push(format!("{} {}", code, msg));
+        Ok(())
+    }
+
+    fn slow(_: i32, _: &str) -> Result<(), String> {
+        std::thread::sleep(Duration::from_secs(1));
+        Ok(())
+    }
+
+    fn panics(_: i32, _: &str) -> Result<(), String> {
+        panic!("handler panicked")
+    }
+
+    fn fails(_: i32, _: &str) -> Result<(), String> {
+        Err("watchdog unreachable".to_string())
+    }
+
+    const BUDGET: Duration = Duration::from_millis(50);
+
+    #[set_entry(crate::fatal::fatal_handler)]
+    static FLUSH: Handler = Handler::new("flush", 1, BUDGET, record);
+    static SLOW: Handler = Handler::new("slow", 5, BUDGET, slow);
+    static PANICS: Handler = Handler::new("panics", 3, BUDGET, panics);
+    static FAILS: Handler = Handler::new("fails", 3, BUDGET, fails);
+    static RECORD: Handler = Handler::new("record", 0, BUDGET, record);
+
+    #[test]
+    fn test_run() {
+        let handlers = [&RECORD, &PANICS, &FAILS, &SLOW, &FLUSH];
+        let failures = run(handlers, 70, "out of memory");
+        assert_eq!(
+            failures,
+            [
+                ("slow", Failure::TimedOut),
+                ("fails", Failure::Error("watchdog unreachable".to_string())),
+                ("panics", Failure::Panicked),
+            ]
+        );
+        assert_eq!(*CALLS.lock().unwrap(), ["70 out of memory"; 2]);
+
+        let handlers = set!(fatal_handler).iter().collect::<Vec<_>>();
+        assert_eq!(handlers.len(), 1);
+        assert_eq!(handlers[0].name(), "flush");
+        assert_eq!(handlers[0].priority(), 1);
+        assert_eq!(handlers[0].budget(), BUDGET);
+    }
+
+    #[test]
+    fn test_fatal() {
+        if std::env::var_os("LINKER_SET_TEST_FATAL").is_some() {
+            fatal(7, "disk on fire");
+        }
+        let output =
+            std::process::Command::new(std::env::current_exe().unwrap())
+                .args(["--exact", "fatal::test::test_fatal", "--nocapture"])
+                .env("LINKER_SET_TEST_FATAL", "1")
+                .output()
+                .unwrap();
+        assert_eq!(output.status.code(), Some(7));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("fatal error 7: disk on fire\n"));
+        assert!(!stderr.contains("fatal error handler"));
+    }
+}
@@ -0,0 +1,121 @@
+//! CPU features required by the entries of linker sets.
+
+use crate::LinkerSet;
+
+/// The CPU features required by an entry in a linker set, recorded by the
+/// [set_entry](crate::set_entry) attribute when given `target_feature =
+/// "..."`.
+pub struct Featured<T>
+where
+    T: 'static,
+{
+    features: &'static str,
+    detect: fn() -> bool,
+    entry: &'static T,
+}
+
+impl<T> Featured<T>
+where
+    T: 'static,
+{
+    #[doc(hidden)]
+    pub const fn new(
+        features: &'static str, detect: fn() -> bool, entry: &'static T,
+    ) -> Self {
+        Self {
+            features,
+            detect,
+            entry,
+        }
+    }
+
+    /// Returns the features required by the entry, separated by commas.
+    pub fn features(&self) -> &'static str {
+        self.features
+    }
+
+    /// Returns true if the running CPU has all of the features.
+    pub fn is_supported(&self) -> bool {
+        (self.detect)()
+    }
+
+    /// Returns the entry.
+    pub fn entry(&self) -> &'static T {
+        self.entry
+    }
+}
+
+impl<T> LinkerSet<T>
+where
+    T: 'static,
+{
+    /// Returns the items in the linker set that the running CPU can use,
+    /// those requiring the most features first.
+    ///
+    /// An item placed into the set with `#[set_entry(name, target_feature
+    /// = "avx2,fma")]` can be used if the CPU has every feature listed,
+    /// and an item placed without one can always be used.
+    pub fn for_cpu(&self) -> Vec<&'static T> {
+        let mut usable = self
+            .iter()
+            .filter_map(|e| {
+                match self.features.iter().find(|f| std::ptr::eq(f.entry, e)) {
+                    Some(f) if !f.is_supported() => None,
+                    Some(f) => Some((f.features.split(',').count(), e)),
+                    None => Some((0, e)),
+                }
+            })
+            .collect::<Vec<_>>();
+        usable.sort_by_key(|(n, _)| std::cmp::Reverse(*n));
+        usable.into_iter().map(|(_, e)| e).collect()
+    }
+
+    /// Returns the CPU features recorded with the items in the linker set.
+    pub fn features(&self) -> &'static [Featured<T>] {
+        self.features
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+
+    pub struct Kernel {
+        name: &'static str,
+    }
+
+    set_declare!(kernels, Kernel, target_feature);
+
+    #[set_entry(kernels)]
+    static SCALAR: Kernel = Kernel { name: "scalar" };
+
+    #[set_entry(kernels, target_feature = "sse2")]
+    #[cfg(target_arch = "x86_64")]
+    static SSE2: Kernel = Kernel { name: "sse2" };
+
+    #[set_entry(kernels, target_feature = "sse,sse2")]
+    #[cfg(target_arch = "x86_64")]
+    static SSE: Kernel = Kernel { name: "sse" };
+
+    #[set_entry(kernels, target_feature = "avx512vp2intersect,avx512f")]
+    #[cfg(target_arch = "x86_64")]
+    static AVX512: Kernel = Kernel { name: "avx512" };
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn test_for_cpu() {
+        let names = set!(kernels)
+            .for_cpu()
+            .iter()
+            .map(|k| k.name)
+            .collect::<Vec<_>>();
+        let avx512 = std::is_x86_feature_detected!("avx512vp2intersect");
+        let expect = match avx512 {
+            true => ["avx512", "sse", "sse2", "scalar"].as_slice(),
+            false => ["sse", "sse2", "scalar"].as_slice(),
+        };
+        assert_eq!(names, expect);
+        assert_eq!(kernels::best_for_cpu().unwrap().name, expect[0]);
+        assert_eq!(set!(kernels).features().len(), 3);
+    }
+}
@@ -0,0 +1,42 @@
+//! Hooks run on the first access to linker sets.
+
+use crate::LinkerSet;
+use std::sync::Once;
+
+/// The hook of a linker set declared with `on_first_access = path`, and
+/// whether it has run.
+pub struct FirstAccess<T> {
+    hook: Option<fn(&T)>,
+    once: Once,
+}
+
+impl<T> FirstAccess<T>
+where
+    T: 'static,
+{
+    #[doc(hidden)]
+    pub const fn new(hook: Option<fn(&T)>) -> Self {
+        Self {
+            hook,
+            once: Once::new(),
+        }
+    }
+
+    /// Returns true if the hook has run, or if there is none.
+    pub fn is_done(&self) -> bool {
+        self.hook.is_none() || self.once.is_completed()
+    }
+
+    /// Run the hook on each element of the set, unless it has already
+    /// run, and return the set.
+    ///
+    /// Users should call the [set!](crate::set) macro instead of this
+    /// function.
+    #[doc(hidden)]
+    pub fn run(&self, set: LinkerSet<T>) -> LinkerSet<T> {
+        if let Some(hook) = self.hook {
+            self.once.call_once(|| set.iter().for_each(hook));
+        }
+        set
+    }
+}
@@ -0,0 +1,263 @@
+//! Bit flags registered by the crates of a program.
+//!
+//! Each [Flag] placed into the [flag] linker set is assigned a bit of its
+//! own, its index in the set, so that crates across a workspace can each
+//! define the flags they need without coordinating the values of bitmask
+//! constants in one place:
+//!
+//! ```
+//! use linker_set::*;
+//! use linker_set::flags::{self, Flag};
+//!
+//! #[set_entry(linker_set::flags::flag)]
+//! static VERBOSE: Flag = Flag::new("verbose");
+//! #[set_entry(linker_set::flags::flag)]
+//! static DRY_RUN: Flag = Flag::new("dry_run");
+//!
+//! # fn main() {
+//! flags::check::<u32>().unwrap();
+//! let mask: u32 = flags::mask_of(&["verbose", "dry_run"]).unwrap();
+//! assert_eq!(mask, VERBOSE.mask::<u32>() | DRY_RUN.mask::<u32>());
+//! assert_eq!(flags::names_of(mask).len(), 2);
+//! # }
+//! ```
+//!
+//! Masks are integers of a width chosen by the program, any implementing
+//! [Mask].  [check], called at startup, verifies that every flag fits in
+//! the width and that no two flags share a name.
+//!
+//! The order of a set is that in which the linker saw its entries, so the
+//! bit of a flag is fixed for a given binary but may differ in another
+//! build.  Masks should be persisted or exchanged by the names of their
+//! flags, as [names_of] gives them, rather than as integers.
+
+use crate::*;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::ops::BitOr;
+
+set_declare!(flag, Flag, namespaced);
+
+/// A named flag, assigned a bit of its own.
+#[derive(Debug)]
+pub struct Flag {
+    name: &'static str,
+}
+
+impl Flag {
+    /// Create a flag with a name.
+    pub const fn new(name: &'static str) -> Self {
+        Self { name }
+    }
+
+    /// Returns the name of the flag.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the position of the bit of the flag.
+    ///
+    /// # Panics
+    /// Panics if the flag is not in the [flag] linker set.
+    pub fn bit(&self) -> u32 {
+        let position = set!(flag).iter().position(|f| std::ptr::eq(f, self));
+        position.expect("flag is not registered") as u32
+    }
+
+    /// Returns a mask with only the bit of the flag set.
+    ///
+    /// # Panics
+    /// Panics if the bit of the flag does not fit in the mask, or if the
+    /// flag is not in the [flag] linker set.
+    pub fn mask<M>(&self) -> M
+    where
+        M: Mask,
+    {
+        let bit = self.bit();
+        assert!(bit < M::BITS, "flag {} does not fit in the mask", self.name);
+        M::bit(bit)
+    }
+}
+
+/// An integer that holds the bits of flags.
+pub trait Mask: Copy + Eq + BitOr<Output = Self> {
+    /// The number of bits in the integer.
+    const BITS: u32;
+
+    /// Returns the integer with only the given bit set.
+    fn bit(n: u32) -> Self;
+
+    /// Returns the integer with no bits set.
+    fn zero() -> Self;
+}
+
+macro_rules! mask {
+    ($($type:ty),*) => {
+        $(
+            impl Mask for $type {
+                const BITS: u32 = <$type>::BITS;
+
+                fn bit(n: u32) -> Self {
+                    1 << n
+                }
+
+                fn zero() -> Self {
+                    0
+                }
+            }
+        )*
+    };
+}
+
+mask!(u8, u16, u32, u64, u128);
+
+/// An error in the registered flags, or in naming them.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// No flag has the name.
+    Unknown(String),
+    /// Two flags have the name.
+    Duplicate(&'static str),
+    /// More flags are registered than there are bits in the mask.
+    TooMany {
+        /// The number of flags registered.
+        count: usize,
+        /// The number of bits in the mask.
+        bits: u32,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unknown(name) => write!(f, "no flag is named {}", name),
+            Self::Duplicate(name) => write!(f, "two flags are named {}", name),
+            Self::TooMany { count, bits } => write!(
+                f,
+                "{} flags are registered, but the mask has {} bits",
+                count, bits
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Check that the registered flags fit in a mask of type `M`, and that no
+/// two have the same name.
+pub fn check<M>() -> Result<(), Error>
+where
+    M: Mask,
+{
+    let flags = set!(flag);
+    if flags.len() > M::BITS as usize {
+        return Err(Error::TooMany {
+            count: flags.len(),
+            bits: M::BITS,
+        });
+    }
+    let mut names = BTreeSet::new();
+    match flags.iter().find(|f| !names.insert(f.name)) {
+        Some(f) => Err(Error::Duplicate(f.name)),
+        None => Ok(()),
+    }
+}
+
+/// Returns the position of the bit of the flag with a name, if any.
+pub fn bit_of(name: &str) -> Option<u32> {
+    set!(flag)
+        .iter()
+        .position(|f| f.name == name)
+        .map(|i| i as u32)
+}
+
+/// Returns a mask with the bits of the flags with the given names set.
+///
+/// # Panics
+/// Panics if the bit of a named flag does not fit in the mask, which
+/// [check] reports without panicking.
+pub fn mask_of<M>(names: &[&str]) -> Result<M, Error>
+where
+    M: Mask,
+{
+    names.iter().try_fold(M::zero(), |mask, name| {
+        let bit =
+            bit_of(name).ok_or_else(|| Error::Unknown(name.to_string()))?;
+        assert!(bit < M::BITS, "flag {} does not fit in the mask", name);
+        Ok(mask | M::bit(bit))
+    })
+}
+
+/// Returns the names of the flags whose bits are set in a mask, in order
+/// of their bits.
+pub fn names_of<M>(mask: M) -> Vec<&'static str>
+where
+    M: Mask,
+{
+    set!(flag)
+        .iter()
+        .take(M::BITS as usize)
+        .enumerate()
+        .filter(|&(i, _)| mask | M::bit(i as u32) == mask)
+        .map(|(_, f)| f.name)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[set_entry(crate::flags::flag)]
+    static READ: Flag = Flag::new("read");
+    #[set_entry(crate::flags::flag)]
+    static WRITE: Flag = Flag::new("write");
+    #[set_entry(crate::flags::flag)]
+    static EXECUTE: Flag = Flag::new("execute");
+
+    #[test]
+    fn test_flags() {
+        check::<u8>().unwrap();
+        let bits = [READ.bit(), WRITE.bit(), EXECUTE.bit()];
+        assert_eq!(bits.iter().collect::<BTreeSet<_>>().len(), 3);
+        assert!(bits.iter().all(|&b| b < 3));
+        assert_eq!(bit_of("write"), Some(WRITE.bit()));
+
+        let mask = mask_of::<u8>(&["read", "execute"]).unwrap();
+        assert_eq!(mask, READ.mask::<u8>() | EXECUTE.mask::<u8>());
+        let mut names = names_of(mask);
+        names.sort();
+        assert_eq!(names, ["execute", "read"]);
+        assert_eq!(
+            mask_of::<u8>(&["read", "sync"]),
+            Err(Error::Unknown("sync".to_string()))
+        );
+        assert_eq!(mask_of::<u64>(&[]), Ok(0));
+    }
+
+    #[test]
+    fn test_width() {
+        #[derive(Clone, Copy, Eq, PartialEq)]
+        struct Narrow(u8);
+        impl BitOr for Narrow {
+            type Output = Self;
+            fn bitor(self, other: Self) -> Self {
+                Narrow(self.0 | other.0)
+            }
+        }
+        impl Mask for Narrow {
+            const BITS: u32 = 2;
+            fn bit(n: u32) -> Self {
+                Narrow(1 << n)
+            }
+            fn zero() -> Self {
+                Narrow(0)
+            }
+        }
+        let e = check::<Narrow>().unwrap_err();
+        assert_eq!(e, Error::TooMany { count: 3, bits: 2 });
+        assert_eq!(
+            e.to_string(),
+            "3 flags are registered, but the mask has 2 bits"
+        );
+    }
+}
@@ -0,0 +1,224 @@
+//! Content type negotiation over registered formats.
+//!
+//! Each [Format] names the media types it handles and carries a factory,
+//! of a type chosen by the program, for whatever decodes that format.  The
+//! program declares a linker set of formats, and [negotiate] picks the one
+//! for the content type of a request, so that no central match over
+//! content types is needed.
+//!
+//! With the `serde` feature enabled, this module declares its own
+//! `format` set, whose factories hand a type-erased deserializer of a body
+//! to a callback, and `deserialize` decodes a body of any registered
+//! content type into a value of a type implementing `DeserializeOwned`.
+//!
+//! A format matches a content type if one of its media types equals the
+//! content type without parameters, ignoring case.  Failing that, a
+//! content type with a structured syntax suffix, such as
+//! `application/problem+json`, matches a format for `application/json`.
+
+#[cfg(feature = "serde")]
+use crate::{set, set_declare};
+#[cfg(feature = "serde")]
+use std::fmt;
+
+/// A format of request or response bodies.
+pub struct Format<F>
+where
+    F: 'static,
+{
+    /// Name of the format.
+    pub name: &'static str,
+    /// The media types of the format, such as `application/json`.
+    pub content_types: &'static [&'static str],
+    /// Factory for whatever handles the format.
+    pub factory: F,
+}
+
+/// Returns the media type of a content type, without parameters.
+fn media_type(content_type: &str) -> &str {
+    content_type.split(';').next().unwrap_or_default().trim()
+}
+
+fn accepts<F>(format: &Format<F>, media_type: &str) -> bool {
+    format
+        .content_types
+        .iter()
+        .any(|t| t.eq_ignore_ascii_case(media_type))
+}
+
+/// Returns the format for a content type, if any.
+pub fn negotiate<'a, I, F>(
+    formats: I, content_type: &str,
+) -> Option<&'a Format<F>>
+where
+    I: IntoIterator<Item = &'a Format<F>>,
+    F: 'static,
+{
+    let media_type = media_type(content_type);
+    let formats = formats.into_iter().collect::<Vec<_>>();
+    if let Some(f) = formats.iter().find(|f| accepts(f, media_type)) {
+        return Some(f);
+    }
+    let (ty, subtype) = media_type.split_once('/')?;
+    let (_, suffix) = subtype.rsplit_once('+')?;
+    let base = format!("{}/{}", ty, suffix);
+    formats.into_iter().find(|f| accepts(f, &base))
+}
+
+/// A function handed a type-erased deserializer by a [Factory].
+#[cfg(feature = "serde")]
+pub type Visit<'a> = &'a mut dyn FnMut(
+    &mut dyn erased_serde::Deserializer<'_>,
+) -> Result<(), erased_serde::Error>;
+
+/// Calls a [Visit] with a deserializer of the given bytes, and returns an
+/// error if the bytes hold anything after the value deserialized.
+#[cfg(feature = "serde")]
+pub type Factory = fn(&[u8], Visit<'_>) -> Result<(), erased_serde::Error>;
+
+#[cfg(feature = "serde")]
+set_declare!(format, Format<Factory>, namespaced);
+
+/// An error deserializing a body.
+#[cfg(feature = "serde")]
+#[derive(Debug)]
+pub enum Error {
+    /// No format is registered for the content type.
+    Unsupported(String),
+    /// The body is not valid in its format or for the type.
+    Invalid(erased_serde::Error),
+}
+
+#[cfg(feature = "serde")]
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported(ty) => {
+                write!(f, "unsupported content type {}", ty)
+            }
+            Self::Invalid(e) => write!(f, "invalid body: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl std::error::Error for Error {}
+
+/// Deserializes a body by the format registered for its content type.
+///
+/// ```
+/// use linker_set::*;
+/// use linker_set::formats::{format, Factory, Format};
+/// use std::collections::BTreeMap;
+///
+/// #[set_entry(linker_set::formats::format)]
+/// static JSON: Format<Factory> = Format {
+///     name: "json",
+///     content_types: &["application/json"],
+///     factory: |bytes, visit| {
+///         let mut de = serde_json::Deserializer::from_slice(bytes);
+///         visit(&mut <dyn erased_serde::Deserializer>::erase(&mut de))?;
+///         de.end().map_err(serde::de::Error::custom)
+///     },
+/// };
+///
+/// # fn main() {
+/// let body = br#"{"a": [1, 2]}"#;
+/// let map: BTreeMap<String, Vec<u32>> =
+///     formats::deserialize("application/json", body).unwrap();
+/// assert_eq!(map["a"], [1, 2]);
+/// assert!(formats::deserialize::<u32>("text/plain", b"1").is_err());
+/// # }
+/// ```
+#[cfg(feature = "serde")]
+pub fn deserialize<T>(content_type: &str, bytes: &[u8]) -> Result<T, Error>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let unsupported = || Error::Unsupported(content_type.to_owned());
+    let format =
+        negotiate(set!(format), content_type).ok_or_else(unsupported)?;
+    let mut value = None;
+    (format.factory)(bytes, &mut |de| {
+        value = Some(erased_serde::deserialize(de)?);
+        Ok(())
+    })
+    .map_err(Error::Invalid)?;
+    value.ok_or_else(|| {
+        let e = "format did not deserialize the body";
+        Error::Invalid(serde::de::Error::custom(e))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::*;
+
+    type Parse = fn(&[u8]) -> Option<u32>;
+
+    set_declare!(format, Format<Parse>);
+
+    #[set_entry(format)]
+    static DECIMAL: Format<Parse> = Format {
+        name: "decimal",
+        content_types: &["text/plain", "text/x-decimal"],
+        factory: |b| std::str::from_utf8(b).ok()?.parse().ok(),
+    };
+
+    #[set_entry(format)]
+    static JSON: Format<Parse> = Format {
+        name: "json",
+        content_types: &["application/json"],
+        factory: |b| std::str::from_utf8(b).ok()?.trim().parse().ok(),
+    };
+
+    fn name(content_type: &str) -> Option<&'static str> {
+        negotiate(set!(format), content_type).map(|f| f.name)
+    }
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(name("text/plain"), Some("decimal"));
+        assert_eq!(name("Text/X-Decimal; charset=utf-8"), Some("decimal"));
+        assert_eq!(name("application/json"), Some("json"));
+        assert_eq!(name("application/problem+json"), Some("json"));
+        assert_eq!(name("application/xml"), None);
+        assert_eq!(name("garbage"), None);
+        let f = negotiate(set!(format), "application/json").unwrap();
+        assert_eq!((f.factory)(b" 42\n"), Some(42));
+    }
+
+    #[cfg(feature = "serde")]
+    #[set_entry(crate::formats::format)]
+    static SERDE_JSON: Format<Factory> = Format {
+        name: "json",
+        content_types: &["application/json"],
+        factory: |bytes, visit| {
+            let mut de = serde_json::Deserializer::from_slice(bytes);
+            visit(&mut <dyn erased_serde::Deserializer>::erase(&mut de))?;
+            de.end().map_err(serde::de::Error::custom)
+        },
+    };
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_deserialize() {
+        use std::collections::BTreeMap;
+
+        type Map = BTreeMap<String, Vec<u32>>;
+        let map = Map::from([("a".into(), vec![1, 2]), ("b".into(), vec![])]);
+        let bytes = serde_json::to_vec(&map).unwrap();
+        let ty = "application/problem+json; charset=utf-8";
+        assert_eq!(deserialize::<Map>(ty, &bytes).unwrap(), map);
+
+        let e = deserialize::<Map>("application/xml", &bytes).unwrap_err();
+        assert!(
+            matches!(e, Error::Unsupported(ref t) if t == "application/xml")
+        );
+        let ty = "application/json";
+        let e = deserialize::<Map>(ty, b"[1, 2]").unwrap_err();
+        assert!(matches!(e, Error::Invalid(_)));
+        assert!(deserialize::<u32>(ty, b"1 2").is_err());
+    }
+}
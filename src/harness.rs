@@ -0,0 +1,317 @@
+//! A test harness for the self tests recorded with the entries of sets.
+//!
+//! The checks recorded by `#[set_entry(name, self_test = path)]`, in a set
+//! declared with the `self_test` option, can run as ordinary tests, one
+//! for each entry, named after the set and the static holding the entry.
+//! [main] parses the command line understood by the standard test
+//! harness, so that a test target built with it works with `cargo test`,
+//! with `cargo nextest`, which lists tests with `--list --format terse`
+//! and runs each with `--exact`, and with the usual filtering by name:
+//!
+//! ```toml
+//! # Cargo.toml
+//! [[test]]
+//! name = "self_tests"
+//! harness = false
+//! ```
+//!
+//! ```ignore
+//! // tests/self_tests.rs
+//! use linker_set::*;
+//! use linker_set::harness::{self, trials};
+//! use app::{handlers, routes};
+//!
+//! fn main() -> std::process::ExitCode {
+//!     let mut all = trials("handlers", set!(handlers));
+//!     all.extend(trials("routes", set!(routes)));
+//!     harness::main(all)
+//! }
+//! ```
+//!
+//! A [Trial] is a name and a function, which is all that other harnesses,
+//! such as `libtest-mimic`, need of a test:
+//!
+//! ```ignore
+//! let trials = trials("handlers", set!(handlers)).into_iter().map(|t| {
+//!     libtest_mimic::Trial::test(t.name(), move || Ok(t.run()?))
+//! });
+//! ```
+
+use crate::LinkerSet;
+use std::io::Write;
+use std::process::ExitCode;
+
+/// A test to run.
+pub struct Trial {
+    name: String,
+    run: Box<dyn Fn() -> Result<(), String> + Send + Sync>,
+}
+
+impl Trial {
+    /// Create a test from its name and a function that runs it.
+    pub fn new<F>(name: impl Into<String>, run: F) -> Self
+    where
+        F: Fn() -> Result<(), String> + Send + Sync + 'static,
+    {
+        Self {
+            name: name.into(),
+            run: Box::new(run),
+        }
+    }
+
+    /// Returns the name of the test.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Run the test.
+    ///
+    /// A test that panics fails with the message of the panic.
+    pub fn run(&self) -> Result<(), String> {
+        crate::self_test::caught(&self.run)
+    }
+}
+
+/// Returns a test for each self test recorded with the entries of a set,
+/// named `set::STATIC`.
+pub fn trials<T>(set: &str, linker_set: LinkerSet<T>) -> Vec<Trial>
+where
+    T: Sync + 'static,
+{
+    linker_set
+        .self_tests()
+        .iter()
+        .map(|t| Trial::new(format!("{}::{}", set, t.name()), || t.run()))
+        .collect()
+}
+
+/// The arguments of the standard test harness that select and list tests.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Arguments {
+    /// List the tests instead of running them.
+    pub list: bool,
+    /// List tests in the terse format, `NAME: test`.
+    pub terse: bool,
+    /// Select only ignored tests, of which there are none.
+    pub ignored: bool,
+    /// Select tests named exactly by a filter, rather than containing one.
+    pub exact: bool,
+    /// Select tests matching any of these filters, or all if there are
+    /// none.
+    pub filters: Vec<String>,
+    /// Skip tests containing any of these.
+    pub skip: Vec<String>,
+}
+
+impl Arguments {
+    /// Parse arguments, ignoring those that affect only how the standard
+    /// harness runs or reports tests.
+    pub fn parse<I>(args: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (flag, value) = match arg.split_once('=') {
+                Some((f, v)) if f.starts_with("--") => {
+                    (f.to_string(), Some(v.to_string()))
+                }
+                _ => (arg, None),
+            };
+            let mut value = || {
+                value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or(format!("{} requires a value", flag))
+            };
+            match flag.as_str() {
+                "--list" => parsed.list = true,
+                "--ignored" => parsed.ignored = true,
+                "--exact" => parsed.exact = true,
+                "--skip" => parsed.skip.push(value()?),
+                "--format" => parsed.terse = value()? == "terse",
+                "--test-threads" | "--color" | "-Z" => drop(value()?),
+                "--nocapture" | "--show-output" | "--include-ignored"
+                | "-q" | "--quiet" => (),
+                f if f.starts_with('-') => {
+                    return Err(format!("unknown option {}", f))
+                }
+                _ => parsed.filters.push(flag),
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn selects(&self, name: &str) -> bool {
+        let matches = |f: &String| match self.exact {
+            true => name == f,
+            false => name.contains(f.as_str()),
+        };
+        !self.ignored
+            && (self.filters.is_empty() || self.filters.iter().any(matches))
+            && !self.skip.iter().any(|s| name.contains(s.as_str()))
+    }
+}
+
+/// The outcome of running tests.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Conclusion {
+    /// The number of tests that passed.
+    pub passed: usize,
+    /// The number of tests that failed.
+    pub failed: usize,
+    /// The number of tests not selected.
+    pub filtered_out: usize,
+}
+
+/// Run, or list, the selected tests, reporting them as the standard
+/// harness does.
+pub fn run<W>(
+    args: &Arguments, trials: &[Trial], out: &mut W,
+) -> std::io::Result<Conclusion>
+where
+    W: Write,
+{
+    let selected = trials
+        .iter()
+        .filter(|t| args.selects(t.name()))
+        .collect::<Vec<_>>();
+    let mut conclusion = Conclusion {
+        filtered_out: trials.len() - selected.len(),
+        ..Conclusion::default()
+    };
+
+    if args.list {
+        for t in &selected {
+            writeln!(out, "{}: test", t.name())?;
+        }
+        if !args.terse {
+            writeln!(out)?;
+            writeln!(out, "{} tests, 0 benchmarks", selected.len())?;
+        }
+        return Ok(conclusion);
+    }
+
+    writeln!(out)?;
+    writeln!(out, "running {} tests", selected.len())?;
+    let mut failures = Vec::new();
+    for t in selected {
+        match t.run() {
+            Ok(()) => {
+                conclusion.passed += 1;
+                writeln!(out, "test {} ... ok", t.name())?;
+            }
+            Err(e) => {
+                conclusion.failed += 1;
+                writeln!(out, "test {} ... FAILED", t.name())?;
+                failures.push((t.name(), e));
+            }
+        }
+    }
+    if !failures.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "failures:")?;
+        for (name, e) in &failures {
+            writeln!(out)?;
+            writeln!(out, "---- {} ----", name)?;
+            writeln!(out, "{}", e)?;
+        }
+        writeln!(out)?;
+        writeln!(out, "failures:")?;
+        for (name, _) in &failures {
+            writeln!(out, "    {}", name)?;
+        }
+    }
+    let result = if failures.is_empty() { "ok" } else { "FAILED" };
+    writeln!(out)?;
+    writeln!(
+        out,
+        "test result: {}. {} passed; {} failed; 0 ignored; 0 measured; {} \
+         filtered out",
+        result, conclusion.passed, conclusion.failed, conclusion.filtered_out
+    )?;
+    writeln!(out)?;
+    Ok(conclusion)
+}
+
+/// Run the tests selected by the command line, as the `main` of a test
+/// target built with `harness = false`.
+pub fn main(trials: Vec<Trial>) -> ExitCode {
+    let args = match Arguments::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::from(101);
+        }
+    };
+    match run(&args, &trials, &mut std::io::stdout().lock()) {
+        Ok(c) if c.failed == 0 => ExitCode::SUCCESS,
+        _ => ExitCode::from(101),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(args: &[&str]) -> Arguments {
+        Arguments::parse(args.iter().map(|a| a.to_string())).unwrap()
+    }
+
+    fn output(args: &Arguments, trials: &[Trial]) -> (Conclusion, String) {
+        let mut out = Vec::new();
+        let conclusion = run(args, trials, &mut out).unwrap();
+        (conclusion, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_harness() {
+        let trials = vec![
+            Trial::new("set::GOOD", || Ok(())),
+            Trial::new("set::BAD", || Err("broken".to_string())),
+            Trial::new("other::GOOD", || Ok(())),
+            Trial::new("other::PANIC", || panic!("oops")),
+        ];
+
+        let list = args(&["--list", "--format", "terse", "set::"]);
+        let (_, out) = output(&list, &trials);
+        assert_eq!(out, "set::GOOD: test\nset::BAD: test\n");
+        let (_, out) = output(&args(&["--list", "--ignored"]), &trials);
+        assert_eq!(out, "\n0 tests, 0 benchmarks\n");
+
+        let exact = args(&["--exact", "set::GOOD", "--test-threads=1"]);
+        let (conclusion, out) = output(&exact, &trials);
+        assert_eq!(conclusion.passed, 1);
+        assert_eq!(conclusion.filtered_out, 3);
+        assert!(out.contains("test set::GOOD ... ok\n"));
+
+        let all = args(&["--nocapture", "--skip", "other"]);
+        let (conclusion, out) = output(&all, &trials);
+        assert_eq!((conclusion.passed, conclusion.failed), (1, 1));
+        assert!(out.contains("---- set::BAD ----\nbroken\n"));
+        assert!(out.contains("test result: FAILED. 1 passed; 1 failed;"));
+
+        let (conclusion, out) = output(&args(&["PANIC"]), &trials);
+        assert_eq!(conclusion.failed, 1);
+        assert!(out.contains("---- other::PANIC ----\npanicked: oops\n"));
+
+        assert!(Arguments::parse(["--bogus".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_trials() {
+        use crate::set;
+        use crate::test::checked;
+
+        let trials = trials("checked", set!(checked));
+        let mut names = trials.iter().map(Trial::name).collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(
+            names,
+            ["checked::CONST_ODD", "checked::EVEN", "checked::ODD"]
+        );
+        let failed = trials.iter().filter(|t| t.run().is_err()).count();
+        assert_eq!(failed, 2);
+    }
+}
@@ -0,0 +1,1186 @@
+//! Inspection of the linker sets in compiled artifacts.
+//!
+//! This module reads ELF files well enough to find the sections holding
+//! linker sets and the symbols of the entries within them.  It backs the
+//! `cargo linker-set` subcommand, but it can also be used directly.
+//!
+//! ```no_run
+//! use linker_set::inspect::{self, Elf};
+//!
+//! let data = std::fs::read("target/debug/app").unwrap();
+//! let elf = Elf::parse(&data).unwrap();
+//! for set in inspect::sets(&elf) {
+//!     println!("{} {}", set.name, set.size);
+//! }
+//! ```
+
+use crate::deny::DenyList;
+use crate::layout::Report;
+use std::cell::OnceCell;
+use std::collections::BTreeMap;
+use std::fmt;
+
+pub mod generate;
+pub mod query;
+
+/// Prefix of the names of sections holding linker sets.
+pub const SECTION_PREFIX: &str = "set_";
+
+/// Prefix of the names of the sets of test entries, after
+/// [SECTION_PREFIX] and the namespace, if any, of the set.
+const TEST_PREFIX: &str = "_test_";
+
+/// Kinds of the sections holding what is recorded beside the entries of a
+/// set, after [SECTION_PREFIX] and the namespace, if any, of the set.
+const HELPER_KINDS: [&str; 5] =
+    ["_keyed_", "_meta_", "_selftest_", "_features_", TEST_PREFIX];
+
+const SHT_SYMTAB: u32 = 2;
+const SHT_NOBITS: u32 = 8;
+
+/// An error encountered while reading an artifact.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The file is not an ELF file.
+    NotElf,
+    /// The file is truncated or a header points outside of it.
+    Truncated,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotElf => write!(f, "not an ELF file"),
+            Self::Truncated => write!(f, "truncated or malformed ELF file"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A section of an ELF file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Section {
+    /// The name of the section.
+    pub name: String,
+    /// The type of the section, e.g. `SHT_PROGBITS`.
+    pub kind: u32,
+    /// The flags of the section.
+    pub flags: u64,
+    /// The virtual address of the section.
+    pub addr: u64,
+    /// The offset of the section within the file.
+    pub offset: u64,
+    /// The size of the section in bytes.
+    pub size: u64,
+    /// The section header index of an associated section.
+    pub link: u32,
+    /// The alignment of the section.
+    pub align: u64,
+}
+
+/// A symbol from the symbol table of an ELF file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Symbol {
+    /// The raw, possibly mangled, name of the symbol.
+    pub name: String,
+    /// The value, usually the address, of the symbol.
+    pub value: u64,
+    /// The size of the object named by the symbol.
+    pub size: u64,
+    /// The index of the section containing the symbol.
+    pub section: usize,
+}
+
+/// A parsed ELF file.
+pub struct Elf<'a> {
+    data: &'a [u8],
+    wide: bool,
+    little: bool,
+    sections: Vec<Section>,
+    /* the symbols with a size, by the index of their section, in order
+     * of address, read from the symbol table once, on first use */
+    entries: OnceCell<Result<BTreeMap<usize, Vec<Symbol>>, Error>>,
+}
+
+impl<'a> Elf<'a> {
+    /// Parse the headers of an ELF file.
+    pub fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        if data.len() < 16 || &data[..4] != b"\x7fELF" {
+            return Err(Error::NotElf);
+        }
+        let wide = match data[4] {
+            1 => false,
+            2 => true,
+            _ => return Err(Error::NotElf),
+        };
+        let little = match data[5] {
+            1 => true,
+            2 => false,
+            _ => return Err(Error::NotElf),
+        };
+        let mut elf = Self {
+            data,
+            wide,
+            little,
+            sections: Vec::new(),
+            entries: OnceCell::new(),
+        };
+        elf.sections = elf.read_sections()?;
+        Ok(elf)
+    }
+
+    /// Returns true if the file is a 64-bit ELF file.
+    pub fn is_64(&self) -> bool {
+        self.wide
+    }
+
+    /// Returns true if the file is little-endian.
+    pub fn is_little_endian(&self) -> bool {
+        self.little
+    }
+
+    /// Returns the sections of the file.
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    /// Returns the section with the given name.
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|s| s.name == name)
+    }
+
+    /// Returns the contents of a section, which is empty if the section
+    /// occupies no space in the file.
+    pub fn section_data(&self, section: &Section) -> Result<&'a [u8], Error> {
+        if section.kind == SHT_NOBITS {
+            return Ok(&[]);
+        }
+        self.bytes(section.offset, section.size)
+    }
+
+    /// Returns the symbols in the symbol table of the file.
+    pub fn symbols(&self) -> Result<Vec<Symbol>, Error> {
+        let Some(symtab) = self.sections.iter().find(|s| s.kind == SHT_SYMTAB)
+        else {
+            return Ok(Vec::new());
+        };
+        let strtab = self
+            .sections
+            .get(symtab.link as usize)
+            .ok_or(Error::Truncated)?;
+        let strtab = self.section_data(strtab)?;
+        let data = self.section_data(symtab)?;
+        let entsize = if self.wide { 24 } else { 16 };
+        let mut symbols = Vec::new();
+        for sym in data.chunks_exact(entsize) {
+            let (name, value, size, section) = if self.wide {
+                (
+                    self.u32(sym, 0)?,
+                    self.u64(sym, 8)?,
+                    self.u64(sym, 16)?,
+                    self.u16(sym, 6)?,
+                )
+            } else {
+                (
+                    self.u32(sym, 0)?,
+                    self.u32(sym, 4)?.into(),
+                    self.u32(sym, 8)?.into(),
+                    self.u16(sym, 14)?,
+                )
+            };
+            symbols.push(Symbol {
+                name: string_at(strtab, name as usize)?,
+                value,
+                size,
+                section: section as usize,
+            });
+        }
+        Ok(symbols)
+    }
+
+    /// Read an unsigned integer the size of an address in the file.
+    pub fn read_addr(&self, data: &[u8], at: usize) -> Result<u64, Error> {
+        if self.wide {
+            self.u64(data, at)
+        } else {
+            self.u32(data, at).map(u64::from)
+        }
+    }
+
+    fn bytes(&self, offset: u64, len: u64) -> Result<&'a [u8], Error> {
+        let start = usize::try_from(offset).map_err(|_| Error::Truncated)?;
+        let len = usize::try_from(len).map_err(|_| Error::Truncated)?;
+        let end = start.checked_add(len).ok_or(Error::Truncated)?;
+        self.data.get(start..end).ok_or(Error::Truncated)
+    }
+
+    fn array<const N: usize>(
+        &self, data: &[u8], at: usize,
+    ) -> Result<[u8; N], Error> {
+        let bytes = data.get(at..at + N).ok_or(Error::Truncated)?;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    fn u16(&self, data: &[u8], at: usize) -> Result<u16, Error> {
+        let b = self.array(data, at)?;
+        Ok(if self.little {
+            u16::from_le_bytes(b)
+        } else {
+            u16::from_be_bytes(b)
+        })
+    }
+
+    fn u32(&self, data: &[u8], at: usize) -> Result<u32, Error> {
+        let b = self.array(data, at)?;
+        Ok(if self.little {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        })
+    }
+
+    fn u64(&self, data: &[u8], at: usize) -> Result<u64, Error> {
+        let b = self.array(data, at)?;
+        Ok(if self.little {
+            u64::from_le_bytes(b)
+        } else {
+            u64::from_be_bytes(b)
+        })
+    }
+
+    fn read_sections(&self) -> Result<Vec<Section>, Error> {
+        let d = self.data;
+        let (shoff, shentsize, shnum, shstrndx) = if self.wide {
+            (
+                self.u64(d, 0x28)?,
+                self.u16(d, 0x3a)?,
+                self.u16(d, 0x3c)?,
+                self.u16(d, 0x3e)?,
+            )
+        } else {
+            (
+                self.u32(d, 0x20)?.into(),
+                self.u16(d, 0x2e)?,
+                self.u16(d, 0x30)?,
+                self.u16(d, 0x32)?,
+            )
+        };
+        let table =
+            self.bytes(shoff, u64::from(shentsize) * u64::from(shnum))?;
+        let mut sections = Vec::new();
+        let mut names = Vec::new();
+        for sh in table.chunks_exact(shentsize.max(1).into()) {
+            names.push(self.u32(sh, 0)?);
+            let section = if self.wide {
+                Section {
+                    name: String::new(),
+                    kind: self.u32(sh, 4)?,
+                    flags: self.u64(sh, 8)?,
+                    addr: self.u64(sh, 16)?,
+                    offset: self.u64(sh, 24)?,
+                    size: self.u64(sh, 32)?,
+                    link: self.u32(sh, 40)?,
+                    align: self.u64(sh, 48)?,
+                }
+            } else {
+                Section {
+                    name: String::new(),
+                    kind: self.u32(sh, 4)?,
+                    flags: self.u32(sh, 8)?.into(),
+                    addr: self.u32(sh, 12)?.into(),
+                    offset: self.u32(sh, 16)?.into(),
+                    size: self.u32(sh, 20)?.into(),
+                    link: self.u32(sh, 24)?,
+                    align: self.u32(sh, 32)?.into(),
+                }
+            };
+            sections.push(section);
+        }
+        if let Some(strtab) = sections.get(shstrndx as usize) {
+            let strtab = self.section_data(strtab)?;
+            for (section, name) in sections.iter_mut().zip(names) {
+                section.name = string_at(strtab, name as usize)?;
+            }
+        }
+        Ok(sections)
+    }
+}
+
+fn string_at(table: &[u8], at: usize) -> Result<String, Error> {
+    let s = table.get(at..).ok_or(Error::Truncated)?;
+    let len = s.iter().position(|&b| b == 0).ok_or(Error::Truncated)?;
+    Ok(String::from_utf8_lossy(&s[..len]).into_owned())
+}
+
+/// The section holding a linker set.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SetSection {
+    /// The name of the linker set.
+    pub name: String,
+    /// The index of the section within the file.
+    pub index: usize,
+    /// The virtual address of the start of the set.
+    pub addr: u64,
+    /// The size of the set in bytes.
+    pub size: u64,
+}
+
+/// Returns the sections of a file that hold linker sets.
+///
+/// The sections that hold what is recorded beside the entries of a set,
+/// such as its [test entries](crate::test_entry) or the keys of a keyed
+/// set, are not linker sets and are left out.
+pub fn sets(elf: &Elf) -> Vec<SetSection> {
+    sections(elf)
+        .into_iter()
+        .filter(|s| !HELPER_KINDS.iter().any(|kind| is_kind(&s.name, kind)))
+        .collect()
+}
+
+/// Returns every section of a file named as a linker set, including those
+/// that hold what is recorded beside the entries of a set.
+fn sections(elf: &Elf) -> Vec<SetSection> {
+    elf.sections()
+        .iter()
+        .enumerate()
+        .filter_map(|(index, s)| {
+            s.name.strip_prefix(SECTION_PREFIX).map(|name| SetSection {
+                name: name.to_string(),
+                index,
+                addr: s.addr,
+                size: s.size,
+            })
+        })
+        .collect()
+}
+
+/// Returns true if a section, named after [SECTION_PREFIX], holds what is
+/// recorded beside the entries of a set, of a kind such as [TEST_PREFIX],
+/// either of a set named by itself or of a namespaced one.
+fn is_kind(section: &str, kind: &str) -> bool {
+    section.starts_with(kind) || section.contains(&format!("_{}", kind))
+}
+
+fn lines<'a>(elf: &Elf<'a>, name: &str) -> Result<Vec<&'a str>, Error> {
+    let Some(section) = elf.section(name) else {
+        return Ok(Vec::new());
+    };
+    let data = elf.section_data(section)?;
+    let text = std::str::from_utf8(data).map_err(|_| Error::Truncated)?;
+    Ok(text.lines().filter(|l| !l.is_empty()).collect())
+}
+
+/// Returns the descriptions of the declared sets embedded in a file, one
+/// line of JSON for each.
+///
+/// The format of the lines is described in the [schema](crate::schema)
+/// module.  A file built without this crate has no descriptions.
+pub fn schema<'a>(elf: &Elf<'a>) -> Result<Vec<&'a str>, Error> {
+    lines(elf, crate::schema::SECTION)
+}
+
+/// Returns the format strings interned into a file, one line of JSON for
+/// each.
+///
+/// The format of the lines is described in the
+/// [interned](crate::interned) module.
+pub fn interned<'a>(elf: &Elf<'a>) -> Result<Vec<&'a str>, Error> {
+    lines(elf, crate::interned::SECTION)
+}
+
+/// Returns the sets that have entries placed by the
+/// [test_entry](crate::test_entry) attribute, which a release build should
+/// not, named after the sets themselves.
+///
+/// The section of the test entries of each set is found in the
+/// [schema](crate::schema), or, in a file without one, by its name.
+pub fn sets_with_test_entries(elf: &Elf) -> Vec<SetSection> {
+    let declared = schema(elf)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|line| {
+            let test = schema_string(line, "test_section")?;
+            Some((test, schema_string(line, "section")?))
+        })
+        .collect::<BTreeMap<_, _>>();
+    sections(elf)
+        .into_iter()
+        .filter(|s| s.size > 0)
+        .filter_map(|mut s| {
+            let section = format!("{}{}", SECTION_PREFIX, s.name);
+            s.name = match declared.get(section.as_str()) {
+                Some(set) => set.strip_prefix(SECTION_PREFIX)?.to_string(),
+                None => test_set_name(&s.name)?,
+            };
+            Some(s)
+        })
+        .collect()
+}
+
+/// Returns the name of the set whose test entries a section holds, given
+/// the name of the section after [SECTION_PREFIX], such as `stuff` for
+/// `_test_stuff` and `app__stuff` for `app___test_stuff`.
+fn test_set_name(section: &str) -> Option<String> {
+    if let Some(name) = section.strip_prefix(TEST_PREFIX) {
+        return Some(name.to_string());
+    }
+    let (namespace, name) =
+        section.split_once(&format!("__{}", TEST_PREFIX))?;
+    Some(format!("{}__{}", namespace, name))
+}
+
+/// Returns a string from the description of a set in the schema, which
+/// holds no escapes, since it names a section.
+fn schema_string<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let key = format!(",\"{}\":\"", key);
+    let at = line.find(&key)? + key.len();
+    line[at..].split('"').next()
+}
+
+/// Returns the symbols of the entries in a linker set.
+pub fn entries(elf: &Elf, set: &SetSection) -> Result<Vec<Symbol>, Error> {
+    let by_section = elf.entries.get_or_init(|| {
+        let mut by_section = BTreeMap::<_, Vec<_>>::new();
+        for sym in elf.symbols()? {
+            if sym.size > 0 {
+                by_section.entry(sym.section).or_default().push(sym);
+            }
+        }
+        for symbols in by_section.values_mut() {
+            symbols.sort_by_key(|s| s.value);
+        }
+        Ok(by_section)
+    });
+    let by_section = by_section.as_ref().map_err(|e| *e)?;
+    Ok(by_section.get(&set.index).cloned().unwrap_or_default())
+}
+
+/// Returns a number from the description of a set in the schema.
+fn schema_number(
+    elf: &Elf, set: &str, key: &str,
+) -> Result<Option<u64>, Error> {
+    let prefix = format!("{{\"name\":\"{}\",", set);
+    let key = format!(",\"{}\":", key);
+    for line in schema(elf)? {
+        if line.starts_with(&prefix) {
+            let at = line.rfind(&key).ok_or(Error::Truncated)?;
+            let (n, _) =
+                decimal(&line[at + key.len()..]).ok_or(Error::Truncated)?;
+            return Ok(Some(n as u64));
+        }
+    }
+    Ok(None)
+}
+
+/// Returns how the elements of a linker set fall across cache lines, if
+/// the size of its elements is recorded in the [schema].
+pub fn layout(elf: &Elf, set: &SetSection) -> Result<Option<Report>, Error> {
+    let size = schema_number(elf, &set.name, "size")?.unwrap_or(0);
+    if size == 0 {
+        return Ok(None);
+    }
+    let align = schema_number(elf, &set.name, "align")?.unwrap_or(1);
+    let len = set.size / size;
+    Ok(Some(Report::new(
+        size as usize,
+        align as usize,
+        len as usize,
+        set.addr,
+    )))
+}
+
+fn decimal(s: &str) -> Option<(usize, &str)> {
+    let digits = s.bytes().take_while(u8::is_ascii_digit).count();
+    Some((s[..digits].parse().ok()?, &s[digits..]))
+}
+
+/// Returns the name of the crate that defines a mangled symbol.
+///
+/// Both the legacy and v0 mangling schemes are understood.
+pub fn crate_of(symbol: &str) -> Option<&str> {
+    if let Some(rest) = symbol.strip_prefix("_ZN") {
+        let (len, rest) = decimal(rest)?;
+        rest.get(..len)
+    } else if let Some(mut rest) = symbol.strip_prefix("_R") {
+        while let Some(r) = rest.strip_prefix('N') {
+            rest = r.get(1..)?;
+        }
+        rest = rest.strip_prefix('C')?;
+        if let Some(r) = rest.strip_prefix('s') {
+            rest = &r[r.find('_')? + 1..];
+        }
+        let (len, rest) = decimal(rest)?;
+        let rest = rest.strip_prefix('_').unwrap_or(rest);
+        rest.get(..len)
+    } else {
+        None
+    }
+}
+
+/// Name under which entries of unknown origin are reported.
+pub const UNKNOWN_CRATE: &str = "?";
+
+/// Returns the number of bytes contributed to a linker set by each crate.
+pub fn crate_sizes(
+    elf: &Elf, set: &SetSection,
+) -> Result<BTreeMap<String, u64>, Error> {
+    let mut sizes = BTreeMap::new();
+    let mut total = 0;
+    for sym in entries(elf, set)? {
+        let name = crate_of(&sym.name).unwrap_or(UNKNOWN_CRATE);
+        *sizes.entry(name.to_string()).or_insert(0) += sym.size;
+        total += sym.size;
+    }
+    if total < set.size {
+        *sizes.entry(UNKNOWN_CRATE.to_string()).or_insert(0) +=
+            set.size - total;
+    }
+    Ok(sizes)
+}
+
+/// An entry of a linker set contributed by a crate denied by a
+/// [DenyList].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Denied {
+    /// The name of the linker set.
+    pub set: String,
+    /// The name of the crate.
+    pub krate: String,
+    /// The demangled name of the entry, or the number of bytes of the set
+    /// not held by any symbol, attributed to [UNKNOWN_CRATE].
+    pub entry: String,
+}
+
+/// Returns the entries of the linker sets of a file that a list forbids
+/// the crates defining them to contribute, by set and address.
+pub fn denied(elf: &Elf, list: &DenyList) -> Result<Vec<Denied>, Error> {
+    let mut denied = Vec::new();
+    for set in sets(elf) {
+        let mut push = |krate: &str, entry| {
+            if list.denies(&set.name, krate) {
+                denied.push(Denied {
+                    set: set.name.clone(),
+                    krate: krate.to_string(),
+                    entry,
+                });
+            }
+        };
+        let mut total = 0;
+        for sym in entries(elf, &set)? {
+            let krate = crate_of(&sym.name).unwrap_or(UNKNOWN_CRATE);
+            push(krate, demangle(&sym.name));
+            total += sym.size;
+        }
+        if total < set.size {
+            let gap =
+                format!("{} bytes not held by any symbol", set.size - total);
+            push(UNKNOWN_CRATE, gap);
+        }
+    }
+    Ok(denied)
+}
+
+/// Returns a readable name for a mangled symbol.
+///
+/// Paths of symbols mangled with the legacy or the v0 scheme are
+/// reconstructed without their hashes and disambiguators, so that names
+/// are stable from build to build.  Other symbols, and v0 symbols using
+/// parts of the scheme that entries do not, such as `dyn` types, are
+/// returned unchanged.
+pub fn demangle(symbol: &str) -> String {
+    if let Some(rest) = symbol.strip_prefix("_R") {
+        return V0::new(rest)
+            .demangle()
+            .unwrap_or_else(|| symbol.to_string());
+    }
+    let Some(mut rest) = symbol.strip_prefix("_ZN") else {
+        return symbol.to_string();
+    };
+    let mut parts = Vec::new();
+    while let Some((len, r)) = decimal(rest) {
+        let Some(part) = r.get(..len) else {
+            return symbol.to_string();
+        };
+        parts.push(part);
+        rest = &r[len..];
+    }
+    if rest != "E" || parts.is_empty() {
+        return symbol.to_string();
+    }
+    let last = parts[parts.len() - 1];
+    let is_hash = last.len() == 17
+        && last.starts_with('h')
+        && last[1..].bytes().all(|b| b.is_ascii_hexdigit());
+    if is_hash {
+        parts.pop();
+    }
+    parts.join("::")
+}
+
+/// A parser of the paths of symbols mangled with the v0 scheme, given the
+/// symbol after its `_R`, to which backreferences are relative.
+struct V0<'a> {
+    s: &'a [u8],
+    at: usize,
+    /* within a type, whose generic arguments follow no :: */
+    in_type: usize,
+}
+
+impl<'a> V0<'a> {
+    fn new(s: &'a str) -> Self {
+        Self {
+            s: s.as_bytes(),
+            at: 0,
+            in_type: 0,
+        }
+    }
+
+    fn demangle(&mut self) -> Option<String> {
+        /* an encoding version, which is omitted for the first */
+        while self.peek()?.is_ascii_digit() {
+            self.at += 1;
+        }
+        self.path()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.s.get(self.at).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let c = self.peek()?;
+        self.at += 1;
+        Some(c)
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        let found = self.peek() == Some(c);
+        self.at += usize::from(found);
+        found
+    }
+
+    /// A number in base 62 terminated by `_`, where `_` alone is zero.
+    fn base62(&mut self) -> Option<u64> {
+        if self.eat(b'_') {
+            return Some(0);
+        }
+        let mut n = 0u64;
+        loop {
+            let d = match self.next()? {
+                c @ b'0'..=b'9' => c - b'0',
+                c @ b'a'..=b'z' => c - b'a' + 10,
+                c @ b'A'..=b'Z' => c - b'A' + 36,
+                b'_' => return n.checked_add(1),
+                _ => return None,
+            };
+            n = n.checked_mul(62)?.checked_add(d.into())?;
+        }
+    }
+
+    /// A number given by a tag, such as `s` for a disambiguator, or zero if
+    /// the tag is absent.
+    fn tagged(&mut self, tag: u8) -> Option<u64> {
+        if self.eat(tag) {
+            self.base62()?.checked_add(1)
+        } else {
+            Some(0)
+        }
+    }
+
+    fn ident(&mut self) -> Option<&'a str> {
+        self.tagged(b's')?;
+        if self.peek() == Some(b'u') {
+            return None; // punycode
+        }
+        let start = self.at;
+        if !self.eat(b'0') {
+            while self.peek()?.is_ascii_digit() {
+                self.at += 1;
+            }
+        }
+        let len = std::str::from_utf8(&self.s[start..self.at]).ok()?;
+        let len = len.parse::<usize>().ok()?;
+        self.eat(b'_');
+        let ident = self.s.get(self.at..self.at + len)?;
+        self.at += len;
+        std::str::from_utf8(ident).ok()
+    }
+
+    /// Parse what a backreference points to, returning to where it was.
+    fn backref<T>(&mut self, f: fn(&mut Self) -> Option<T>) -> Option<T> {
+        let to = usize::try_from(self.base62()?).ok()?;
+        if to >= self.at {
+            return None;
+        }
+        let back = std::mem::replace(&mut self.at, to);
+        let parsed = f(self);
+        self.at = back;
+        parsed
+    }
+
+    fn path(&mut self) -> Option<String> {
+        Some(match self.next()? {
+            b'C' => self.ident()?.to_string(),
+            b'N' => {
+                let ns = self.next()?;
+                let parent = self.path()?;
+                let n = self.tagged(b's')?;
+                let ident = self.ident()?;
+                if ns.is_ascii_lowercase() && ident.is_empty() {
+                    parent
+                } else if ns.is_ascii_lowercase() {
+                    format!("{}::{}", parent, ident)
+                } else {
+                    let kind = match ns {
+                        b'C' => "closure".to_string(),
+                        b'S' => "shim".to_string(),
+                        _ => char::from(ns).to_string(),
+                    };
+                    let sep = if ident.is_empty() { "" } else { ":" };
+                    format!("{}::{{{}{}{}#{}}}", parent, kind, sep, ident, n)
+                }
+            }
+            b'M' => {
+                self.impl_path()?;
+                format!("<{}>", self.ty()?)
+            }
+            b'X' => {
+                self.impl_path()?;
+                let ty = self.ty()?;
+                format!("<{} as {}>", ty, self.trait_path()?)
+            }
+            b'Y' => {
+                let ty = self.ty()?;
+                format!("<{} as {}>", ty, self.trait_path()?)
+            }
+            b'I' => {
+                let path = self.path()?;
+                let sep = if self.in_type > 0 { "" } else { "::" };
+                format!("{}{}<{}>", path, sep, self.args()?)
+            }
+            b'B' => self.backref(Self::path)?,
+            _ => return None,
+        })
+    }
+
+    /// The path of a trait, which is written as a type is.
+    fn trait_path(&mut self) -> Option<String> {
+        self.in_type += 1;
+        let path = self.path();
+        self.in_type -= 1;
+        path
+    }
+
+    fn impl_path(&mut self) -> Option<()> {
+        self.tagged(b's')?;
+        self.path().map(drop)
+    }
+
+    fn args(&mut self) -> Option<String> {
+        let mut args = Vec::new();
+        while !self.eat(b'E') {
+            if self.eat(b'L') {
+                self.base62()?;
+                args.push("'_".to_string());
+            } else if self.eat(b'K') {
+                args.push(self.constant()?);
+            } else {
+                args.push(self.ty()?);
+            }
+        }
+        Some(args.join(", "))
+    }
+
+    fn ty(&mut self) -> Option<String> {
+        self.in_type += 1;
+        let ty = self.ty_inner();
+        self.in_type -= 1;
+        ty
+    }
+
+    fn ty_inner(&mut self) -> Option<String> {
+        let basic = match self.peek()? {
+            b'a' => "i8",
+            b'b' => "bool",
+            b'c' => "char",
+            b'd' => "f64",
+            b'e' => "str",
+            b'f' => "f32",
+            b'h' => "u8",
+            b'i' => "isize",
+            b'j' => "usize",
+            b'l' => "i32",
+            b'm' => "u32",
+            b'n' => "i128",
+            b'o' => "u128",
+            b'p' => "_",
+            b's' => "i16",
+            b't' => "u16",
+            b'u' => "()",
+            b'v' => "...",
+            b'x' => "i64",
+            b'y' => "u64",
+            b'z' => "!",
+            _ => "",
+        };
+        if !basic.is_empty() {
+            self.at += 1;
+            return Some(basic.to_string());
+        }
+        Some(match self.next()? {
+            c @ (b'R' | b'Q') => {
+                if self.eat(b'L') {
+                    self.base62()?;
+                }
+                let mutable = if c == b'Q' { "mut " } else { "" };
+                format!("&{}{}", mutable, self.ty()?)
+            }
+            c @ (b'P' | b'O') => {
+                let mutable = if c == b'O' { "mut" } else { "const" };
+                format!("*{} {}", mutable, self.ty()?)
+            }
+            b'S' => format!("[{}]", self.ty()?),
+            b'A' => {
+                let ty = self.ty()?;
+                format!("[{}; {}]", ty, self.constant()?)
+            }
+            b'T' => {
+                let mut tys = Vec::new();
+                while !self.eat(b'E') {
+                    tys.push(self.ty()?);
+                }
+                match tys.len() {
+                    1 => format!("({},)", tys[0]),
+                    _ => format!("({})", tys.join(", ")),
+                }
+            }
+            b'B' => self.backref(Self::ty)?,
+            _ => {
+                self.at -= 1;
+                self.path()?
+            }
+        })
+    }
+
+    /// A constant of an integer or boolean type, in hexadecimal.
+    fn constant(&mut self) -> Option<String> {
+        if self.eat(b'B') {
+            return self.backref(Self::constant);
+        }
+        let ty = self.next()?;
+        if ty == b'p' {
+            return Some("_".to_string());
+        }
+        if !b"abhijlmnostxy".contains(&ty) {
+            return None;
+        }
+        let negative = self.eat(b'n');
+        let start = self.at;
+        while self.peek()?.is_ascii_hexdigit() {
+            self.at += 1;
+        }
+        let digits = std::str::from_utf8(&self.s[start..self.at]).ok()?;
+        let value = u128::from_str_radix(digits, 16).unwrap_or(0);
+        if !self.eat(b'_') {
+            return None;
+        }
+        Some(match (ty, negative) {
+            (b'b', _) => (value != 0).to_string(),
+            (_, true) => format!("-{}", value),
+            _ => value.to_string(),
+        })
+    }
+}
+
+/// A change to an entry between two artifacts.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum EntryChange {
+    /// The entry is only in the new artifact.
+    Added(String, u64),
+    /// The entry is only in the old artifact.
+    Removed(String, u64),
+    /// The entry changed size from the old artifact to the new one.
+    Resized(String, u64, u64),
+}
+
+/// The differences in one linker set between two artifacts.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SetDiff {
+    /// The name of the linker set.
+    pub name: String,
+    /// The size of the set in the old artifact, if present.
+    pub old_size: Option<u64>,
+    /// The size of the set in the new artifact, if present.
+    pub new_size: Option<u64>,
+    /// The entries that changed, ordered by name.
+    pub changes: Vec<EntryChange>,
+}
+
+fn entry_sizes(
+    elf: &Elf, set: Option<&SetSection>,
+) -> Result<BTreeMap<String, u64>, Error> {
+    let mut sizes = BTreeMap::new();
+    if let Some(set) = set {
+        for sym in entries(elf, set)? {
+            *sizes.entry(demangle(&sym.name)).or_insert(0) += sym.size;
+        }
+    }
+    Ok(sizes)
+}
+
+/// Compare entries, given as names and sizes, of old and new versions of
+/// a linker set.
+pub fn diff_entries(
+    old: &BTreeMap<String, u64>, new: &BTreeMap<String, u64>,
+) -> Vec<EntryChange> {
+    let mut changes = Vec::new();
+    for (name, &size) in old {
+        match new.get(name) {
+            None => changes.push(EntryChange::Removed(name.clone(), size)),
+            Some(&n) if n != size => {
+                changes.push(EntryChange::Resized(name.clone(), size, n))
+            }
+            Some(_) => (),
+        }
+    }
+    for (name, &size) in new {
+        if !old.contains_key(name) {
+            changes.push(EntryChange::Added(name.clone(), size));
+        }
+    }
+    changes.sort_by(|a, b| change_name(a).cmp(change_name(b)));
+    changes
+}
+
+fn change_name(change: &EntryChange) -> &str {
+    match change {
+        EntryChange::Added(n, _)
+        | EntryChange::Removed(n, _)
+        | EntryChange::Resized(n, _, _) => n,
+    }
+}
+
+/// Compare the linker sets of two artifacts, returning the sets that
+/// differ.
+pub fn diff(old: &Elf, new: &Elf) -> Result<Vec<SetDiff>, Error> {
+    let old_sets = sets(old);
+    let new_sets = sets(new);
+    let mut names = old_sets
+        .iter()
+        .chain(&new_sets)
+        .map(|s| s.name.as_str())
+        .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+
+    let mut diffs = Vec::new();
+    for name in names {
+        let o = old_sets.iter().find(|s| s.name == name);
+        let n = new_sets.iter().find(|s| s.name == name);
+        let changes =
+            diff_entries(&entry_sizes(old, o)?, &entry_sizes(new, n)?);
+        let old_size = o.map(|s| s.size);
+        let new_size = n.map(|s| s.size);
+        if old_size != new_size || !changes.is_empty() {
+            diffs.push(SetDiff {
+                name: name.to_string(),
+                old_size,
+                new_size,
+                changes,
+            });
+        }
+    }
+    Ok(diffs)
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    pub(crate) fn exe() -> Vec<u8> {
+        std::fs::read(std::env::current_exe().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_sets() {
+        let data = exe();
+        let elf = Elf::parse(&data).unwrap();
+        let sets = sets(&elf);
+        let stuff = sets.iter().find(|s| s.name == "stuff").unwrap();
+        assert_eq!(stuff.size, 24);
+        let sizes = crate_sizes(&elf, stuff).unwrap();
+        assert_eq!(sizes, BTreeMap::from([("linker_set".to_string(), 24)]));
+        let fixtures = "linker_set___test_fixtures";
+        assert!(sections(&elf).iter().any(|s| s.name == fixtures));
+        for set in &sets {
+            let kind = HELPER_KINDS.iter().find(|k| is_kind(&set.name, k));
+            assert!(kind.is_none(), "{}", set.name);
+        }
+    }
+
+    #[test]
+    fn test_is_kind() {
+        assert!(is_kind("_test_stuff", TEST_PREFIX));
+        assert!(is_kind("app___keyed_stuff", "_keyed_"));
+        assert!(is_kind("_disabled__meta_stuff", "_meta_"));
+        assert!(!is_kind("stuff", TEST_PREFIX));
+        assert!(!is_kind("app__stuff", "_keyed_"));
+        assert!(!is_kind("_disabled_stuff", "_meta_"));
+    }
+
+    #[test]
+    fn test_sets_with_test_entries() {
+        let data = exe();
+        let elf = Elf::parse(&data).unwrap();
+        let sets = sets_with_test_entries(&elf);
+        let stuff = sets.iter().find(|s| s.name == "stuff").unwrap();
+        assert_eq!(stuff.size, 8);
+        let names = entries(&elf, stuff).unwrap();
+        assert_eq!(demangle(&names[0].name), "linker_set::test::TEST_ONLY");
+        assert!(sets.iter().all(|s| s.name != "consts"));
+        let fixtures = "linker_set__fixtures";
+        let fixtures = sets.iter().find(|s| s.name == fixtures).unwrap();
+        assert_eq!(fixtures.size, 2);
+    }
+
+    crate::set_declare!(fixtures, u16, namespaced, test_entries);
+
+    #[crate::test_entry(self::fixtures)]
+    static FIXTURE: u16 = 7;
+
+    #[test]
+    fn test_test_set_name() {
+        assert_eq!(test_set_name("_test_stuff").unwrap(), "stuff");
+        assert_eq!(test_set_name("app___test_stuff").unwrap(), "app__stuff");
+        assert!(test_set_name("app__stuff").is_none());
+        let line = r#"{"name":"x","section":"set_a__x","type":"u8"}"#;
+        assert_eq!(schema_string(line, "section"), Some("set_a__x"));
+        assert_eq!(schema_string(line, "test_section"), None);
+    }
+
+    #[test]
+    #[cfg(feature = "schema")]
+    fn test_schema() {
+        let data = exe();
+        let elf = Elf::parse(&data).unwrap();
+        let schema = schema(&elf).unwrap();
+        let expect = concat!(
+            r#"{"name":"stuff","crate":"linker_set","#,
+            r#""module":"linker_set::test::stuff","section":"set_stuff","#,
+            r#""test_section":"set__test_stuff","type":"u64","#,
+            r#""size":8,"align":8}"#,
+        );
+        assert!(schema.contains(&expect), "{:?}", schema);
+    }
+
+    #[test]
+    #[cfg(feature = "schema")]
+    fn test_layout() {
+        use crate::test::stuff;
+
+        let data = exe();
+        let elf = Elf::parse(&data).unwrap();
+        let sets = sets(&elf);
+        let set = sets.iter().find(|s| s.name == "stuff").unwrap();
+        let report = layout(&elf, set).unwrap().unwrap();
+        assert_eq!(report, crate::set!(stuff).layout_report());
+        assert_eq!((report.size, report.len, report.per_line), (8, 3, 8));
+    }
+
+    #[test]
+    fn test_denied() {
+        let data = exe();
+        let elf = Elf::parse(&data).unwrap();
+        let list = DenyList::default().deny("linker_set", Some("stuff"));
+        let denied = denied(&elf, &list).unwrap();
+        assert_eq!(denied.len(), 3);
+        assert!(denied.iter().all(|d| d.set == "stuff"));
+        assert!(denied.iter().all(|d| d.krate == "linker_set"));
+        let list = DenyList::default().deny("other", None);
+        assert_eq!(super::denied(&elf, &list).unwrap(), []);
+    }
+
+    #[test]
+    fn test_crate_of() {
+        assert_eq!(crate_of("_ZN3foo3bar17h0123456789abcdefE"), Some("foo"));
+        assert_eq!(crate_of("_RNvNtCs1234_7mycrate4test3FOO"), Some("mycrate"));
+        assert_eq!(crate_of("_RNvCs1_4__foo3BAR"), Some("_foo"));
+        assert_eq!(crate_of("plain"), None);
+    }
+
+    #[test]
+    fn test_demangle() {
+        let sym = "_ZN10linker_set4test3FOO17h0123456789abcdefE";
+        assert_eq!(demangle(sym), "linker_set::test::FOO");
+        assert_eq!(demangle("_ZN3foo3barE"), "foo::bar");
+        assert_eq!(demangle("no_mangle"), "no_mangle");
+
+        let v0 = [
+            (
+                "_RNvNtCs9cbNMGGDgAl_10linker_set4test9TEST_ONLY",
+                "linker_set::test::TEST_ONLY",
+            ),
+            (
+                "_RNvNtNtCs1_10linker_set4test5stuff11___EMPTY_SET",
+                "linker_set::test::stuff::__EMPTY_SET",
+            ),
+            (
+                "_RNvNvNtCs1_10linker_set10build_info1__10BUILD_INFO",
+                "linker_set::build_info::_::BUILD_INFO",
+            ),
+            (
+                "_RNvNvMNtCs1_10linker_set11diagnosticsNtB4_11CompileFail\
+                 5check4NEXT",
+                "<linker_set::diagnostics::CompileFail>::check::NEXT",
+            ),
+            (
+                "_RNvNCNvNtCs1_3std9panicking12default_hook011FIRST_PANIC",
+                "std::panicking::default_hook::{closure#0}::FIRST_PANIC",
+            ),
+            (
+                "_RNvXs_NtCs1_3app1aINtB4_4WrapRShENtNtB6_3fmt7Display3fmt",
+                "<app::a::Wrap<&[u8]> as app::fmt::Display>::fmt",
+            ),
+            ("_RINtNtCs1_3app5table5TableKj8_E", "app::table::Table::<8>"),
+        ];
+        for (sym, name) in v0 {
+            assert_eq!(demangle(sym), name);
+        }
+        assert_eq!(
+            demangle("_RNvCs1_3appDNtB2_1TEL_"),
+            "_RNvCs1_3appDNtB2_1TEL_"
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let data = exe();
+        let elf = Elf::parse(&data).unwrap();
+        assert_eq!(diff(&elf, &elf).unwrap(), []);
+
+        let old = BTreeMap::from([
+            ("a".to_string(), 8),
+            ("b".to_string(), 8),
+            ("c".to_string(), 8),
+        ]);
+        let new = BTreeMap::from([
+            ("b".to_string(), 16),
+            ("c".to_string(), 8),
+            ("d".to_string(), 4),
+        ]);
+        assert_eq!(
+            diff_entries(&old, &new),
+            [
+                EntryChange::Removed("a".to_string(), 8),
+                EntryChange::Resized("b".to_string(), 8, 16),
+                EntryChange::Added("d".to_string(), 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_not_elf() {
+        assert_eq!(Elf::parse(b"hello").err(), Some(Error::NotElf));
+    }
+}
@@ -0,0 +1,232 @@
+//! Rust source replicating the contents of a linker set in a compiled
+//! artifact.
+//!
+//! A host-side simulator or test double of firmware can be built from the
+//! registries of the firmware without running it, by decoding the entries
+//! of a set into const arrays.  Each entry is decoded as by
+//! [query::records], into an `Entry` holding the name of the static and
+//! crate that registered it and the fields given by a [Layout], and its
+//! bytes are kept as well:
+//!
+//! ```text
+//! pub const SIZE: usize = 16;
+//! pub struct Entry { pub name: &'static str, ..., pub priority: u32 }
+//! pub const ENTRIES: [Entry; 2] = [...];
+//! pub const BYTES: [[u8; SIZE]; 2] = [...];
+//! ```
+//!
+//! Pointers in the bytes are not meaningful, since they point into the
+//! artifact, and those in position-independent files are filled in by
+//! relocations.
+//!
+//! A build script can write the source for a set and include it into a
+//! module, or `cargo linker-set generate FILE --set SET [--layout FILE]`
+//! prints it:
+//!
+//! ```no_run
+//! // build.rs
+//! use linker_set::inspect::{generate, query::Layout, Elf};
+//!
+//! let data = std::fs::read("firmware.elf").unwrap();
+//! let elf = Elf::parse(&data).unwrap();
+//! let layout = Layout::parse("priority u32 8").unwrap();
+//! let source = generate::rust(&elf, "handlers", &layout).unwrap();
+//! let out = std::env::var("OUT_DIR").unwrap();
+//! std::fs::write(format!("{}/handlers.rs", out), source).unwrap();
+//! ```
+//!
+//! [dispatch] instead writes a function finding the entry of a set by the
+//! value of a field, such as an opcode, with a `match`, as described in
+//! the [dispatch](crate::dispatch) module, and `--dispatch FIELD --type
+//! TYPE` prints it.
+
+use super::query::{self, Error, Kind, Layout, Value};
+use super::{sets, Elf};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+fn rust_type(kind: Kind) -> String {
+    match kind {
+        Kind::Unsigned(n) => format!("u{}", 8 * n),
+        Kind::Signed(n) => format!("i{}", 8 * n),
+        Kind::Bool => "bool".to_string(),
+    }
+}
+
+/// Returns Rust source with const arrays replicating the entries of a
+/// set, decoding the fields given by a layout.
+pub fn rust(elf: &Elf, set: &str, layout: &Layout) -> Result<String, Error> {
+    let records = query::records(elf, set, layout)?;
+    let sets = sets(elf);
+    let section = sets.iter().find(|s| s.name == set).unwrap();
+    let data = elf.section_data(&elf.sections()[section.index])?;
+    let size = match records.first().and_then(|r| r.get("size")) {
+        Some(Value::Int(size)) => *size as usize,
+        _ => query::element_size(elf, set)?.unwrap_or(0) as usize,
+    };
+
+    let mut out = String::new();
+    let w = &mut out;
+    let n = records.len();
+    let _ = writeln!(w, "// Generated by linker-set from the `{}` set.", set);
+    let _ = writeln!(w, "// Do not edit.");
+    let _ = writeln!(w);
+    let _ = writeln!(w, "/// The size of an entry in bytes.");
+    let _ = writeln!(w, "pub const SIZE: usize = {};", size);
+    let _ = writeln!(w);
+    let _ = writeln!(w, "/// An entry of the `{}` set.", set);
+    let _ = writeln!(w, "#[derive(Clone, Copy, Debug, Eq, PartialEq)]");
+    let _ = writeln!(w, "pub struct Entry {{");
+    let _ = writeln!(w, "    /// The name of the static holding the entry.");
+    let _ = writeln!(w, "    pub name: &'static str,");
+    let _ = writeln!(w, "    /// The crate that registered the entry.");
+    let _ = writeln!(w, "    pub krate: &'static str,");
+    for field in &layout.fields {
+        let _ = writeln!(w, "    /// The `{}` field.", field.name);
+        let (name, ty) = (&field.name, rust_type(field.kind));
+        let _ = writeln!(w, "    pub {}: {},", name, ty);
+    }
+    let _ = writeln!(w, "}}");
+    let _ = writeln!(w);
+    let _ = writeln!(w, "/// The entries of the `{}` set.", set);
+    let _ = writeln!(w, "pub const ENTRIES: [Entry; {}] = [", n);
+    for record in &records {
+        let _ = writeln!(w, "    Entry {{");
+        for (name, value) in &record.fields {
+            let name = match name.as_str() {
+                "address" | "size" => continue,
+                "crate" => "krate",
+                name => name,
+            };
+            let value = match value {
+                Value::Str(s) => format!("{:?}", s),
+                value => value.to_string(),
+            };
+            let _ = writeln!(w, "        {}: {},", name, value);
+        }
+        let _ = writeln!(w, "    }},");
+    }
+    let _ = writeln!(w, "];");
+    let _ = writeln!(w);
+    let _ = writeln!(w, "/// The bytes of the entries of the `{}` set.", set);
+    let _ = writeln!(w, "pub const BYTES: [[u8; SIZE]; {}] = [", n);
+    for record in &records {
+        let Some(Value::Int(addr)) = record.get("address") else {
+            unreachable!();
+        };
+        let start = (*addr as u64 - section.addr) as usize;
+        let bytes = data.get(start..start + size).unwrap_or(&[]);
+        let bytes = match bytes.len() {
+            0 => vec!["0".to_string(); size],
+            _ => bytes.iter().map(|b| format!("{:#04x}", b)).collect(),
+        };
+        let _ = writeln!(w, "    [{}],", bytes.join(", "));
+    }
+    let _ = writeln!(w, "];");
+    Ok(out)
+}
+
+/// Returns Rust source with a function, `lookup`, that finds the entry of
+/// a set by the value of a field, with a `match` over the values of the
+/// entries in the artifact.
+///
+/// The field must be an integer in the layout, and `element` names the
+/// element type of the set.  Each arm names the static holding an entry
+/// by its path, which begins with the name of its crate, so the statics
+/// must be visible where the source is included, and a crate including
+/// the lookup of its own entries must declare `extern crate self as
+/// name;`.  A value matching no entry is passed to `fallback`, which the
+/// including module must define, so that an entry registered after the
+/// source was generated is still found, as by a `dispatch::Table`.
+pub fn dispatch(
+    elf: &Elf, set: &str, layout: &Layout, field: &str, element: &str,
+) -> Result<String, Error> {
+    let kind = layout.fields.iter().find(|f| f.name == field);
+    let Some(Kind::Unsigned(_) | Kind::Signed(_)) = kind.map(|f| f.kind) else {
+        let msg = format!("{} is not an integer field of the layout", field);
+        return Err(Error::Dispatch(msg));
+    };
+    let ty = rust_type(kind.unwrap().kind);
+    let mut arms = BTreeMap::new();
+    for record in query::records(elf, set, layout)? {
+        let (Some(Value::Str(name)), Some(Value::Int(value))) =
+            (record.get("name"), record.get(field))
+        else {
+            let msg = "an entry has no name".to_string();
+            return Err(Error::Dispatch(msg));
+        };
+        if let Some(other) = arms.insert(*value, name.clone()) {
+            let msg =
+                format!("{} and {} share {} {}", other, name, field, value);
+            return Err(Error::Dispatch(msg));
+        }
+    }
+
+    let mut out = String::new();
+    let w = &mut out;
+    let _ = writeln!(w, "// Generated by linker-set from the `{}` set.", set);
+    let _ = writeln!(w, "// Do not edit.");
+    let _ = writeln!(w);
+    let _ = writeln!(w, "/// Returns the entry of the `{}` set", set);
+    let _ = writeln!(w, "/// whose `{}` field has a value.", field);
+    let _ = writeln!(w, "#[inline]");
+    let _ = writeln!(
+        w,
+        "pub fn lookup({}: {}) -> Option<&'static {}> {{",
+        field, ty, element
+    );
+    let _ = writeln!(w, "    match {} {{", field);
+    for (value, name) in &arms {
+        let _ = writeln!(w, "        {} => Some(&{}),", value, name);
+    }
+    let _ = writeln!(w, "        _ => fallback({}),", field);
+    let _ = writeln!(w, "    }}");
+    let _ = writeln!(w, "}}");
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::inspect::test::exe;
+
+    #[test]
+    fn test_rust() {
+        let data = exe();
+        let elf = Elf::parse(&data).unwrap();
+        let layout = Layout::parse("low u8 0\nhigh u8 7").unwrap();
+        let source = rust(&elf, "stuff", &layout).unwrap();
+        assert!(source.contains("pub const SIZE: usize = 8;"));
+        assert!(source.contains("pub const ENTRIES: [Entry; 3] = ["));
+        assert!(source.contains("        name: \"linker_set::test::BAR\",\n"));
+        assert!(source.contains("        low: 224,\n        high: 86,\n"));
+        assert!(source.contains(
+            "    [0xe0, 0xcc, 0x6a, 0x45, 0x09, 0x93, 0x0e, 0x56],\n"
+        ));
+        assert_eq!(
+            rust(&elf, "nonesuch", &layout),
+            Err(Error::NoSuchSet("nonesuch".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_dispatch() {
+        let data = exe();
+        let elf = Elf::parse(&data).unwrap();
+        let layout = Layout::parse("low u8 0\nodd bool 0").unwrap();
+        let source = dispatch(&elf, "stuff", &layout, "low", "u64").unwrap();
+        assert!(source
+            .contains("pub fn lookup(low: u8) -> Option<&'static u64> {\n"));
+        assert!(source.contains(
+            "        102 => Some(&linker_set::test_use_ext::FOO),\n        \
+             153 => Some(&linker_set::test::FOO),\n        \
+             224 => Some(&linker_set::test::BAR),\n        \
+             _ => fallback(low),\n"
+        ));
+        let e = dispatch(&elf, "stuff", &layout, "odd", "u64").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "cannot dispatch: odd is not an integer field of the layout"
+        );
+    }
+}
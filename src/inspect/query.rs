@@ -0,0 +1,728 @@
+//! Queries over the entries of a linker set in a compiled artifact.
+//!
+//! Each entry of a set is decoded into a [Record] of named values.  Every
+//! record has the fields `name`, the demangled name of the static holding
+//! the entry, `crate`, `address`, and `size`.  The size of each element is
+//! taken from the description embedded by [set_declare!](crate::set_declare),
+//! so a set can be walked even in a stripped file, and otherwise from the
+//! symbols of the entries.
+//!
+//! The fields of the element type itself are decoded according to a
+//! [Layout], a text file with a line for each field giving its name, type,
+//! and offset:
+//!
+//! ```text
+//! # fields of Handler
+//! priority u32 8
+//! enabled bool 12
+//! ```
+//!
+//! The [layout!](crate::layout) macro writes this file for a type, so it
+//! need not be kept up to date by hand.  Integers and `bool` can be
+//! decoded; pointers cannot, since those in position-independent files are
+//! filled in by relocations.
+//!
+//! A [Filter] selects records with comparisons of fields to literals,
+//! joined by `and` and `or` and grouped with parentheses:
+//!
+//! ```text
+//! priority > 10 and (crate == "net" or name == 'app::FALLBACK')
+//! ```
+//!
+//! `cargo linker-set query FILE --set SET [--layout FILE] [--where
+//! FILTER] [--format text|json]` prints the records that match.
+//!
+//! ```no_run
+//! use linker_set::inspect::{self, query, Elf};
+//!
+//! let data = std::fs::read("target/debug/app").unwrap();
+//! let elf = Elf::parse(&data).unwrap();
+//! let layout = query::Layout::parse("priority u32 8").unwrap();
+//! let filter = query::Filter::parse("priority > 10").unwrap();
+//! for record in query::records(&elf, "handlers", &layout).unwrap() {
+//!     if filter.matches(&record) {
+//!         println!("{}", record.to_json());
+//!     }
+//! }
+//! ```
+
+use super::{crate_of, demangle, entries, sets, Elf, UNKNOWN_CRATE};
+use std::cmp::Ordering;
+use std::fmt;
+
+/// An error encountered while running a query.
+#[derive(Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The file could not be read.
+    Elf(super::Error),
+    /// The file has no set of the given name.
+    NoSuchSet(String),
+    /// A line of a layout, numbered from one, is malformed.
+    Layout(usize, String),
+    /// A field of the layout lies outside of an entry.
+    Range(String),
+    /// A filter is malformed.
+    Filter(String),
+    /// The entries of a set cannot be dispatched on a field.
+    Dispatch(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Elf(e) => e.fmt(f),
+            Self::NoSuchSet(set) => write!(f, "no such set: {}", set),
+            Self::Layout(line, msg) => {
+                write!(f, "layout line {}: {}", line, msg)
+            }
+            Self::Range(field) => write!(f, "field {} is outside entry", field),
+            Self::Filter(msg) => write!(f, "bad filter: {}", msg),
+            Self::Dispatch(msg) => write!(f, "cannot dispatch: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<super::Error> for Error {
+    fn from(e: super::Error) -> Self {
+        Self::Elf(e)
+    }
+}
+
+/// A value decoded from an entry or written in a filter.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Value {
+    /// An integer of any width.
+    Int(i128),
+    /// A boolean.
+    Bool(bool),
+    /// A string.
+    Str(String),
+}
+
+impl Value {
+    fn compare(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Self::Int(a), Self::Int(b)) => Some(a.cmp(b)),
+            (Self::Bool(a), Self::Bool(b)) => Some(a.cmp(b)),
+            (Self::Str(a), Self::Str(b)) => Some(a.cmp(b)),
+            _ => None,
+        }
+    }
+
+    fn json(&self, out: &mut String) {
+        match self {
+            Self::Int(n) => out.push_str(&n.to_string()),
+            Self::Bool(b) => out.push_str(&b.to_string()),
+            Self::Str(s) => crate::json::string(out, s),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int(n) => n.fmt(f),
+            Self::Bool(b) => b.fmt(f),
+            Self::Str(s) => s.fmt(f),
+        }
+    }
+}
+
+/// The type of a field that can be decoded.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Kind {
+    /// An unsigned integer of the given number of bytes.
+    Unsigned(usize),
+    /// A signed integer of the given number of bytes.
+    Signed(usize),
+    /// A boolean.
+    Bool,
+}
+
+impl Kind {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "u8" => Self::Unsigned(1),
+            "u16" => Self::Unsigned(2),
+            "u32" => Self::Unsigned(4),
+            "u64" => Self::Unsigned(8),
+            "i8" => Self::Signed(1),
+            "i16" => Self::Signed(2),
+            "i32" => Self::Signed(4),
+            "i64" => Self::Signed(8),
+            "bool" => Self::Bool,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Self::Unsigned(n) | Self::Signed(n) => n,
+            Self::Bool => 1,
+        }
+    }
+
+    fn decode(self, elf: &Elf, data: &[u8]) -> Value {
+        let mut bytes = [0; 8];
+        if elf.is_little_endian() {
+            bytes[..data.len()].copy_from_slice(data);
+        } else {
+            bytes[8 - data.len()..].copy_from_slice(data);
+        }
+        let n = if elf.is_little_endian() {
+            u64::from_le_bytes(bytes)
+        } else {
+            u64::from_be_bytes(bytes)
+        };
+        match self {
+            Self::Unsigned(_) => Value::Int(n.into()),
+            Self::Signed(size) => {
+                let shift = 64 - 8 * size as u32;
+                Value::Int((((n << shift) as i64) >> shift).into())
+            }
+            Self::Bool => Value::Bool(n != 0),
+        }
+    }
+}
+
+/// A type whose values can be decoded from a field of an entry.
+pub trait Scalar {
+    /// The name of the type in a [Layout].
+    const KIND: &'static str;
+}
+
+macro_rules! scalar {
+    ($($type:ty),*) => {
+        $(impl Scalar for $type {
+            const KIND: &'static str = stringify!($type);
+        })*
+    };
+}
+
+scalar!(u8, u16, u32, u64, i8, i16, i32, i64, bool);
+
+#[cfg(target_pointer_width = "64")]
+impl Scalar for usize {
+    const KIND: &'static str = "u64";
+}
+
+#[cfg(target_pointer_width = "64")]
+impl Scalar for isize {
+    const KIND: &'static str = "i64";
+}
+
+#[cfg(target_pointer_width = "32")]
+impl Scalar for usize {
+    const KIND: &'static str = "u32";
+}
+
+#[cfg(target_pointer_width = "32")]
+impl Scalar for isize {
+    const KIND: &'static str = "i32";
+}
+
+/// Returns the line of a [Layout] for a field.
+///
+/// Users should call the [layout!](crate::layout) macro instead of this
+/// function.
+#[doc(hidden)]
+pub fn field<T, F>(name: &str, offset: usize, _: fn(&T) -> &F) -> String
+where
+    F: Scalar,
+{
+    format!("{} {} {}\n", name, F::KIND, offset)
+}
+
+/// Write the [Layout](crate::inspect::query::Layout) of some fields of a type.
+///
+/// Given a type and the names of fields that are integers or booleans,
+/// returns the text of a layout for them.  A test or build step can write
+/// it to a file for `cargo linker-set query --layout`.
+///
+/// ```
+/// #[repr(C)]
+/// struct Handler {
+///     name: &'static str,
+///     priority: u32,
+/// }
+///
+/// let layout = linker_set::layout!(Handler { priority });
+/// assert_eq!(layout, "priority u32 16\n");
+/// ```
+#[macro_export]
+macro_rules! layout {
+    ($type:ty { $($field:ident),* $(,)? }) => {{
+        let mut layout = ::std::string::String::new();
+        $(layout.push_str(&$crate::inspect::query::field(
+            stringify!($field),
+            ::std::mem::offset_of!($type, $field),
+            |t: &$type| &t.$field,
+        ));)*
+        layout
+    }};
+}
+
+/// A field of the element type of a set.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Field {
+    /// The name of the field.
+    pub name: String,
+    /// The type of the field.
+    pub kind: Kind,
+    /// The offset of the field within the element.
+    pub offset: usize,
+}
+
+/// The fields of the element type of a set to decode.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Layout {
+    /// The fields.
+    pub fields: Vec<Field>,
+}
+
+impl Layout {
+    /// Parse a layout, ignoring blank lines and comments starting with `#`.
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let mut fields = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let bad = |msg: &str| Error::Layout(i + 1, msg.to_string());
+            let [name, kind, offset] =
+                line.split_whitespace().collect::<Vec<_>>()[..]
+            else {
+                return Err(bad("expected NAME TYPE OFFSET"));
+            };
+            let kind = Kind::parse(kind).ok_or_else(|| bad("unknown type"))?;
+            let offset = offset.parse().map_err(|_| bad("bad offset"))?;
+            fields.push(Field {
+                name: name.to_string(),
+                kind,
+                offset,
+            });
+        }
+        Ok(Self { fields })
+    }
+}
+
+/// The values decoded from an entry of a set.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Record {
+    /// Each field with its value, in order.
+    pub fields: Vec<(String, Value)>,
+}
+
+impl Record {
+    /// Returns the value of a field.
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.fields.iter().find(|(n, _)| n == name).map(|(_, v)| v)
+    }
+
+    /// Returns the record as a line of JSON.
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{");
+        for (i, (name, value)) in self.fields.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            crate::json::string(&mut out, name);
+            out.push(':');
+            value.json(&mut out);
+        }
+        out.push('}');
+        out
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (name, value)) in self.fields.iter().enumerate() {
+            let sep = if i > 0 { " " } else { "" };
+            write!(f, "{}{}={}", sep, name, value)?;
+        }
+        Ok(())
+    }
+}
+
+pub(super) fn element_size(elf: &Elf, set: &str) -> Result<Option<u64>, Error> {
+    Ok(super::schema_number(elf, set, "size")?)
+}
+
+/// Decode the entries of a set.
+pub fn records(
+    elf: &Elf, set: &str, layout: &Layout,
+) -> Result<Vec<Record>, Error> {
+    let sets = sets(elf);
+    let set = sets
+        .iter()
+        .find(|s| s.name == set)
+        .ok_or_else(|| Error::NoSuchSet(set.to_string()))?;
+    let data = elf.section_data(&elf.sections()[set.index])?;
+    let symbols = entries(elf, set)?;
+
+    let spans = match element_size(elf, &set.name)? {
+        Some(size) if size > 0 => (0..set.size / size)
+            .map(|i| (set.addr + i * size, size))
+            .collect(),
+        _ => symbols
+            .iter()
+            .map(|s| (s.value, s.size))
+            .collect::<Vec<_>>(),
+    };
+
+    let mut records = Vec::new();
+    for (addr, size) in spans {
+        let symbol = symbols.iter().find(|s| s.value == addr);
+        let name = symbol.map_or(String::new(), |s| demangle(&s.name));
+        let krate = symbol
+            .and_then(|s| crate_of(&s.name))
+            .unwrap_or(UNKNOWN_CRATE);
+        let mut fields = vec![
+            ("name".to_string(), Value::Str(name)),
+            ("crate".to_string(), Value::Str(krate.to_string())),
+            ("address".to_string(), Value::Int(addr.into())),
+            ("size".to_string(), Value::Int(size.into())),
+        ];
+        for field in &layout.fields {
+            let len = field.kind.size();
+            if (field.offset + len) as u64 > size {
+                return Err(Error::Range(field.name.clone()));
+            }
+            let start = (addr - set.addr) as usize + field.offset;
+            let value = match data.get(start..start + len) {
+                Some(bytes) => field.kind.decode(elf, bytes),
+                None if data.is_empty() => field.kind.decode(elf, &[]),
+                None => return Err(super::Error::Truncated.into()),
+            };
+            fields.push((field.name.clone(), value));
+        }
+        records.push(Record { fields });
+    }
+    Ok(records)
+}
+
+/// A comparison in a [Filter].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Op {
+    /// `==`, or `=`
+    Eq,
+    /// `!=`
+    Ne,
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+}
+
+impl Op {
+    fn test(self, ord: Ordering) -> bool {
+        match self {
+            Self::Eq => ord.is_eq(),
+            Self::Ne => ord.is_ne(),
+            Self::Lt => ord.is_lt(),
+            Self::Le => ord.is_le(),
+            Self::Gt => ord.is_gt(),
+            Self::Ge => ord.is_ge(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+    Word(String),
+    Value(Value),
+    Op(Op),
+    And,
+    Or,
+    Open,
+    Close,
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, Error> {
+    let bad = |msg: &str| Error::Filter(msg.to_string());
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '"' | '\'' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, q)) if q == c => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, e)) => value.push(e),
+                            None => return Err(bad("unterminated string")),
+                        },
+                        Some((_, ch)) => value.push(ch),
+                        None => return Err(bad("unterminated string")),
+                    }
+                }
+                Token::Value(Value::Str(value))
+            }
+            '=' | '!' | '<' | '>' | '&' | '|' => {
+                let next = chars.peek().map(|&(_, n)| n);
+                let (op, two) = match (c, next) {
+                    ('=', Some('=')) => (Token::Op(Op::Eq), true),
+                    ('=', _) => (Token::Op(Op::Eq), false),
+                    ('!', Some('=')) => (Token::Op(Op::Ne), true),
+                    ('<', Some('=')) => (Token::Op(Op::Le), true),
+                    ('<', _) => (Token::Op(Op::Lt), false),
+                    ('>', Some('=')) => (Token::Op(Op::Ge), true),
+                    ('>', _) => (Token::Op(Op::Gt), false),
+                    ('&', Some('&')) => (Token::And, true),
+                    ('|', Some('|')) => (Token::Or, true),
+                    _ => return Err(bad(&format!("unexpected {:?}", c))),
+                };
+                if two {
+                    chars.next();
+                }
+                op
+            }
+            _ => {
+                let mut end = s.len();
+                while let Some(&(j, n)) = chars.peek() {
+                    if !(n.is_alphanumeric() || "_:-".contains(n)) {
+                        end = j;
+                        break;
+                    }
+                    chars.next();
+                }
+                word(&s[i..end])?
+            }
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+fn word(w: &str) -> Result<Token, Error> {
+    let number = |w: &str| match w.strip_prefix("0x") {
+        Some(hex) => i128::from_str_radix(hex, 16).ok(),
+        None => w.parse().ok(),
+    };
+    Ok(match w {
+        "and" => Token::And,
+        "or" => Token::Or,
+        "true" => Token::Value(Value::Bool(true)),
+        "false" => Token::Value(Value::Bool(false)),
+        _ if w.starts_with(|c: char| c.is_ascii_digit() || c == '-') => {
+            let n = match w.strip_prefix('-') {
+                Some(w) => number(w).map(|n| -n),
+                None => number(w),
+            };
+            let n =
+                n.ok_or_else(|| Error::Filter(format!("bad number {}", w)))?;
+            Token::Value(Value::Int(n))
+        }
+        _ => Token::Word(w.to_string()),
+    })
+}
+
+/// A condition on the fields of a [Record].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Filter {
+    /// Matches every record.
+    All,
+    /// Compares a field to a value.
+    Compare(String, Op, Value),
+    /// Matches if both conditions do.
+    And(Box<Filter>, Box<Filter>),
+    /// Matches if either condition does.
+    Or(Box<Filter>, Box<Filter>),
+}
+
+struct Parser {
+    tokens: std::vec::IntoIter<Token>,
+    peeked: Option<Token>,
+}
+
+impl Parser {
+    fn peek(&mut self) -> Option<&Token> {
+        if self.peeked.is_none() {
+            self.peeked = self.tokens.next();
+        }
+        self.peeked.as_ref()
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        self.peek();
+        self.peeked.take()
+    }
+
+    fn or(&mut self) -> Result<Filter, Error> {
+        let mut f = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            f = Filter::Or(Box::new(f), Box::new(self.and()?));
+        }
+        Ok(f)
+    }
+
+    fn and(&mut self) -> Result<Filter, Error> {
+        let mut f = self.primary()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            f = Filter::And(Box::new(f), Box::new(self.primary()?));
+        }
+        Ok(f)
+    }
+
+    fn primary(&mut self) -> Result<Filter, Error> {
+        let bad = |msg: &str| Error::Filter(msg.to_string());
+        match self.next() {
+            Some(Token::Open) => {
+                let f = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(f),
+                    _ => Err(bad("expected )")),
+                }
+            }
+            Some(Token::Word(field)) => {
+                let Some(Token::Op(op)) = self.next() else {
+                    return Err(bad(&format!(
+                        "expected operator after {}",
+                        field
+                    )));
+                };
+                let value = match self.next() {
+                    Some(Token::Value(v)) => v,
+                    Some(Token::Word(w)) => Value::Str(w),
+                    _ => {
+                        return Err(bad(&format!(
+                            "expected value after {}",
+                            field
+                        )))
+                    }
+                };
+                Ok(Filter::Compare(field, op, value))
+            }
+            _ => Err(bad("expected field or (")),
+        }
+    }
+}
+
+impl Filter {
+    /// Parse a filter.  An empty filter matches every record.
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?.into_iter(),
+            peeked: None,
+        };
+        if parser.peek().is_none() {
+            return Ok(Self::All);
+        }
+        let f = parser.or()?;
+        match parser.next() {
+            None => Ok(f),
+            Some(t) => Err(Error::Filter(format!("unexpected {:?}", t))),
+        }
+    }
+
+    /// Returns true if a record matches the filter.
+    ///
+    /// A comparison with a field that the record lacks, or with a value of
+    /// a different type, does not match.
+    pub fn matches(&self, record: &Record) -> bool {
+        match self {
+            Self::All => true,
+            Self::Compare(field, op, value) => record
+                .get(field)
+                .and_then(|v| v.compare(value))
+                .is_some_and(|ord| op.test(ord)),
+            Self::And(a, b) => a.matches(record) && b.matches(record),
+            Self::Or(a, b) => a.matches(record) || b.matches(record),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::inspect::test::exe;
+
+    #[test]
+    fn test_filter() {
+        let record = Record {
+            fields: vec![
+                ("name".to_string(), Value::Str("a::FOO".to_string())),
+                ("priority".to_string(), Value::Int(-3)),
+                ("on".to_string(), Value::Bool(true)),
+            ],
+        };
+        let matches = |f: &str| Filter::parse(f).unwrap().matches(&record);
+        assert!(matches(""));
+        assert!(matches("priority < 0"));
+        assert!(matches("priority >= -3 and on == true"));
+        assert!(matches("name == a::FOO && priority != 0x10"));
+        assert!(matches("name == \"b\" or (priority > -5 and on != false)"));
+        assert!(!matches("priority == \"-3\""));
+        assert!(!matches("missing == 0"));
+        assert!(Filter::parse("priority >").is_err());
+        assert!(Filter::parse("(priority > 1").is_err());
+        assert!(Filter::parse("priority > 1 2").is_err());
+    }
+
+    #[test]
+    fn test_layout() {
+        #[repr(C)]
+        struct Handler {
+            name: &'static str,
+            priority: i16,
+            enabled: bool,
+        }
+        let text = layout!(Handler { priority, enabled });
+        let layout = Layout::parse(&format!("# comment\n\n{}", text)).unwrap();
+        assert_eq!(layout.fields.len(), 2);
+        assert_eq!(layout.fields[0].kind, Kind::Signed(2));
+        assert_eq!(layout.fields[1].offset, 18);
+        assert_eq!(
+            Layout::parse("x u128 0"),
+            Err(Error::Layout(1, "unknown type".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_records() {
+        let data = exe();
+        let elf = Elf::parse(&data).unwrap();
+        let layout = Layout::parse("value u64 0\nlow u8 0").unwrap();
+        let filter = Filter::parse("value > 0x5000000000000000").unwrap();
+        let mut names = records(&elf, "stuff", &layout)
+            .unwrap()
+            .into_iter()
+            .filter(|r| filter.matches(r))
+            .map(|r| r.get("name").unwrap().to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(
+            names,
+            ["linker_set::test::BAR", "linker_set::test_use_ext::FOO"]
+        );
+
+        let all = records(&elf, "stuff", &layout).unwrap();
+        let foo = all.iter().find(|r| r.get("low") == Some(&Value::Int(0x99)));
+        assert_eq!(foo.unwrap().get("size"), Some(&Value::Int(8)));
+        assert!(foo.unwrap().to_json().starts_with("{\"name\":"));
+
+        let wide = Layout::parse("x u64 4").unwrap();
+        assert_eq!(
+            records(&elf, "stuff", &wide),
+            Err(Error::Range("x".to_string()))
+        );
+        assert_eq!(
+            records(&elf, "nonesuch", &layout),
+            Err(Error::NoSuchSet("nonesuch".to_string()))
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! Format strings interned into a section that need not be loaded.
+//!
+//! For logging on small devices, the [intern!](crate::intern) macro
+//! replaces a format string with a 32-bit id and records the string, with
+//! where it was written, in the [SECTION] section.  The device sends the
+//! id in place of the string, and a host reads the table back from the
+//! binary with [inspect::interned](crate::inspect::interned) or `cargo
+//! linker-set strings FILE` to turn ids into strings again.
+//!
+//! ```
+//! use linker_set::intern;
+//!
+//! # fn main() {
+//! let id: u32 = intern!("temperature {=u16} out of range");
+//! # let _ = id;
+//! # }
+//! ```
+//!
+//! Each entry of the table is a line of JSON:
+//!
+//! ```text
+//! {"id":3502136740,"format":"temperature {=u16} out of range","file":"src/main.rs","line":4}
+//! ```
+//!
+//! The program never reads the table, so the section can be left out of
+//! the loaded image.  With a linker script, mark it as an information
+//! section, as `etc/linker_set_fmt.x` does:
+//!
+//! ```text
+//! SECTIONS { .linker_set_fmt 0 (INFO) : { KEEP(*(.linker_set_fmt)) } }
+//! INSERT AFTER .bss;
+//! ```
+
+use crate::json::Writer;
+
+/// Name of the section holding the interned format strings.
+pub const SECTION: &str = ".linker_set_fmt";
+
+/// Returns the id of a format string written at a place in the source,
+/// a 32-bit FNV-1a hash of all three.
+#[doc(hidden)]
+pub const fn id(format: &str, file: &str, line: u32) -> u32 {
+    const fn hash(mut h: u32, s: &[u8]) -> u32 {
+        let mut i = 0;
+        while i < s.len() {
+            h = (h ^ s[i] as u32).wrapping_mul(0x0100_0193);
+            i += 1;
+        }
+        h
+    }
+    let h = hash(0x811c_9dc5, format.as_bytes());
+    let h = hash(h, &[0]);
+    let h = hash(h, file.as_bytes());
+    hash(h, &line.to_le_bytes())
+}
+
+const fn write<const N: usize>(
+    format: &str, file: &str, line: u32,
+) -> Writer<N> {
+    let w = Writer::new().raw("{\"id\":");
+    let w = w.number(id(format, file, line) as usize);
+    let w = w.raw(",\"format\":").string(format, 0);
+    let w = w.raw(",\"file\":").string(file, 0);
+    let w = w.raw(",\"line\":").number(line as usize);
+    w.raw("}\n")
+}
+
+/// Returns the length of the entry for a format string.
+#[doc(hidden)]
+pub const fn len(format: &str, file: &str, line: u32) -> usize {
+    write::<0>(format, file, line).len()
+}
+
+/// Returns the entry for a format string.
+#[doc(hidden)]
+pub const fn encode<const N: usize>(
+    format: &str, file: &str, line: u32,
+) -> [u8; N] {
+    write::<N>(format, file, line).into_inner()
+}
+
+/// Intern a format string, returning its id.
+///
+/// The id depends on the string and on where the macro is called, so two
+/// calls never share an id, and a host can report where a message came
+/// from.
+#[macro_export]
+macro_rules! intern {
+    ($format:literal) => {{
+        const LEN: usize = $crate::interned::len($format, file!(), line!());
+
+        $crate::__set_section!(
+            [".linker_set_fmt"]
+            #[used]
+            static ENTRY: [u8; LEN] =
+                $crate::interned::encode($format, file!(), line!());
+        );
+
+        const ID: u32 = $crate::interned::id($format, file!(), line!());
+        ID
+    }};
+}
+
+#[cfg(test)]
+mod test {
+    use crate::inspect::{self, Elf};
+
+    #[test]
+    fn test_intern() {
+        let (a, line) = (intern!("a \"quoted\" {}"), line!());
+        let b = intern!("a \"quoted\" {}");
+        assert_ne!(a, b);
+
+        let data = inspect::test::exe();
+        let elf = Elf::parse(&data).unwrap();
+        let table = inspect::interned(&elf).unwrap();
+        let expect = format!(
+            r#"{{"id":{},"format":"a \"quoted\" {{}}","file":"{}","line":{}}}"#,
+            a,
+            file!(),
+            line
+        );
+        assert!(table.contains(&expect.as_str()), "{:?}", table);
+    }
+}
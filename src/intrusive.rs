@@ -0,0 +1,235 @@
+//! Threading the elements of a linker set into intrusive collections.
+//!
+//! Elements of a set are pinned for the life of the program, so an element
+//! can hold the links of an intrusive list or tree of which it is a node.
+//! [collect_into] inserts every element of a set into such a collection,
+//! replacing the one-time linking code that would otherwise run at
+//! startup.  Any collection can implement [Collection]; [List] is a
+//! lock-free singly linked list provided for the common case.
+//!
+//! ```
+//! use linker_set::*;
+//! use linker_set::intrusive::{self, Link, List, Node};
+//!
+//! struct Driver {
+//!     name: &'static str,
+//!     link: Link<Driver>,
+//! }
+//!
+//! impl Node for Driver {
+//!     fn link(&self) -> &Link<Self> {
+//!         &self.link
+//!     }
+//! }
+//!
+//! set_declare!(drivers, Driver);
+//!
+//! #[set_entry(drivers)]
+//! static NULL: Driver = Driver {
+//!     name: "null",
+//!     link: Link::new(),
+//! };
+//!
+//! static DRIVERS: List<Driver> = List::new();
+//!
+//! # fn main() {
+//! intrusive::collect_into(&DRIVERS, set!(drivers));
+//! assert_eq!(DRIVERS.iter().next().unwrap().name, "null");
+//! # }
+//! ```
+
+use crate::LinkerSet;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+
+/// An intrusive collection into which pinned nodes can be inserted.
+pub trait Collection<T>
+where
+    T: 'static,
+{
+    /// Insert a node into the collection.
+    fn insert(&self, node: Pin<&'static T>);
+}
+
+/// Insert every element of a linker set into a collection.
+pub fn collect_into<T, C>(collection: &C, set: LinkerSet<T>)
+where
+    T: 'static,
+    C: Collection<T> + ?Sized,
+{
+    set.iter_pinned().for_each(|node| collection.insert(node));
+}
+
+/// The link embedded in each node of a [List].
+pub struct Link<T> {
+    next: AtomicPtr<T>,
+    linked: AtomicBool,
+}
+
+impl<T> Link<T> {
+    /// Create a link that is not yet in a list.
+    pub const fn new() -> Self {
+        Self {
+            next: AtomicPtr::new(std::ptr::null_mut()),
+            linked: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns true if the node has been inserted into a list.
+    pub fn is_linked(&self) -> bool {
+        self.linked.load(Ordering::Acquire)
+    }
+}
+
+impl<T> Default for Link<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A type that can be a node of a [List].
+pub trait Node: Sized + Sync + 'static {
+    /// Returns the link embedded in the node.
+    fn link(&self) -> &Link<Self>;
+}
+
+/// A lock-free, singly linked, intrusive list of static nodes.
+///
+/// Nodes can be inserted but never removed, so the list can be walked at
+/// any time without locking.  Each node can be in only one list, and
+/// inserting a node a second time does nothing, so collecting a set into
+/// a list more than once, say from two initialization paths, is harmless.
+pub struct List<T> {
+    head: AtomicPtr<T>,
+}
+
+impl<T> List<T>
+where
+    T: Node,
+{
+    /// Create an empty list.
+    pub const fn new() -> Self {
+        Self {
+            head: AtomicPtr::new(std::ptr::null_mut()),
+        }
+    }
+
+    /// Returns an iterator over the nodes of the list, most recently
+    /// inserted first.
+    pub fn iter(&self) -> ListIter<T> {
+        ListIter {
+            next: self.head.load(Ordering::Acquire),
+        }
+    }
+
+    /// Returns true if the list has no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Acquire).is_null()
+    }
+}
+
+impl<T> Default for List<T>
+where
+    T: Node,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Collection<T> for List<T>
+where
+    T: Node,
+{
+    fn insert(&self, node: Pin<&'static T>) {
+        let node = node.get_ref();
+        let link = node.link();
+        if link.linked.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let ptr = node as *const T as *mut T;
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            link.next.store(head, Ordering::Relaxed);
+            match self.head.compare_exchange_weak(
+                head,
+                ptr,
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(h) => head = h,
+            }
+        }
+    }
+}
+
+/// An iterator over the nodes of a [List].
+pub struct ListIter<T> {
+    next: *const T,
+}
+
+impl<T> Iterator for ListIter<T>
+where
+    T: Node,
+{
+    type Item = &'static T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        /* nodes are only ever inserted from Pin<&'static T>, so each
+         * pointer in the list is valid for the life of the program */
+        let node = unsafe { self.next.as_ref() }?;
+        self.next = node.link().next.load(Ordering::Acquire);
+        Some(node)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::*;
+
+    pub struct Task {
+        id: u8,
+        link: Link<Task>,
+    }
+
+    impl Node for Task {
+        fn link(&self) -> &Link<Self> {
+            &self.link
+        }
+    }
+
+    set_declare!(tasks, Task);
+
+    #[set_entry(tasks)]
+    static ONE: Task = Task {
+        id: 1,
+        link: Link::new(),
+    };
+    #[set_entry(tasks)]
+    static TWO: Task = Task {
+        id: 2,
+        link: Link::new(),
+    };
+    #[set_entry(tasks)]
+    static THREE: Task = Task {
+        id: 3,
+        link: Link::new(),
+    };
+
+    static TASKS: List<Task> = List::new();
+
+    #[test]
+    fn test_collect() {
+        assert!(TASKS.is_empty());
+        let threads = (0..4)
+            .map(|_| std::thread::spawn(|| collect_into(&TASKS, set!(tasks))))
+            .collect::<Vec<_>>();
+        threads.into_iter().for_each(|t| t.join().unwrap());
+        let mut ids = TASKS.iter().map(|t| t.id).collect::<Vec<_>>();
+        ids.sort();
+        assert_eq!(ids, [1, 2, 3]);
+        assert!(ONE.link.is_linked());
+    }
+}
@@ -0,0 +1,177 @@
+//! Invariants of whole linker sets, checked across their entries.
+//!
+//! A self test, given to [set_entry] as `self_test = path`, checks one
+//! entry.  Some properties hold only of a set as a whole, such as that no
+//! two entries share a key, or that the priorities of the entries are
+//! distinct.  A function registered with the
+//! [set_invariant](macro@crate::set_invariant) attribute checks such a
+//! property: it is placed into the [invariant] linker set, and
+//! [validate_all] calls every function there on the set it names.
+//!
+//! ```
+//! use linker_set::*;
+//! use linker_set::invariant;
+//! use std::collections::BTreeSet;
+//!
+//! pub struct Command {
+//!     name: &'static str,
+//! }
+//!
+//! set_declare!(commands, Command);
+//!
+//! #[set_entry(commands)]
+//! static GET: Command = Command { name: "get" };
+//! #[set_entry(commands)]
+//! static PUT: Command = Command { name: "get" };
+//!
+//! #[set_invariant(commands)]
+//! fn names_unique(all: &LinkerSet<Command>) -> Result<(), String> {
+//!     let mut names = BTreeSet::new();
+//!     match all.iter().find(|c| !names.insert(c.name)) {
+//!         Some(c) => Err(format!("{} is registered twice", c.name)),
+//!         None => Ok(()),
+//!     }
+//! }
+//!
+//! # fn main() {
+//! let failures = invariant::validate_all().unwrap_err();
+//! assert_eq!(failures[0].1, "get is registered twice");
+//! # }
+//! ```
+
+use crate::*;
+
+set_declare!(invariant, Invariant, namespaced);
+
+/// An invariant of a linker set.
+pub struct Invariant {
+    name: &'static str,
+    set: &'static str,
+    check: fn() -> Result<(), String>,
+}
+
+impl Invariant {
+    /// Create an invariant from its name, the name of the set it checks,
+    /// and the function checking it.
+    pub const fn new(
+        name: &'static str, set: &'static str,
+        check: fn() -> Result<(), String>,
+    ) -> Self {
+        Self { name, set, check }
+    }
+
+    /// Returns the name of the invariant, which is the path of its
+    /// function.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the name of the set that the invariant checks.
+    pub fn set(&self) -> &'static str {
+        self.set
+    }
+
+    /// Check the invariant.
+    ///
+    /// An invariant that panics fails with the message of the panic.
+    pub fn check(&self) -> Result<(), String> {
+        crate::self_test::caught(self.check)
+    }
+}
+
+/// Check every invariant registered in the [invariant] linker set,
+/// returning the name of each that fails, in order of name, with its
+/// error.
+pub fn validate_all() -> Result<(), Vec<(&'static str, String)>> {
+    let mut invariants = set!(invariant).iter().collect::<Vec<_>>();
+    invariants.sort_by_key(|i| i.name);
+    let failures = invariants
+        .into_iter()
+        .filter_map(|i| i.check().err().map(|e| (i.name, e)))
+        .collect::<Vec<_>>();
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    pub struct Route {
+        path: &'static str,
+        priority: u32,
+    }
+
+    set_declare!(routes, Route);
+
+    #[set_entry(routes)]
+    static ROOT: Route = Route {
+        path: "/",
+        priority: 1,
+    };
+    #[set_entry(routes)]
+    static USERS: Route = Route {
+        path: "/users",
+        priority: 2,
+    };
+    #[set_entry(routes)]
+    static ADMIN: Route = Route {
+        path: "/admin",
+        priority: 2,
+    };
+
+    #[set_invariant(routes)]
+    fn paths_absolute(all: &LinkerSet<Route>) -> Result<(), String> {
+        match all.iter().find(|r| !r.path.starts_with('/')) {
+            Some(r) => Err(format!("{} is relative", r.path)),
+            None => Ok(()),
+        }
+    }
+
+    #[set_invariant(routes)]
+    fn priorities_distinct(all: &LinkerSet<Route>) -> Result<(), String> {
+        let mut priorities = all.iter().map(|r| r.priority).collect::<Vec<_>>();
+        priorities.sort();
+        match priorities.windows(2).find(|w| w[0] == w[1]) {
+            Some(w) => Err(format!("priority {} is shared", w[0])),
+            None => Ok(()),
+        }
+    }
+
+    #[set_invariant(routes)]
+    fn home_exists(all: &LinkerSet<Route>) -> Result<(), String> {
+        let home = all.iter().find(|r| r.path == "/home");
+        assert!(home.expect("no route for /home").priority > 0);
+        Ok(())
+    }
+
+    #[set_invariant(routes)]
+    #[cfg(any())]
+    fn never(_: &LinkerSet<Route>) -> Result<(), String> {
+        unreachable!()
+    }
+
+    #[test]
+    fn test_invariants() {
+        let routes = set!(invariant)
+            .iter()
+            .filter(|i| i.set() == "routes")
+            .count();
+        assert_eq!(routes, 3);
+        let failures = validate_all().unwrap_err();
+        let home = "linker_set::invariant::test::home_exists";
+        let name = "linker_set::invariant::test::priorities_distinct";
+        assert_eq!(
+            failures,
+            [
+                (home, "panicked: no route for /home".to_string()),
+                (name, "priority 2 is shared".to_string())
+            ]
+        );
+        assert_eq!(ROOT.priority + USERS.priority, 3);
+        assert_eq!(ADMIN.path, "/admin");
+    }
+}
@@ -0,0 +1,114 @@
+//! Minimal JSON output for the exporters in this crate.
+
+use std::fmt::Write;
+
+/// Append a string to the output as a quoted JSON string.
+pub(crate) fn string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                write!(out, "\\u{:04x}", c as u32).unwrap();
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// A writer of JSON in const fns, into a buffer of exactly the right size.
+pub(crate) struct Writer<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+}
+
+/* const fns cannot take mutable references on older compilers, so the
+ * writer is passed by value.  A writer of size zero only counts. */
+impl<const N: usize> Writer<N> {
+    pub(crate) const fn new() -> Self {
+        Self {
+            buf: [0; N],
+            len: 0,
+        }
+    }
+
+    /// Returns the number of bytes written, or that would have been.
+    pub(crate) const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the buffer, which must have been filled exactly.
+    pub(crate) const fn into_inner(self) -> [u8; N] {
+        assert!(self.len == N);
+        self.buf
+    }
+
+    pub(crate) const fn push(mut self, b: u8) -> Self {
+        if self.len < N {
+            self.buf[self.len] = b;
+        }
+        self.len += 1;
+        self
+    }
+
+    pub(crate) const fn raw(mut self, s: &str) -> Self {
+        let s = s.as_bytes();
+        let mut i = 0;
+        while i < s.len() {
+            self = self.push(s[i]);
+            i += 1;
+        }
+        self
+    }
+
+    /// Write a quoted string, stopping at the first occurrence of `end`.
+    pub(crate) const fn string(mut self, s: &str, end: u8) -> Self {
+        const HEX: &[u8] = b"0123456789abcdef";
+        let s = s.as_bytes();
+        self = self.push(b'"');
+        let mut i = 0;
+        while i < s.len() && s[i] != end {
+            let c = s[i];
+            if c == b'"' || c == b'\\' {
+                self = self.push(b'\\').push(c);
+            } else if c < 0x20 {
+                self = self.raw("\\u00");
+                self = self.push(HEX[(c >> 4) as usize]);
+                self = self.push(HEX[(c & 15) as usize]);
+            } else {
+                self = self.push(c);
+            }
+            i += 1;
+        }
+        self.push(b'"')
+    }
+
+    pub(crate) const fn number(mut self, n: usize) -> Self {
+        let mut div = 1;
+        while n / div >= 10 {
+            div *= 10;
+        }
+        while div > 0 {
+            self = self.push(b'0' + (n / div % 10) as u8);
+            div /= 10;
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_string() {
+        let mut out = String::new();
+        string(&mut out, "a\"b\\c\nd\u{1}");
+        assert_eq!(out, r#""a\"b\\c\nd\u0001""#);
+    }
+}
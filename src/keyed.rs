@@ -0,0 +1,513 @@
+//! Keys and priorities recorded about the entries of linker sets.
+
+use crate::LinkerSet;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, BuildHasherDefault, Hasher};
+
+/// How a keyed linker set chooses among entries with the same key, given
+/// as `set_declare!(name, type, keyed, on_duplicate = policy)`.
+///
+/// The first and last entries for a key are those first and last in the
+/// section holding the set, which is the order in which the linker saw
+/// them.  That order is fixed for a given link, but it may change with
+/// the order of crates on the command line of the linker.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum OnDuplicate {
+    /// Panic if a key has more than one entry, regardless of priority.
+    Panic,
+    /// Choose the first entry for each key.
+    FirstWins,
+    /// Choose the last entry for each key.
+    LastWins,
+    /// Choose the entry of highest priority for each key, and panic if
+    /// two entries tie for highest priority.
+    #[default]
+    Priority,
+}
+
+impl OnDuplicate {
+    #[doc(hidden)]
+    pub fn conflict(&self) -> &'static str {
+        match self {
+            Self::Priority => "entries of equal priority",
+            _ => "more than one entry",
+        }
+    }
+}
+
+/// The key and priority of an entry in a linker set, recorded by the
+/// [set_entry](crate::set_entry) attribute when given `key = "..."`.
+pub struct Keyed<T>
+where
+    T: 'static,
+{
+    key: &'static str,
+    priority: i32,
+    entry: &'static T,
+}
+
+impl<T> Keyed<T>
+where
+    T: 'static,
+{
+    #[doc(hidden)]
+    pub const fn new(
+        key: &'static str, priority: i32, entry: &'static T,
+    ) -> Self {
+        Self {
+            key,
+            priority,
+            entry,
+        }
+    }
+
+    /// Returns the key of the entry.
+    pub fn key(&self) -> &'static str {
+        self.key
+    }
+
+    /// Returns the priority of the entry.
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    /// Returns the entry.
+    pub fn entry(&self) -> &'static T {
+        self.entry
+    }
+
+    /// Choose the entry of highest priority for each key.
+    ///
+    /// Returns the first key for which two entries tie for highest
+    /// priority as an error.
+    pub fn resolve<I>(
+        keyed: I,
+    ) -> Result<BTreeMap<&'static str, &'static T>, &'static str>
+    where
+        I: IntoIterator<Item = &'static Self>,
+    {
+        Self::resolve_with(keyed, OnDuplicate::Priority)
+    }
+
+    /// Choose an entry for each key by a policy.
+    ///
+    /// Returns the first key whose entries conflict under the policy as an
+    /// error.
+    pub fn resolve_with<I>(
+        keyed: I, policy: OnDuplicate,
+    ) -> Result<BTreeMap<&'static str, &'static T>, &'static str>
+    where
+        I: IntoIterator<Item = &'static Self>,
+    {
+        if policy == OnDuplicate::Priority {
+            return Self::by_priority(keyed);
+        }
+        let mut chosen = BTreeMap::new();
+        for k in keyed {
+            match (chosen.contains_key(k.key), policy) {
+                (true, OnDuplicate::Panic) => return Err(k.key),
+                (true, OnDuplicate::FirstWins) => (),
+                _ => {
+                    chosen.insert(k.key, k.entry);
+                }
+            }
+        }
+        Ok(chosen)
+    }
+
+    /// Returns the key closest to a key, such as one mistyped on a command
+    /// line, if any is within two edits of it, and fewer than it has
+    /// characters.
+    ///
+    /// An edit inserts, deletes, or replaces one character.  Of keys
+    /// equally close, the least is returned, so that the suggestion does
+    /// not depend on the order of the set.
+    pub fn closest<I>(keyed: I, key: &str) -> Option<&'static str>
+    where
+        I: IntoIterator<Item = &'static Self>,
+    {
+        let limit = 2.min(key.chars().count().saturating_sub(1));
+        keyed
+            .into_iter()
+            .map(|k| (distance(key, k.key), k.key))
+            .filter(|&(d, _)| d <= limit)
+            .min()
+            .map(|(_, k)| k)
+    }
+
+    /// Describe the entry chosen for each key by a policy, naming the
+    /// entries of a set, for the report of `reflect::configuration`.
+    #[doc(hidden)]
+    pub fn choices(
+        keyed: LinkerSet<Self>, policy: OnDuplicate, set: LinkerSet<T>,
+    ) -> Vec<String> {
+        let resolved = match Self::resolve_with(keyed.iter(), policy) {
+            Ok(resolved) => resolved,
+            Err(key) => {
+                return vec![format!("{} for {}", policy.conflict(), key)]
+            }
+        };
+        resolved
+            .into_iter()
+            .map(|(key, entry)| {
+                let priority = keyed
+                    .iter()
+                    .find(|k| k.key == key && std::ptr::eq(k.entry, entry))
+                    .map_or(0, |k| k.priority);
+                format!(
+                    "key {:?}: {} (priority {})",
+                    key,
+                    set.describe(entry),
+                    priority
+                )
+            })
+            .collect()
+    }
+
+    fn by_priority<I>(
+        keyed: I,
+    ) -> Result<BTreeMap<&'static str, &'static T>, &'static str>
+    where
+        I: IntoIterator<Item = &'static Self>,
+    {
+        let mut best = BTreeMap::<_, (&Self, bool)>::new();
+        for k in keyed {
+            let tie = match best.get(k.key) {
+                Some((b, _)) if b.priority > k.priority => continue,
+                Some((b, _)) => b.priority == k.priority,
+                None => false,
+            };
+            best.insert(k.key, (k, tie));
+        }
+        if let Some((key, _)) = best.iter().find(|(_, (_, tie))| *tie) {
+            return Err(key);
+        }
+        Ok(best
+            .into_iter()
+            .map(|(key, (k, _))| (key, k.entry))
+            .collect())
+    }
+}
+
+/// An index of the entries chosen for the keys of a keyed linker set, by
+/// which `lookup()` in its module finds the entry of a key, given as
+/// `set_declare!(name, type, keyed, lookup = kind)`.
+///
+/// The kind is `btree`, the default, for a `BTreeMap`, `hash` for a
+/// `HashMap` with the hasher of the standard library, `fx` for one with
+/// [FxBuildHasher], `hash(path)` for one with the `BuildHasher` at the
+/// path, such as `ahash::RandomState`, or `sorted(n)` for a [SortedSlice]
+/// holding up to `n` keys, which allocates nothing.
+pub trait Lookup<T>: Sized
+where
+    T: 'static,
+{
+    /// Build the index, choosing an entry for each key by a policy, as
+    /// [Keyed::resolve_with] does.
+    ///
+    /// Returns the first key whose entries conflict under the policy as an
+    /// error.
+    fn build<I>(keyed: I, policy: OnDuplicate) -> Result<Self, &'static str>
+    where
+        I: IntoIterator<Item = &'static Keyed<T>>;
+
+    /// Returns the entry chosen for a key.
+    fn get(&self, key: &str) -> Option<&'static T>;
+}
+
+impl<T> Lookup<T> for BTreeMap<&'static str, &'static T>
+where
+    T: 'static,
+{
+    fn build<I>(keyed: I, policy: OnDuplicate) -> Result<Self, &'static str>
+    where
+        I: IntoIterator<Item = &'static Keyed<T>>,
+    {
+        Keyed::resolve_with(keyed, policy)
+    }
+
+    fn get(&self, key: &str) -> Option<&'static T> {
+        BTreeMap::get(self, key).copied()
+    }
+}
+
+impl<T, S> Lookup<T> for HashMap<&'static str, &'static T, S>
+where
+    T: 'static,
+    S: BuildHasher + Default,
+{
+    fn build<I>(keyed: I, policy: OnDuplicate) -> Result<Self, &'static str>
+    where
+        I: IntoIterator<Item = &'static Keyed<T>>,
+    {
+        Ok(Keyed::resolve_with(keyed, policy)?.into_iter().collect())
+    }
+
+    fn get(&self, key: &str) -> Option<&'static T> {
+        HashMap::get(self, key).copied()
+    }
+}
+
+/// The hasher of the Rust compiler, which hashes short keys, such as the
+/// names of commands, quickly, though it does not resist keys chosen to
+/// collide.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FxHasher {
+    hash: u64,
+}
+
+const FX_SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+impl FxHasher {
+    fn add(&mut self, word: u64) {
+        self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(FX_SEED);
+    }
+}
+
+impl Hasher for FxHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+        for c in &mut chunks {
+            self.add(u64::from_le_bytes(c.try_into().unwrap()));
+        }
+        for &b in chunks.remainder() {
+            self.add(b as u64);
+        }
+    }
+
+    fn write_u8(&mut self, n: u8) {
+        self.add(n as u64);
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.add(n as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+/// Builds an [FxHasher], for a keyed set declared with `lookup = fx`.
+pub type FxBuildHasher = BuildHasherDefault<FxHasher>;
+
+/// An index of up to `N` keys in order, searched by bisection, which is
+/// built in place, without allocating.
+pub struct SortedSlice<T, const N: usize>
+where
+    T: 'static,
+{
+    slots: [Option<(&'static Keyed<T>, bool)>; N],
+    len: usize,
+}
+
+impl<T, const N: usize> SortedSlice<T, N>
+where
+    T: 'static,
+{
+    fn keys(&self) -> impl Iterator<Item = (&'static Keyed<T>, bool)> + '_ {
+        self.slots[..self.len].iter().map(|s| s.unwrap())
+    }
+
+    fn find(&self, key: &str) -> Result<usize, usize> {
+        self.slots[..self.len].binary_search_by(|s| s.unwrap().0.key.cmp(key))
+    }
+
+    /// Returns the keys, in order, with the entry chosen for each.
+    pub fn iter(
+        &self,
+    ) -> impl Iterator<Item = (&'static str, &'static T)> + '_ {
+        self.keys().map(|(k, _)| (k.key, k.entry))
+    }
+
+    /// Returns the number of keys.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if there are no keys.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T, const N: usize> Lookup<T> for SortedSlice<T, N>
+where
+    T: 'static,
+{
+    /// # Panics
+    /// Panics if the entries have more than `N` keys.
+    fn build<I>(keyed: I, policy: OnDuplicate) -> Result<Self, &'static str>
+    where
+        I: IntoIterator<Item = &'static Keyed<T>>,
+    {
+        let mut index = Self {
+            slots: [None; N],
+            len: 0,
+        };
+        for k in keyed {
+            let i = match index.find(k.key) {
+                Ok(i) => i,
+                Err(i) => {
+                    assert!(index.len < N, "more than {} keys", N);
+                    index.slots[i..=index.len].rotate_right(1);
+                    index.slots[i] = Some((k, false));
+                    index.len += 1;
+                    continue;
+                }
+            };
+            let (best, _) = index.slots[i].unwrap();
+            let tie = match policy {
+                OnDuplicate::Panic => return Err(k.key),
+                OnDuplicate::FirstWins => continue,
+                OnDuplicate::LastWins => false,
+                OnDuplicate::Priority if best.priority > k.priority => continue,
+                OnDuplicate::Priority => best.priority == k.priority,
+            };
+            index.slots[i] = Some((k, tie));
+        }
+        if let Some((k, _)) = index.keys().find(|(_, tie)| *tie) {
+            return Err(k.key);
+        }
+        Ok(index)
+    }
+
+    fn get(&self, key: &str) -> Option<&'static T> {
+        let i = self.find(key).ok()?;
+        Some(self.slots[i].unwrap().0.entry)
+    }
+}
+
+/// Returns the number of characters to insert, delete, or replace to turn
+/// one string into another.
+fn distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let replace = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = replace.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static A: u8 = 1;
+    static B: u8 = 2;
+    static C: u8 = 3;
+
+    #[test]
+    fn test_resolve() {
+        static KEYED: [Keyed<u8>; 4] = [
+            Keyed::new("x", 0, &A),
+            Keyed::new("x", 50, &B),
+            Keyed::new("y", -1, &C),
+            Keyed::new("x", 10, &C),
+        ];
+        let resolved = Keyed::resolve(&KEYED).unwrap();
+        assert_eq!(resolved, BTreeMap::from([("x", &B), ("y", &C)]));
+
+        static TIED: [Keyed<u8>; 3] = [
+            Keyed::new("x", 5, &A),
+            Keyed::new("x", 5, &B),
+            Keyed::new("x", 1, &C),
+        ];
+        assert_eq!(Keyed::resolve(&TIED), Err("x"));
+        static BROKEN: [Keyed<u8>; 3] = [
+            Keyed::new("x", 5, &A),
+            Keyed::new("x", 5, &B),
+            Keyed::new("x", 6, &C),
+        ];
+        assert_eq!(Keyed::resolve(&BROKEN).unwrap()["x"], &C);
+
+        let resolve = |policy| Keyed::resolve_with(&KEYED, policy);
+        assert_eq!(resolve(OnDuplicate::Panic), Err("x"));
+        let first = resolve(OnDuplicate::FirstWins).unwrap();
+        assert_eq!(first, BTreeMap::from([("x", &A), ("y", &C)]));
+        let last = resolve(OnDuplicate::LastWins).unwrap();
+        assert_eq!(last, BTreeMap::from([("x", &C), ("y", &C)]));
+        assert_eq!(Keyed::resolve_with(&TIED, OnDuplicate::Priority), Err("x"));
+    }
+
+    #[test]
+    fn test_closest() {
+        static KEYED: [Keyed<u8>; 4] = [
+            Keyed::new("sqrt", 0, &A),
+            Keyed::new("sub", 0, &B),
+            Keyed::new("add", 0, &C),
+            Keyed::new("mul", 0, &A),
+        ];
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("", "abc"), 3);
+        assert_eq!(distance("färben", "farben"), 1);
+        assert_eq!(Keyed::closest(&KEYED, "sqtr"), Some("sqrt"));
+        assert_eq!(Keyed::closest(&KEYED, "ad"), Some("add"));
+        assert_eq!(Keyed::closest(&KEYED, "sum"), Some("sub"));
+        assert_eq!(Keyed::closest(&KEYED, "mol"), Some("mul"));
+        assert_eq!(Keyed::closest(&KEYED, "add"), Some("add"));
+        assert_eq!(Keyed::closest(&KEYED, "a"), None);
+        assert_eq!(Keyed::closest(&KEYED, "divide"), None);
+    }
+
+    fn build<L>(keyed: &'static [Keyed<u8>], policy: OnDuplicate) -> Vec<u8>
+    where
+        L: Lookup<u8>,
+    {
+        match L::build(keyed, policy) {
+            Ok(index) => ["x", "y", "z"]
+                .iter()
+                .map(|k| index.get(k).map_or(0, |e| *e))
+                .collect(),
+            Err(key) => key.bytes().collect(),
+        }
+    }
+
+    #[test]
+    fn test_lookup() {
+        static KEYED: [Keyed<u8>; 5] = [
+            Keyed::new("y", 1, &A),
+            Keyed::new("x", 1, &A),
+            Keyed::new("x", 2, &B),
+            Keyed::new("x", 1, &C),
+            Keyed::new("y", 1, &B),
+        ];
+        type Tree = BTreeMap<&'static str, &'static u8>;
+        type Hash = HashMap<&'static str, &'static u8, FxBuildHasher>;
+        type Sorted = SortedSlice<u8, 2>;
+        for policy in [
+            OnDuplicate::Panic,
+            OnDuplicate::FirstWins,
+            OnDuplicate::LastWins,
+            OnDuplicate::Priority,
+        ] {
+            let tree = build::<Tree>(&KEYED, policy);
+            assert_eq!(build::<Hash>(&KEYED, policy), tree);
+            assert_eq!(build::<Sorted>(&KEYED, policy), tree);
+        }
+        assert_eq!(build::<Sorted>(&KEYED, OnDuplicate::LastWins), [3, 2, 0]);
+        assert_eq!(build::<Sorted>(&KEYED, OnDuplicate::Priority), b"y");
+
+        let sorted = Sorted::build(&KEYED, OnDuplicate::FirstWins).unwrap();
+        assert_eq!(sorted.iter().collect::<Vec<_>>(), [("x", &A), ("y", &A)]);
+        assert_eq!(sorted.len(), 2);
+        let e = std::panic::catch_unwind(|| {
+            SortedSlice::<u8, 1>::build(&KEYED, OnDuplicate::FirstWins).ok()
+        });
+        assert!(e.is_err());
+    }
+
+    #[test]
+    fn test_fx() {
+        let hash = |s: &str| FxBuildHasher::default().hash_one(s);
+        assert_eq!(hash("gzip"), hash("gzip"));
+        assert_ne!(hash("gzip"), hash("zstd"));
+        assert_ne!(hash("command_a"), hash("command_b"));
+    }
+}
@@ -0,0 +1,204 @@
+//! Composition of layers registered by the crates of a program.
+//!
+//! Each [Layer] names a layer of instrumentation, such as of a `tracing`
+//! subscriber, and carries a factory, returning a type chosen by the
+//! program, that builds it.  The program declares a linker set of layers,
+//! each crate places the layers it owns into the set, and at startup the
+//! program composes them with [compose], so that the binary need not know
+//! about the instrumentation of every crate it links.
+//!
+//! With the `tracing` feature enabled, this module declares its own
+//! `layer` set, of boxed `tracing_subscriber` layers of the registry, and
+//! `subscriber` composes them into one subscriber for the program to
+//! install.
+//!
+//! Layers are composed in order of priority, highest first, and those of
+//! the same priority in order of name.  As with the entries of a keyed
+//! set, a layer replaces any of the same name and lower priority, so that
+//! a binary can replace the layer of a library by registering one of the
+//! same name with a higher priority.
+
+#[cfg(feature = "tracing")]
+use crate::{set, set_declare};
+use std::collections::BTreeMap;
+#[cfg(feature = "tracing")]
+use tracing_subscriber::layer::{Layered, SubscriberExt};
+#[cfg(feature = "tracing")]
+use tracing_subscriber::Registry;
+
+/// A layer of instrumentation registered in a linker set.
+pub struct Layer<L>
+where
+    L: 'static,
+{
+    /// Name of the layer.
+    pub name: &'static str,
+    /// Priority of the layer, which orders it among the others and lets
+    /// it replace a layer of the same name.
+    pub priority: i32,
+    /// Factory building the layer.
+    pub factory: fn() -> L,
+}
+
+/// Build the given layers, those of highest priority first, and those of
+/// the same priority in order of name.
+///
+/// Of layers with the same name, only the one of highest priority is
+/// built.  Returns the first name for which two layers tie for highest
+/// priority as an error.
+pub fn compose<'a, I, L>(layers: I) -> Result<Vec<L>, &'static str>
+where
+    I: IntoIterator<Item = &'a Layer<L>>,
+    L: 'static,
+{
+    let mut best = BTreeMap::<_, (&Layer<L>, bool)>::new();
+    for layer in layers {
+        let tie = match best.get(layer.name) {
+            Some((b, _)) if b.priority > layer.priority => continue,
+            Some((b, _)) => b.priority == layer.priority,
+            None => false,
+        };
+        best.insert(layer.name, (layer, tie));
+    }
+    if let Some((name, _)) = best.iter().find(|(_, (_, tie))| *tie) {
+        return Err(name);
+    }
+    let mut chosen = best.into_values().map(|(l, _)| l).collect::<Vec<_>>();
+    chosen.sort_by_key(|l| std::cmp::Reverse(l.priority));
+    Ok(chosen.into_iter().map(|l| (l.factory)()).collect())
+}
+
+/// A boxed `tracing_subscriber` layer of the registry.
+#[cfg(feature = "tracing")]
+pub type Boxed = Box<dyn tracing_subscriber::Layer<Registry> + Send + Sync>;
+
+#[cfg(feature = "tracing")]
+set_declare!(layer, Layer<Boxed>, namespaced);
+
+/// Composes the layers of the [layer] set over a registry, as [compose]
+/// does, into one subscriber.
+///
+/// ```
+/// use linker_set::*;
+/// use linker_set::layers::{Boxed, Layer};
+/// use tracing_subscriber::Layer as _;
+///
+/// #[set_entry(linker_set::layers::layer)]
+/// static NONE: Layer<Boxed> = Layer {
+///     name: "none",
+///     priority: 0,
+///     factory: || tracing_subscriber::layer::Identity::new().boxed(),
+/// };
+///
+/// # fn main() {
+/// let subscriber = layers::subscriber().unwrap();
+/// tracing::subscriber::set_global_default(subscriber).unwrap();
+/// # }
+/// ```
+#[cfg(feature = "tracing")]
+pub fn subscriber() -> Result<Layered<Vec<Boxed>, Registry>, &'static str> {
+    Ok(tracing_subscriber::registry().with(compose(set!(layer))?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::*;
+
+    type Make = Box<dyn Fn(&str) -> String + Send + Sync>;
+
+    set_declare!(layer, Layer<Make>);
+
+    #[set_entry(layer)]
+    static UPPER: Layer<Make> = Layer {
+        name: "upper",
+        priority: 10,
+        factory: || Box::new(|s| s.to_uppercase()),
+    };
+
+    #[set_entry(layer)]
+    static TRIM: Layer<Make> = Layer {
+        name: "trim",
+        priority: 0,
+        factory: || Box::new(|s| s.trim().to_string()),
+    };
+
+    #[set_entry(layer)]
+    static QUOTE: Layer<Make> = Layer {
+        name: "quote",
+        priority: 0,
+        factory: || Box::new(|s| format!("'{}'", s)),
+    };
+
+    #[set_entry(layer)]
+    static QUOTE_TWICE: Layer<Make> = Layer {
+        name: "quote",
+        priority: 5,
+        factory: || Box::new(|s| format!("''{}''", s)),
+    };
+
+    #[test]
+    fn test_compose() {
+        let layers = compose(set!(layer)).unwrap();
+        assert_eq!(layers.len(), 3);
+        let out = layers.iter().fold(" log ".to_string(), |s, l| l(&s));
+        assert_eq!(out, "'' LOG ''");
+        assert_eq!((QUOTE.factory)()("x"), "'x'");
+
+        static TIED: Layer<Make> = Layer {
+            name: "quote",
+            priority: 5,
+            factory: || Box::new(|s| s.to_string()),
+        };
+        let layers = set!(layer).iter().chain([&TIED]);
+        assert_eq!(compose(layers).err(), Some("quote"));
+    }
+
+    #[cfg(feature = "tracing")]
+    mod registry {
+        use super::*;
+        use std::sync::Mutex;
+        use tracing_subscriber::layer::Context;
+        use tracing_subscriber::Layer as _;
+
+        static SEEN: Mutex<Vec<&str>> = Mutex::new(Vec::new());
+
+        struct Record(&'static str);
+
+        impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for Record {
+            fn on_event(&self, _: &tracing::Event<'_>, _: Context<'_, S>) {
+                SEEN.lock().unwrap().push(self.0);
+            }
+        }
+
+        #[set_entry(crate::layers::layer)]
+        static AUDIT: Layer<Boxed> = Layer {
+            name: "audit",
+            priority: 10,
+            factory: || Record("audit").boxed(),
+        };
+
+        #[set_entry(crate::layers::layer)]
+        static FMT: Layer<Boxed> = Layer {
+            name: "fmt",
+            priority: 0,
+            factory: || Record("fmt").boxed(),
+        };
+
+        #[set_entry(crate::layers::layer)]
+        static FMT_BINARY: Layer<Boxed> = Layer {
+            name: "fmt",
+            priority: 5,
+            factory: || Record("fmt of the binary").boxed(),
+        };
+
+        #[test]
+        fn test_subscriber() {
+            let subscriber = subscriber().unwrap();
+            tracing::subscriber::with_default(subscriber, || {
+                tracing::info!("event");
+            });
+            assert_eq!(*SEEN.lock().unwrap(), ["audit", "fmt of the binary"]);
+        }
+    }
+}
@@ -0,0 +1,262 @@
+//! The layout of the elements of linker sets in memory.
+//!
+//! A set that is scanned on a hot path, such as a table of descriptors
+//! matched against each request, is fastest when its elements pack evenly
+//! into cache lines.  `LinkerSet::layout_report` describes how the
+//! elements of a set fall across lines, and [Report::suggestions] proposes
+//! changes to the element type that would keep each within as few lines
+//! as possible:
+//!
+//! ```
+//! use linker_set::*;
+//!
+//! pub struct Descriptor {
+//!     id: u32,
+//!     flags: [u8; 20],
+//! }
+//!
+//! set_declare!(descriptors, Descriptor);
+//!
+//! #[set_entry(descriptors)]
+//! static DISK: Descriptor = Descriptor { id: 1, flags: [0; 20] };
+//!
+//! # fn main() {
+//! let report = set!(descriptors).layout_report();
+//! assert_eq!((report.size, report.per_line), (24, 2));
+//! for suggestion in report.suggestions() {
+//!     println!("{}", suggestion);
+//! }
+//! # }
+//! ```
+//!
+//! `cargo linker-set layout` reports the same of each set in a binary.
+
+use crate::LinkerSet;
+use std::fmt;
+
+/// The size of a cache line assumed by the reports, in bytes.
+pub const CACHE_LINE: usize = 64;
+
+/// How the elements of a linker set fall across cache lines.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Report {
+    /// The size of each element, in bytes.
+    pub size: usize,
+    /// The alignment of each element, in bytes.
+    pub align: usize,
+    /// The number of elements.
+    pub len: usize,
+    /// The size of all of the elements, in bytes.
+    pub bytes: usize,
+    /// The number of whole elements that fit in a cache line, which is
+    /// zero if an element is larger than a line.
+    pub per_line: usize,
+    /// The number of elements that straddle more cache lines than an
+    /// element of their size must.
+    pub straddling: usize,
+}
+
+impl Report {
+    /// Describe `len` elements of `size` bytes, aligned to `align`, that
+    /// begin at address `start`.
+    pub fn new(size: usize, align: usize, len: usize, start: u64) -> Self {
+        let line = CACHE_LINE as u64;
+        let least = size.div_ceil(CACHE_LINE) as u64;
+        let straddling = (0..len as u64)
+            .map(|i| start + i * size as u64)
+            .filter(|&first| {
+                let last = first + size as u64 - 1;
+                last / line - first / line + 1 > least
+            })
+            .count();
+        Self {
+            size,
+            align,
+            len,
+            bytes: size * len,
+            per_line: CACHE_LINE / size,
+            straddling,
+        }
+    }
+
+    /// Returns true if any element straddles more cache lines than it
+    /// must.
+    pub fn straddles(&self) -> bool {
+        self.straddling > 0
+    }
+
+    /// Returns changes to the element type that would stop any element
+    /// from straddling more cache lines than it must, if any do.
+    pub fn suggestions(&self) -> Vec<Suggestion> {
+        if !self.straddles() {
+            return Vec::new();
+        }
+        if CACHE_LINE % self.size == 0 || self.size % CACHE_LINE == 0 {
+            let align = self.size.min(CACHE_LINE);
+            return vec![Suggestion::Align { align }];
+        }
+        let (up, down) = if self.size < CACHE_LINE {
+            let up = self.size.next_power_of_two();
+            (up, up / 2)
+        } else {
+            let down = self.size / CACHE_LINE * CACHE_LINE;
+            (down + CACHE_LINE, down)
+        };
+        let mut suggestions = vec![Suggestion::Pad {
+            size: up,
+            cost: (up - self.size) * self.len,
+        }];
+        if self.size - down <= self.size / 8 {
+            suggestions.push(Suggestion::Shrink {
+                size: down,
+                saving: (self.size - down) * self.len,
+            });
+        }
+        suggestions
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} x {} bytes (align {}) = {} bytes, {} per line, {} straddling",
+            self.len,
+            self.size,
+            self.align,
+            self.bytes,
+            self.per_line,
+            self.straddling
+        )
+    }
+}
+
+/// A change to the element type of a linker set that would keep its
+/// elements within as few cache lines as possible.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Suggestion {
+    /// Align the element type, whose size already divides or is a multiple
+    /// of a cache line, so that the set begins on a boundary.
+    Align {
+        /// The alignment, in bytes.
+        align: usize,
+    },
+    /// Pad the element type to a size that divides or is a multiple of a
+    /// cache line.
+    Pad {
+        /// The padded size, in bytes.
+        size: usize,
+        /// The bytes added to the set.
+        cost: usize,
+    },
+    /// Shrink the element type to a size that divides or is a multiple of
+    /// a cache line, which it exceeds only slightly.
+    Shrink {
+        /// The shrunken size, in bytes.
+        size: usize,
+        /// The bytes removed from the set.
+        saving: usize,
+    },
+}
+
+impl fmt::Display for Suggestion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Align { align } => {
+                write!(
+                    f,
+                    "align the element type with #[repr(align({}))]",
+                    align
+                )
+            }
+            Self::Pad { size, cost } => write!(
+                f,
+                "pad the element type to {} bytes with #[repr(align({}))], \
+                 adding {} bytes",
+                size,
+                size.min(CACHE_LINE),
+                cost
+            ),
+            Self::Shrink { size, saving } => write!(
+                f,
+                "shrink the element type to {} bytes, saving {} bytes",
+                size, saving
+            ),
+        }
+    }
+}
+
+impl<T> LinkerSet<T>
+where
+    T: 'static,
+{
+    /// Returns how the elements of the linker set fall across cache lines.
+    pub fn layout_report(&self) -> Report {
+        let (start, len) = self.as_raw_parts();
+        Report::new(
+            std::mem::size_of::<T>(),
+            std::mem::align_of::<T>(),
+            len,
+            start as u64,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::*;
+
+    #[repr(C, align(64))]
+    pub struct Line {
+        bytes: [u8; 64],
+    }
+
+    set_declare!(lines, Line);
+
+    #[set_entry(lines)]
+    static FIRST: Line = Line { bytes: [1; 64] };
+    #[set_entry(lines)]
+    static SECOND: Line = Line { bytes: [2; 64] };
+
+    #[test]
+    fn test_layout_report() {
+        let report = set!(lines).layout_report();
+        assert_eq!((report.size, report.align, report.len), (64, 64, 2));
+        assert_eq!((report.bytes, report.per_line), (128, 1));
+        assert!(!report.straddles() && report.suggestions().is_empty());
+        assert_eq!(FIRST.bytes[0] + SECOND.bytes[0], 3);
+    }
+
+    #[test]
+    fn test_suggestions() {
+        let report = Report::new(16, 8, 8, 0x1008);
+        assert_eq!((report.per_line, report.straddling), (4, 2));
+        assert_eq!(report.suggestions(), [Suggestion::Align { align: 16 }]);
+
+        let report = Report::new(36, 4, 16, 0x1000);
+        assert_eq!((report.per_line, report.straddling), (1, 8));
+        let pad = Suggestion::Pad {
+            size: 64,
+            cost: 448,
+        };
+        let shrink = Suggestion::Shrink {
+            size: 32,
+            saving: 64,
+        };
+        assert_eq!(report.suggestions(), [pad, shrink]);
+        assert_eq!(
+            shrink.to_string(),
+            "shrink the element type to 32 bytes, saving 64 bytes"
+        );
+
+        let report = Report::new(96, 8, 4, 0x1010);
+        assert_eq!((report.per_line, report.straddling), (0, 2));
+        let pad = Suggestion::Pad {
+            size: 128,
+            cost: 128,
+        };
+        assert_eq!(report.suggestions(), [pad]);
+        assert_eq!(Report::new(96, 8, 0, 0x1000).straddling, 0);
+    }
+}
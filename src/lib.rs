@@ -31,31 +31,261 @@
 //! # }
 //! ```
 //!
-//! The [set_declare!] macro outputs a module definition.  The module must
-//! be imported into the scope of calls to the [set_entry] attribute and the
-//! [set!] macro.
+//! The `calculator` example in the repository is a complete program built
+//! this way, whose operations are registered by separate modules.
 //!
-//! If you make a linker set of an integer type, you should use typed
-//! literals, not generic integer literals.  I.e.
+//! The [set_entry] attribute may also be applied to a const, in which case
+//! a hidden static initialized from the const is placed into the set.
+//! The symbol names of entries are always mangled; `#[no_mangle]` and
+//! `#[export_name]` are removed from them, so that entries with the same
+//! name in different crates never collide.
 //!
-//! ```
-//! use linker_set::*;
+//! The [set!] macro creates a proxy object from the symbols bracketing
+//! the set each time it is used, which is cheap but not free.  Code that
+//! accesses a set in a hot path can call `handle()` in the module made by
+//! [set_declare!] instead, which returns a proxy object created once and
+//! cached, at the cost of a single atomic load.
 //!
-//! set_declare!(foo, u64);
+//! An entry placed with `#[set_entry(name, target_feature = "avx2")]`,
+//! into a set declared with the `target_feature` option, records the CPU
+//! features it requires, such as a SIMD variant of a kernel.
+//! `LinkerSet::for_cpu` yields the entries that the running CPU can use,
+//! and `best_for_cpu()` in the module made by [set_declare!] returns the
+//! one requiring the most features.
 //!
-//! #[set_entry(foo)]
-//! static FOO: u64 = 1000u64; // not 1000 ❌
-//! ```
+//! Code that takes a set can be generic over [SetRead], which
+//! [LinkerSet] implements, so that its unit tests can pass a `MockSet` of
+//! fixtures instead, with the `mock` feature enabled.
+//!
+//! The [test_entry] attribute places a static or const into a separate
+//! section kept for tests, of a set declared with the `test_entries`
+//! option, such as for mock drivers registered only under `cfg(test)`.
+//! [set_test!] yields those entries, and [set!] never does.
+//!
+//! The [set_declare!] macro outputs a module definition.  The module must
+//! be imported into the scope of calls to the [set_entry] attribute and the
+//! [set!] macro.
+//!
+//! Linker sets are supported on Linux, Android, Fuchsia, the BSDs, and
+//! illumos, whose linkers all define the symbols bracketing a section, and
+//! on macOS and the other Apple systems, whose Mach-O linker does too for
+//! sections of the `__DATA` segment.  A Mach-O section name holds at most
+//! 16 bytes, so the section of a set whose ELF section name is longer, such
+//! as `set_fatal_handler`, is named by its first 8 bytes and a hash of the
+//! whole name.  [Reflection](reflect) still gives the readable name.  A
+//! program declaring two sets that would share a Mach-O section fails to
+//! link on ELF, naming the section, and [seal::seal_all] fails on Mach-O
+//! itself, which cannot detect it at link time.  Inspecting a binary,
+//! generating C headers, and `cargo linker-set` read ELF alone.
 //!
-//! Generic integer literals will defeat a typechecking mechanism that is
-//! output by the [set_entry] macro.
+//! The crate builds with Rust 1.81 and later, and the `manifest` feature
+//! with 1.88.  Before 1.89, `#[used]` does not keep a section from the
+//! linker's `--gc-sections`, which drops those that only inspection reads,
+//! such as the schema, test entries, and interned strings, unless the
+//! program links with `-C link-arg=-Wl,--no-gc-sections`.  Before 1.82,
+//! [set_declare!] cannot reject an element type that has no values.
 //!
 //! All items in a set should be of the same size, the size of the declared
 //! type.  Otherwise, stuff won't work.  The macros make an attempt to
 //! typecheck set entries, but they aren't foolproof.  Caveat scriptor.
 //!
+//! The compiler sees the start and stop symbols bracketing a set as two
+//! unrelated statics, so the elements between them are reached through a
+//! pointer with exposed provenance, computed from the symbols' addresses,
+//! rather than by pointer arithmetic between the symbols.
+//!
+//! Every element of a set is a static, or a slice borrowed for the life
+//! of the program, so it never moves and is never dropped.  Elements are
+//! thus pinned, and `LinkerSet::iter_pinned` yields them as
+//! `Pin<&'static T>` for code, such as intrusive collections, that links
+//! them to one another by address.  The [intrusive] module threads the
+//! elements of a set into an intrusive list, or any other such collection,
+//! at startup.
+//!
+//! Entries are immutable, except those of a set of [BootCell], which can
+//! be patched in place early in a program, as described there.
+//! [set_reserve] places entries holding the [ConstDefault] of the element
+//! type, to be filled in this way, so that such a table keeps the same
+//! size from one build to the next.
+//! [seal::seal_all] marks the end of startup: it builds what is derived
+//! lazily from every set, such as the entries chosen by keyed sets, and
+//! then patching a set fails loudly.  [seal::seal_all_read_only] also maps
+//! the sets read-only.
+//!
+//! The adapters of [StaticIterator], such as `map_static`, keep references
+//! into elements `'static` through a chain of iterator adapters.
+//!
+//! Elements may be of any `Sync` type that is not zero-sized, including
+//! those whose layout has a niche, such as references, function pointers,
+//! and `NonZeroU32`, and `Option`s of them, which are the same size as the
+//! types they wrap.  A set of `Option<fn()>`, for instance, holds optional
+//! hooks, and `LinkerSet::iter_some` yields only those that are present.
+//! The elements are always initialized by Rust, so their niches hold.  A
+//! type with no values, such as an enum with no variants or a struct
+//! holding one, is rejected when the set is declared.
+//!
+//! The elements of a set of a `Copy` type, such as a table of numbers,
+//! can be had by value: `LinkerSet::iter_copied` yields copies of them,
+//! and `LinkerSet::to_vec` and `LinkerSet::to_array` collect them.
+//!
+//! The number of elements is not a const, even on nightly, because the
+//! linker places them only after const evaluation, which cannot measure
+//! the distance between the symbols bounding a set, so an array sized for
+//! a set is instead checked when `to_array` is called.  Only a sealed set,
+//! whose entries are all placed within its crate, counts them as the
+//! compiler expands them, and so has a const length, as described under
+//! [set_declare!].
+//!
+//! For a linker set of trait objects, declared with an element type of
+//! `&'static dyn Trait`, the [register_impl] attribute on an impl of the
+//! trait places the implementation into the set without a separate static.
+//! Similarly, the [register_const] attribute on an impl places the value
+//! of one of its associated consts, such as a tuning constant kept with
+//! the type it tunes, into a set.
+//!
 //! The index operator is kind of just for fun.  Obviously you shouldn't
 //! depend on the linker to provide any specific ordering.
+//! `LinkerSet::get`, `first`, and `last` return `None` rather than panic
+//! when there is no such element, such as in a set whose size varies with
+//! the features of a build.  With the `shuffle` feature enabled, such as
+//! for tests in CI, iterating a set yields its entries in an order chosen
+//! for each run, to catch code that relies on the order, as described in
+//! the `shuffle` module.
+//!
+//! Each entry appears in its set exactly once, with or without LTO, thin
+//! or fat, and however many codegen units the crates are split into.
+//!
+//! # Metadata
+//!
+//! With the `metadata` feature enabled, the [set_entry] attribute also
+//! records the name of each static placed into a set and the module that
+//! defines it, along with its doc comment.  `LinkerSet::iter_named`
+//! yields each entry paired with its name, `LinkerSet::iter_meta` yields
+//! all that is recorded about each entry, such as its documentation for
+//! help output, and `LinkerSet::by_crate` groups the entries by the crate
+//! that registered them, so that everything a misbehaving dependency put
+//! into a set can be found without knowing the names of its entries.
+//!
+//! # Markers
+//!
+//! With the `markers` feature enabled, the [set_entry] attribute also
+//! emits a hidden function that does nothing, named for the set and the
+//! static, such as `__linker_set_entry_stuff_FOO` for the static `FOO` in
+//! the set `stuff`.  Searching for `__linker_set_entry_stuff_` then finds
+//! every entry of the set across a repository, with grep, with the symbol
+//! search of a language server, or with a script assigning owners to
+//! code, without expanding the attribute.
+//!
+//! # Models
+//!
+//! Tools for formal verification, such as Kani, cannot follow the symbols
+//! that the linker defines.  With the `model` feature enabled, the module
+//! output by [set_declare!] has a `MODEL` static.  Once a slice is stored
+//! into it, the [set!] macro yields that slice instead of the linker set,
+//! so a proof harness can supply bounded, possibly nondeterministic,
+//! contents:
+//!
+//! ```ignore
+//! #[kani::proof]
+//! fn check_dispatch() {
+//!     let handlers: [Handler; 2] = kani::any();
+//!     handlers::MODEL.set(Box::leak(Box::new(handlers))).unwrap();
+//!     dispatch(kani::any());
+//! }
+//! ```
+//!
+//! # Raw bytes
+//!
+//! With the `pod` feature enabled, the elements of a set of plain old
+//! data, a type implementing `pod::Pod`, can be viewed as bytes with
+//! `LinkerSet::as_bytes` and made from bytes with
+//! `pod::from_foreign_bytes`, as described in the `pod` module.
+//!
+//! # Asynchronous use
+//!
+//! With the `async` feature enabled, `LinkerSet::stream` yields the
+//! elements of a set as a `futures_core::Stream`, and
+//! `LinkerSet::for_each_concurrent` awaits a function on each element with
+//! at most a given number running at once, on any runtime, as described in
+//! the `stream` module.
+//!
+//! # Compression
+//!
+//! With the `compress` feature enabled, the `compress!` macro compresses
+//! a string literal at compile time into a `Compressed`, for large tables
+//! of text, such as translated messages, whose size in the binary matters.
+//! The text is expanded the first time it is read, and a `Compressed`
+//! dereferences to `str`, as described in the `compressed` module.
+//!
+//! # SQL functions
+//!
+//! With the `rusqlite` feature enabled, `rusqlite::Connection` implements
+//! [udf::Install], so that the scalar and aggregate functions registered in
+//! the [udf::function] set are installed into a connection as it is opened
+//! by [udf::install], as described in the [udf] module.
+//!
+//! # Deserialization
+//!
+//! With the `serde` feature enabled, `formats::deserialize` decodes a body
+//! by the format registered for its content type in the `formats::format`
+//! set, as described in the [formats] module.
+//!
+//! # Tracing
+//!
+//! With the `tracing` feature enabled, `layers::subscriber` composes the
+//! `tracing_subscriber` layers registered in the `layers::layer` set by any
+//! crate into one subscriber, as described in the [layers] module.
+//!
+//! # Counters
+//!
+//! A set of atomic integers, such as per-module statistics, can be totaled
+//! with `LinkerSet::sum`, read with `snapshot`, and zeroed with `reset`,
+//! as described in the [counters] module.
+//!
+//! # Reflection
+//!
+//! Every declared set is recorded in a registry that can be walked with
+//! [reflect::sets].  With the `debug-report` feature enabled,
+//! [reflect::report] lists sets that are empty or were never accessed.
+//! [reflect::configuration] describes every set with its entries, the
+//! entry a singleton set chose, and the entry of highest priority for each
+//! key of a keyed set, for logging the effective configuration at startup.
+//! [handle_debug_args], called early in `main`, prints it and exits when
+//! the program is run with `--linker-set-dump[=NAME]`, or with the
+//! environment variable `LINKER_SET_DUMP` set.
+//!
+//! # Inspection
+//!
+//! The `cargo linker-set` subcommand, built from this crate, reports the
+//! linker sets in a compiled artifact.  `cargo linker-set sizes FILE`
+//! prints the size of each set and how much of it each crate contributed;
+//! with `--budget SET=BYTES`, it fails if a set exceeds its budget.
+//! `cargo linker-set diff OLD NEW` lists the entries added, removed, or
+//! resized between two builds.  `cargo linker-set schema FILE` prints the
+//! description of each declared set that is embedded in the artifact, as
+//! described in the [schema] module, unless the default `schema` feature
+//! is disabled.  `cargo linker-set strings FILE` prints the format
+//! strings interned with [intern!], as described in the [interned]
+//! module.  `cargo linker-set query FILE --set SET` prints the entries of
+//! a set, decoding and filtering them as described in the
+//! [inspect::query] module, and `cargo linker-set generate FILE --set SET`
+//! prints Rust source replicating them, as described in the
+//! [inspect::generate] module.  `cargo linker-set layout FILE` reports
+//! how the elements of each set fall across cache lines, as described in
+//! the [layout] module.  `cargo linker-set deny FILE --list LIST` fails if
+//! crates contribute entries where a list denies them, as described in
+//! the [deny] module.  `cargo linker-set manifest DIR` prints as JSON the
+//! entries that a build registered into each set, with the file and line
+//! of each, recorded with the `manifest` feature as described in the
+//! [manifest] module, and `cargo linker-set lint DIR` fails if an entry
+//! is registered into a set that no crate declares, or a set has no
+//! entries.  The [inspect] module provides the same information as a
+//! library.
+//!
+//! # Debugging
+//!
+//! The [debug_support] module provides scripts for GDB and LLDB that print
+//! linker sets as their elements.  The scripts are experimental.
 //!
 //! # History
 //!
@@ -84,12 +314,182 @@
 //! [CPS]: https://en.wikipedia.org/wiki/Continuation-passing_style
 //! [FreeBSD]: https://github.com/freebsd/freebsd-src/blob/main/sys/sys/linker_set.h
 
-pub use linker_set_proc::set_entry;
+extern crate self as linker_set;
+
+/* Linker sets need a linker that defines __start_ and __stop_ symbols for
+ * sections named like C identifiers.  The linkers of these systems do,
+ * although the illumos linker does so only for sections whose names begin
+ * with set_, which is why every section used by this crate does.  The
+ * Mach-O linker of Apple systems defines section$start$ and section$end$
+ * symbols instead, for sections whose names fit in 16 bytes. */
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "fuchsia",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "illumos",
+    target_vendor = "apple",
+)))]
+compile_error!("linker-set does not support this target");
+
+pub use adapters::StaticIterator;
+pub use build_info::build_info;
+#[cfg(feature = "compress")]
+pub use compressed::Compressed;
+pub use entry_ref::{EntryRef, RefError};
+pub use extended::{ExtendedSet, ExtendedSetIter};
+pub use fatal::fatal;
+pub use features::Featured;
+pub use first_access::FirstAccess;
+pub use keyed::{
+    FxBuildHasher, FxHasher, Keyed, Lookup, OnDuplicate, SortedSlice,
+};
+#[doc(hidden)]
+pub use linker_set_proc::__set_declared;
+#[cfg(feature = "compress")]
+pub use linker_set_proc::compress;
+#[doc(hidden)]
+pub use linker_set_proc::{__mach_o_guard, __mach_o_name, __mach_o_symbol};
+pub use linker_set_proc::{
+    register_const, register_impl, set_entry, set_invariant, set_reserve,
+    shutdown, stage_init, test_entry, SetAbi,
+};
+pub use maybe::MaybeSet;
+#[cfg(feature = "metadata")]
+pub use meta::EntryMeta;
+pub use nested::SetRef;
+pub use patch::BootCell;
+#[cfg(feature = "mock")]
+pub use read::MockSet;
+pub use read::SetRead;
+pub use reflect::handle_debug_args;
+pub use reloadable::{
+    ReadIter, ReloadError, Reloadable, ReloadableSet, WriteIter,
+};
+pub use reserve::ConstDefault;
+pub use self_test::SelfTest;
+pub use sub::{Sub, SubsetId, SubsetIter};
+pub use tracked::Tracked;
+
+pub mod abi;
+mod adapters;
+pub mod build_info;
+pub mod c_header;
+#[cfg(feature = "compress")]
+pub mod compressed;
+pub mod counters;
+pub mod debug_support;
+pub mod deny;
+pub mod di;
+pub mod diagnostics;
+pub mod dispatch;
+mod entry_ref;
+pub mod errors;
+mod extended;
+pub mod fatal;
+mod features;
+mod first_access;
+pub mod flags;
+pub mod formats;
+pub mod harness;
+pub mod inspect;
+pub mod interned;
+pub mod intrusive;
+pub mod invariant;
+mod json;
+mod keyed;
+pub mod layers;
+pub mod layout;
+pub mod manifest;
+mod maybe;
+#[cfg(feature = "metadata")]
+mod meta;
+mod nested;
+mod patch;
+pub mod plugins;
+#[cfg(feature = "pod")]
+pub mod pod;
+mod read;
+pub mod reflect;
+mod reloadable;
+pub mod replay;
+mod reserve;
+pub mod routes;
+pub mod schema;
+pub mod seal;
+mod self_test;
+#[cfg(feature = "shuffle")]
+pub mod shuffle;
+pub mod shutdown;
+pub mod stage;
+#[cfg(feature = "async")]
+pub mod stream;
+mod sub;
+mod tracked;
+pub mod udf;
+pub mod versioned;
+pub mod workers;
+
+/// Returns the elements of a linker set given its start and stop symbols.
+///
+/// As far as the compiler knows, the start and stop symbols are distinct
+/// statics of one element each, so neither pointer arithmetic between them
+/// (e.g., `offset_from`) nor access beyond the first element through the
+/// start symbol is justified under Rust's provenance rules.  The linker
+/// defined the symbols to bracket memory that Rust did not allocate, so the
+/// length is computed from their addresses alone, and the elements are
+/// accessed through a pointer with exposed provenance, which is how Rust
+/// models such memory.  Pointer-integer `as` casts have exactly these
+/// semantics (`expose_provenance` and `with_exposed_provenance`) on every
+/// compiler version.
+///
+/// # Safety
+/// The pointers must be start and stop pointers generated by the linker.
+unsafe fn span<'a, T>(start: *const T, stop: *const T) -> &'a [T] {
+    let size = std::mem::size_of::<T>();
+    assert!(size > 0, "linker set elements must not be zero-sized");
+    assert!(start.is_aligned() && stop.is_aligned());
+    let (start, stop) = (start as usize, stop as usize);
+    assert!(start <= stop);
+    let base = start as *const T;
+    std::slice::from_raw_parts(base, (stop - start) / size)
+}
+
+/// Returns true if two strings are equal, at compile time.
+#[doc(hidden)]
+pub const fn __same_str(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Returns true if a size is within the maximum size of the elements of a
+/// set, at compile time.
+#[doc(hidden)]
+pub const fn __fits(size: usize, max: usize) -> bool {
+    size <= max
+}
 
 /// An iterator that yields the elements in a linker set.
 pub struct LinkerSetIter<T> {
     next: *const T,
     stop: *const T,
+    #[cfg(feature = "shuffle")]
+    start: *const T,
+    #[cfg(feature = "shuffle")]
+    order: shuffle::Order,
 }
 
 impl<T> LinkerSetIter<T> {
@@ -100,8 +500,35 @@ impl<T> LinkerSetIter<T> {
     /// # Safety
     /// The pointers must be start and end pointers generated by the linker.
     pub unsafe fn new(start: *const T, stop: *const T) -> Self {
-        assert!(start < stop);
-        Self { next: start, stop }
+        Self::from_slice(span(start, stop))
+    }
+
+    fn from_slice(slice: &[T]) -> Self {
+        let range = slice.as_ptr_range();
+        Self {
+            next: range.start,
+            stop: range.end,
+            #[cfg(feature = "shuffle")]
+            start: range.start,
+            #[cfg(feature = "shuffle")]
+            order: shuffle::Order::new::<T>(slice.len()),
+        }
+    }
+
+    /// Returns the element at the position of `next`, which must not be
+    /// `stop`.
+    #[cfg(not(feature = "shuffle"))]
+    unsafe fn current(&self) -> *const T {
+        self.next
+    }
+
+    /// Returns the element at the position of `next`, which must not be
+    /// `stop`, in the order shuffled for this run.
+    #[cfg(feature = "shuffle")]
+    unsafe fn current(&self) -> *const T {
+        let i = self.next.offset_from(self.start) as usize;
+        let len = self.stop.offset_from(self.start) as usize;
+        self.start.add(self.order.index(i, len))
     }
 }
 
@@ -116,7 +543,7 @@ where
             None
         } else {
             unsafe {
-                let x = self.next.as_ref();
+                let x = self.current().as_ref();
                 self.next = self.next.add(1);
                 x
             }
@@ -144,7 +571,7 @@ where
 
 impl<T> std::iter::FusedIterator for LinkerSetIter<T> where T: 'static {}
 
-unsafe impl<T: Send> Send for LinkerSetIter<T> {}
+unsafe impl<T: Sync> Send for LinkerSetIter<T> {}
 
 /// A proxy object that represents a linker set.
 ///
@@ -155,9 +582,12 @@ pub struct LinkerSet<T>
 where
     T: 'static,
 {
-    start: *const T,
-    stop: *const T,
     slice: &'static [T],
+    #[cfg(feature = "metadata")]
+    meta: &'static [EntryMeta<T>],
+    self_tests: &'static [SelfTest<T>],
+    features: &'static [Featured<T>],
+    name: Option<&'static str>,
 }
 
 impl<T> LinkerSet<T>
@@ -169,15 +599,239 @@ where
     /// # Safety
     /// The pointers must be start and end pointers generated by the linker.
     pub unsafe fn new(start: *const T, stop: *const T) -> Self {
-        assert!(start < stop);
-        let len = stop.offset_from(start).try_into().unwrap();
-        let slice = std::slice::from_raw_parts(start, len);
-        Self { start, stop, slice }
+        Self {
+            slice: span(start, stop),
+            #[cfg(feature = "metadata")]
+            meta: &[],
+            self_tests: &[],
+            features: &[],
+            name: None,
+        }
+    }
+
+    /// Create a linker set proxy over the elements of a slice rather than
+    /// those placed into a section by the linker.
+    ///
+    /// This is useful for testing code that takes a linker set, or for
+    /// proofs over it with the `model` feature.
+    pub fn from_slice(slice: &'static [T]) -> Self {
+        Self {
+            slice,
+            #[cfg(feature = "metadata")]
+            meta: &[],
+            self_tests: &[],
+            features: &[],
+            name: None,
+        }
+    }
+
+    /// Attach the metadata recorded about the entries of the linker set.
+    ///
+    /// Users should call the [set!] macro instead of this function.
+    ///
+    /// # Safety
+    /// The pointers must be start and end pointers generated by the linker
+    /// for the metadata section of this linker set.
+    #[cfg(feature = "metadata")]
+    pub unsafe fn with_metadata(
+        mut self, start: *const EntryMeta<T>, stop: *const EntryMeta<T>,
+    ) -> Self {
+        self.meta = span(start, stop);
+        self
+    }
+
+    /// Attach the checks recorded with the entries of the linker set.
+    ///
+    /// Users should call the [set!] macro instead of this function.
+    ///
+    /// # Safety
+    /// The pointers must be start and end pointers generated by the linker
+    /// for the self-test section of this linker set.
+    pub unsafe fn with_self_tests(
+        mut self, start: *const SelfTest<T>, stop: *const SelfTest<T>,
+    ) -> Self {
+        self.self_tests = span(start, stop);
+        self
+    }
+
+    /// Attach the CPU features recorded with the entries of the linker
+    /// set.
+    ///
+    /// Users should call the [set!] macro instead of this function.
+    ///
+    /// # Safety
+    /// The pointers must be start and end pointers generated by the linker
+    /// for the feature section of this linker set.
+    pub unsafe fn with_features(
+        mut self, start: *const Featured<T>, stop: *const Featured<T>,
+    ) -> Self {
+        self.features = span(start, stop);
+        self
+    }
+
+    /// Attach the name of the linker set.
+    ///
+    /// Users should call the [set!] macro instead of this function.
+    #[doc(hidden)]
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Returns the name of the linker set, if it was created by the [set!]
+    /// macro.
+    pub fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+
+    /// Write a line describing the linker set, with its name, number of
+    /// elements, and address range, without allocating.
+    ///
+    /// This is safe to call where allocating is not, such as from a panic
+    /// hook or an out-of-memory handler, to include the state of
+    /// registries in crash output.
+    pub fn write_brief<W>(&self, w: &mut W) -> std::fmt::Result
+    where
+        W: std::fmt::Write + ?Sized,
+    {
+        match self.name {
+            Some(name) => write!(w, "{}", name)?,
+            None => write!(w, "<{}>", std::any::type_name::<T>())?,
+        }
+        let range = self.slice.as_ptr_range();
+        write!(
+            w,
+            ": {} x {} bytes at {:p}..{:p}",
+            self.len(),
+            std::mem::size_of::<T>(),
+            range.start,
+            range.end
+        )
+    }
+
+    /// Run the check recorded with each entry of the linker set by
+    /// `#[set_entry(name, self_test = path)]`, which requires the set to be
+    /// declared with the `self_test` option.
+    ///
+    /// Returns the name of the static holding each entry that fails its
+    /// check, with the error returned by the check, or the message of the
+    /// panic of a check that panics.
+    pub fn run_self_tests(&self) -> Result<(), Vec<(&'static str, String)>> {
+        let failures = self
+            .self_tests
+            .iter()
+            .filter_map(|t| t.run().err().map(|e| (t.name(), e)))
+            .collect::<Vec<_>>();
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures)
+        }
+    }
+
+    /// Returns the checks recorded with the entries of the linker set, such
+    /// as to run them with the [harness].
+    pub fn self_tests(&self) -> &'static [SelfTest<T>] {
+        self.self_tests
     }
 
     /// Returns an iterator over the items in the linker set.
     pub fn iter(&self) -> LinkerSetIter<T> {
-        unsafe { LinkerSetIter::new(self.start, self.stop) }
+        LinkerSetIter::from_slice(self.slice)
+    }
+
+    /// Returns an iterator over the items in the linker set that calls
+    /// `audit` with each item as it is yielded.
+    ///
+    /// This lets a registry that dispatches on untrusted input record
+    /// which entry handled what, without instrumenting every call site.
+    pub fn iter_audited<F>(
+        &self, audit: F,
+    ) -> std::iter::Inspect<LinkerSetIter<T>, F>
+    where
+        F: FnMut(&&'static T),
+    {
+        self.iter().inspect(audit)
+    }
+
+    /// Returns an iterator over the items in the linker set, pinned.
+    ///
+    /// Items of a linker set are statics, which stay where they are for
+    /// the life of the program, so pinning them needs no unsafe code.
+    pub fn iter_pinned(
+        &self,
+    ) -> impl Iterator<Item = std::pin::Pin<&'static T>> {
+        self.iter().map(std::pin::Pin::static_ref)
+    }
+
+    /// Returns an iterator over the items in the linker set, each paired
+    /// with the name of the static that holds it.
+    #[cfg(feature = "metadata")]
+    pub fn iter_named(
+        &self,
+    ) -> impl Iterator<Item = (&'static str, &'static T)> {
+        self.meta.iter().map(|m| (m.name(), m.entry()))
+    }
+
+    /// Returns an iterator over the metadata recorded about the items in
+    /// the linker set, such as their names and doc comments.
+    #[cfg(feature = "metadata")]
+    pub fn iter_meta(&self) -> std::slice::Iter<'static, EntryMeta<T>> {
+        self.meta.iter()
+    }
+
+    /// Returns the items in the linker set grouped by the crate that
+    /// defines them, in order of crate name.
+    #[cfg(feature = "metadata")]
+    pub fn by_crate(
+        &self,
+    ) -> impl Iterator<Item = (&'static str, Vec<&'static T>)> {
+        let mut crates = std::collections::BTreeMap::<_, Vec<_>>::new();
+        for m in self.meta {
+            crates.entry(m.crate_name()).or_default().push(m.entry());
+        }
+        crates.into_iter()
+    }
+
+    /// Returns a description of each item in the linker set: the path of
+    /// its static, with the `metadata` feature, or otherwise its index and
+    /// address.
+    pub fn entry_names(&self) -> Vec<String> {
+        self.iter().map(|e| self.describe(e)).collect()
+    }
+
+    #[cfg(feature = "metadata")]
+    pub(crate) fn describe(&self, entry: &'static T) -> String {
+        match self.iter_meta().find(|m| std::ptr::eq(m.entry(), entry)) {
+            Some(m) => format!("{}::{}", m.module(), m.name()),
+            None => self.describe_index(entry),
+        }
+    }
+
+    #[cfg(not(feature = "metadata"))]
+    pub(crate) fn describe(&self, entry: &'static T) -> String {
+        self.describe_index(entry)
+    }
+
+    fn describe_index(&self, entry: &'static T) -> String {
+        match self.slice.iter().position(|e| std::ptr::eq(e, entry)) {
+            Some(i) => format!("entry {} at {:p}", i, entry),
+            None => format!("entry at {:p}", entry),
+        }
+    }
+
+    /// Returns a pointer to the first element of the linker set and the
+    /// number of elements, such as to hand the set to C.
+    ///
+    /// The elements are contiguous, `size_of::<T>()` bytes apart, and the
+    /// pointer is aligned for `T` and never null, though it must not be
+    /// read through if the set is empty.  The elements live, unchanged,
+    /// for the life of the program, and must not be written through the
+    /// pointer unless `T` has interior mutability.  Their layout matches
+    /// that of a C array only if `T` has a C equivalent, such as a
+    /// `#[repr(C)]` struct; see [set_export_c!] to give C an accessor.
+    pub fn as_raw_parts(&self) -> (*const T, usize) {
+        (self.slice.as_ptr(), self.slice.len())
     }
 
     /// Returns the number of elements in the linker set.
@@ -187,7 +841,58 @@ where
 
     /// Returns true if the linker set contains zero elements.
     pub fn is_empty(&self) -> bool {
-        self.start == self.stop
+        self.slice.is_empty()
+    }
+
+    /// Returns the element at an index, or `None` if the index is out of
+    /// range, such as in a set whose size varies with the features of a
+    /// build.
+    pub fn get(&self, i: usize) -> Option<&'static T> {
+        self.slice.get(i)
+    }
+
+    /// Returns the first element, or `None` if the set is empty.
+    pub fn first(&self) -> Option<&'static T> {
+        self.slice.first()
+    }
+
+    /// Returns the last element, or `None` if the set is empty.
+    pub fn last(&self) -> Option<&'static T> {
+        self.slice.last()
+    }
+}
+
+impl<T> LinkerSet<Option<T>>
+where
+    T: 'static,
+{
+    /// Returns an iterator over the items in the linker set that are
+    /// present, such as the hooks that are registered in a set of
+    /// optional hooks.
+    pub fn iter_some(&self) -> impl Iterator<Item = &'static T> {
+        self.iter().filter_map(Option::as_ref)
+    }
+}
+
+impl<T> LinkerSet<T>
+where
+    T: Copy + 'static,
+{
+    /// Returns an iterator over copies of the items in the linker set, such
+    /// as the numbers of a table, rather than references to them.
+    pub fn iter_copied(&self) -> std::iter::Copied<LinkerSetIter<T>> {
+        self.iter().copied()
+    }
+
+    /// Returns copies of the items in the linker set.
+    pub fn to_vec(&self) -> Vec<T> {
+        self.slice.to_vec()
+    }
+
+    /// Returns copies of the items in the linker set as an array, if it
+    /// has exactly `N` of them.
+    pub fn to_array<const N: usize>(&self) -> Option<[T; N]> {
+        self.slice.try_into().ok()
     }
 }
 
@@ -215,69 +920,1424 @@ where
     }
 }
 
-unsafe impl<T: Send> Send for LinkerSet<T> {}
+unsafe impl<T: Sync> Send for LinkerSet<T> {}
 unsafe impl<T: Sync> Sync for LinkerSet<T> {} // readonly once created
 
 /// Declare the name of a linker set.
 ///
 /// This macro outputs a module into the current scope.  The module must
 /// be brought into scope should the linker set be used within another module.
+/// Besides the symbols used by the other macros, the module provides
+/// `SECTION_NAME`, the name of the section holding the set, and
+/// `start_addr()` and `stop_addr()`, which return the addresses bracketing
+/// it.
+///
+/// Options follow the type, separated by commas, in any order, such as
+/// `set_declare!(name, type, keyed, sealed, stage = "mmu_up")`.  A set may
+/// be either `singleton` or `keyed`, but not both, and `on_duplicate` and
+/// `lookup` apply only to a keyed set.
+///
+/// A set declared as `set_declare!(name, type, sealed)` is sealed: its
+/// module is visible only within the declaring crate, so other crates
+/// cannot name it to contribute entries, and it is namespaced, as
+/// described below, so that no other crate can merge entries into its
+/// section either.  Since all its entries are then known to the compiler,
+/// the module also provides the const `LOCAL_LEN`, the number of entries,
+/// such as to size an array, and, from Rust 1.90, the const `LOCAL`, an
+/// array of references to them.  An entry of a sealed set must name it by
+/// a path beginning with `crate`, `self`, or `super`, and it holds at
+/// most 64 entries.
+///
+/// A set declared as `set_declare!(name, type, cfg(predicate))` is
+/// disabled when the predicate is false: the [set!] macro then yields an
+/// empty set, and entries still placed into it are inert, so code using
+/// the set compiles and links either way.  To drop the entries from the
+/// binary, they must be guarded by the same predicate.
+///
+/// A set declared as `set_declare!(name, type, singleton)` is meant to
+/// hold exactly one entry, such as the implementation of an interface
+/// chosen at link time.  Its module provides `get()`, which returns the
+/// entry, checking on the first call that there is exactly one.
+///
+/// A set declared as `set_declare!(name, type, keyed)` resolves entries by
+/// key.  Entries placed into it with `#[set_entry(name, key = "...",
+/// priority = n)]` are recorded with their key and priority, and the
+/// module provides `resolved()`, which maps each key to the entry of
+/// highest priority, so that a crate can override an entry of a library
+/// by registering one with a higher priority.
+/// Declared as `set_declare!(name, type, keyed, on_duplicate = policy)`,
+/// the set chooses among entries with the same key by the policy instead,
+/// one of `panic`, `first_wins`, `last_wins`, or `priority`, the default;
+/// see [OnDuplicate].
+/// The module also provides `lookup()`, which finds the entry chosen for a
+/// key in an index built on the first call.  The index is a `BTreeMap`
+/// unless the set is declared with `lookup = kind` after `keyed` or the
+/// policy, such as `lookup = fx` for a `HashMap` with a fast hasher for
+/// short keys, `lookup = hash(ahash::RandomState)` for one with a hasher
+/// of another crate, or `lookup = sorted(64)` for a sorted slice of up to
+/// 64 keys that allocates nothing; see [Lookup].  Given a key that has
+/// no entry, such as one mistyped by a user, `suggest()` returns the key
+/// closest to it, if any is close; see [Keyed::closest].
+///
+/// A set declared as `set_declare!(name, type, on_first_access = path)`,
+/// where the path names a `fn(&type)`, calls the function on each entry
+/// the first time the set is accessed with the [set!] macro, such as to
+/// log the effective configuration or to register the entries with a
+/// library in C, exactly once and without a separate call at startup.
+/// The function must not itself access the set.
+///
+/// A set takes entries with self tests, entries requiring CPU features,
+/// or test entries only if declared with the `self_test`,
+/// `target_feature`, or `test_entries` option respectively, which gives
+/// it the section holding such entries, and `best_for_cpu()` for
+/// `target_feature`.  Other sets have none of these sections.
+///
+/// A set declared as `set_declare!(name, type, max_size = 64)` must keep
+/// its entries within a size, such as that of a cache line or of a
+/// descriptor read by hardware.  An entry of a larger type fails to
+/// compile, with an error naming its static.
+///
+/// A set declared as `set_declare!(name, type, stage = "mmu_up")` belongs
+/// to a boot stage.  In debug builds, accessing it with the [set!] macro
+/// before `stage::advance` has reached the stage panics, as described in
+/// the [stage] module.
+///
+/// Two crates that each declare a set of the same name share its section,
+/// so that their entries merge.  A set declared as `set_declare!(name,
+/// type, namespaced)` has a section named also for the declaring crate,
+/// so that it merges with no other.  Its entries must name it by a path
+/// beginning with that crate, as `#[set_entry(krate::name)]` from another
+/// crate, or `#[set_entry(crate::name)]` or `#[set_entry(self::name)]`
+/// from the crate itself, making the crate into whose set an entry goes
+/// explicit.  An entry that names a namespaced set by a bare name, or
+/// another set by a path, fails to compile.
 #[macro_export]
 macro_rules! set_declare {
-    ($set:ident, $type:ty) => {
-        pub mod $set {
+    ($set:ident, $type:ty $(, $($option:tt)+)?) => {
+        $crate::__set_options!(
+            [$set, $type; no no no no] [pub] [""] [] [] [None] [usize::MAX]
+            [plain] [] [] $(, $($option)+)?
+        );
+    };
+}
+
+/* collects the options of set_declare!, in any order, into the vis, ns,
+ * cfg, hook, stage, max, kind, policy, and lookup of the set, and into
+ * whether it has self tests, entries by CPU feature, and test entries,
+ * and whether it is sealed */
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __set_options {
+    (
+        [$set:ident, $type:ty; $st:ident $tf:ident $te:ident $se:ident]
+        $vis:tt $ns:tt $cfg:tt $hook:tt $stage:tt $max:tt $kind:tt
+        $policy:tt $lookup:tt, sealed $($rest:tt)*
+    ) => {
+        $crate::__set_options!(
+            [$set, $type; $st $tf $te yes] [pub(crate)]
+            [concat!(env!("CARGO_CRATE_NAME"), "__")]
+            $cfg $hook $stage $max $kind $policy $lookup $($rest)*
+        );
+    };
+    (
+        $head:tt $vis:tt $ns:tt $cfg:tt $hook:tt $stage:tt $max:tt $kind:tt
+        $policy:tt $lookup:tt, namespaced $($rest:tt)*
+    ) => {
+        $crate::__set_options!(
+            $head $vis [concat!(env!("CARGO_CRATE_NAME"), "__")]
+            $cfg $hook $stage $max $kind $policy $lookup $($rest)*
+        );
+    };
+    (
+        $head:tt $vis:tt $ns:tt $cfg:tt $hook:tt $stage:tt $max:tt [plain]
+        $policy:tt $lookup:tt, singleton $($rest:tt)*
+    ) => {
+        $crate::__set_options!(
+            $head $vis $ns $cfg $hook $stage $max [singleton] $policy $lookup
+            $($rest)*
+        );
+    };
+    (
+        $head:tt $vis:tt $ns:tt $cfg:tt $hook:tt $stage:tt $max:tt [plain]
+        $policy:tt $lookup:tt, keyed $($rest:tt)*
+    ) => {
+        $crate::__set_options!(
+            $head $vis $ns $cfg $hook $stage $max [keyed] $policy $lookup
+            $($rest)*
+        );
+    };
+    (
+        $head:tt $vis:tt $ns:tt $cfg:tt $hook:tt $stage:tt $max:tt $kind:tt
+        $policy:tt $lookup:tt, singleton $($rest:tt)*
+    ) => {
+        compile_error!(
+            "`singleton` and `keyed` cannot be combined or repeated"
+        );
+    };
+    (
+        $head:tt $vis:tt $ns:tt $cfg:tt $hook:tt $stage:tt $max:tt $kind:tt
+        $policy:tt $lookup:tt, keyed $($rest:tt)*
+    ) => {
+        compile_error!(
+            "`singleton` and `keyed` cannot be combined or repeated"
+        );
+    };
+    (
+        $head:tt $vis:tt $ns:tt $cfg:tt $hook:tt $stage:tt $max:tt $kind:tt
+        $policy:tt $lookup:tt, on_duplicate = $new:ident $($rest:tt)*
+    ) => {
+        $crate::__set_options!(
+            $head $vis $ns $cfg $hook $stage $max $kind [$new] $lookup
+            $($rest)*
+        );
+    };
+    (
+        $head:tt $vis:tt $ns:tt $cfg:tt $hook:tt $stage:tt $max:tt $kind:tt
+        $policy:tt $lookup:tt, lookup = $new:ident($($args:tt)*)
+        $($rest:tt)*
+    ) => {
+        $crate::__set_options!(
+            $head $vis $ns $cfg $hook $stage $max $kind $policy
+            [$new($($args)*)] $($rest)*
+        );
+    };
+    (
+        $head:tt $vis:tt $ns:tt $cfg:tt $hook:tt $stage:tt $max:tt $kind:tt
+        $policy:tt $lookup:tt, lookup = $new:ident $($rest:tt)*
+    ) => {
+        $crate::__set_options!(
+            $head $vis $ns $cfg $hook $stage $max $kind $policy [$new]
+            $($rest)*
+        );
+    };
+    (
+        $head:tt $vis:tt $ns:tt $cfg:tt $hook:tt $stage:tt $max:tt $kind:tt
+        $policy:tt $lookup:tt, cfg($new:meta) $($rest:tt)*
+    ) => {
+        $crate::__set_options!(
+            $head $vis $ns [$new] $hook $stage $max $kind $policy $lookup
+            $($rest)*
+        );
+    };
+    (
+        $head:tt $vis:tt $ns:tt $cfg:tt $hook:tt $stage:tt $max:tt $kind:tt
+        $policy:tt $lookup:tt, on_first_access = $new:path
+        $(, $($rest:tt)*)?
+    ) => {
+        $crate::__set_options!(
+            $head $vis $ns $cfg [$new] $stage $max $kind $policy $lookup
+            $(, $($rest)*)?
+        );
+    };
+    (
+        $head:tt $vis:tt $ns:tt $cfg:tt $hook:tt $stage:tt $max:tt $kind:tt
+        $policy:tt $lookup:tt, max_size = $new:expr $(, $($rest:tt)*)?
+    ) => {
+        $crate::__set_options!(
+            $head $vis $ns $cfg $hook $stage [$new] $kind $policy $lookup
+            $(, $($rest)*)?
+        );
+    };
+    (
+        $head:tt $vis:tt $ns:tt $cfg:tt $hook:tt $stage:tt $max:tt $kind:tt
+        $policy:tt $lookup:tt, stage = $new:literal $($rest:tt)*
+    ) => {
+        $crate::__set_options!(
+            $head $vis $ns $cfg $hook [Some($new)] $max $kind $policy $lookup
+            $($rest)*
+        );
+    };
+    (
+        [$set:ident, $type:ty; $st:ident $tf:ident $te:ident $se:ident]
+        $vis:tt $ns:tt $cfg:tt $hook:tt $stage:tt $max:tt $kind:tt
+        $policy:tt $lookup:tt, self_test $($rest:tt)*
+    ) => {
+        $crate::__set_options!(
+            [$set, $type; yes $tf $te $se] $vis $ns $cfg $hook $stage $max $kind
+            $policy $lookup $($rest)*
+        );
+    };
+    (
+        [$set:ident, $type:ty; $st:ident $tf:ident $te:ident $se:ident]
+        $vis:tt $ns:tt $cfg:tt $hook:tt $stage:tt $max:tt $kind:tt
+        $policy:tt $lookup:tt, target_feature $($rest:tt)*
+    ) => {
+        $crate::__set_options!(
+            [$set, $type; $st yes $te $se] $vis $ns $cfg $hook $stage $max $kind
+            $policy $lookup $($rest)*
+        );
+    };
+    (
+        [$set:ident, $type:ty; $st:ident $tf:ident $te:ident $se:ident]
+        $vis:tt $ns:tt $cfg:tt $hook:tt $stage:tt $max:tt $kind:tt
+        $policy:tt $lookup:tt, test_entries $($rest:tt)*
+    ) => {
+        $crate::__set_options!(
+            [$set, $type; $st $tf yes $se] $vis $ns $cfg $hook $stage $max $kind
+            $policy $lookup $($rest)*
+        );
+    };
+    (
+        $head:tt $vis:tt $ns:tt $cfg:tt $hook:tt $stage:tt $max:tt $kind:tt
+        $policy:tt $lookup:tt, $other:tt $($rest:tt)*
+    ) => {
+        compile_error!(
+            "expected `sealed`, `namespaced`, `singleton`, `keyed`, \
+             `on_duplicate`, `lookup`, `cfg`, `on_first_access`, \
+             `max_size`, `stage`, `self_test`, `target_feature`, or \
+             `test_entries`"
+        );
+    };
+    (
+        $head:tt $vis:tt $ns:tt [] $hook:tt $stage:tt $max:tt $kind:tt
+        $policy:tt $lookup:tt
+    ) => {
+        $crate::__set_kind!(
+            $head $vis [""] $ns $hook $stage $max $kind $policy $lookup
+        );
+    };
+    (
+        $head:tt $vis:tt $ns:tt [$cfg:meta] $hook:tt $stage:tt $max:tt
+        $kind:tt $policy:tt $lookup:tt
+    ) => {
+        #[cfg($cfg)]
+        $crate::__set_kind!(
+            $head $vis [""] $ns $hook $stage $max $kind $policy $lookup
+        );
+        #[cfg(not($cfg))]
+        $crate::__set_kind!(
+            $head $vis ["_disabled_"] $ns $hook $stage $max $kind $policy
+            $lookup
+        );
+    };
+}
+
+/* declares a set of a kind, with the helpers of keyed and singleton sets */
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __set_kind {
+    (
+        [$set:ident, $type:ty; $($helpers:ident)*] [$($vis:tt)*]
+        [$prefix:literal] [$ns:expr]
+        $hook:tt [$stage:expr] [$max:expr] [plain] [] []
+    ) => {
+        $crate::__set_declare!(
+            $($vis)*, $set, $type, $prefix, $ns, [$($helpers)*], $hook,
+            $stage, $max, None
+        );
+    };
+    (
+        [$set:ident, $type:ty; $($helpers:ident)*] [$($vis:tt)*]
+        [$prefix:literal] [$ns:expr]
+        $hook:tt [$stage:expr] [$max:expr] [singleton] [] []
+    ) => {
+        $crate::__set_declare!(
+            $($vis)*, $set, $type, $prefix, $ns, [$($helpers)*], $hook,
+            $stage, $max,
+            Some($crate::reflect::Chooser::new(__choices, __resolve)),
+            $crate::__set_singleton!($set, $type);
+        );
+    };
+    (
+        $head:tt $vis:tt $prefix:tt $ns:tt $hook:tt $stage:tt $max:tt [keyed]
+        [] $lookup:tt
+    ) => {
+        $crate::__set_kind!(
+            $head $vis $prefix $ns $hook $stage $max [keyed] [priority]
+            $lookup
+        );
+    };
+    (
+        $head:tt $vis:tt $prefix:tt $ns:tt $hook:tt $stage:tt $max:tt [keyed]
+        $policy:tt []
+    ) => {
+        $crate::__set_kind!(
+            $head $vis $prefix $ns $hook $stage $max [keyed] $policy [btree]
+        );
+    };
+    (
+        [$set:ident, $type:ty; $($helpers:ident)*] [$($vis:tt)*]
+        [$prefix:literal] [$ns:expr]
+        $hook:tt [$stage:expr] [$max:expr] [keyed] [$policy:ident]
+        [$($lookup:tt)+]
+    ) => {
+        $crate::__set_declare!(
+            $($vis)*, $set, $type, $prefix, $ns, [$($helpers)*], $hook,
+            $stage, $max,
+            Some($crate::reflect::Chooser::new(__choices, __resolve)),
+            $crate::__set_keyed!(
+                $set, $type, concat!($prefix, $ns),
+                $crate::__on_duplicate!($policy),
+                $crate::__lookup!($type, $($lookup)+)
+            );
+        );
+    };
+    (
+        $head:tt $vis:tt $prefix:tt $ns:tt $hook:tt $stage:tt $max:tt
+        $($rest:tt)*
+    ) => {
+        compile_error!("`on_duplicate` and `lookup` require `keyed`");
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __set_declare {
+    (
+        $vis:vis, $set:ident, $type:ty, $prefix:literal, $ns:expr,
+        [$self_tests:ident $features:ident $tests:ident $sealed:ident],
+        [$($hook:path)?], $stage:expr, $max:expr, $chooser:expr
+        $(, $extra:item)*
+    ) => {
+        #[doc = concat!("The `", stringify!($set), "` linker set.")]
+        $vis mod $set {
             #[allow(unused_imports)]
             use super::*;
-            paste::paste! {
-                extern {
-                    /* rust thinks we're allowing these things to come in from
-                     * C code, so if type is a function, it gets cranky because
-                     * it thinks we're proposing to call a function in C with
-                     * rust calling convention. */
-                    #[allow(improper_ctypes)]
-                    pub static [<__start_set_ $set>]: $type;
-                    #[allow(improper_ctypes)]
-                    pub static [<__stop_set_ $set>]: $type;
+            /* only the addresses of these symbols are used, so they are
+             * declared as bytes rather than as the element type, which
+             * need not be one that C could exchange.  span() checks that
+             * they are aligned for the element type. */
+            $crate::__set_bounds!(
+                /// The first element of the linker set.
+                pub __START,
+                /// One past the last element of the linker set.
+                __STOP,
+                "set_", $prefix, $ns, stringify!($set)
+            );
+
+            /* ensure the section exists, so that the start and stop
+             * symbols are defined even if the set has no entries */
+            $crate::__set_section!(
+                ["set_", $prefix, $ns, stringify!($set)]
+                #[used]
+                static __EMPTY_SET: [$type; 0] = [];
+            );
+
+            /* named by the typecheck of each entry, so that a mismatch
+             * is reported against the type as declared */
+            #[doc(hidden)]
+            pub type __Type = $type;
+
+            const _: () = assert!(
+                ::std::mem::size_of::<$type>() != 0,
+                "linker set elements must not be zero-sized"
+            );
+
+            $crate::__set_declared!($set, $type);
+
+            /* compared with the size of each entry, so that an entry too
+             * large is reported against its static */
+            #[doc(hidden)]
+            pub const __MAX_SIZE: usize = $max;
+
+            const _: () = assert!(
+                $crate::__fits(::std::mem::size_of::<$type>(), __MAX_SIZE),
+                "linker set elements must not be larger than max_size"
+            );
+
+            /* compared with the namespace of each entry, so that an
+             * entry naming the set wrongly fails to compile rather than
+             * landing in a section that nothing reads */
+            #[doc(hidden)]
+            pub const __NAMESPACE: &str = $ns;
+
+            /// The name of the section holding the linker set.
+            #[allow(dead_code)]
+            pub const SECTION_NAME: &str =
+                concat!("set_", $prefix, $ns, stringify!($set));
+
+            /// Returns the address of the first element of the linker
+            /// set.
+            #[allow(dead_code, unused_unsafe)] // safe since rust 1.82
+            pub fn start_addr() -> *const $type {
+                unsafe { ::std::ptr::addr_of!(__START).cast() }
+            }
+
+            /// Returns the address one past the last element of the
+            /// linker set.
+            #[allow(dead_code, unused_unsafe)] // safe since rust 1.82
+            pub fn stop_addr() -> *const $type {
+                unsafe { ::std::ptr::addr_of!(__STOP).cast() }
+            }
+
+            #[doc(hidden)]
+            pub static __ACCESSED: ::std::sync::atomic::AtomicBool =
+                ::std::sync::atomic::AtomicBool::new(false);
+
+            /* checked by set!() in debug builds */
+            #[doc(hidden)]
+            pub const __STAGE: Option<&str> = $stage;
+
+            $crate::__set_first_access!($type $(, $hook)?);
+
+            /* the proxy that set!() returns, before its first access */
+            #[doc(hidden)]
+            #[inline]
+            pub fn __linker_set() -> $crate::LinkerSet<$type> {
+                __features(__self_tests(__entries()))
+            }
+
+            /// Returns a proxy object for the linker set, created by the
+            /// first call and cached, so that later calls cost a single
+            /// atomic load.
+            ///
+            /// A model set with the `model` feature after the first
+            /// call is not seen through the cached object.
+            #[allow(dead_code)]
+            pub fn handle() -> &'static $crate::LinkerSet<$type> {
+                static HANDLE: ::std::sync::OnceLock<
+                    $crate::LinkerSet<$type>,
+                > = ::std::sync::OnceLock::new();
+                HANDLE.get_or_init(|| $crate::set!($set))
+            }
+
+            $crate::__set_section!(
+                ["linker_set_declared"]
+                #[used]
+                static __SET_INFO: $crate::reflect::SetInfo =
+                    $crate::reflect::SetInfo::new(
+                        stringify!($set),
+                        module_path!(),
+                        SECTION_NAME,
+                        $crate::__mach_o_name!(
+                            "set_", $prefix, $ns, stringify!($set)
+                        ),
+                        stringify!($type),
+                        ::std::mem::size_of::<$type>(),
+                        || start_addr() as usize..stop_addr() as usize,
+                        || __entries().entry_names(),
+                        $chooser,
+                        __STAGE,
+                        || {
+                            $crate::set!($set);
+                        },
+                        &__ACCESSED,
+                    );
+            );
+
+            /* the entries as the report of the configuration names them,
+             * read without marking the set as accessed */
+            #[allow(dead_code)]
+            #[inline]
+            fn __entries() -> $crate::LinkerSet<$type> {
+                unsafe {
+                    $crate::__set_with_meta!(
+                        $set,
+                        $crate::LinkerSet::new(start_addr(), stop_addr())
+                    )
                 }
             }
+
+            $crate::__set_declare_schema!($set, $type);
+            $crate::__set_declare_meta!($set, $type, concat!($prefix, $ns));
+            $crate::__set_declare_self_tests!(
+                $self_tests, $set, $type, concat!($prefix, $ns)
+            );
+            $crate::__set_declare_features!(
+                $features, $set, $type, concat!($prefix, $ns)
+            );
+            $crate::__set_declare_tests!(
+                $tests, $set, $type, concat!($prefix, $ns)
+            );
+            $crate::__set_declare_slots!($sealed, $type);
+            $crate::__set_declare_model!($type);
+            $($extra)*
         }
     };
 }
 
-/// Create a linker set proxy object for iteration or indexing.
+#[doc(hidden)]
 #[macro_export]
-macro_rules! set {
-    ($set:ident) => {{
-        paste::paste! {
-            unsafe {
-                LinkerSet::new(
-                    &$set::[<__start_set_ $set>],
-                    &$set::[<__stop_set_ $set>],
-                )
-            }
+macro_rules! __set_first_access {
+    ($type:ty) => {
+        #[doc(hidden)]
+        #[inline(always)]
+        pub fn __first_access(
+            set: $crate::LinkerSet<$type>,
+        ) -> $crate::LinkerSet<$type> {
+            set
         }
-    }};
+    };
+    ($type:ty, $hook:path) => {
+        #[doc(hidden)]
+        pub static __FIRST_ACCESS: $crate::FirstAccess<$type> =
+            $crate::FirstAccess::new(Some($hook));
+
+        #[doc(hidden)]
+        #[inline]
+        pub fn __first_access(
+            set: $crate::LinkerSet<$type>,
+        ) -> $crate::LinkerSet<$type> {
+            __FIRST_ACCESS.run(set)
+        }
+    };
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use std::collections::HashSet;
+#[cfg(feature = "schema")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __set_declare_schema {
+    ($set:ident, $type:ty) => {
+        const __SCHEMA_LEN: usize = $crate::schema::len(
+            stringify!($set),
+            module_path!(),
+            (SECTION_NAME, __TEST_SECTION),
+            stringify!($type),
+            ::std::mem::size_of::<$type>(),
+            ::std::mem::align_of::<$type>(),
+        );
 
-    set_declare!(stuff, u64);
+        $crate::__set_section!(
+            ["linker_set_schema"]
+            #[used]
+            static __SCHEMA: [u8; __SCHEMA_LEN] = $crate::schema::encode(
+                stringify!($set),
+                module_path!(),
+                (SECTION_NAME, __TEST_SECTION),
+                stringify!($type),
+                ::std::mem::size_of::<$type>(),
+                ::std::mem::align_of::<$type>(),
+            );
+        );
+    };
+}
 
-    #[set_entry(stuff)]
-    static FOO: u64 = 0x4F202A76B86A7299u64;
-    #[set_entry(stuff)]
-    static BAR: u64 = 0x560E9309456ACCE0u64;
+#[cfg(not(feature = "schema"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __set_declare_schema {
+    ($set:ident, $type:ty) => {};
+}
 
-    #[test]
-    fn test_set_contents() {
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __on_duplicate {
+    (panic) => {
+        $crate::OnDuplicate::Panic
+    };
+    (first_wins) => {
+        $crate::OnDuplicate::FirstWins
+    };
+    (last_wins) => {
+        $crate::OnDuplicate::LastWins
+    };
+    (priority) => {
+        $crate::OnDuplicate::Priority
+    };
+    ($other:ident) => {
+        compile_error!(
+            "expected `panic`, `first_wins`, `last_wins`, or `priority`"
+        )
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __set_keyed {
+    ($set:ident, $type:ty, $prefix:expr, $policy:expr, $lookup:ty) => {
+        $crate::__set_bounds!(
+            #[doc(hidden)]
+            pub __START_KEYED,
+            #[doc(hidden)]
+            __STOP_KEYED,
+            "set_", $prefix, "_keyed_", stringify!($set)
+        );
+
+        $crate::__set_section!(
+            ["set_", $prefix, "_keyed_", stringify!($set)]
+            #[used]
+            static __EMPTY_KEYED: [$crate::Keyed<$type>; 0] = [];
+        );
+
+        fn __keyed() -> $crate::LinkerSet<$crate::Keyed<$type>> {
+            unsafe {
+                $crate::LinkerSet::new(
+                    ::std::ptr::addr_of!(__START_KEYED).cast(),
+                    ::std::ptr::addr_of!(__STOP_KEYED).cast(),
+                )
+            }
+        }
+
+        #[doc(hidden)]
+        pub fn __choices() -> ::std::vec::Vec<::std::string::String> {
+            $crate::Keyed::choices(__keyed(), ON_DUPLICATE, __entries())
+        }
+
+        #[doc(hidden)]
+        pub fn __resolve() -> ::std::result::Result<(), ::std::string::String> {
+            match $crate::Keyed::resolve_with(__keyed(), ON_DUPLICATE) {
+                Ok(_) => {
+                    resolved();
+                    index();
+                    Ok(())
+                }
+                Err(key) => Err(::std::format!(
+                    "has {} for {}",
+                    ON_DUPLICATE.conflict(),
+                    key
+                )),
+            }
+        }
+
+        /// The policy choosing among entries with the same key.
+        pub const ON_DUPLICATE: $crate::OnDuplicate = $policy;
+
+        /// Returns the entry chosen for each key by [ON_DUPLICATE],
+        /// by default that of highest priority.
+        ///
+        /// # Panics
+        /// Panics if the entries of a key conflict under the policy,
+        /// such as two tying for highest priority.
+        pub fn resolved(
+        ) -> &'static ::std::collections::BTreeMap<&'static str, &'static $type>
+        {
+            static RESOLVED: ::std::sync::OnceLock<
+                ::std::collections::BTreeMap<&'static str, &'static $type>,
+            > = ::std::sync::OnceLock::new();
+            RESOLVED.get_or_init(|| {
+                $crate::__set_accessed!($set);
+                match $crate::Keyed::resolve_with(__keyed(), ON_DUPLICATE) {
+                    Ok(resolved) => resolved,
+                    Err(key) => panic!(
+                        "linker set {} has {} for {}",
+                        stringify!($set),
+                        ON_DUPLICATE.conflict(),
+                        key
+                    ),
+                }
+            })
+        }
+
+        /// The index by which [lookup] finds the entry of a key.
+        pub type Index = $lookup;
+
+        /* built once, apart from resolved(), so that a set looked up by
+         * key in a hot path need not build a BTreeMap */
+        fn index() -> &'static Index {
+            static INDEX: ::std::sync::OnceLock<Index> =
+                ::std::sync::OnceLock::new();
+            INDEX.get_or_init(|| {
+                $crate::__set_accessed!($set);
+                let keyed = __keyed();
+                match <Index as $crate::Lookup<$type>>::build(
+                    keyed.iter(),
+                    ON_DUPLICATE,
+                ) {
+                    Ok(index) => index,
+                    Err(key) => panic!(
+                        "linker set {} has {} for {}",
+                        stringify!($set),
+                        ON_DUPLICATE.conflict(),
+                        key
+                    ),
+                }
+            })
+        }
+
+        /// Returns the entry chosen for a key by [ON_DUPLICATE], found in
+        /// the [Index] of the set.
+        ///
+        /// # Panics
+        /// Panics if the entries of a key conflict under the policy.
+        #[allow(dead_code)]
+        pub fn lookup(key: &str) -> Option<&'static $type> {
+            <Index as $crate::Lookup<$type>>::get(index(), key)
+        }
+
+        /// Returns the key closest to one that has no entry, such as to
+        /// suggest in place of a key mistyped by a user.
+        #[allow(dead_code)]
+        pub fn suggest(key: &str) -> Option<&'static str> {
+            $crate::Keyed::closest(__keyed(), key)
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __lookup {
+    ($type:ty, btree) => {
+        ::std::collections::BTreeMap<&'static str, &'static $type>
+    };
+    ($type:ty, hash) => {
+        $crate::__lookup!($type, hash(::std::hash::RandomState))
+    };
+    ($type:ty, fx) => {
+        $crate::__lookup!($type, hash($crate::FxBuildHasher))
+    };
+    ($type:ty, hash($hasher:ty)) => {
+        ::std::collections::HashMap<&'static str, &'static $type, $hasher>
+    };
+    ($type:ty, sorted($n:expr)) => {
+        $crate::SortedSlice<$type, { $n }>
+    };
+    ($type:ty, $($other:tt)+) => {
+        compile_error!(
+            "expected `btree`, `hash`, `fx`, `hash(hasher)`, or `sorted(n)`"
+        )
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __set_singleton {
+    ($set:ident, $type:ty) => {
+        #[doc(hidden)]
+        pub fn __choices() -> ::std::vec::Vec<::std::string::String> {
+            match __entries().entry_names().as_slice() {
+                [entry] => ::std::vec![::std::format!("chosen {}", entry)],
+                entries => ::std::vec![::std::format!(
+                    "{} entries instead of one",
+                    entries.len()
+                )],
+            }
+        }
+
+        #[doc(hidden)]
+        pub fn __resolve() -> ::std::result::Result<(), ::std::string::String> {
+            let set = $crate::__set_with_model!($set, unsafe {
+                $crate::LinkerSet::new(start_addr(), stop_addr())
+            });
+            match set.len() {
+                1 => {
+                    get();
+                    Ok(())
+                }
+                n => Err(::std::format!("has {} entries instead of one", n)),
+            }
+        }
+
+        /// Returns the only entry in the linker set.
+        ///
+        /// # Panics
+        /// Panics if the set does not have exactly one entry.
+        pub fn get() -> &'static $type {
+            static ENTRY: ::std::sync::OnceLock<&'static $type> =
+                ::std::sync::OnceLock::new();
+            ENTRY.get_or_init(|| {
+                $crate::__set_accessed!($set);
+                let set = $crate::__set_with_model!($set, unsafe {
+                    $crate::LinkerSet::new(start_addr(), stop_addr())
+                });
+                match set.len() {
+                    1 => set.iter().next().unwrap(),
+                    n => panic!(
+                        "linker set {} has {} entries instead of one",
+                        stringify!($set),
+                        n
+                    ),
+                }
+            })
+        }
+    };
+}
+
+#[cfg(feature = "metadata")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __set_declare_meta {
+    ($set:ident, $type:ty, $prefix:expr) => {
+        $crate::__set_bounds!(
+            #[doc(hidden)]
+            pub __START_META,
+            #[doc(hidden)]
+            __STOP_META,
+            "set_", $prefix, "_meta_", stringify!($set)
+        );
+
+        $crate::__set_section!(
+            ["set_", $prefix, "_meta_", stringify!($set)]
+            #[used]
+            static __EMPTY_META: [$crate::EntryMeta<$type>; 0] = [];
+        );
+    };
+}
+
+#[cfg(not(feature = "metadata"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __set_declare_meta {
+    ($set:ident, $type:ty, $prefix:expr) => {};
+}
+
+#[cfg(feature = "metadata")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __set_with_meta {
+    ($set:ident, $linker_set:expr) => {
+        $linker_set.with_metadata(
+            ::std::ptr::addr_of!($set::__START_META).cast(),
+            ::std::ptr::addr_of!($set::__STOP_META).cast(),
+        )
+    };
+}
+
+#[cfg(not(feature = "metadata"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __set_with_meta {
+    ($set:ident, $linker_set:expr) => {
+        $linker_set
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __set_declare_self_tests {
+    (no, $set:ident, $type:ty, $prefix:expr) => {
+        /* checked by each entry with a self test */
+        #[doc(hidden)]
+        pub const __SELF_TESTS: bool = false;
+
+        #[doc(hidden)]
+        #[inline(always)]
+        pub fn __self_tests(
+            set: $crate::LinkerSet<$type>,
+        ) -> $crate::LinkerSet<$type> {
+            set
+        }
+    };
+    (yes, $set:ident, $type:ty, $prefix:expr) => {
+        $crate::__set_bounds!(
+            #[doc(hidden)]
+            pub __START_SELF_TEST,
+            #[doc(hidden)]
+            __STOP_SELF_TEST,
+            "set_", $prefix, "_selftest_", stringify!($set)
+        );
+
+        $crate::__set_section!(
+            ["set_", $prefix, "_selftest_", stringify!($set)]
+            #[used]
+            static __EMPTY_SELF_TEST: [$crate::SelfTest<$type>; 0] = [];
+        );
+
+        #[doc(hidden)]
+        pub const __SELF_TESTS: bool = true;
+
+        #[doc(hidden)]
+        #[inline]
+        pub fn __self_tests(
+            set: $crate::LinkerSet<$type>,
+        ) -> $crate::LinkerSet<$type> {
+            unsafe {
+                set.with_self_tests(
+                    ::std::ptr::addr_of!(__START_SELF_TEST).cast(),
+                    ::std::ptr::addr_of!(__STOP_SELF_TEST).cast(),
+                )
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __set_declare_tests {
+    (no, $set:ident, $type:ty, $prefix:expr) => {
+        /* checked by each test entry, and by set_test!() */
+        #[doc(hidden)]
+        pub const __TEST_ENTRIES: bool = false;
+
+        #[allow(dead_code)]
+        const __TEST_SECTION: Option<&str> = None;
+
+        #[doc(hidden)]
+        pub fn __tests() -> $crate::LinkerSet<$type> {
+            $crate::LinkerSet::from_slice(&[])
+        }
+    };
+    (yes, $set:ident, $type:ty, $prefix:expr) => {
+        $crate::__set_bounds!(
+            #[doc(hidden)]
+            pub __START_TEST,
+            #[doc(hidden)]
+            __STOP_TEST,
+            "set_", $prefix, "_test_", stringify!($set)
+        );
+
+        $crate::__set_section!(
+            ["set_", $prefix, "_test_", stringify!($set)]
+            #[used]
+            static __EMPTY_TEST: [$type; 0] = [];
+        );
+
+        #[doc(hidden)]
+        pub const __TEST_ENTRIES: bool = true;
+
+        #[allow(dead_code)]
+        const __TEST_SECTION: Option<&str> =
+            Some(concat!("set_", $prefix, "_test_", stringify!($set)));
+
+        #[doc(hidden)]
+        pub fn __tests() -> $crate::LinkerSet<$type> {
+            unsafe {
+                $crate::LinkerSet::new(
+                    ::std::ptr::addr_of!(__START_TEST).cast(),
+                    ::std::ptr::addr_of!(__STOP_TEST).cast(),
+                )
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __set_declare_features {
+    (no, $set:ident, $type:ty, $prefix:expr) => {
+        /* checked by each entry requiring CPU features */
+        #[doc(hidden)]
+        pub const __TARGET_FEATURES: bool = false;
+
+        #[doc(hidden)]
+        #[inline(always)]
+        pub fn __features(
+            set: $crate::LinkerSet<$type>,
+        ) -> $crate::LinkerSet<$type> {
+            set
+        }
+    };
+    (yes, $set:ident, $type:ty, $prefix:expr) => {
+        $crate::__set_bounds!(
+            #[doc(hidden)]
+            pub __START_FEATURES,
+            #[doc(hidden)]
+            __STOP_FEATURES,
+            "set_", $prefix, "_features_", stringify!($set)
+        );
+
+        $crate::__set_section!(
+            ["set_", $prefix, "_features_", stringify!($set)]
+            #[used]
+            static __EMPTY_FEATURES: [$crate::Featured<$type>; 0] = [];
+        );
+
+        #[doc(hidden)]
+        pub const __TARGET_FEATURES: bool = true;
+
+        #[doc(hidden)]
+        #[inline]
+        pub fn __features(
+            set: $crate::LinkerSet<$type>,
+        ) -> $crate::LinkerSet<$type> {
+            unsafe {
+                set.with_features(
+                    ::std::ptr::addr_of!(__START_FEATURES).cast(),
+                    ::std::ptr::addr_of!(__STOP_FEATURES).cast(),
+                )
+            }
+        }
+
+        /// Returns the entry of the linker set that the running CPU
+        /// can use and that requires the most CPU features, chosen on
+        /// the first call.
+        #[allow(dead_code)]
+        pub fn best_for_cpu() -> Option<&'static $type> {
+            static BEST: ::std::sync::OnceLock<Option<&'static $type>> =
+                ::std::sync::OnceLock::new();
+            *BEST.get_or_init(|| $crate::set!($set).for_cpu().first().copied())
+        }
+    };
+}
+
+/// Declare the symbols that the linker defines at the bounds of a section,
+/// given the name of the section as the arguments of `concat!`.
+///
+/// On ELF, these are `__start_` and `__stop_` followed by the name of the
+/// section.  On Mach-O, whose sections have names of at most 16 bytes in
+/// a segment, they are `section$start$__DATA$` and `section$end$__DATA$`
+/// followed by the name that `__mach_o_name!` gives the section.
+///
+/// A section declared by a set is guarded by `__mach_o_guard!`, so that a
+/// program declaring two sets sharing a Mach-O section fails to link.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __set_bounds {
+    (
+        $(#[$start_meta:meta])* $vis:vis $start:ident,
+        $(#[$stop_meta:meta])* $stop:ident,
+        $($name:expr),+ $(,)?
+    ) => {
+        $crate::__set_bounds!(
+            @extern
+            $(#[$start_meta])* $vis $start,
+            $(#[$stop_meta])* $stop,
+            $($name),+
+        );
+        $crate::__mach_o_guard!($($name),+);
+    };
+    (
+        @extern
+        $(#[$start_meta:meta])* $vis:vis $start:ident,
+        $(#[$stop_meta:meta])* $stop:ident,
+        $($name:expr),+ $(,)?
+    ) => {
+        extern "C" {
+            $(#[$start_meta])*
+            #[cfg_attr(
+                not(target_vendor = "apple"),
+                link_name = concat!("__start_", $($name),+)
+            )]
+            #[cfg_attr(
+                target_vendor = "apple",
+                link_name = $crate::__mach_o_symbol!(start, $($name),+)
+            )]
+            $vis static $start: u8;
+            $(#[$stop_meta])*
+            #[cfg_attr(
+                not(target_vendor = "apple"),
+                link_name = concat!("__stop_", $($name),+)
+            )]
+            #[cfg_attr(
+                target_vendor = "apple",
+                link_name = $crate::__mach_o_symbol!(stop, $($name),+)
+            )]
+            $vis static $stop: u8;
+        }
+    };
+}
+
+/// Place an item into a section, given the name of the section as the
+/// arguments of `concat!`, on ELF or on Mach-O.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __set_section {
+    ([$($name:expr),+ $(,)?] $item:item) => {
+        #[cfg_attr(
+            not(target_vendor = "apple"),
+            link_section = concat!($($name),+)
+        )]
+        #[cfg_attr(
+            target_vendor = "apple",
+            link_section = concat!(
+                "__DATA,",
+                $crate::__mach_o_name!($($name),+),
+                ",regular,no_dead_strip"
+            )
+        )]
+        $item
+    };
+}
+
+/* the slots that the entries of a set placed within its own crate fill,
+ * which a sealed set, all of whose entries are so placed, counts and
+ * gathers into a const array */
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __set_declare_slots {
+    (no, $type:ty) => {
+        $crate::__set_slot!($type);
+
+        /* compared with the slot of each entry */
+        #[doc(hidden)]
+        pub const __SLOTS: usize = usize::MAX;
+    };
+    (yes, $type:ty) => {
+        $crate::__set_slot!($type);
+
+        #[doc(hidden)]
+        pub const __SLOTS: usize = 64;
+
+        $crate::__set_local!(
+            $type;
+            0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24
+            25 26 27 28 29 30 31 32 33 34 35 36 37 38 39 40 41 42 43 44 45
+            46 47 48 49 50 51 52 53 54 55 56 57 58 59 60 61 62 63
+        );
+    };
+}
+
+#[cfg(const_static_refs)]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __set_slot {
+    ($type:ty) => {
+        #[doc(hidden)]
+        pub struct __Slot<const K: usize>;
+
+        /* what a slot that no entry fills yields; the inherent consts of
+         * a filled slot take precedence over these */
+        #[doc(hidden)]
+        pub trait __Vacant {
+            const PRESENT: bool = false;
+            const ENTRY: Option<&'static $type> = None;
+        }
+
+        impl<const K: usize> __Vacant for __Slot<K> {}
+    };
+}
+
+#[cfg(not(const_static_refs))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __set_slot {
+    ($type:ty) => {
+        #[doc(hidden)]
+        pub struct __Slot<const K: usize>;
+
+        /* what a slot that no entry fills yields; the inherent consts of
+         * a filled slot take precedence over these */
+        #[doc(hidden)]
+        pub trait __Vacant {
+            const PRESENT: bool = false;
+        }
+
+        impl<const K: usize> __Vacant for __Slot<K> {}
+    };
+}
+
+#[cfg(const_static_refs)]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __set_local {
+    ($type:ty; $($slot:literal)*) => {
+        /// The number of entries of the sealed set.
+        #[allow(dead_code)]
+        pub const LOCAL_LEN: usize =
+            0 $(+ __Slot::<$slot>::PRESENT as usize)*;
+
+        /// References to the entries of the sealed set, in the order in
+        /// which the compiler expanded them.
+        #[allow(dead_code, unused_assignments)]
+        pub const LOCAL: [&'static $type; LOCAL_LEN] = {
+            let mut entries = [None; LOCAL_LEN];
+            let mut len = 0;
+            $(
+                if let Some(entry) = __Slot::<$slot>::ENTRY {
+                    entries[len] = Some(entry);
+                    len += 1;
+                }
+            )*
+            // SAFETY: every element is Some, and Option<&T> is laid out
+            // as &T is
+            unsafe {
+                ::std::mem::transmute::<
+                    [Option<&'static $type>; LOCAL_LEN],
+                    [&'static $type; LOCAL_LEN],
+                >(entries)
+            }
+        };
+    };
+}
+
+#[cfg(not(const_static_refs))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __set_local {
+    ($type:ty; $($slot:literal)*) => {
+        /// The number of entries of the sealed set.
+        #[allow(dead_code)]
+        pub const LOCAL_LEN: usize =
+            0 $(+ __Slot::<$slot>::PRESENT as usize)*;
+    };
+}
+
+#[cfg(feature = "model")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __set_declare_model {
+    ($type:ty) => {
+        /// A model that replaces the contents of the linker set, if set.
+        #[allow(dead_code)]
+        pub static MODEL: ::std::sync::OnceLock<&'static [$type]> =
+            ::std::sync::OnceLock::new();
+    };
+}
+
+#[cfg(not(feature = "model"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __set_declare_model {
+    ($type:ty) => {};
+}
+
+#[cfg(feature = "model")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __set_with_model {
+    ($set:ident, $linker_set:expr) => {
+        match $set::MODEL.get() {
+            Some(model) => $crate::LinkerSet::from_slice(model),
+            None => $linker_set,
+        }
+    };
+}
+
+#[cfg(not(feature = "model"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __set_with_model {
+    ($set:ident, $linker_set:expr) => {
+        $linker_set
+    };
+}
+
+#[cfg(feature = "debug-report")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __set_accessed {
+    ($set:ident) => {
+        $set::__ACCESSED.store(true, ::std::sync::atomic::Ordering::Relaxed)
+    };
+}
+
+#[cfg(not(feature = "debug-report"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __set_accessed {
+    ($set:ident) => {};
+}
+
+/// Create a linker set proxy object for iteration or indexing.
+#[macro_export]
+macro_rules! set {
+    ($set:ident) => {{
+        $crate::__set_accessed!($set);
+        #[cfg(debug_assertions)]
+        $crate::stage::__check($set::__STAGE, stringify!($set));
+        let set = $crate::__set_with_model!($set, $set::__linker_set());
+        $set::__first_access(set.with_name(stringify!($set)))
+    }};
+}
+
+/// Define a function, callable from C, that returns the elements of a
+/// linker set.
+///
+/// `set_export_c!(name, type, function)` defines an unmangled `extern "C"`
+/// function that returns a pointer to the first element of the set, of
+/// the declared type, and stores the number of elements through its
+/// argument, unless it is null.  C declares it as
+///
+/// ```c
+/// const struct handler *function(size_t *len);
+/// ```
+///
+/// as does a header written by [c_header::Header::accessor].  The
+/// guarantees about the elements are those of `LinkerSet::as_raw_parts`.
+#[macro_export]
+macro_rules! set_export_c {
+    ($set:ident, $type:ty, $function:ident) => {
+        /// Returns the elements of a linker set, storing their number
+        /// through `len` unless it is null.
+        ///
+        /// # Safety
+        /// `len` must be null or valid for writes.
+        #[no_mangle]
+        pub unsafe extern "C" fn $function(len: *mut usize) -> *const $type {
+            let (start, n) = $crate::set!($set).as_raw_parts();
+            if !len.is_null() {
+                len.write(n);
+            }
+            start
+        }
+    };
+}
+
+/// Create a linker set proxy object over the entries placed into a set
+/// by the [test_entry] attribute.
+///
+/// Test entries are kept apart from the entries of the set itself, so
+/// [set!] never yields them, even if one is compiled into a release build
+/// by mistake, and `cargo linker-set release FILE` can check that a build
+/// has none.
+#[macro_export]
+macro_rules! set_test {
+    ($set:ident) => {{
+        const _: () = assert!(
+            $set::__TEST_ENTRIES,
+            "a set must be declared with `test_entries` to take test entries"
+        );
+        $set::__tests()
+    }};
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashSet;
+
+    set_declare!(stuff, u64, test_entries);
+
+    #[set_entry(stuff)]
+    #[no_mangle]
+    static FOO: u64 = 0x4F202A76B86A7299u64;
+    #[set_entry(stuff)]
+    static BAR: u64 = 0x560E9309456ACCE0u64;
+
+    #[test_entry(stuff)]
+    static TEST_ONLY: u64 = 0x7E57;
+
+    #[test]
+    fn test_test_entry() {
+        assert_eq!(set_test!(stuff).iter().collect::<Vec<_>>(), [&TEST_ONLY]);
+        assert!(set!(stuff).iter().all(|x| !std::ptr::eq(x, &TEST_ONLY)));
+    }
+
+    #[test]
+    fn test_set_contents() {
         let actual = set!(stuff).iter().collect::<HashSet<_>>();
         let expect = HashSet::from([&FOO, &BAR, &0x6666666666666666]);
         assert_eq!(actual, expect);
     }
 
+    #[test]
+    fn test_handle() {
+        assert!(std::ptr::eq(stuff::handle(), stuff::handle()));
+        let actual = stuff::handle().iter().collect::<HashSet<_>>();
+        assert_eq!(actual, set!(stuff).iter().collect::<HashSet<_>>());
+    }
+
+    struct Buffer {
+        bytes: [u8; 128],
+        len: usize,
+    }
+
+    impl std::fmt::Write for Buffer {
+        fn write_str(&mut self, s: &str) -> std::fmt::Result {
+            let end = self.len + s.len();
+            let dest =
+                self.bytes.get_mut(self.len..end).ok_or(std::fmt::Error)?;
+            dest.copy_from_slice(s.as_bytes());
+            self.len = end;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_write_brief() {
+        let mut buf = Buffer {
+            bytes: [0; 128],
+            len: 0,
+        };
+        let set = set!(stuff);
+        set.write_brief(&mut buf).unwrap();
+        let brief = std::str::from_utf8(&buf.bytes[..buf.len]).unwrap();
+        let start = set.first().unwrap() as *const u64;
+        let expect = format!("stuff: 3 x 8 bytes at {:p}..", start);
+        assert!(brief.starts_with(&expect), "{}", brief);
+        assert_eq!(set.name(), Some("stuff"));
+
+        static ARRAY: [u32; 2] = [1, 2];
+        let mut brief = String::new();
+        LinkerSet::from_slice(&ARRAY)
+            .write_brief(&mut brief)
+            .unwrap();
+        assert!(brief.starts_with("<u32>: 2 x 4 bytes at 0x"));
+    }
+
+    set_export_c!(stuff, u64, linker_set_test_stuff);
+
+    #[test]
+    fn test_export_c() {
+        let mut len = 0;
+        let start = unsafe { linker_set_test_stuff(&mut len) };
+        assert_eq!((start, len), set!(stuff).as_raw_parts());
+        let expect = set!(stuff).iter().collect::<HashSet<_>>();
+        let actual = unsafe { std::slice::from_raw_parts(start, len) };
+        assert_eq!(actual.iter().collect::<HashSet<_>>(), expect);
+        let start = unsafe { linker_set_test_stuff(std::ptr::null_mut()) };
+        assert_eq!(start, set!(stuff).as_raw_parts().0);
+    }
+
     #[test]
     fn test_set_iter_len() {
         const LEN: usize = 3;
@@ -287,6 +2347,26 @@ mod test {
         assert_eq!(iter.count(), LEN);
     }
 
+    #[test]
+    fn test_iter_raw() {
+        static ARRAY: [u32; 3] = [1, 2, 3];
+        let range = ARRAY.as_ptr_range();
+        let iter = unsafe { LinkerSetIter::new(range.start, range.end) };
+        assert_eq!(iter.len(), 3);
+        let mut all = iter.copied().collect::<Vec<_>>();
+        all.sort();
+        assert_eq!(all, ARRAY);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_misaligned() {
+        static ARRAY: [u32; 3] = [1, 2, 3];
+        let start = ARRAY.as_ptr().cast::<u8>().wrapping_add(1);
+        let stop = start.wrapping_add(8);
+        unsafe { LinkerSet::new(start.cast::<u32>(), stop.cast::<u32>()) };
+    }
+
     #[test]
     fn test_into() {
         let mut actual = HashSet::new();
@@ -309,11 +2389,620 @@ mod test {
         assert_eq!(actual, expect);
     }
 
+    #[test]
+    #[cfg(feature = "markers")]
+    fn test_markers() {
+        __linker_set_entry_stuff_FOO();
+        __linker_set_entry_stuff_BAR();
+    }
+
+    #[test]
+    fn test_get() {
+        let set = set!(stuff);
+        assert!(std::ptr::eq(set.get(1).unwrap(), &set[1]));
+        assert!(set.get(3).is_none());
+        assert!(std::ptr::eq(set.first().unwrap(), &set[0]));
+        assert!(std::ptr::eq(set.last().unwrap(), &set[2]));
+        let empty = LinkerSet::<u64>::from_slice(&[]);
+        assert_eq!(
+            (empty.get(0), empty.first(), empty.last()),
+            (None, None, None)
+        );
+    }
+
+    #[test]
+    fn test_copied() {
+        let set = set!(stuff);
+        let mut actual = set.iter_copied().collect::<Vec<_>>();
+        actual.sort();
+        assert_eq!(actual, [FOO, BAR, 0x6666666666666666]);
+        let mut vec = set.to_vec();
+        vec.sort();
+        assert_eq!(vec, actual);
+        assert_eq!(set.to_array::<3>().map(|a| a.len()), Some(3));
+        assert_eq!(set.to_array::<2>(), None);
+    }
+
+    #[test]
+    fn test_iter_audited() {
+        let mut audited = Vec::new();
+        let found = set!(stuff)
+            .iter_audited(|&x| audited.push(x))
+            .find(|&&x| x == BAR);
+        assert_eq!(found, Some(&BAR));
+        assert_eq!(audited.last(), Some(&&BAR));
+    }
+
+    #[test]
+    fn test_addr() {
+        assert_eq!(stuff::SECTION_NAME, "set_stuff");
+        let len = stuff::stop_addr() as usize - stuff::start_addr() as usize;
+        assert_eq!(len, 3 * std::mem::size_of::<u64>());
+        assert_eq!(set!(stuff).first().unwrap(), unsafe {
+            &*stuff::start_addr()
+        });
+    }
+
     #[test]
     fn test_is_empty() {
         assert!(!set!(stuff).is_empty());
     }
 
+    #[cfg(feature = "metadata")]
+    #[test]
+    fn test_iter_named() {
+        let actual = set!(stuff).iter_named().collect::<HashSet<_>>();
+        let expect = HashSet::from([
+            ("FOO", &FOO),
+            ("BAR", &BAR),
+            ("FOO", &test_use_ext::FOO),
+        ]);
+        assert_eq!(actual, expect);
+    }
+
+    set_declare!(commands, &'static str);
+
+    /// Print the version.
+    ///
+    ///   Takes no arguments.
+    #[set_entry(commands)]
+    static VERSION: &str = "version";
+    #[set_entry(commands)]
+    #[doc = "Print help."]
+    const HELP: &str = "help";
+    #[set_entry(commands)]
+    static QUIT: &str = "quit";
+
+    #[cfg(feature = "metadata")]
+    #[test]
+    fn test_doc() {
+        let mut docs = set!(commands)
+            .iter_meta()
+            .map(|m| (*m.entry(), m.doc()))
+            .collect::<Vec<_>>();
+        docs.sort();
+        let expect = [
+            ("help", "Print help."),
+            ("quit", ""),
+            ("version", "Print the version.\n\n  Takes no arguments."),
+        ];
+        assert_eq!(docs, expect);
+    }
+
+    #[cfg(feature = "metadata")]
+    #[test]
+    fn test_by_crate() {
+        let crates = set!(stuff).by_crate().collect::<Vec<_>>();
+        assert_eq!(crates.len(), 1);
+        let (name, entries) = &crates[0];
+        assert_eq!(*name, "linker_set");
+        let expect = HashSet::from([&FOO, &BAR, &test_use_ext::FOO]);
+        assert_eq!(entries.iter().copied().collect::<HashSet<_>>(), expect);
+        let modules = set!(stuff).meta.iter().map(|m| m.module());
+        assert_eq!(
+            modules.collect::<HashSet<_>>(),
+            HashSet::from(["linker_set::test", "linker_set::test_use_ext"])
+        );
+    }
+
+    set_declare!(checked, u32, self_test);
+
+    fn even(n: &u32) -> Result<(), String> {
+        match n % 2 {
+            0 => Ok(()),
+            _ => Err(format!("{} is odd", n)),
+        }
+    }
+
+    #[set_entry(checked, self_test = even)]
+    static EVEN: u32 = 4;
+    #[set_entry(checked, self_test = even)]
+    static ODD: u32 = 3;
+    #[set_entry(checked, self_test = self::even)]
+    const CONST_ODD: u32 = 5;
+    #[set_entry(checked)]
+    static UNCHECKED: u32 = 7;
+
+    #[test]
+    fn test_self_tests() {
+        let mut failures = set!(checked).run_self_tests().unwrap_err();
+        failures.sort();
+        assert_eq!(
+            failures,
+            [
+                ("CONST_ODD", "5 is odd".to_string()),
+                ("ODD", "3 is odd".to_string())
+            ]
+        );
+        assert_eq!(set!(checked).len(), 4);
+        assert_eq!(set!(stuff).run_self_tests(), Ok(()));
+    }
+
+    set_declare!(validated, u32, self_test);
+
+    fn nonzero(n: &u32) -> Result<(), String> {
+        if *n == 0 {
+            panic!("divisor is zero");
+        }
+        Ok(())
+    }
+
+    #[set_entry(validated, self_test = nonzero)]
+    static ZERO: u32 = 0;
+    #[set_entry(validated, self_test = nonzero)]
+    static NONZERO: u32 = 2;
+
+    #[test]
+    fn test_self_test_panics() {
+        let failures = set!(validated).run_self_tests().unwrap_err();
+        let e = "panicked: divisor is zero".to_string();
+        assert_eq!(failures, [("ZERO", e)]);
+        assert_eq!(set!(validated).iter().sum::<u32>(), ZERO + NONZERO);
+    }
+
+    #[test]
+    fn test_unwind_safe() {
+        use std::panic::{RefUnwindSafe, UnwindSafe};
+
+        fn unwind_safe<T: UnwindSafe + RefUnwindSafe>(_: &T) {}
+        unwind_safe(&set!(stuff));
+        unwind_safe(&set!(stuff).iter());
+        let n = std::panic::catch_unwind(|| set!(stuff).iter().count());
+        assert_eq!(n.unwrap(), set!(stuff).len());
+    }
+
+    set_declare!(consts, u32);
+
+    #[set_entry(consts)]
+    const ONE: u32 = 1;
+    #[set_entry(consts)]
+    static TWO: u32 = ONE + 1;
+
+    #[test]
+    fn test_const() {
+        let actual = set!(consts).iter().collect::<HashSet<_>>();
+        assert_eq!(actual, HashSet::from([&1, &2]));
+        #[cfg(feature = "metadata")]
+        {
+            let names = set!(consts).iter_named().map(|(n, _)| n);
+            assert_eq!(
+                names.collect::<HashSet<_>>(),
+                HashSet::from(["ONE", "TWO"])
+            );
+        }
+    }
+
+    set_declare!(private, u8, sealed);
+
+    set_declare!(hidden, &'static str, keyed, sealed, max_size = 16);
+
+    #[set_entry(self::private)]
+    static SECRET: u8 = 42;
+
+    #[set_entry(self::hidden, key = "k")]
+    static HIDDEN: &str = "v";
+
+    #[test]
+    fn test_sealed() {
+        assert_eq!(set!(private).iter().collect::<Vec<_>>(), [&SECRET]);
+        assert_eq!(private::SECTION_NAME, "set_linker_set__private");
+        assert_eq!(*hidden::lookup("k").unwrap(), "v");
+    }
+
+    set_declare!(sized, u32, sealed);
+
+    #[set_entry(self::sized)]
+    static FIRST: u32 = 1;
+
+    mod elsewhere {
+        #[crate::set_entry(super::sized)]
+        static SECOND: u32 = 2;
+    }
+
+    /* sized by the set, as only a sealed set can be */
+    const ZEROS: [u32; sized::LOCAL_LEN] = [0; sized::LOCAL_LEN];
+
+    #[test]
+    fn test_sealed_const() {
+        assert_eq!(ZEROS.len(), 2);
+        assert_eq!(set!(sized).len(), sized::LOCAL_LEN);
+        #[cfg(const_static_refs)]
+        {
+            const SUM: u32 = *sized::LOCAL[0] + *sized::LOCAL[1];
+            assert_eq!(SUM, 3);
+            let mut local = sized::LOCAL.map(|e| *e);
+            local.sort();
+            assert_eq!(local, [1, 2]);
+        }
+    }
+
+    #[test]
+    fn test_mach_o_names() {
+        assert_eq!(__mach_o_name!("set_", "", stringify!(stuff)), "set_stuff");
+        assert_eq!(
+            __mach_o_name!(concat!("set_", "ünïcödé"), "_names"),
+            "set_ün9ce463ce"
+        );
+        assert_eq!(
+            __mach_o_symbol!(
+                start,
+                "set_",
+                concat!(env!("CARGO_CRATE_NAME"), "__"),
+                stringify!(fatal_handler)
+            ),
+            "\x01section$start$__DATA$set_link91ee32af"
+        );
+        assert_eq!(
+            __mach_o_symbol!(stop, "set_", "", stringify!(fatal_handler)),
+            "\x01section$end$__DATA$set_fata305acff5"
+        );
+    }
+
+    set_declare!(scoped, u16, namespaced);
+
+    #[set_entry(self::scoped)]
+    static SCOPED_SELF: u16 = 1;
+    #[set_entry(crate::test::scoped)]
+    static SCOPED_CRATE: u16 = 2;
+
+    mod unscoped {
+        use super::*;
+
+        set_declare!(scoped, u16);
+
+        #[set_entry(scoped)]
+        pub static UNSCOPED: u16 = 3;
+    }
+
+    #[test]
+    fn test_namespaced() {
+        assert_eq!(scoped::SECTION_NAME, "set_linker_set__scoped");
+        let mut found = set!(scoped).to_vec();
+        found.sort();
+        assert_eq!(found, [SCOPED_SELF, SCOPED_CRATE]);
+        let unscoped = unscoped::scoped::handle();
+        assert_eq!(unscoped.iter().collect::<Vec<_>>(), [&unscoped::UNSCOPED]);
+    }
+
+    set_declare!(enabled, u64, cfg(test));
+    set_declare!(disabled, u64, cfg(not(test)));
+
+    #[set_entry(enabled)]
+    static ON: u64 = 1;
+    #[set_entry(disabled)]
+    static OFF: u64 = 2;
+
+    #[test]
+    fn test_cfg() {
+        assert_eq!(set!(enabled).iter().collect::<Vec<_>>(), [&ON]);
+        assert!(set!(disabled).is_empty());
+        assert_eq!(disabled::SECTION_NAME, "set__disabled_disabled");
+        #[cfg(feature = "metadata")]
+        assert_eq!(set!(disabled).iter_named().count(), 0);
+    }
+
+    set_declare!(backend, u64, singleton);
+
+    #[set_entry(backend)]
+    static THE_BACKEND: u64 = 42;
+
+    set_declare!(no_backend, u64, singleton);
+
+    #[test]
+    fn test_singleton() {
+        assert!(std::ptr::eq(backend::get(), &THE_BACKEND));
+    }
+
+    #[test]
+    #[should_panic(expected = "linker set no_backend has 0 entries")]
+    fn test_singleton_missing() {
+        no_backend::get();
+    }
+
+    set_declare!(codecs, u32, keyed);
+
+    #[set_entry(codecs, key = "gzip")]
+    static GZIP_DEFAULT: u32 = 1;
+    #[set_entry(codecs, key = "gzip", priority = 50)]
+    static GZIP_OVERRIDE: u32 = 2;
+    #[set_entry(codecs, key = "zstd", priority = -5)]
+    const ZSTD: u32 = 3;
+    #[set_entry(codecs)]
+    static UNKEYED: u32 = 4;
+
+    #[test]
+    fn test_keyed() {
+        assert_eq!(set!(codecs).len(), 4);
+        let resolved = codecs::resolved();
+        assert_eq!(resolved.len(), 2);
+        assert!(std::ptr::eq(resolved["gzip"], &GZIP_OVERRIDE));
+        assert_eq!(resolved["zstd"], &ZSTD);
+    }
+
+    set_declare!(tied, u32, keyed);
+
+    #[set_entry(tied, key = "k", priority = 1)]
+    static TIED_A: u32 = 1;
+    #[set_entry(tied, key = "k", priority = 1)]
+    static TIED_B: u32 = 2;
+
+    #[test]
+    #[should_panic(expected = "linker set tied has entries of equal priority")]
+    fn test_keyed_tie() {
+        tied::resolved();
+    }
+
+    set_declare!(strict, u32, keyed, on_duplicate = panic);
+
+    #[set_entry(strict, key = "k", priority = 1)]
+    static STRICT_A: u32 = 1;
+    #[set_entry(strict, key = "k", priority = 2)]
+    static STRICT_B: u32 = 2;
+
+    #[test]
+    #[should_panic(expected = "linker set strict has more than one entry")]
+    fn test_keyed_strict() {
+        strict::resolved();
+    }
+
+    set_declare!(lenient, u32, keyed, on_duplicate = first_wins);
+
+    #[set_entry(lenient, key = "k", priority = -1)]
+    static LENIENT_A: u32 = 1;
+    #[set_entry(lenient, key = "k", priority = 1)]
+    static LENIENT_B: u32 = 1;
+
+    #[test]
+    fn test_keyed_first_wins() {
+        assert_eq!(lenient::ON_DUPLICATE, OnDuplicate::FirstWins);
+        let chosen = lenient::resolved()["k"];
+        let first = set!(lenient).iter().next().unwrap();
+        if cfg!(feature = "shuffle") {
+            /* the keyed entries are shuffled apart from the set */
+            let entries = [&LENIENT_A, &LENIENT_B];
+            assert!(entries.iter().any(|&e| std::ptr::eq(chosen, e)));
+        } else {
+            assert!(std::ptr::eq(chosen, first));
+        }
+    }
+
+    set_declare!(dma_descs, [u64; 8], max_size = 64);
+
+    #[set_entry(dma_descs)]
+    static DESCRIPTOR: [u64; 8] = [0; 8];
+
+    #[test]
+    fn test_max_size() {
+        assert_eq!(dma_descs::__MAX_SIZE, 64);
+        assert_eq!(set!(dma_descs).to_vec(), [DESCRIPTOR]);
+    }
+
+    set_declare!(opcodes, u32, keyed, lookup = fx);
+    set_declare!(
+        registers,
+        u32,
+        keyed,
+        on_duplicate = last_wins,
+        lookup = sorted(4)
+    );
+
+    #[set_entry(opcodes, key = "add")]
+    static ADD: u32 = 1;
+    #[set_entry(opcodes, key = "sub", priority = 1)]
+    static SUB: u32 = 2;
+    #[set_entry(registers, key = "sp")]
+    static SP: u32 = 31;
+
+    #[test]
+    fn test_keyed_lookup() {
+        assert_eq!(opcodes::lookup("add"), Some(&ADD));
+        assert_eq!(opcodes::lookup("sub"), Some(&SUB));
+        assert_eq!(opcodes::lookup("mul"), None);
+        assert_eq!(codecs::lookup("zstd"), Some(&ZSTD));
+        let _: Option<&SortedSlice<u32, 4>> = None::<&registers::Index>;
+        assert_eq!(registers::lookup("sp"), Some(&SP));
+        assert_eq!(registers::lookup("pc"), None);
+    }
+
+    #[cfg(feature = "model")]
+    set_declare!(modeled, u32);
+
+    #[cfg(feature = "model")]
+    #[set_entry(modeled)]
+    static REAL: u32 = 1;
+
+    #[cfg(feature = "model")]
+    #[test]
+    fn test_model() {
+        assert_eq!(set!(modeled).iter().collect::<Vec<_>>(), [&REAL]);
+        modeled::MODEL.set(&[7, 8]).unwrap();
+        let mut all = set!(modeled).iter().copied().collect::<Vec<_>>();
+        all.sort();
+        assert_eq!(all, [7, 8]);
+    }
+
+    pub struct Node {
+        id: u8,
+        _pin: std::marker::PhantomPinned,
+    }
+
+    set_declare!(nodes, Node);
+
+    #[set_entry(nodes)]
+    static NODE: Node = Node {
+        id: 7,
+        _pin: std::marker::PhantomPinned,
+    };
+
+    #[test]
+    fn test_pinned() {
+        use std::pin::Pin;
+
+        fn id(node: Pin<&'static Node>) -> u8 {
+            node.get_ref().id
+        }
+
+        let pinned = set!(nodes).iter_pinned().collect::<Vec<_>>();
+        assert_eq!(pinned.len(), 1);
+        assert_eq!(id(pinned[0]), 7);
+        assert!(std::ptr::eq(pinned[0].get_ref(), &NODE));
+    }
+
+    set_declare!(hooks, Option<fn() -> u8>);
+    set_declare!(ids, std::num::NonZeroU32);
+    set_declare!(maybe_ids, Option<std::num::NonZeroU32>);
+    set_declare!(labels, Option<&'static str>);
+
+    fn hook() -> u8 {
+        5
+    }
+
+    #[set_entry(hooks)]
+    static HOOK: Option<fn() -> u8> = Some(hook);
+    #[set_entry(hooks)]
+    static NO_HOOK: Option<fn() -> u8> = None;
+
+    #[set_entry(ids)]
+    const ID: std::num::NonZeroU32 =
+        std::num::NonZeroU32::MIN.saturating_add(8);
+
+    #[set_entry(maybe_ids)]
+    static SOME_ID: Option<std::num::NonZeroU32> = std::num::NonZeroU32::new(3);
+    #[set_entry(maybe_ids)]
+    static NO_ID: Option<std::num::NonZeroU32> = None;
+
+    #[set_entry(labels)]
+    static LABEL: Option<&'static str> = Some("label");
+    #[set_entry(labels)]
+    static NO_LABEL: Option<&'static str> = None;
+
+    /// Returns the number of bytes between the start and stop symbols.
+    fn span_of<T>(start: *const T, stop: *const T) -> usize {
+        stop as usize - start as usize
+    }
+
+    #[test]
+    fn test_niche_elements() {
+        use std::mem::size_of;
+        use std::num::NonZeroU32;
+
+        assert_eq!(size_of::<Option<fn() -> u8>>(), size_of::<fn() -> u8>());
+        assert_eq!(size_of::<Option<NonZeroU32>>(), size_of::<u32>());
+        assert_eq!(size_of::<Option<&str>>(), size_of::<&str>());
+
+        let hooks = set!(hooks);
+        assert_eq!(hooks.len(), 2);
+        assert_eq!(hooks.iter_some().map(|h| h()).collect::<Vec<_>>(), [5]);
+        let span = span_of(hooks::start_addr(), hooks::stop_addr());
+        assert_eq!(span, 2 * size_of::<fn() -> u8>());
+
+        assert_eq!(set!(ids).iter().map(|i| i.get()).collect::<Vec<_>>(), [9]);
+
+        let maybe_ids = set!(maybe_ids);
+        assert_eq!(
+            maybe_ids.iter_some().collect::<Vec<_>>(),
+            [&SOME_ID.unwrap()]
+        );
+        assert_eq!(maybe_ids.iter().filter(|i| i.is_none()).count(), 1);
+        let span = span_of(maybe_ids::start_addr(), maybe_ids::stop_addr());
+        assert_eq!(span, 2 * size_of::<u32>());
+
+        let labels = set!(labels).iter_some().collect::<Vec<_>>();
+        assert_eq!(labels, [&"label"]);
+        assert!(NO_HOOK.is_none() && NO_ID.is_none() && NO_LABEL.is_none());
+        assert_eq!(HOOK.map(|h| h()), Some(5));
+        assert_eq!(LABEL, Some("label"));
+        assert_eq!(ID.get(), 9);
+    }
+
+    static GREETED: std::sync::Mutex<Vec<&str>> =
+        std::sync::Mutex::new(Vec::new());
+
+    fn greet(name: &&'static str) {
+        GREETED.lock().unwrap().push(name);
+    }
+
+    set_declare!(greetings, &'static str, on_first_access = greet);
+
+    #[set_entry(greetings)]
+    static HELLO: &str = "hello";
+    #[set_entry(greetings)]
+    static HOWDY: &str = "howdy";
+
+    #[test]
+    fn test_first_access() {
+        assert!(!greetings::__FIRST_ACCESS.is_done());
+        assert!(GREETED.lock().unwrap().is_empty());
+        assert_eq!(set!(greetings).len(), 2);
+        assert!(greetings::__FIRST_ACCESS.is_done());
+        assert_eq!(set!(greetings).iter().count(), 2);
+        let mut greeted = GREETED.lock().unwrap().clone();
+        greeted.sort();
+        assert_eq!(greeted, [HELLO, HOWDY]);
+    }
+
+    #[test]
+    fn test_from_slice() {
+        let set = LinkerSet::from_slice(&[1u8, 2, 3]);
+        assert_eq!(set.len(), 3);
+        assert_eq!(set[1], 2);
+    }
+
+    set_declare!(attributed, u8);
+
+    /// A documented entry.
+    #[set_entry(attributed)]
+    #[allow(non_upper_case_globals)]
+    static lowercase: u8 = 1;
+
+    #[set_entry(attributed, key = "x")]
+    #[cfg(any())]
+    static COMPILED_OUT: u8 = 2;
+
+    #[set_entry(attributed)]
+    #[cfg(any())]
+    const ALSO_COMPILED_OUT: u8 = 3;
+
+    #[set_entry(attributed)]
+    #[cfg_attr(test, doc = "An entry documented only in tests.")]
+    #[cfg(test)]
+    static KEPT: u8 = 4;
+
+    #[test]
+    fn test_attributes() {
+        let set = set!(attributed).iter().copied().collect::<HashSet<_>>();
+        assert_eq!(set, HashSet::from([lowercase, KEPT]));
+    }
+
+    set_declare!(nothing, u64);
+
+    #[test]
+    fn test_empty() {
+        let set = set!(nothing);
+        assert!(set.is_empty());
+        assert_eq!(set.iter().count(), 0);
+    }
+
     #[derive(Debug, Eq, PartialEq, Hash)]
     pub(crate) struct Foo {
         a: u32,
@@ -343,13 +3032,99 @@ mod test {
     }
 }
 
+#[cfg(test)]
+mod test_register_impl {
+    use super::*;
+    use std::collections::HashSet;
+
+    pub trait Greeter: Sync {
+        fn greet(&self) -> String;
+    }
+
+    set_declare!(greeters, &'static dyn Greeter);
+
+    struct English;
+
+    #[register_impl(greeters)]
+    impl Greeter for English {
+        fn greet(&self) -> String {
+            "hello".to_string()
+        }
+    }
+
+    struct Repeat(usize);
+
+    const fn repeat() -> Repeat {
+        Repeat(2)
+    }
+
+    #[register_impl(greeters, ctor = repeat)]
+    impl Greeter for Repeat {
+        fn greet(&self) -> String {
+            "hi ".repeat(self.0)
+        }
+    }
+
+    #[register_impl(greeters)]
+    #[cfg(any())]
+    impl Greeter for English {
+        fn greet(&self) -> String {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn test_register_impl() {
+        let actual = set!(greeters).iter().map(|g| g.greet());
+        let expect = HashSet::from(["hello".to_string(), "hi hi ".to_string()]);
+        assert_eq!(actual.collect::<HashSet<_>>(), expect);
+    }
+}
+
+#[cfg(test)]
+mod test_register_const {
+    use super::*;
+
+    set_declare!(limits, usize);
+    set_declare!(names, &'static str);
+
+    pub struct Cache;
+
+    #[register_const(limits, Self::LIMIT)]
+    #[register_const(names, Self::NAME)]
+    impl Cache {
+        const LIMIT: usize = 64;
+        const NAME: &'static str = "cache";
+    }
+
+    pub trait Pool {
+        const SIZE: usize;
+    }
+
+    pub struct Threads;
+
+    #[register_const(limits, Self::SIZE)]
+    impl Pool for Threads {
+        const SIZE: usize = 8;
+    }
+
+    #[test]
+    fn test_register_const() {
+        let mut actual = set!(limits).iter().copied().collect::<Vec<_>>();
+        actual.sort();
+        assert_eq!(actual, [Threads::SIZE, Cache::LIMIT]);
+        assert_eq!(set!(names).iter().collect::<Vec<_>>(), [&Cache::NAME]);
+    }
+}
+
 #[cfg(test)]
 mod test_use_ext {
     use super::*;
     use test::stuff;
 
     #[set_entry(stuff)]
-    static FOO: u64 = 0x6666666666666666;
+    #[no_mangle] // would collide with test::FOO if it were not removed
+    pub(super) static FOO: u64 = 0x6666666666666666;
 
     #[test]
     fn test_use() {
@@ -358,3 +3133,126 @@ mod test_use_ext {
         assert_eq!(iter.len(), LEN);
     }
 }
+
+#[cfg(test)]
+mod test_optimization {
+    use crate::diagnostics;
+    use std::path::Path;
+    use std::process::Command;
+
+    /* entries in two crates and in several modules of each, so that with
+     * many codegen units they are spread across object files */
+    const EXTENSION: &str = "
+        use linker_set::*;
+        set_declare!(stuff, u64);
+        #[set_entry(stuff)]
+        static ONE: u64 = 1;
+        pub mod more {
+            use super::*;
+            #[set_entry(stuff)]
+            static TWO: u64 = 2;
+            #[inline(never)]
+            pub fn two() -> u64 { TWO }
+        }
+    ";
+
+    const PROGRAM: &str = "
+        use linker_set::*;
+        use extension::stuff;
+        #[set_entry(stuff)]
+        static FOUR: u64 = 4;
+        mod more {
+            use super::*;
+            #[set_entry(stuff)]
+            pub static EIGHT: u64 = 8;
+        }
+        fn main() {
+            let mut v = set!(stuff).iter().copied().collect::<Vec<_>>();
+            v.sort();
+            assert_eq!(v, [1, 2, 4, 8]);
+            assert_eq!(extension::more::two() + more::EIGHT, 10);
+        }
+    ";
+
+    fn rustc(dir: &Path, args: &[&str]) {
+        let deps = diagnostics::deps().unwrap();
+        let rustc = std::env::var("RUSTC").unwrap_or("rustc".to_string());
+        let output = Command::new(rustc)
+            .args(["--edition", "2021", "-L"])
+            .arg(dir)
+            .arg("-L")
+            .arg(format!("dependency={}", deps.display()))
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(output.status.success(), "rustc {:?}: {}", args, stderr);
+    }
+
+    /// Check that every entry appears in its set exactly once, whether or
+    /// not the crates are optimized together and however many codegen
+    /// units they are split into.
+    #[test]
+    #[ignore = "builds the crate six times; run by its own CI job"]
+    fn test_lto() {
+        let proc = match diagnostics::extern_path("linker_set_proc") {
+            Ok(proc) => proc,
+            Err(e) => panic!("{}", e),
+        };
+        let dir = std::env::temp_dir()
+            .join(format!("linker-set-lto-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("extension.rs"), EXTENSION).unwrap();
+        std::fs::write(dir.join("main.rs"), PROGRAM).unwrap();
+
+        /* cargo builds this crate without the bitcode that LTO needs, so
+         * build it again.  The metadata feature is harmless if the macros
+         * were built without it. */
+        let proc = format!("linker_set_proc={}", proc.display());
+        rustc(
+            &dir,
+            &[
+                "--crate-type=rlib",
+                "--crate-name=linker_set",
+                "--cfg=feature=\"metadata\"",
+                "-Cembed-bitcode=yes",
+                "-Copt-level=3",
+                "--extern",
+                &proc,
+                concat!(env!("CARGO_MANIFEST_DIR"), "/src/lib.rs"),
+            ],
+        );
+        for lto in ["off", "thin", "fat"] {
+            for units in ["1", "16"] {
+                let lto = format!("-Clto={}", lto);
+                let units = format!("-Ccodegen-units={}", units);
+                let flags = [lto.as_str(), &units, "-Copt-level=3"];
+                rustc(
+                    &dir,
+                    &[
+                        &flags[..],
+                        &["--crate-type=rlib", "-Cembed-bitcode=yes"],
+                        &["--extern=linker_set", "extension.rs"],
+                    ]
+                    .concat(),
+                );
+                rustc(
+                    &dir,
+                    &[
+                        &flags[..],
+                        &[
+                            "--extern=linker_set",
+                            "--extern=extension",
+                            "main.rs",
+                        ],
+                    ]
+                    .concat(),
+                );
+                let status = Command::new(dir.join("main")).status().unwrap();
+                assert!(status.success(), "{:?}", flags);
+            }
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,424 @@
+//! Manifests of the entries that a build registers into linker sets.
+//!
+//! Any crate in a build can register behavior into a set, such as a
+//! handler into a dispatch table, and reviewing a change of dependencies
+//! means finding every such registration without reading every crate.
+//! With the `manifest` feature enabled, each expansion of the
+//! [set_entry](crate::set_entry) attribute, and of the attributes built
+//! on it, is recorded with its set, crate, entry, file, and line, into
+//! the directory named by the environment variable
+//! `LINKER_SET_MANIFEST_DIR` while the build runs.  Set for the whole
+//! build, the variable records every crate compiled:
+//!
+//! ```text
+//! cargo clean
+//! LINKER_SET_MANIFEST_DIR=$PWD/target/manifest cargo build --features \
+//!     linker-set/manifest
+//! cargo linker-set manifest target/manifest > registrations.json
+//! ```
+//!
+//! Cargo does not rebuild a crate when the variable changes, so a crate
+//! compiled before it was set is recorded only once it is rebuilt.  A
+//! crate that wants its own registrations recorded on every build can
+//! call [configure] from its build script instead, which records them
+//! under the target directory.
+//!
+//! [read] merges the records in a directory, keeping those of the most
+//! recent compilation of each crate, so that entries since removed are
+//! not reported, and [to_json] writes them as the `manifest` subcommand
+//! of `cargo linker-set` prints them.
+//!
+//! Each expansion of [set_declare](crate::set_declare) is recorded too,
+//! and [declarations] reads them, so that [lint] can report, before
+//! anything is linked, an entry registered into a set that no crate
+//! declares, which otherwise fails to link with an error about a missing
+//! symbol, and a set that no crate populates, which is otherwise silently
+//! empty.  The `lint` subcommand of `cargo linker-set` prints them.
+
+use crate::json;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The environment variable naming the directory of the manifest.
+pub const DIR_VAR: &str = "LINKER_SET_MANIFEST_DIR";
+
+/// An entry registered into a linker set by a build.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Registration {
+    /// The name of the set.
+    pub set: String,
+    /// The name of the crate registering the entry.
+    pub krate: String,
+    /// The name of the entry.
+    pub item: String,
+    /// The file in which the entry is registered, as the compiler named
+    /// it.
+    pub file: String,
+    /// The line of the file at which the entry is registered.
+    pub line: u32,
+}
+
+impl Registration {
+    /// Parse a line of a manifest, as the attributes record it.
+    fn parse(line: &str) -> Option<Self> {
+        let ["entry", set, krate, item, file, n] =
+            line.split('\t').collect::<Vec<_>>()[..]
+        else {
+            return None;
+        };
+        Some(Self {
+            set: set.to_string(),
+            krate: krate.to_string(),
+            item: item.to_string(),
+            file: file.to_string(),
+            line: n.parse().ok()?,
+        })
+    }
+
+    /// Returns the registration as a JSON object.
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        out.push_str("{\"set\":");
+        json::string(&mut out, &self.set);
+        out.push_str(",\"crate\":");
+        json::string(&mut out, &self.krate);
+        out.push_str(",\"item\":");
+        json::string(&mut out, &self.item);
+        out.push_str(",\"file\":");
+        json::string(&mut out, &self.file);
+        out.push_str(&format!(",\"line\":{}}}", self.line));
+        out
+    }
+}
+
+impl fmt::Display for Registration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}::{} at {}:{}",
+            self.set, self.krate, self.item, self.file, self.line
+        )
+    }
+}
+
+/// A linker set declared by a build.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Declaration {
+    /// The name of the set.
+    pub set: String,
+    /// The name of the crate declaring the set.
+    pub krate: String,
+    /// The file in which the set is declared, as the compiler named it.
+    pub file: String,
+    /// The line of the file at which the set is declared.
+    pub line: u32,
+}
+
+impl Declaration {
+    /// Parse a line of a manifest, as [set_declare](crate::set_declare)
+    /// records it.
+    fn parse(line: &str) -> Option<Self> {
+        let ["set", set, krate, file, n] =
+            line.split('\t').collect::<Vec<_>>()[..]
+        else {
+            return None;
+        };
+        Some(Self {
+            set: set.to_string(),
+            krate: krate.to_string(),
+            file: file.to_string(),
+            line: n.parse().ok()?,
+        })
+    }
+}
+
+impl fmt::Display for Declaration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} at {}:{}",
+            self.set, self.krate, self.file, self.line
+        )
+    }
+}
+
+/// A mistake in the use of linker sets found in a manifest.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Finding {
+    /// An entry is registered into a set that no crate declares.
+    Undeclared(Registration),
+    /// A set is declared, but no crate registers an entry into it.
+    Unpopulated(Declaration),
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Undeclared(r) => write!(
+                f,
+                "{}:{}: {}::{} is registered into {}, which no crate \
+                 declares",
+                r.file, r.line, r.krate, r.item, r.set
+            ),
+            Self::Unpopulated(d) => write!(
+                f,
+                "{}:{}: {} of {} has no entries",
+                d.file, d.line, d.set, d.krate
+            ),
+        }
+    }
+}
+
+/// Returns the file of a directory written most recently.
+fn newest(dir: &Path) -> io::Result<Option<PathBuf>> {
+    let mut newest = None;
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let modified = entry.metadata()?.modified()?;
+        if newest.as_ref().map_or(true, |(m, _)| modified > *m) {
+            newest = Some((modified, entry.path()));
+        }
+    }
+    Ok(newest.map(|(_, path)| path))
+}
+
+/// Read the lines of the manifest in a directory, as parsed by `parse`,
+/// from the most recent compilation of each crate, sorted.
+///
+/// A line that cannot be parsed, such as one cut short by a compilation
+/// interrupted while writing it, is skipped.
+fn lines<T, F>(dir: &Path, parse: F) -> io::Result<Vec<T>>
+where
+    T: Ord,
+    F: Fn(&str) -> Option<T>,
+{
+    let mut all = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let Some(file) = newest(&entry.path())? else {
+            continue;
+        };
+        let text = std::fs::read_to_string(file)?;
+        all.extend(text.lines().filter_map(&parse));
+    }
+    all.sort();
+    all.dedup();
+    Ok(all)
+}
+
+/// Read the manifest in a directory, returning the registrations of the
+/// most recent compilation of each crate, sorted by set, crate, and
+/// entry.
+///
+/// A line that cannot be parsed, such as one cut short by a compilation
+/// interrupted while writing it, is skipped.
+pub fn read(dir: impl AsRef<Path>) -> io::Result<Vec<Registration>> {
+    lines(dir.as_ref(), Registration::parse)
+}
+
+/// Read the manifest in a directory, returning the declarations of sets by
+/// the most recent compilation of each crate, sorted by set and crate.
+pub fn declarations(dir: impl AsRef<Path>) -> io::Result<Vec<Declaration>> {
+    lines(dir.as_ref(), Declaration::parse)
+}
+
+/// Returns the registrations into sets that are not declared, and the
+/// declarations of sets into which nothing is registered, sets being
+/// matched by name.
+///
+/// The sets declared by this crate itself, such as
+/// [stage_init](mod@crate::stage::stage_init), are not reported when no
+/// entries are registered into them, since a program need not use them.
+pub fn lint(
+    declarations: &[Declaration], registrations: &[Registration],
+) -> Vec<Finding> {
+    let undeclared = registrations
+        .iter()
+        .filter(|r| !declarations.iter().any(|d| d.set == r.set))
+        .map(|r| Finding::Undeclared(r.clone()));
+    let unpopulated = declarations
+        .iter()
+        .filter(|d| d.krate != "linker_set")
+        .filter(|d| !registrations.iter().any(|r| r.set == d.set))
+        .map(|d| Finding::Unpopulated(d.clone()));
+    undeclared.chain(unpopulated).collect()
+}
+
+/// Returns registrations as a JSON array, one to a line.
+pub fn to_json(registrations: &[Registration]) -> String {
+    let mut out = String::from("[");
+    for (i, r) in registrations.iter().enumerate() {
+        out.push_str(if i == 0 { "\n  " } else { ",\n  " });
+        out.push_str(&r.to_json());
+    }
+    out.push_str("\n]\n");
+    out
+}
+
+/// Record the registrations of the crate being built, called from its
+/// build script.
+///
+/// The registrations are recorded into the directory named by
+/// `LINKER_SET_MANIFEST_DIR`, if it is set, or else into the directory
+/// `linker-set-manifest` of the target directory, such as
+/// `target/debug/linker-set-manifest`.
+///
+/// # Panics
+/// Panics if not called from a build script.
+pub fn configure() {
+    let dir = match std::env::var_os(DIR_VAR) {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            let out = std::env::var_os("OUT_DIR")
+                .expect("configure must be called from a build script");
+            /* OUT_DIR is target/PROFILE/build/PACKAGE-HASH/out */
+            let target = Path::new(&out).ancestors().nth(3).unwrap();
+            target.join("linker-set-manifest")
+        }
+    };
+    println!("cargo:rustc-env={}={}", DIR_VAR, dir.display());
+    println!("cargo:rerun-if-env-changed={}", DIR_VAR);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "linker-set-manifest-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_read() {
+        let dir = temp_dir("read");
+        std::fs::create_dir(dir.join("app")).unwrap();
+        std::fs::create_dir(dir.join("plugin")).unwrap();
+        let old = dir.join("app").join("1.tsv");
+        std::fs::write(&old, "entry\tcommands\tapp\tREMOVED\tsrc/main.rs\t3\n")
+            .unwrap();
+        let hour_ago = SystemTime::now() - Duration::from_secs(3600);
+        std::fs::File::options()
+            .write(true)
+            .open(&old)
+            .unwrap()
+            .set_modified(hour_ago)
+            .unwrap();
+        std::fs::write(
+            dir.join("app").join("2.tsv"),
+            "set\tcommands\tapp\tsrc/main.rs\t2\n\
+             entry\tcommands\tapp\tLIST\tsrc/main.rs\t7\n\
+             set\thooks\tapp\tsrc/main.rs\t3\n\
+             entry\tcommands\tapp\tcut",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("plugin").join("3.tsv"),
+            "entry\tcommands\tplugin\tEVIL\tsrc/lib.rs\t12\n\
+             entry\tcommnads\tplugin\tTYPO\tsrc/lib.rs\t15\n",
+        )
+        .unwrap();
+
+        let all = read(&dir).unwrap();
+        let items = all.iter().map(|r| r.item.as_str()).collect::<Vec<_>>();
+        assert_eq!(items, ["LIST", "EVIL", "TYPO"]);
+        assert_eq!(
+            all[1].to_string(),
+            "commands: plugin::EVIL at src/lib.rs:12"
+        );
+
+        let declared = declarations(&dir).unwrap();
+        assert_eq!(declared.len(), 2);
+        assert_eq!(declared[0].to_string(), "commands of app at src/main.rs:2");
+        let findings = lint(&declared, &all)
+            .iter()
+            .map(Finding::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            findings,
+            [
+                "src/lib.rs:15: plugin::TYPO is registered into commnads, \
+                 which no crate declares",
+                "src/main.rs:3: hooks of app has no entries",
+            ]
+        );
+        assert_eq!(
+            to_json(&all[..1]),
+            "[\n  {\"set\":\"commands\",\"crate\":\"app\",\"item\":\"LIST\",\
+             \"file\":\"src/main.rs\",\"line\":7}\n]\n"
+        );
+        assert_eq!(to_json(&[]), "[\n]\n");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    #[cfg(feature = "manifest")]
+    fn test_record() {
+        use crate::diagnostics::{deps, this_crate};
+        use std::process::Command;
+
+        let dir = temp_dir("record");
+        let file = dir.join("snippet.rs");
+        let source = "use linker_set::*;\n\
+                      set_declare!(stuff, u8);\n\
+                      #[set_entry(stuff)]\n\
+                      static ONE: u8 = 1;\n";
+        std::fs::write(&file, source).unwrap();
+        let deps = deps().unwrap();
+        let lib = match this_crate() {
+            Ok(lib) => lib,
+            Err(e) => panic!("{}", e),
+        };
+        let rustc = std::env::var("RUSTC").unwrap_or("rustc".to_string());
+        let status = Command::new(rustc)
+            .args(["--edition", "2021", "--crate-type", "lib"])
+            .args(["--emit", "metadata", "--crate-name", "snippet"])
+            .env("CARGO_CRATE_NAME", "snippet")
+            .env(DIR_VAR, dir.join("manifest"))
+            .arg("--out-dir")
+            .arg(&dir)
+            .arg("-L")
+            .arg(format!("dependency={}", deps.display()))
+            .arg("--extern")
+            .arg(format!("linker_set={}", lib.display()))
+            .arg(&file)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let declared = declarations(dir.join("manifest")).unwrap();
+        assert_eq!(
+            declared,
+            [Declaration {
+                set: "stuff".to_string(),
+                krate: "snippet".to_string(),
+                file: file.display().to_string(),
+                line: 2,
+            }]
+        );
+        let all = read(dir.join("manifest")).unwrap();
+        assert_eq!(
+            all,
+            [Registration {
+                set: "stuff".to_string(),
+                krate: "snippet".to_string(),
+                item: "ONE".to_string(),
+                file: file.display().to_string(),
+                line: 4,
+            }]
+        );
+        assert!(lint(&declared, &all).is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
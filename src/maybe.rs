@@ -0,0 +1,120 @@
+//! Linker sets that may not have been declared.
+
+use crate::*;
+
+/// A linker set named by a crate that does not know whether any crate in
+/// the program declares it, created by the [set_if_declared!] macro.
+pub struct MaybeSet<T>
+where
+    T: 'static,
+{
+    name: &'static str,
+    set: LinkerSet<T>,
+}
+
+impl<T> MaybeSet<T>
+where
+    T: 'static,
+{
+    #[doc(hidden)]
+    pub unsafe fn new(
+        name: &'static str, start: *const T, stop: *const T,
+    ) -> Self {
+        Self {
+            name,
+            set: LinkerSet::new(start, stop),
+        }
+    }
+
+    /// Returns true if some crate in the program declares the set.
+    ///
+    /// # Panics
+    /// Panics if the set is declared with elements of a different size.
+    pub fn is_declared(&self) -> bool {
+        let size = std::mem::size_of::<T>();
+        let mut declared = false;
+        for info in reflect::sets().iter().filter(|s| s.name() == self.name) {
+            assert!(
+                info.size() == size,
+                "linker set {} is declared in {} with elements of {} bytes, \
+                 not {}",
+                self.name,
+                info.module(),
+                info.size(),
+                size
+            );
+            declared = true;
+        }
+        declared
+    }
+
+    /// Returns the set if some crate in the program declares it.
+    pub fn get(self) -> Option<LinkerSet<T>> {
+        self.is_declared().then_some(self.set)
+    }
+
+    /// Returns the set, which is empty if no crate declares it.
+    pub fn or_empty(self) -> LinkerSet<T> {
+        self.set
+    }
+}
+
+/// Create a proxy object for a linker set that may not be declared.
+///
+/// Unlike [set!], this macro names the set and its element type directly
+/// rather than through the module output by [set_declare!], so a crate can
+/// consume a set that only some programs using it declare.  Where the set
+/// is not declared, the macro yields an empty set rather than a link error.
+///
+/// ```
+/// use linker_set::*;
+///
+/// # fn main() {
+/// let plugins = set_if_declared!(plugins, fn());
+/// assert!(plugins.get().is_none());
+/// # }
+/// ```
+#[macro_export]
+macro_rules! set_if_declared {
+    ($set:ident, $type:ty) => {{
+        /* in a block, where the guard cannot be, but a set that is not
+         * declared needs none */
+        $crate::__set_bounds!(@extern START, STOP, "set_", stringify!($set));
+
+        /* as in set_declare!, ensure that the start and stop symbols are
+         * defined, in this case whether or not the set is declared */
+        $crate::__set_section!(
+            ["set_", stringify!($set)]
+            #[used]
+            static EMPTY: [$type; 0] = [];
+        );
+
+        #[allow(unused_unsafe)] // addr_of! is safe since rust 1.82
+        unsafe {
+            $crate::MaybeSet::<$type>::new(
+                stringify!($set),
+                ::std::ptr::addr_of!(START).cast(),
+                ::std::ptr::addr_of!(STOP).cast(),
+            )
+        }
+    }};
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn test_maybe() {
+        let stuff = set_if_declared!(stuff, u64);
+        assert!(stuff.is_declared());
+        assert_eq!(stuff.get().unwrap().len(), 3);
+        let absent = set_if_declared!(absent, u64);
+        assert!(!absent.is_declared());
+        assert!(absent.or_empty().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "elements of 8 bytes, not 1")]
+    fn test_maybe_size() {
+        set_if_declared!(stuff, u8).is_declared();
+    }
+}
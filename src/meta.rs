@@ -0,0 +1,59 @@
+//! Metadata recorded about the entries of linker sets.
+
+/// Information about an entry in a linker set, recorded by the
+/// [set_entry](crate::set_entry) attribute when the `metadata` feature is
+/// enabled.
+pub struct EntryMeta<T>
+where
+    T: 'static,
+{
+    name: &'static str,
+    module: &'static str,
+    doc: &'static str,
+    entry: &'static T,
+}
+
+impl<T> EntryMeta<T>
+where
+    T: 'static,
+{
+    #[doc(hidden)]
+    pub const fn new(
+        name: &'static str, module: &'static str, doc: &'static str,
+        entry: &'static T,
+    ) -> Self {
+        Self {
+            name,
+            module,
+            doc,
+            entry,
+        }
+    }
+
+    /// Returns the name of the static holding the entry.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the path of the module that defines the entry.
+    pub fn module(&self) -> &'static str {
+        self.module
+    }
+
+    /// Returns the name of the crate that defines the entry.
+    pub fn crate_name(&self) -> &'static str {
+        self.module.split("::").next().unwrap()
+    }
+
+    /// Returns the doc comment of the static holding the entry, with the
+    /// space that conventionally follows `///` removed from each line, or
+    /// an empty string if it has none.
+    pub fn doc(&self) -> &'static str {
+        self.doc
+    }
+
+    /// Returns the entry.
+    pub fn entry(&self) -> &'static T {
+        self.entry
+    }
+}
@@ -0,0 +1,122 @@
+//! Linker sets whose entries are other linker sets.
+
+use crate::LinkerSet;
+
+/// A reference to a linker set, for use as an entry of another set, made
+/// with the [set_ref!](crate::set_ref) macro.
+///
+/// A set of `SetRef<T>` is a two-level registry, such as of categories,
+/// each with a set of implementations, and `LinkerSet::flatten` yields the
+/// elements of all of the sets that it refers to.
+///
+/// ```
+/// use linker_set::*;
+///
+/// pub struct Codec {
+///     name: &'static str,
+/// }
+///
+/// set_declare!(audio, Codec);
+/// set_declare!(video, Codec);
+/// set_declare!(codecs, SetRef<Codec>);
+///
+/// #[set_entry(audio)]
+/// static OPUS: Codec = Codec { name: "opus" };
+/// #[set_entry(video)]
+/// static AV1: Codec = Codec { name: "av1" };
+///
+/// #[set_entry(codecs)]
+/// static AUDIO: SetRef<Codec> = set_ref!(audio);
+/// #[set_entry(codecs)]
+/// static VIDEO: SetRef<Codec> = set_ref!(video);
+///
+/// # fn main() {
+/// let mut names = set!(codecs).flatten().map(|c| c.name).collect::<Vec<_>>();
+/// names.sort();
+/// assert_eq!(names, ["av1", "opus"]);
+/// # }
+/// ```
+pub struct SetRef<T>
+where
+    T: 'static,
+{
+    name: &'static str,
+    set: fn() -> LinkerSet<T>,
+}
+
+impl<T> SetRef<T>
+where
+    T: 'static,
+{
+    #[doc(hidden)]
+    pub const fn new(name: &'static str, set: fn() -> LinkerSet<T>) -> Self {
+        Self { name, set }
+    }
+
+    /// Returns the name of the linker set.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the linker set.
+    pub fn set(&self) -> LinkerSet<T> {
+        (self.set)()
+    }
+}
+
+impl<T> LinkerSet<SetRef<T>>
+where
+    T: 'static,
+{
+    /// Returns an iterator over the items in each of the linker sets that
+    /// the items of this one refer to.
+    pub fn flatten(&self) -> impl Iterator<Item = &'static T> {
+        self.iter().flat_map(|r| r.set().iter())
+    }
+}
+
+/// Make a reference to a linker set, for use as an entry of another set.
+///
+/// See [SetRef].
+#[macro_export]
+macro_rules! set_ref {
+    ($set:ident) => {
+        $crate::SetRef::new(stringify!($set), || $crate::set!($set))
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+
+    set_declare!(small_primes, u32);
+    set_declare!(small_squares, u32);
+    set_declare!(categories, SetRef<u32>);
+
+    #[set_entry(small_primes)]
+    const TWO: u32 = 2;
+    #[set_entry(small_primes)]
+    const THREE: u32 = 3;
+    #[set_entry(small_squares)]
+    const FOUR: u32 = 4;
+
+    #[set_entry(categories)]
+    static PRIMES: SetRef<u32> = set_ref!(small_primes);
+    #[set_entry(categories)]
+    static SQUARES: SetRef<u32> = set_ref!(small_squares);
+
+    #[test]
+    fn test_nested() {
+        let mut all = set!(categories).flatten().copied().collect::<Vec<_>>();
+        all.sort();
+        assert_eq!(all, [2, 3, 4]);
+
+        let mut sizes = set!(categories)
+            .iter()
+            .map(|r| (r.name(), r.set().len()))
+            .collect::<Vec<_>>();
+        sizes.sort();
+        assert_eq!(sizes, [("small_primes", 2), ("small_squares", 1)]);
+        assert_eq!(PRIMES.set().len() + SQUARES.set().len(), 3);
+    }
+}
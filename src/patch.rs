@@ -0,0 +1,254 @@
+//! Entries patched in place before the program goes multithreaded.
+
+use crate::LinkerSet;
+use std::cell::UnsafeCell;
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+
+/// An entry of a linker set that can be patched in place early in the
+/// program.
+///
+/// A bootloader or early initialization code may need to fill in fields
+/// of registered descriptors, such as MMIO addresses discovered at boot,
+/// before anything else reads them.  The entries of a set are statics,
+/// which the compiler assumes never change, so writing to them through a
+/// cast pointer is undefined behavior, and faults if the linker placed
+/// them in read-only memory.
+///
+/// A set whose element type is `BootCell` can be patched instead.  Its
+/// entries have interior mutability, so the compiler places them in
+/// writable memory and does not assume that they are constant.
+/// `LinkerSet::as_mut_slice_once` yields the elements of such a set as a
+/// mutable slice, only once:
+///
+/// ```
+/// use linker_set::*;
+///
+/// pub struct Device {
+///     name: &'static str,
+///     base: usize,
+/// }
+///
+/// set_declare!(devices, BootCell<Device>);
+///
+/// #[set_entry(devices)]
+/// static UART: BootCell<Device> = BootCell::new(Device {
+///     name: "uart",
+///     base: 0,
+/// });
+///
+/// # fn main() {
+/// // before starting any threads or enabling interrupts
+/// let devices = unsafe { set!(devices).as_mut_slice_once() }.unwrap();
+/// for device in devices {
+///     device.base = 0x1000_0000;
+/// }
+/// assert_eq!(UART.get().base, 0x1000_0000);
+/// # }
+/// ```
+///
+/// Code already running on another core when the entries are patched,
+/// such as the secondary CPUs of a kernel, reads them only through
+/// `LinkerSet::acquire_view`, which yields them once the patching code
+/// has handed the slice to `LinkerSet::publish`.  On a weakly ordered
+/// architecture, such as aarch64, a core can otherwise see the entries
+/// before some of the writes to them, even long after they were made.
+#[repr(transparent)]
+pub struct BootCell<T> {
+    value: UnsafeCell<T>,
+}
+
+// Safety: the value is shared as by a T, except while it is patched, and
+// the caller of as_mut_slice_once promises that nothing else accesses it
+// then.
+unsafe impl<T: Sync> Sync for BootCell<T> {}
+
+impl<T> BootCell<T> {
+    /// Create an entry holding a value.
+    pub const fn new(value: T) -> Self {
+        Self {
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Returns the value of the entry.
+    pub fn get(&self) -> &T {
+        unsafe { &*self.value.get() }
+    }
+}
+
+/// The addresses of the sets whose elements have been yielded mutably.
+static PATCHED: Mutex<BTreeSet<usize>> = Mutex::new(BTreeSet::new());
+
+/// The addresses of the sets whose patched elements have been published.
+///
+/// Inserting into it releases, and finding a set in it acquires, the
+/// writes made to the set before it was published, so that a core that
+/// finds a set here sees all of them, on any architecture.
+static PUBLISHED: Mutex<BTreeSet<usize>> = Mutex::new(BTreeSet::new());
+
+impl<T> LinkerSet<BootCell<T>>
+where
+    T: 'static,
+{
+    /// Returns the values of the entries in the linker set as a mutable
+    /// slice, the first time it is called for the set, and `None`
+    /// thereafter.
+    ///
+    /// # Safety
+    /// Until the slice is last used, no entry of the set may be accessed
+    /// by any other means, whether through another linker set proxy
+    /// object, through the static holding the entry, or by another
+    /// thread.  This is the case, for instance, early in a program that
+    /// patches its entries before it starts threads or reads them.
+    ///
+    /// # Panics
+    /// Panics if the linker sets have been sealed with
+    /// [seal_all](crate::seal::seal_all).
+    pub unsafe fn as_mut_slice_once(&self) -> Option<&'static mut [T]> {
+        crate::seal::assert_unsealed("patch a linker set");
+        let cells = self.slice;
+        if !cells.is_empty()
+            && !PATCHED.lock().unwrap().insert(cells.as_ptr() as usize)
+        {
+            return None;
+        }
+        let base = UnsafeCell::raw_get(cells.as_ptr().cast());
+        Some(std::slice::from_raw_parts_mut(base, cells.len()))
+    }
+
+    /// Publish the values patched through the slice yielded by
+    /// [as_mut_slice_once](Self::as_mut_slice_once), so that
+    /// [acquire_view](Self::acquire_view) yields them on any thread or
+    /// core.
+    ///
+    /// Taking the slice ends the patching, since no write through it can
+    /// follow.
+    ///
+    /// # Panics
+    /// Panics if the slice does not hold the elements of the set.
+    pub fn publish(&self, patched: &'static mut [T]) {
+        let cells = self.slice;
+        let base = UnsafeCell::raw_get(cells.as_ptr().cast());
+        assert!(
+            std::ptr::eq(patched.as_ptr(), base)
+                && patched.len() == cells.len(),
+            "publishing the elements of another set"
+        );
+        PUBLISHED.lock().unwrap().insert(cells.as_ptr() as usize);
+    }
+
+    /// Returns the values of the entries of the linker set, if they have
+    /// been [published](Self::publish), and `None` if not.
+    ///
+    /// Every write made through the slice before it was published is
+    /// visible through the values returned, however weakly the
+    /// architecture orders memory.  An empty set is always published.
+    pub fn acquire_view(&self) -> Option<&'static [T]> {
+        let cells = self.slice;
+        if !cells.is_empty()
+            && !PUBLISHED
+                .lock()
+                .unwrap()
+                .contains(&(cells.as_ptr() as usize))
+        {
+            return None;
+        }
+        let base = UnsafeCell::raw_get(cells.as_ptr().cast());
+        Some(unsafe { std::slice::from_raw_parts(base, cells.len()) })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+
+    pub struct Descriptor {
+        name: &'static str,
+        base: usize,
+    }
+
+    set_declare!(descriptors, BootCell<Descriptor>);
+
+    #[set_entry(descriptors)]
+    static TIMER: BootCell<Descriptor> = BootCell::new(Descriptor {
+        name: "timer",
+        base: 0,
+    });
+    #[set_entry(descriptors)]
+    static RTC: BootCell<Descriptor> = BootCell::new(Descriptor {
+        name: "rtc",
+        base: 0,
+    });
+
+    pub struct Mailbox {
+        core: usize,
+        addr: usize,
+    }
+
+    set_declare!(mailboxes, BootCell<Mailbox>);
+
+    #[set_entry(mailboxes)]
+    static CORE1: BootCell<Mailbox> =
+        BootCell::new(Mailbox { core: 1, addr: 0 });
+    #[set_entry(mailboxes)]
+    static CORE2: BootCell<Mailbox> =
+        BootCell::new(Mailbox { core: 2, addr: 0 });
+
+    #[test]
+    fn test_publish() {
+        /* the secondary cores are already waiting for their mailboxes */
+        let readers = (0..4)
+            .map(|_| {
+                std::thread::spawn(|| loop {
+                    if let Some(view) = set!(mailboxes).acquire_view() {
+                        break view.iter().map(|m| m.addr).sum::<usize>();
+                    }
+                    std::hint::spin_loop();
+                })
+            })
+            .collect::<Vec<_>>();
+
+        assert!(set!(mailboxes).acquire_view().is_none());
+        let mailboxes = unsafe { set!(mailboxes).as_mut_slice_once() };
+        let mailboxes = mailboxes.unwrap();
+        for m in mailboxes.iter_mut() {
+            m.addr = 0x8000 + m.core * 0x100;
+        }
+        set!(mailboxes).publish(mailboxes);
+        for reader in readers {
+            assert_eq!(reader.join().unwrap(), 0x1_0300);
+        }
+        assert_eq!(CORE1.get().addr + CORE2.get().addr, 0x1_0300);
+
+        static EMPTY: [BootCell<Mailbox>; 0] = [];
+        assert!(LinkerSet::from_slice(&EMPTY).acquire_view().is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "publishing the elements of another set")]
+    fn test_publish_other() {
+        static OTHER: [BootCell<u8>; 1] = [BootCell::new(0)];
+        static MORE: [BootCell<u8>; 1] = [BootCell::new(0)];
+        let other =
+            unsafe { LinkerSet::from_slice(&OTHER).as_mut_slice_once() };
+        LinkerSet::from_slice(&MORE).publish(other.unwrap());
+    }
+
+    #[test]
+    fn test_patch() {
+        let descriptors = unsafe { set!(descriptors).as_mut_slice_once() };
+        for d in descriptors.unwrap() {
+            d.base = if d.name == "timer" { 0x1000 } else { 0x2000 };
+        }
+        assert!(unsafe { set!(descriptors).as_mut_slice_once() }.is_none());
+        assert_eq!(TIMER.get().base, 0x1000);
+        assert_eq!(RTC.get().base, 0x2000);
+        let mut bases = set!(descriptors)
+            .iter()
+            .map(|d| d.get().base)
+            .collect::<Vec<_>>();
+        bases.sort();
+        assert_eq!(bases, [0x1000, 0x2000]);
+    }
+}
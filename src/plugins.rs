@@ -0,0 +1,248 @@
+//! Plugins linked into a program, discovered before any is activated.
+//!
+//! Each plugin is described by a [Plugin] registered into the [plugin]
+//! linker set, with its name, its version, the names of the plugins it
+//! requires, and the function that initializes it.  [enumerate] returns
+//! the descriptions without running any code of the plugins, so that a
+//! program can decide, such as from its configuration, which to use, and
+//! [activate] then initializes those it selects, each after those it
+//! requires.
+//!
+//! ```
+//! use linker_set::*;
+//! use linker_set::plugins::{self, Plugin};
+//!
+//! #[set_entry(linker_set::plugins::plugin)]
+//! static METRICS: Plugin = Plugin {
+//!     name: "metrics",
+//!     version: "1.2.0",
+//!     requires: &[],
+//!     init: || (),
+//! };
+//! #[set_entry(linker_set::plugins::plugin)]
+//! static EXPORTER: Plugin = Plugin {
+//!     name: "exporter",
+//!     version: "0.3.1",
+//!     requires: &["metrics"],
+//!     init: || (),
+//! };
+//!
+//! # fn main() {
+//! let all = plugins::enumerate();
+//! let names = all.iter().map(|p| p.name).collect::<Vec<_>>();
+//! assert_eq!(names, ["exporter", "metrics"]);
+//! assert_eq!(all[0].requires, ["metrics"]);
+//! let disabled = ["exporter"];
+//! let active = plugins::activate(|p| !disabled.contains(&p.name)).unwrap();
+//! assert_eq!(active, ["metrics"]);
+//! # }
+//! ```
+//!
+//! A plugin is initialized at most once, however many times [activate] is
+//! called, so that plugins can be activated in more than one step.
+
+use crate::*;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Mutex;
+
+set_declare!(plugin, Plugin, namespaced);
+
+/// The description of a plugin.
+#[derive(Debug)]
+pub struct Plugin {
+    /// The name of the plugin, which must be unique within the program.
+    pub name: &'static str,
+    /// The version of the plugin.
+    pub version: &'static str,
+    /// The names of the plugins that must be initialized before this one.
+    pub requires: &'static [&'static str],
+    /// The function that initializes the plugin.
+    pub init: fn(),
+}
+
+/// Why plugins could not be activated.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PluginError {
+    /// Two plugins have the same name.
+    Duplicate(&'static str),
+    /// A plugin requires one that is not linked in, or that is neither
+    /// selected nor already active.
+    Missing {
+        /// The name of the plugin.
+        plugin: &'static str,
+        /// The name of the plugin it requires.
+        requires: &'static str,
+    },
+    /// A plugin requires, directly or not, itself.
+    Cycle(&'static str),
+}
+
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Duplicate(name) => {
+                write!(f, "plugin {} is registered twice", name)
+            }
+            Self::Missing { plugin, requires } => write!(
+                f,
+                "plugin {} requires {}, which is not activated",
+                plugin, requires
+            ),
+            Self::Cycle(name) => write!(f, "plugin {} requires itself", name),
+        }
+    }
+}
+
+impl std::error::Error for PluginError {}
+
+static ACTIVE: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+/// Returns the descriptions of the plugins linked into the program, in
+/// order of name, without initializing any of them.
+pub fn enumerate() -> Vec<&'static Plugin> {
+    let mut plugins = set!(plugin).iter().collect::<Vec<_>>();
+    plugins.sort_by_key(|p| p.name);
+    plugins
+}
+
+/// Returns the names of the plugins activated so far, in the order in
+/// which they were initialized.
+pub fn active() -> Vec<&'static str> {
+    ACTIVE.lock().unwrap().clone()
+}
+
+/// Add a plugin to the order of initialization after those it requires.
+fn visit(
+    p: &'static Plugin, selected: &BTreeMap<&str, &'static Plugin>,
+    active: &[&str], visiting: &mut Vec<&'static str>,
+    order: &mut Vec<&'static Plugin>,
+) -> Result<(), PluginError> {
+    if active.contains(&p.name) || order.iter().any(|o| o.name == p.name) {
+        return Ok(());
+    }
+    if visiting.contains(&p.name) {
+        return Err(PluginError::Cycle(p.name));
+    }
+    visiting.push(p.name);
+    for &requires in p.requires {
+        if active.contains(&requires) {
+            continue;
+        }
+        let Some(r) = selected.get(requires) else {
+            let plugin = p.name;
+            return Err(PluginError::Missing { plugin, requires });
+        };
+        visit(r, selected, active, visiting, order)?;
+    }
+    visiting.pop();
+    order.push(p);
+    Ok(())
+}
+
+/// Initialize the plugins for which `filter` returns true, and that are
+/// not already active, each after those it requires, returning their
+/// names in the order initialized.
+///
+/// No plugin is initialized unless every one selected can be: each that it
+/// requires must be selected too, or already active.
+///
+/// # Panics
+/// Panics if the linker sets have been sealed with
+/// [seal_all](crate::seal::seal_all), since a plugin registers into the
+/// sets of the program as it is initialized.
+pub fn activate<F>(mut filter: F) -> Result<Vec<&'static str>, PluginError>
+where
+    F: FnMut(&Plugin) -> bool,
+{
+    crate::seal::assert_unsealed("activate plugins");
+    let mut all = BTreeMap::new();
+    for p in set!(plugin).iter() {
+        if all.insert(p.name, p).is_some() {
+            return Err(PluginError::Duplicate(p.name));
+        }
+    }
+    let selected = all
+        .into_iter()
+        .filter(|(_, p)| filter(p))
+        .collect::<BTreeMap<_, _>>();
+
+    /* held while initializing, so that two threads cannot both initialize
+     * a plugin */
+    let mut active = ACTIVE.lock().unwrap();
+    let mut order = Vec::new();
+    for p in selected.values() {
+        visit(p, &selected, &active, &mut Vec::new(), &mut order)?;
+    }
+    let mut names = Vec::new();
+    for p in order {
+        (p.init)();
+        active.push(p.name);
+        names.push(p.name);
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static INITIALIZED: Mutex<Vec<&str>> = Mutex::new(Vec::new());
+
+    macro_rules! plugin {
+        ($ident:ident, $name:literal $(, $requires:literal)*) => {
+            #[set_entry(crate::plugins::plugin)]
+            static $ident: Plugin = Plugin {
+                name: $name,
+                version: "1.0.0",
+                requires: &[$($requires),*],
+                init: || INITIALIZED.lock().unwrap().push($name),
+            };
+        };
+    }
+
+    plugin!(STORAGE, "storage");
+    plugin!(CACHE, "cache", "storage");
+    plugin!(API, "api", "cache", "storage");
+    plugin!(LOOP_A, "loop_a", "loop_b");
+    plugin!(LOOP_B, "loop_b", "loop_a");
+    plugin!(ORPHAN, "orphan", "absent");
+
+    #[test]
+    fn test_plugins() {
+        let all = enumerate();
+        let names = all.iter().map(|p| p.name).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["api", "cache", "loop_a", "loop_b", "orphan", "storage"]
+        );
+        assert_eq!(all[0].requires, ["cache", "storage"]);
+        assert!(INITIALIZED.lock().unwrap().is_empty());
+
+        let e = activate(|p| p.name == "cache").unwrap_err();
+        let missing = PluginError::Missing {
+            plugin: "cache",
+            requires: "storage",
+        };
+        assert_eq!(e, missing);
+        assert_eq!(
+            e.to_string(),
+            "plugin cache requires storage, which is not activated"
+        );
+        let e = activate(|p| p.name.starts_with("loop")).unwrap_err();
+        assert_eq!(e, PluginError::Cycle("loop_a"));
+        assert!(activate(|p| p.name == "orphan").is_err());
+        assert!(active().is_empty());
+
+        let names = activate(|p| p.name == "cache" || p.name == "storage");
+        assert_eq!(names.unwrap(), ["storage", "cache"]);
+        assert_eq!(activate(|p| p.name == "api").unwrap(), ["api"]);
+        assert!(activate(|_| false).unwrap().is_empty());
+        assert_eq!(*INITIALIZED.lock().unwrap(), ["storage", "cache", "api"]);
+        assert_eq!(active(), ["storage", "cache", "api"]);
+        assert_eq!(API.version, "1.0.0");
+        assert_eq!(STORAGE.requires.len() + LOOP_B.requires.len(), 1);
+        assert_eq!(LOOP_A.requires.len() + ORPHAN.requires.len(), 2);
+        assert_eq!(CACHE.requires, ["storage"]);
+    }
+}
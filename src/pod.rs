@@ -0,0 +1,158 @@
+//! Views of linker sets as raw bytes.
+//!
+//! The elements of a set of a type implementing [Pod], plain old data
+//! that has no padding and for which every pattern of bits is a valid
+//! value, can be viewed as bytes and made from bytes without ad hoc
+//! unsafe code.  `LinkerSet::as_bytes` views the memory of a set, such as
+//! to write it to a file or compare it with the section read from an
+//! artifact by the [inspect](crate::inspect) module, and
+//! [from_foreign_bytes] makes elements again from bytes read elsewhere,
+//! such as to replay a set in a test with `LinkerSet::from_slice`:
+//!
+//! ```
+//! use linker_set::*;
+//! use linker_set::pod::{self, Pod};
+//!
+//! #[derive(Clone, Copy, Debug, PartialEq)]
+//! #[repr(C)]
+//! pub struct Limit {
+//!     id: u32,
+//!     max: u32,
+//! }
+//!
+//! // no padding, and any bits are valid
+//! unsafe impl Pod for Limit {}
+//!
+//! set_declare!(limits, Limit);
+//!
+//! #[set_entry(limits)]
+//! static FILES: Limit = Limit { id: 1, max: 1024 };
+//!
+//! # fn main() {
+//! let bytes = set!(limits).as_bytes();
+//! assert_eq!(bytes.len(), 8);
+//! assert_eq!(pod::from_foreign_bytes::<Limit>(bytes).unwrap(), [FILES]);
+//! # }
+//! ```
+//!
+//! Bytes from another program are taken to be in the byte order and
+//! layout of this one, which [SetAbi](macro@crate::SetAbi) can verify.
+//! `Pod` plays the part of `bytemuck::Pod` or of zerocopy's `FromBytes`
+//! and `IntoBytes`, with the same requirements, without depending on
+//! either.
+
+use crate::LinkerSet;
+use std::fmt;
+
+/// A type with no padding, no interior mutability, and no invalid
+/// patterns of bits, so that its values can be viewed as bytes and made
+/// from any bytes of the right length.
+///
+/// # Safety
+/// The type must be `Copy`, must have no padding bytes, and every pattern
+/// of bits of its size must be a valid value.  A `#[repr(C)]` or
+/// `#[repr(transparent)]` struct whose fields are all `Pod` and leave no
+/// gaps between them qualifies.
+pub unsafe trait Pod: Copy + 'static {}
+
+macro_rules! pod {
+    ($($type:ty),*) => {
+        $(unsafe impl Pod for $type {})*
+    };
+}
+
+pod!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+pod!(f32, f64);
+
+unsafe impl<T, const N: usize> Pod for [T; N] where T: Pod {}
+
+/// Bytes that do not make a whole number of elements.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LengthError {
+    /// The number of bytes.
+    pub len: usize,
+    /// The size of an element.
+    pub size: usize,
+}
+
+impl fmt::Display for LengthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} bytes are not a whole number of {}-byte elements",
+            self.len, self.size
+        )
+    }
+}
+
+impl std::error::Error for LengthError {}
+
+/// Make elements from bytes laid out as they would be in a linker set,
+/// which need not be aligned for the element type.
+pub fn from_foreign_bytes<T>(bytes: &[u8]) -> Result<Vec<T>, LengthError>
+where
+    T: Pod,
+{
+    let size = std::mem::size_of::<T>();
+    if size == 0 || bytes.len() % size != 0 {
+        return Err(LengthError {
+            len: bytes.len(),
+            size,
+        });
+    }
+    Ok(bytes
+        .chunks_exact(size)
+        /* Pod makes any bytes of the right length a valid value */
+        .map(|c| unsafe { c.as_ptr().cast::<T>().read_unaligned() })
+        .collect())
+}
+
+impl<T> LinkerSet<T>
+where
+    T: Pod,
+{
+    /// Returns the memory of the elements of the linker set as bytes.
+    pub fn as_bytes(&self) -> &'static [u8] {
+        let (start, len) = self.as_raw_parts();
+        let len = len * std::mem::size_of::<T>();
+        /* Pod guarantees that every byte of an element is initialized */
+        unsafe { std::slice::from_raw_parts(start.cast(), len) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::*;
+
+    set_declare!(words, [u16; 2]);
+
+    #[set_entry(words)]
+    static FIRST: [u16; 2] = [1, 2];
+    #[set_entry(words)]
+    static SECOND: [u16; 2] = [3, 4];
+
+    #[test]
+    fn test_bytes() {
+        let words = set!(words);
+        let bytes = words.as_bytes();
+        assert_eq!(bytes.len(), 8);
+        let mut back = from_foreign_bytes::<[u16; 2]>(bytes).unwrap();
+        assert_eq!(back, words.to_vec());
+        back.sort();
+        assert_eq!(back, [FIRST, SECOND]);
+
+        /* misaligned by one byte */
+        let shifted = [&[0][..], bytes].concat();
+        let back = from_foreign_bytes::<[u16; 2]>(&shifted[1..]).unwrap();
+        assert_eq!(back, words.to_vec());
+
+        let e = from_foreign_bytes::<u32>(&bytes[..6]).unwrap_err();
+        assert_eq!(e, LengthError { len: 6, size: 4 });
+        assert_eq!(
+            e.to_string(),
+            "6 bytes are not a whole number of 4-byte elements"
+        );
+        assert_eq!(from_foreign_bytes::<u64>(&[]).unwrap(), [0u64; 0]);
+    }
+}
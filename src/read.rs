@@ -0,0 +1,200 @@
+//! Reading the elements of a set, whether or not the linker gathered them.
+
+use crate::{LinkerSet, LinkerSetIter};
+use std::iter::Map;
+
+/// The elements of a set, as read by code that takes a linker set but
+/// need not know where its elements come from.
+///
+/// Code generic over `SetRead` rather than taking a [LinkerSet] can be
+/// given the elements of a set of the program, or, in a unit test, a
+/// `MockSet` holding fixtures, without declaring a linker set for them.
+///
+/// ```
+/// use linker_set::*;
+///
+/// pub struct Route {
+///     path: &'static str,
+/// }
+///
+/// pub fn find<S: SetRead<Route>>(routes: &S, path: &str) -> bool {
+///     routes.iter().any(|r| r.path == path)
+/// }
+///
+/// set_declare!(routes, Route);
+///
+/// #[set_entry(routes)]
+/// static HOME: Route = Route { path: "/" };
+///
+/// # fn main() {
+/// assert!(find(&set!(routes), "/"));
+/// assert!(!find(&set!(routes), "/login"));
+/// # }
+/// ```
+pub trait SetRead<T> {
+    /// The iterator over the elements of the set.
+    type Iter<'a>: Iterator<Item = &'a T>
+    where
+        Self: 'a,
+        T: 'a;
+
+    /// Returns an iterator over the elements of the set.
+    fn iter(&self) -> Self::Iter<'_>;
+
+    /// Returns the number of elements in the set.
+    fn len(&self) -> usize;
+
+    /// Returns the element at an index, or `None` if the index is out of
+    /// range.
+    fn get(&self, i: usize) -> Option<&T>;
+
+    /// Returns true if the set has no elements.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> SetRead<T> for LinkerSet<T>
+where
+    T: 'static,
+{
+    type Iter<'a> = Map<LinkerSetIter<T>, fn(&'static T) -> &'a T>;
+
+    fn iter(&self) -> Self::Iter<'_> {
+        LinkerSet::iter(self).map(|x| x)
+    }
+
+    fn len(&self) -> usize {
+        LinkerSet::len(self)
+    }
+
+    fn get(&self, i: usize) -> Option<&T> {
+        LinkerSet::get(self, i)
+    }
+}
+
+impl<T, S> SetRead<T> for &S
+where
+    S: SetRead<T> + ?Sized,
+{
+    type Iter<'a>
+        = S::Iter<'a>
+    where
+        Self: 'a,
+        T: 'a;
+
+    fn iter(&self) -> S::Iter<'_> {
+        (**self).iter()
+    }
+
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+
+    fn get(&self, i: usize) -> Option<&T> {
+        (**self).get(i)
+    }
+}
+
+/// A set whose elements are held on the heap rather than gathered by the
+/// linker, to stand in for a linker set in tests of code generic over
+/// [SetRead].
+///
+/// It is available with the `mock` feature, which a crate can enable for
+/// its tests alone, in the `dev-dependencies` of its manifest.
+///
+/// ```
+/// use linker_set::*;
+///
+/// fn total<S: SetRead<u32>>(sizes: &S) -> u32 {
+///     sizes.iter().sum()
+/// }
+///
+/// let fixtures = MockSet::new(vec![4096, 512]);
+/// assert_eq!(total(&fixtures), 4608);
+/// ```
+#[cfg(feature = "mock")]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MockSet<T> {
+    elements: Vec<T>,
+}
+
+#[cfg(feature = "mock")]
+impl<T> MockSet<T> {
+    /// Create a set holding elements.
+    pub fn new(elements: Vec<T>) -> Self {
+        Self { elements }
+    }
+
+    /// Add an element to the end of the set.
+    pub fn push(&mut self, element: T) {
+        self.elements.push(element);
+    }
+}
+
+#[cfg(feature = "mock")]
+impl<T> FromIterator<T> for MockSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::new(iter.into_iter().collect())
+    }
+}
+
+#[cfg(feature = "mock")]
+impl<T> SetRead<T> for MockSet<T> {
+    type Iter<'a>
+        = std::slice::Iter<'a, T>
+    where
+        T: 'a;
+
+    fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.elements.iter()
+    }
+
+    fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    fn get(&self, i: usize) -> Option<&T> {
+        self.elements.get(i)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+
+    set_declare!(listen_ports, u16);
+
+    #[set_entry(listen_ports)]
+    static HTTP: u16 = 80;
+    #[set_entry(listen_ports)]
+    static HTTPS: u16 = 443;
+
+    fn privileged<S: SetRead<u16>>(ports: S) -> usize {
+        ports.iter().filter(|&&p| p < 1024).count()
+    }
+
+    #[test]
+    fn test_set_read() {
+        let set = set!(listen_ports);
+        assert_eq!(privileged(&set), 2);
+        assert_eq!(SetRead::len(&set), 2);
+        assert!(!SetRead::is_empty(&&set));
+        assert!(SetRead::get(&set, 1).is_some_and(|&p| p == 80 || p == 443));
+        assert!(SetRead::get(&set, 2).is_none());
+        assert_eq!(privileged(set), 2);
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn test_mock_set() {
+        let mut mock = [22, 8080].into_iter().collect::<MockSet<u16>>();
+        assert_eq!(privileged(&mock), 1);
+        mock.push(25);
+        assert_eq!(privileged(&mock), 2);
+        assert_eq!(SetRead::get(&mock, 2), Some(&25));
+        assert_eq!(mock.len(), 3);
+        assert!(MockSet::<u16>::default().is_empty());
+        assert_eq!(mock, MockSet::new(vec![22, 8080, 25]));
+    }
+}
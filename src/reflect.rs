@@ -0,0 +1,526 @@
+//! Reflection over the linker sets declared in a program.
+//!
+//! Every [set_declare!](crate::set_declare) records a [SetInfo] describing
+//! the set in a registry that can be walked at runtime with [sets].  With
+//! the `debug-report` feature enabled, the [set!](crate::set) macro also
+//! notes which sets have been accessed, and [report] lists sets that are
+//! empty or were never accessed, which catches registrations that have
+//! silently stopped landing anywhere.
+//!
+//! [configuration] describes the sets as a whole, with their entries and
+//! the entries that keyed and singleton sets choose, to be logged when a
+//! program starts.  [handle_debug_args] prints it on request, from the
+//! command line or the environment, for support engineers to inspect a
+//! program as built.
+
+use crate::*;
+use std::fmt;
+use std::ops::Range;
+use std::sync::atomic::AtomicBool;
+#[cfg(feature = "debug-report")]
+use std::sync::atomic::Ordering;
+
+/// A description of a declared linker set.
+pub struct SetInfo {
+    name: &'static str,
+    module: &'static str,
+    section: &'static str,
+    mach_o_section: &'static str,
+    type_name: &'static str,
+    size: usize,
+    span: fn() -> Range<usize>,
+    entries: fn() -> Vec<String>,
+    chooser: Option<Chooser>,
+    stage: Option<&'static str>,
+    finalize: fn(),
+    #[cfg_attr(not(feature = "debug-report"), allow(dead_code))]
+    accessed: &'static AtomicBool,
+}
+
+impl SetInfo {
+    #[doc(hidden)]
+    #[allow(clippy::too_many_arguments)]
+    pub const fn new(
+        name: &'static str, module: &'static str, section: &'static str,
+        mach_o_section: &'static str, type_name: &'static str, size: usize,
+        span: fn() -> Range<usize>, entries: fn() -> Vec<String>,
+        chooser: Option<Chooser>, stage: Option<&'static str>, finalize: fn(),
+        accessed: &'static AtomicBool,
+    ) -> Self {
+        Self {
+            name,
+            module,
+            section,
+            mach_o_section,
+            type_name,
+            size,
+            span,
+            entries,
+            chooser,
+            stage,
+            finalize,
+            accessed,
+        }
+    }
+
+    /// Returns the name of the linker set.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the path of the module declaring the linker set.
+    pub fn module(&self) -> &'static str {
+        self.module
+    }
+
+    /// Returns the name of the section holding the linker set, which the
+    /// declarations of a set by several crates share.
+    pub fn section(&self) -> &'static str {
+        self.section
+    }
+
+    /// Returns the name of the section holding the linker set on Mach-O,
+    /// which limits it to 16 bytes.  A longer [section](Self::section) is
+    /// cut short and followed by a hash of the whole name.
+    pub fn mach_o_section(&self) -> &'static str {
+        self.mach_o_section
+    }
+
+    /// Returns the element type of the linker set, as written.
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Returns the size of an element of the linker set.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the number of elements in the linker set.
+    pub fn len(&self) -> usize {
+        self.span().len() / self.size
+    }
+
+    /// Returns the addresses of the memory holding the elements of the
+    /// linker set.
+    pub(crate) fn span(&self) -> Range<usize> {
+        (self.span)()
+    }
+
+    /// Returns true if the linker set contains zero elements.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a description of each element of the linker set, as
+    /// `LinkerSet::entry_names` gives it.
+    pub fn entry_names(&self) -> Vec<String> {
+        (self.entries)()
+    }
+
+    /// Returns a description of the entries that a keyed or singleton
+    /// set chooses, such as the entry of each key, or `None` for a set of
+    /// another kind.
+    pub fn choices(&self) -> Option<Vec<String>> {
+        self.chooser.as_ref().map(|c| (c.describe)())
+    }
+
+    /// Returns the boot stage to which the linker set belongs, if any.
+    pub fn stage(&self) -> Option<&'static str> {
+        self.stage
+    }
+
+    /// Build the structures derived lazily from the linker set, such as
+    /// its cached proxy and the entries chosen by a keyed or singleton
+    /// set, returning how the set could not choose its entries, or that
+    /// its boot stage has not been reached.
+    pub(crate) fn finalize(&self) -> Result<(), String> {
+        if let Some(stage) = self.stage.filter(|s| !stage::reached(s)) {
+            return Err(format!("belongs to boot stage {}", stage)
+                + ", which has not been reached");
+        }
+        (self.finalize)();
+        match &self.chooser {
+            Some(c) => (c.resolve)(),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns true if the linker set has been accessed with [set!] from
+    /// the crate that made this declaration.
+    #[cfg(feature = "debug-report")]
+    pub fn accessed(&self) -> bool {
+        self.accessed.load(Ordering::Relaxed)
+    }
+}
+
+/// How a keyed or singleton linker set chooses among its entries.
+#[doc(hidden)]
+pub struct Chooser {
+    describe: fn() -> Vec<String>,
+    resolve: fn() -> Result<(), String>,
+}
+
+impl Chooser {
+    #[doc(hidden)]
+    pub const fn new(
+        describe: fn() -> Vec<String>, resolve: fn() -> Result<(), String>,
+    ) -> Self {
+        Self { describe, resolve }
+    }
+}
+
+impl fmt::Debug for SetInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SetInfo")
+            .field("name", &self.name)
+            .field("module", &self.module)
+            .field("section", &self.section)
+            .field("type_name", &self.type_name)
+            .field("size", &self.size)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+#[doc(hidden)]
+pub mod declared {
+    use super::SetInfo;
+
+    crate::__set_bounds!(pub __START, __STOP, "linker_set_declared");
+
+    crate::__set_section!(
+        ["linker_set_declared"]
+        #[used]
+        static __EMPTY_SET_LINKER_SET_DECLARED: [SetInfo; 0] = [];
+    );
+}
+
+/// Returns the declarations of all linker sets in the program.
+///
+/// A set declared by several crates has a declaration from each of them.
+pub fn sets() -> LinkerSet<SetInfo> {
+    unsafe {
+        LinkerSet::new(
+            std::ptr::addr_of!(declared::__START).cast(),
+            std::ptr::addr_of!(declared::__STOP).cast(),
+        )
+    }
+}
+
+/// The effective configuration of a program: each linker set, with its
+/// entries and those it chooses.
+///
+/// Its display is meant to be logged when a program starts, answering
+/// which entries a build actually contains:
+///
+/// ```text
+/// linker set handlers (app::handlers, &'static dyn Handler): 2 entries
+///     app::http::INDEX
+///     app::http::STATUS
+/// linker set formats (app::formats, Format): 2 entries
+///     app::JSON
+///     plugins::JSON
+///     key "json": plugins::JSON (priority 10)
+/// ```
+///
+/// Entries are named by their statics with the `metadata` feature, and by
+/// their index and address without it.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Configuration {
+    /// The linker sets, in order of name.
+    pub sets: Vec<SetConfiguration>,
+}
+
+/// The configuration of one linker set in a [Configuration].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SetConfiguration {
+    /// The name of the linker set.
+    pub name: &'static str,
+    /// The path of the module declaring the linker set.
+    pub module: &'static str,
+    /// The element type of the linker set, as written.
+    pub type_name: &'static str,
+    /// A description of each entry.
+    pub entries: Vec<String>,
+    /// A description of the entries chosen by a keyed or singleton set.
+    pub choices: Vec<String>,
+}
+
+impl fmt::Display for Configuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for set in &self.sets {
+            let plural = if set.entries.len() == 1 { "y" } else { "ies" };
+            writeln!(
+                f,
+                "linker set {} ({}, {}): {} entr{}",
+                set.name,
+                set.module,
+                set.type_name,
+                set.entries.len(),
+                plural
+            )?;
+            for line in set.entries.iter().chain(&set.choices) {
+                writeln!(f, "    {}", line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the effective configuration of the program.
+///
+/// Reading the configuration does not count as accessing the sets, so
+/// it neither runs their `on_first_access` hooks nor hides them from
+/// the report of sets never accessed.  A set declared by several crates
+/// is listed once.
+pub fn configuration() -> Configuration {
+    let mut seen = std::collections::BTreeSet::new();
+    let mut sets = sets()
+        .iter()
+        .filter(|info| seen.insert(info.section()))
+        .map(|info| SetConfiguration {
+            name: info.name(),
+            module: info.module(),
+            type_name: info.type_name(),
+            entries: info.entry_names(),
+            choices: info.choices().unwrap_or_default(),
+        })
+        .collect::<Vec<_>>();
+    sets.sort_by_key(|set| (set.name, set.module));
+    Configuration { sets }
+}
+
+/// The option of the command line requesting a dump of the linker sets.
+pub const DUMP_OPTION: &str = "--linker-set-dump";
+
+/// The environment variable requesting a dump of the linker sets.
+pub const DUMP_VAR: &str = "LINKER_SET_DUMP";
+
+/// Returns the set requested to be dumped, given `Some(None)` for every
+/// set, by the first of the arguments of a program naming the option, or
+/// else by the value of the environment variable, if set.
+fn dump_request<I>(args: I, var: Option<String>) -> Option<Option<String>>
+where
+    I: IntoIterator<Item = String>,
+{
+    let requested = |value: &str| match value {
+        "" => None,
+        name => Some(name.to_string()),
+    };
+    args.into_iter()
+        .find_map(|arg| match arg.strip_prefix(DUMP_OPTION)? {
+            "" => Some(None),
+            rest => rest.strip_prefix('=').map(requested),
+        })
+        .or_else(|| var.as_deref().map(requested))
+}
+
+/// Returns the configuration of the linker sets of a name, or of every
+/// set given `None`.
+fn dump(name: Option<&str>) -> Configuration {
+    let mut config = configuration();
+    config
+        .sets
+        .retain(|set| name.map_or(true, |name| set.name == name));
+    config
+}
+
+/// Print the configuration of the linker sets and exit, if requested.
+///
+/// Called early in `main`, this gives any program a switch to describe
+/// its linker sets, as [configuration] does, without writing one.  The
+/// option `--linker-set-dump` anywhere among the arguments of the program
+/// requests every set, and `--linker-set-dump=NAME` only the sets named
+/// `NAME`; failing that, the environment variable `LINKER_SET_DUMP`
+/// requests the set it names, or every set if empty.  If neither is
+/// given, this returns.  The program exits with status 1 if no set has
+/// the requested name.
+pub fn handle_debug_args() {
+    let var = std::env::var(DUMP_VAR).ok();
+    let Some(name) = dump_request(std::env::args().skip(1), var) else {
+        return;
+    };
+    let config = dump(name.as_deref());
+    if config.sets.is_empty() {
+        eprintln!("no linker set is named {}", name.unwrap_or_default());
+        std::process::exit(1);
+    }
+    print!("{}", config);
+    std::process::exit(0);
+}
+
+/// A report of linker sets that may indicate mistakes.
+#[cfg(feature = "debug-report")]
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct Report {
+    /// Names of the linker sets with no entries.
+    pub empty: Vec<&'static str>,
+    /// Names of the linker sets never accessed with [set!].
+    pub unaccessed: Vec<&'static str>,
+}
+
+#[cfg(feature = "debug-report")]
+impl Report {
+    /// Returns true if the report has nothing to report.
+    pub fn is_clean(&self) -> bool {
+        self.empty.is_empty() && self.unaccessed.is_empty()
+    }
+}
+
+#[cfg(feature = "debug-report")]
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for name in &self.empty {
+            writeln!(f, "linker set {} is empty", name)?;
+        }
+        for name in &self.unaccessed {
+            writeln!(f, "linker set {} was never accessed", name)?;
+        }
+        Ok(())
+    }
+}
+
+/// Report linker sets that are empty or have not been accessed so far.
+#[cfg(feature = "debug-report")]
+pub fn report() -> Report {
+    use std::collections::BTreeMap;
+
+    let mut seen = BTreeMap::new();
+    for info in sets() {
+        let (empty, accessed) =
+            seen.entry(info.name()).or_insert((true, false));
+        *empty &= info.is_empty();
+        *accessed |= info.accessed();
+    }
+    let mut report = Report::default();
+    for (name, (empty, accessed)) in seen {
+        if empty {
+            report.empty.push(name);
+        }
+        if !accessed {
+            report.unaccessed.push(name);
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    set_declare!(reflected, u16);
+
+    #[set_entry(reflected)]
+    static ONE: u16 = 1u16;
+    #[set_entry(reflected)]
+    static TWO: u16 = 2u16;
+
+    #[test]
+    fn test_sets() {
+        let info = sets().iter().find(|s| s.name() == "reflected").unwrap();
+        assert_eq!(info.module(), "linker_set::reflect::test::reflected");
+        assert_eq!(info.type_name(), "u16");
+        assert_eq!(info.size(), 2);
+        assert_eq!(info.len(), 2);
+        assert_eq!(info.mach_o_section(), "set_reflected");
+
+        let info = sets().iter().find(|s| s.name() == "fatal_handler");
+        let info = info.unwrap();
+        assert_eq!(info.section(), "set_linker_set__fatal_handler");
+        assert_eq!(info.mach_o_section(), "set_link91ee32af");
+    }
+
+    /* named for the registry of declarations, which it must not join */
+    set_declare!(linker_set_declared, u16);
+
+    #[set_entry(linker_set_declared)]
+    static IMPOSTOR: u16 = 3u16;
+
+    #[test]
+    fn test_registry_names() {
+        let name = "linker_set_declared";
+        let info = sets().iter().find(|s| s.name() == name).unwrap();
+        assert_eq!(info.section(), "set_linker_set_declared");
+        assert_eq!(info.len(), 1);
+        assert!(sets().iter().all(|s| s.size() > 0));
+    }
+
+    set_declare!(chosen, u16, singleton);
+
+    #[set_entry(chosen)]
+    static ONLY: u16 = 3u16;
+
+    set_declare!(keys, u16, keyed);
+
+    #[set_entry(keys, key = "k", priority = 1)]
+    static LOW: u16 = 4u16;
+    #[set_entry(keys, key = "k", priority = 2)]
+    static HIGH: u16 = 5u16;
+
+    #[test]
+    fn test_configuration() {
+        let config = configuration();
+        let find = |name| config.sets.iter().find(|s| s.name == name).unwrap();
+        let reflected = find("reflected");
+        assert_eq!(reflected.entries.len(), 2);
+        assert!(reflected.choices.is_empty());
+        assert_eq!(find("chosen").choices.len(), 1);
+        let keys = find("keys");
+        assert_eq!(keys.entries.len(), 2);
+        assert_eq!(keys.choices.len(), 1);
+        assert!(keys.choices[0].starts_with("key \"k\": "));
+        assert!(keys.choices[0].ends_with(" (priority 2)"));
+        #[cfg(feature = "metadata")]
+        {
+            let only = "chosen linker_set::reflect::test::ONLY";
+            assert_eq!(find("chosen").choices, [only]);
+            let high = "key \"k\": linker_set::reflect::test::HIGH";
+            assert_eq!(keys.choices, [format!("{} (priority 2)", high)]);
+        }
+        let text = config.to_string();
+        assert!(
+            text.contains("linker set reflected (linker_set::reflect::test")
+        );
+        assert_eq!(chosen::get(), &ONLY);
+        assert_eq!(keys::resolved()["k"], &HIGH);
+        assert_ne!(LOW, HIGH);
+    }
+
+    #[test]
+    fn test_dump() {
+        let args = |args: &[&str]| {
+            args.iter().map(|a| a.to_string()).collect::<Vec<_>>()
+        };
+        let request = dump_request(args(&["-v", "--linker-set-dump"]), None);
+        assert_eq!(request, Some(None));
+        let request = dump_request(args(&["--linker-set-dump=keys"]), None);
+        assert_eq!(request, Some(Some("keys".to_string())));
+        let request = dump_request(args(&["--linker-set-dumps"]), None);
+        assert_eq!(request, None);
+        let request = dump_request(args(&[]), Some("chosen".to_string()));
+        assert_eq!(request, Some(Some("chosen".to_string())));
+        assert_eq!(dump_request(args(&[]), Some(String::new())), Some(None));
+
+        let config = dump(Some("keys"));
+        assert_eq!(config.sets.len(), 1);
+        assert_eq!(config.sets[0].entries.len(), 2);
+        assert!(dump(Some("missing")).sets.is_empty());
+        assert!(dump(None).sets.len() > 2);
+    }
+
+    #[cfg(feature = "debug-report")]
+    set_declare!(never_filled, u16);
+
+    #[cfg(feature = "debug-report")]
+    #[test]
+    fn test_report() {
+        let report = report();
+        assert!(report.empty.contains(&"never_filled"));
+        assert!(report.unaccessed.contains(&"never_filled"));
+        assert!(!report.empty.contains(&"reflected"));
+        let _ = set!(reflected);
+        assert!(!super::report().unaccessed.contains(&"reflected"));
+        assert!(!report.is_clean());
+    }
+}
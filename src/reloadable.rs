@@ -0,0 +1,281 @@
+//! Entries whose values can be reloaded while a program runs.
+
+use crate::{LinkerSet, LinkerSetIter};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/* held for reading while the entries of any set are read together, and
+ * for writing while any are written, so that a reader never sees some
+ * entries of a reload but not others */
+static RELOAD: RwLock<()> = RwLock::new(());
+
+/// An entry of a linker set holding a value, under a key, that can be
+/// replaced while the program runs.
+pub struct Reloadable<T> {
+    key: &'static str,
+    value: RwLock<T>,
+}
+
+impl<T> Reloadable<T> {
+    /// Create an entry holding a value under a key.
+    pub const fn new(key: &'static str, value: T) -> Self {
+        Self {
+            key,
+            value: RwLock::new(value),
+        }
+    }
+
+    /// Returns the key of the entry.
+    pub fn key(&self) -> &'static str {
+        self.key
+    }
+
+    /// Returns the value of the entry, locked for reading.
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.value.read().unwrap()
+    }
+}
+
+/// A linker set of [Reloadable] entries, such as the tunables of a
+/// long-running daemon, to be read again from its configuration without a
+/// restart.
+///
+/// ```
+/// use linker_set::*;
+///
+/// set_declare!(tunables, Reloadable<u32>);
+///
+/// #[set_entry(tunables)]
+/// static WORKERS: Reloadable<u32> = Reloadable::new("workers", 4);
+/// #[set_entry(tunables)]
+/// static BACKLOG: Reloadable<u32> = Reloadable::new("backlog", 128);
+///
+/// # fn main() {
+/// let set = ReloadableSet::new(set!(tunables));
+/// let unknown = set.reload_text("workers = 8\nthreads = 2\n").unwrap();
+/// assert_eq!(unknown, ["threads"]);
+/// assert_eq!(*WORKERS.read(), 8);
+/// let total = set.read_iter().map(|(_, v)| *v).sum::<u32>();
+/// assert_eq!(total, 136);
+/// # }
+/// ```
+///
+/// A single lock, shared by every such set, is held for reading by
+/// [read_iter](Self::read_iter) and for writing by
+/// [write_iter](Self::write_iter) and by reloading, so that the entries
+/// read together are all from before a reload or all from after it.  A
+/// thread must therefore not reload while it iterates over a set.
+pub struct ReloadableSet<T>
+where
+    T: 'static,
+{
+    set: LinkerSet<Reloadable<T>>,
+}
+
+/// A line of a configuration that could not be parsed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReloadError {
+    /// The number of the line, counting from one.
+    pub line: usize,
+    /// Why the line could not be parsed.
+    pub reason: String,
+}
+
+impl fmt::Display for ReloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+impl std::error::Error for ReloadError {}
+
+impl<T> ReloadableSet<T>
+where
+    T: 'static,
+{
+    /// Create a reloadable set from a linker set of reloadable entries.
+    pub fn new(set: LinkerSet<Reloadable<T>>) -> Self {
+        Self { set }
+    }
+
+    /// Returns an iterator over the keys of the entries, each with its
+    /// value locked for reading.
+    pub fn read_iter(&self) -> ReadIter<T> {
+        ReadIter {
+            _lock: RELOAD.read().unwrap(),
+            iter: self.set.iter(),
+        }
+    }
+
+    /// Returns an iterator over the keys of the entries, each with its
+    /// value locked for writing.
+    ///
+    /// # Panics
+    /// Panics if the linker sets have been sealed.
+    pub fn write_iter(&self) -> WriteIter<T> {
+        crate::seal::assert_unsealed("reload a linker set");
+        WriteIter {
+            _lock: RELOAD.write().unwrap(),
+            iter: self.set.iter(),
+        }
+    }
+
+    /// Returns the value of the first entry with a key, locked for
+    /// reading.
+    pub fn get(&self, key: &str) -> Option<RwLockReadGuard<'static, T>> {
+        self.set.iter().find(|e| e.key == key).map(|e| e.read())
+    }
+
+    /// Give new values to the entries with matching keys, all at once,
+    /// returning the keys that match no entry.
+    ///
+    /// # Panics
+    /// Panics if the linker sets have been sealed.
+    pub fn reload<I, K>(&self, values: I) -> Vec<K>
+    where
+        I: IntoIterator<Item = (K, T)>,
+        K: AsRef<str>,
+        T: Clone,
+    {
+        crate::seal::assert_unsealed("reload a linker set");
+        let _lock = RELOAD.write().unwrap();
+        let mut unknown = Vec::new();
+        for (key, value) in values {
+            let mut matching =
+                self.set.iter().filter(|e| e.key == key.as_ref()).peekable();
+            if matching.peek().is_none() {
+                unknown.push(key);
+                continue;
+            }
+            matching.for_each(|e| *e.value.write().unwrap() = value.clone());
+        }
+        unknown
+    }
+
+    /// Give new values, parsed from lines of `key = value`, to the entries
+    /// with matching keys, all at once, returning the keys that match no
+    /// entry.  Blank lines and comments starting with `#` are ignored.
+    ///
+    /// No entry is changed unless every line can be parsed.
+    ///
+    /// # Panics
+    /// Panics if the linker sets have been sealed.
+    pub fn reload_text(&self, text: &str) -> Result<Vec<String>, ReloadError>
+    where
+        T: Clone + FromStr,
+        T::Err: fmt::Display,
+    {
+        let mut values = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |reason: String| ReloadError {
+                line: i + 1,
+                reason,
+            };
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(error("expected KEY = VALUE".to_string()));
+            };
+            let value =
+                value.trim().parse().map_err(|e| error(format!("{}", e)))?;
+            values.push((key.trim().to_string(), value));
+        }
+        Ok(self.reload(values))
+    }
+
+    /// Returns the linker set of the entries.
+    pub fn linker_set(&self) -> &LinkerSet<Reloadable<T>> {
+        &self.set
+    }
+}
+
+/// An iterator over the entries of a [ReloadableSet], with their values
+/// locked for reading.
+pub struct ReadIter<T>
+where
+    T: 'static,
+{
+    _lock: RwLockReadGuard<'static, ()>,
+    iter: LinkerSetIter<Reloadable<T>>,
+}
+
+impl<T> Iterator for ReadIter<T>
+where
+    T: 'static,
+{
+    type Item = (&'static str, RwLockReadGuard<'static, T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|e| (e.key, e.read()))
+    }
+}
+
+/// An iterator over the entries of a [ReloadableSet], with their values
+/// locked for writing.
+pub struct WriteIter<T>
+where
+    T: 'static,
+{
+    _lock: RwLockWriteGuard<'static, ()>,
+    iter: LinkerSetIter<Reloadable<T>>,
+}
+
+impl<T> Iterator for WriteIter<T>
+where
+    T: 'static,
+{
+    type Item = (&'static str, RwLockWriteGuard<'static, T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|e| (e.key, e.value.write().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::*;
+
+    set_declare!(knobs, Reloadable<u64>);
+
+    #[set_entry(knobs)]
+    static TIMEOUT: Reloadable<u64> = Reloadable::new("timeout", 30);
+    #[set_entry(knobs)]
+    static RETRIES: Reloadable<u64> = Reloadable::new("retries", 3);
+
+    #[test]
+    fn test_reload() {
+        let set = ReloadableSet::new(set!(knobs));
+        assert_eq!(set.linker_set().len(), 2);
+        assert_eq!(*set.get("timeout").unwrap(), 30);
+        assert!(set.get("missing").is_none());
+
+        let unknown = set.reload([("retries", 5), ("verbose", 1)]);
+        assert_eq!(unknown, ["verbose"]);
+        assert_eq!(*RETRIES.read(), 5);
+        assert_eq!(*TIMEOUT.read(), 30);
+
+        let e = set
+            .reload_text("timeout = 60\nretries = many\n")
+            .unwrap_err();
+        assert_eq!(e.line, 2);
+        assert_eq!(e.to_string(), "line 2: invalid digit found in string");
+        assert_eq!(*TIMEOUT.read(), 30);
+        let e = set.reload_text("# comment\n\ntimeout\n").unwrap_err();
+        assert_eq!(e.to_string(), "line 3: expected KEY = VALUE");
+
+        let text = "timeout = 60 # seconds\n";
+        assert!(set.reload_text(text).unwrap().is_empty());
+        assert_eq!(*TIMEOUT.read(), 60);
+
+        for (_, mut value) in set.write_iter() {
+            *value *= 2;
+        }
+        let mut all = set.read_iter().map(|(k, v)| (k, *v)).collect::<Vec<_>>();
+        all.sort();
+        assert_eq!(all, [("retries", 10), ("timeout", 120)]);
+    }
+}
@@ -0,0 +1,370 @@
+//! Snapshots of linker sets, for replaying them in tests.
+//!
+//! `LinkerSet::export` encodes the elements of a set, such as in a
+//! diagnostic command of a production binary, and a [ReplaySet] decodes
+//! them again elsewhere, so that unit tests of the code consuming the set
+//! can run against the registry as it was captured, without linking the
+//! entries that made it up.
+//!
+//! Elements are encoded by the [Snapshot] trait, which this module
+//! implements for integers, floats, `bool`, strings, and `Option`s and
+//! `Vec`s of those, and which a struct implements field by field:
+//!
+//! ```
+//! use linker_set::*;
+//! use linker_set::replay::{self, ReplaySet, Snapshot};
+//!
+//! #[derive(Debug, PartialEq)]
+//! pub struct Route {
+//!     path: &'static str,
+//!     weight: u32,
+//! }
+//!
+//! impl Snapshot for Route {
+//!     fn write(&self, out: &mut Vec<u8>) {
+//!         self.path.write(out);
+//!         self.weight.write(out);
+//!     }
+//!
+//!     fn read(input: &mut &[u8]) -> Result<Self, replay::Error> {
+//!         Ok(Route {
+//!             path: Snapshot::read(input)?,
+//!             weight: Snapshot::read(input)?,
+//!         })
+//!     }
+//! }
+//!
+//! set_declare!(routes, Route);
+//!
+//! #[set_entry(routes)]
+//! static INDEX: Route = Route { path: "/", weight: 1 };
+//!
+//! # fn main() {
+//! let bytes = set!(routes).export();
+//! let replay = ReplaySet::<Route>::import(&bytes).unwrap();
+//! assert_eq!(replay.iter().collect::<Vec<_>>(), [&INDEX]);
+//! # }
+//! ```
+//!
+//! A `&'static str` decoded from a snapshot is leaked, which suits tests
+//! but not a long-running program that imports snapshots repeatedly.
+
+use crate::LinkerSet;
+use std::fmt;
+
+/// The first bytes of a snapshot.
+const MAGIC: &[u8; 8] = b"LNKSET\x00\x01";
+
+/// An error decoding a snapshot.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The data does not begin as a snapshot does.
+    NotSnapshot,
+    /// The data ends in the middle of an element.
+    Truncated,
+    /// The data holds a value that is not valid for its type.
+    Invalid(&'static str),
+    /// The data continues after the last element.
+    TrailingData,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotSnapshot => write!(f, "not a linker set snapshot"),
+            Self::Truncated => write!(f, "snapshot is truncated"),
+            Self::Invalid(what) => write!(f, "invalid {} in snapshot", what),
+            Self::TrailingData => write!(f, "trailing data after snapshot"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A type whose values can be written to and read from a snapshot.
+pub trait Snapshot: Sized {
+    /// Append the encoding of the value to a buffer.
+    fn write(&self, out: &mut Vec<u8>);
+
+    /// Decode a value from the front of the input, advancing past it.
+    fn read(input: &mut &[u8]) -> Result<Self, Error>;
+}
+
+fn take<'a>(input: &mut &'a [u8], n: usize) -> Result<&'a [u8], Error> {
+    if input.len() < n {
+        return Err(Error::Truncated);
+    }
+    let (head, tail) = input.split_at(n);
+    *input = tail;
+    Ok(head)
+}
+
+macro_rules! snapshot_number {
+    ($($type:ty),*) => {
+        $(
+            impl Snapshot for $type {
+                fn write(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn read(input: &mut &[u8]) -> Result<Self, Error> {
+                    let bytes = take(input, std::mem::size_of::<$type>())?;
+                    Ok(<$type>::from_le_bytes(bytes.try_into().unwrap()))
+                }
+            }
+        )*
+    };
+}
+
+snapshot_number!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+impl Snapshot for usize {
+    fn write(&self, out: &mut Vec<u8>) {
+        (*self as u64).write(out)
+    }
+
+    fn read(input: &mut &[u8]) -> Result<Self, Error> {
+        usize::try_from(u64::read(input)?).map_err(|_| Error::Invalid("usize"))
+    }
+}
+
+impl Snapshot for bool {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8)
+    }
+
+    fn read(input: &mut &[u8]) -> Result<Self, Error> {
+        match u8::read(input)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error::Invalid("bool")),
+        }
+    }
+}
+
+fn write_str(s: &str, out: &mut Vec<u8>) {
+    s.len().write(out);
+    out.extend_from_slice(s.as_bytes());
+}
+
+impl Snapshot for String {
+    fn write(&self, out: &mut Vec<u8>) {
+        write_str(self, out)
+    }
+
+    fn read(input: &mut &[u8]) -> Result<Self, Error> {
+        let len = usize::read(input)?;
+        let bytes = take(input, len)?.to_vec();
+        String::from_utf8(bytes).map_err(|_| Error::Invalid("string"))
+    }
+}
+
+impl Snapshot for &'static str {
+    fn write(&self, out: &mut Vec<u8>) {
+        write_str(self, out)
+    }
+
+    fn read(input: &mut &[u8]) -> Result<Self, Error> {
+        Ok(String::read(input)?.leak())
+    }
+}
+
+impl<T> Snapshot for Option<T>
+where
+    T: Snapshot,
+{
+    fn write(&self, out: &mut Vec<u8>) {
+        self.is_some().write(out);
+        if let Some(value) = self {
+            value.write(out);
+        }
+    }
+
+    fn read(input: &mut &[u8]) -> Result<Self, Error> {
+        match bool::read(input)? {
+            true => Ok(Some(T::read(input)?)),
+            false => Ok(None),
+        }
+    }
+}
+
+impl<T> Snapshot for Vec<T>
+where
+    T: Snapshot,
+{
+    fn write(&self, out: &mut Vec<u8>) {
+        self.len().write(out);
+        self.iter().for_each(|value| value.write(out));
+    }
+
+    fn read(input: &mut &[u8]) -> Result<Self, Error> {
+        let len = usize::read(input)?;
+        (0..len).map(|_| T::read(input)).collect()
+    }
+}
+
+impl<T> LinkerSet<T>
+where
+    T: Snapshot + 'static,
+{
+    /// Returns a snapshot of the elements of the linker set, which a
+    /// [ReplaySet] can import.
+    pub fn export(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        self.len().write(&mut out);
+        self.iter().for_each(|e| e.write(&mut out));
+        out
+    }
+}
+
+/// The elements of a linker set, imported from a snapshot.
+///
+/// A replayed set offers the same means of iteration and indexing as a
+/// `LinkerSet`.  `leak` yields its elements as a slice for
+/// `LinkerSet::from_slice`, or to install as the model of a set with the
+/// `model` feature, so that code calling [set!](crate::set) sees them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplaySet<T> {
+    elements: Vec<T>,
+}
+
+impl<T> ReplaySet<T>
+where
+    T: Snapshot,
+{
+    /// Decode the elements of a snapshot made by `LinkerSet::export`.
+    pub fn import(mut bytes: &[u8]) -> Result<Self, Error> {
+        if take(&mut bytes, MAGIC.len()) != Ok(MAGIC) {
+            return Err(Error::NotSnapshot);
+        }
+        let elements = Vec::read(&mut bytes)?;
+        if !bytes.is_empty() {
+            return Err(Error::TrailingData);
+        }
+        Ok(Self { elements })
+    }
+}
+
+impl<T> ReplaySet<T> {
+    /// Returns an iterator over the elements.
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.elements.iter()
+    }
+
+    /// Returns the number of elements.
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    /// Returns true if there are no elements.
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// Returns the elements as a slice that lives for the rest of the
+    /// program.
+    pub fn leak(self) -> &'static [T] {
+        self.elements.leak()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a ReplaySet<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T, I> std::ops::Index<I> for ReplaySet<T>
+where
+    I: std::slice::SliceIndex<[T], Output = T>,
+{
+    type Output = T;
+
+    fn index(&self, i: I) -> &Self::Output {
+        self.elements.index(i)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::*;
+
+    #[derive(Debug, PartialEq)]
+    pub struct Limit {
+        name: &'static str,
+        max: Option<u64>,
+        tags: Vec<String>,
+    }
+
+    impl Snapshot for Limit {
+        fn write(&self, out: &mut Vec<u8>) {
+            self.name.write(out);
+            self.max.write(out);
+            self.tags.write(out);
+        }
+
+        fn read(input: &mut &[u8]) -> Result<Self, Error> {
+            Ok(Self {
+                name: Snapshot::read(input)?,
+                max: Snapshot::read(input)?,
+                tags: Snapshot::read(input)?,
+            })
+        }
+    }
+
+    set_declare!(replayed, i32);
+
+    #[set_entry(replayed)]
+    static MINUS: i32 = -7;
+    #[set_entry(replayed)]
+    static PLUS: i32 = 7;
+
+    #[test]
+    fn test_replay() {
+        let bytes = set!(replayed).export();
+        let replay = ReplaySet::<i32>::import(&bytes).unwrap();
+        assert_eq!(replay.len(), 2);
+        let expect = set!(replayed).iter().collect::<Vec<_>>();
+        assert_eq!(replay.iter().collect::<Vec<_>>(), expect);
+        assert_eq!(replay[1], *expect[1]);
+        let model = LinkerSet::from_slice(replay.leak());
+        assert_eq!(model.iter().sum::<i32>(), MINUS + PLUS);
+
+        assert_eq!(
+            ReplaySet::<i32>::import(b"LNKSET"),
+            Err(Error::NotSnapshot)
+        );
+        let short = &bytes[..bytes.len() - 1];
+        assert_eq!(ReplaySet::<i32>::import(short), Err(Error::Truncated));
+        let mut long = bytes.clone();
+        long.push(0);
+        assert_eq!(ReplaySet::<i32>::import(&long), Err(Error::TrailingData));
+    }
+
+    #[test]
+    fn test_snapshot() {
+        static LIMITS: [Limit; 0] = [];
+        assert!(ReplaySet::<Limit>::import(
+            &LinkerSet::from_slice(&LIMITS).export()
+        )
+        .unwrap()
+        .is_empty());
+
+        let limit = Limit {
+            name: "open files",
+            max: Some(1024),
+            tags: vec!["os".to_string(), "posix".to_string()],
+        };
+        let mut bytes = Vec::new();
+        limit.write(&mut bytes);
+        assert_eq!(Limit::read(&mut bytes.as_slice()), Ok(limit));
+        assert_eq!(
+            bool::read(&mut [2].as_slice()),
+            Err(Error::Invalid("bool"))
+        );
+    }
+}
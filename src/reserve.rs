@@ -0,0 +1,129 @@
+//! Entries reserved in a linker set, holding default values to be patched.
+
+use crate::BootCell;
+use std::sync::atomic::*;
+
+/// A type with a default value that can initialize a static, as the
+/// entries reserved with [set_reserve](crate::set_reserve) are.
+///
+/// `Default::default` cannot be called where a static is initialized, so
+/// a type whose entries are reserved gives its default value as a const:
+///
+/// ```
+/// use linker_set::*;
+///
+/// #[derive(Clone, Copy, Default, Eq, PartialEq)]
+/// pub struct Slot {
+///     irq: u32,
+///     handler: Option<fn()>,
+/// }
+///
+/// impl ConstDefault for Slot {
+///     const DEFAULT: Self = Self {
+///         irq: 0,
+///         handler: None,
+///     };
+/// }
+///
+/// set_declare!(slots, BootCell<Slot>);
+///
+/// #[set_entry(slots)]
+/// static TIMER: BootCell<Slot> = BootCell::new(Slot {
+///     irq: 3,
+///     handler: None,
+/// });
+///
+/// set_reserve!(slots, 7);
+///
+/// # fn main() {
+/// let slots = unsafe { set!(slots).as_mut_slice_once() }.unwrap();
+/// assert_eq!(slots.len(), 8);
+/// let free = slots.iter_mut().find(|s| **s == Slot::default()).unwrap();
+/// free.irq = 9;
+/// # }
+/// ```
+///
+/// A type that implements both should give the same value for each.
+pub trait ConstDefault: Sized {
+    /// The default value of the type.
+    const DEFAULT: Self;
+}
+
+macro_rules! const_default {
+    ($value:expr => $($type:ty),*) => {
+        $(
+            impl ConstDefault for $type {
+                const DEFAULT: Self = $value;
+            }
+        )*
+    };
+}
+
+const_default!(0 => u8, u16, u32, u64, u128, usize);
+const_default!(0 => i8, i16, i32, i64, i128, isize);
+const_default!(0.0 => f32, f64);
+const_default!(false => bool);
+const_default!('\0' => char);
+const_default!("" => &'static str);
+const_default!(AtomicBool::new(false) => AtomicBool);
+const_default!(AtomicU8::new(0) => AtomicU8);
+const_default!(AtomicU16::new(0) => AtomicU16);
+const_default!(AtomicU32::new(0) => AtomicU32);
+const_default!(AtomicU64::new(0) => AtomicU64);
+const_default!(AtomicUsize::new(0) => AtomicUsize);
+
+impl<T> ConstDefault for Option<T> {
+    const DEFAULT: Self = None;
+}
+
+impl<T> ConstDefault for &'static [T] {
+    const DEFAULT: Self = &[];
+}
+
+impl<T, const N: usize> ConstDefault for [T; N]
+where
+    T: ConstDefault,
+{
+    const DEFAULT: Self = [const { T::DEFAULT }; N];
+}
+
+impl<T> ConstDefault for BootCell<T>
+where
+    T: ConstDefault,
+{
+    const DEFAULT: Self = BootCell::new(T::DEFAULT);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+
+    set_declare!(vectors, BootCell<[u16; 2]>);
+
+    #[set_entry(vectors)]
+    static RESET: BootCell<[u16; 2]> = BootCell::new([0x100, 1]);
+
+    set_reserve!(vectors, 3);
+    set_reserve!(vectors, 1);
+
+    set_declare!(spare, Option<&'static str>);
+    set_reserve!(spare, 2,);
+
+    #[test]
+    fn test_reserve() {
+        let vectors = unsafe { set!(vectors).as_mut_slice_once() }.unwrap();
+        assert_eq!(vectors.len(), 5);
+        let reserved = vectors.iter().filter(|v| **v == [0; 2]).count();
+        assert_eq!(reserved, 4);
+        for (i, v) in vectors.iter_mut().filter(|v| **v == [0; 2]).enumerate() {
+            *v = [0x200 + i as u16, 2];
+        }
+        assert_eq!(RESET.get(), &[0x100, 1]);
+        let mut all =
+            set!(vectors).iter().map(|v| v.get()[0]).collect::<Vec<_>>();
+        all.sort();
+        assert_eq!(all, [0x100, 0x200, 0x201, 0x202, 0x203]);
+
+        assert_eq!(set!(spare).iter().collect::<Vec<_>>(), [&None; 2]);
+    }
+}
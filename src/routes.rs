@@ -0,0 +1,263 @@
+//! Route metadata collected from handlers and exported as OpenAPI.
+//!
+//! Handlers describe themselves by registering a [Route] into the [route]
+//! linker set.  The metadata is plain data, so [openapi] can produce an
+//! OpenAPI 3 document for the whole program at runtime.
+//!
+//! ```
+//! use linker_set::*;
+//! use linker_set::routes::{self, Method, Response, Route};
+//!
+//! #[set_entry(linker_set::routes::route)]
+//! static GET_USER: Route = Route {
+//!     method: Method::Get,
+//!     path: "/users/{id}",
+//!     operation: "getUser",
+//!     summary: "Fetch a user",
+//!     tags: &["users"],
+//!     request: None,
+//!     responses: &[Response {
+//!         status: 200,
+//!         description: "The user",
+//!         schema: Some("User"),
+//!     }],
+//! };
+//!
+//! # fn main() {
+//! let doc = routes::openapi("example", "1.0");
+//! assert!(doc.contains(r#""/users/{id}":{"get":"#));
+//! # }
+//! ```
+
+use crate::*;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+set_declare!(route, Route, namespaced);
+
+/// An HTTP method.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd, Hash)]
+pub enum Method {
+    /// GET
+    Get,
+    /// PUT
+    Put,
+    /// POST
+    Post,
+    /// DELETE
+    Delete,
+    /// OPTIONS
+    Options,
+    /// HEAD
+    Head,
+    /// PATCH
+    Patch,
+}
+
+impl Method {
+    /// Returns the name of the method as used in OpenAPI path items.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Get => "get",
+            Self::Put => "put",
+            Self::Post => "post",
+            Self::Delete => "delete",
+            Self::Options => "options",
+            Self::Head => "head",
+            Self::Patch => "patch",
+        }
+    }
+}
+
+/// A possible response from a route.
+#[derive(Debug)]
+pub struct Response {
+    /// The HTTP status code.
+    pub status: u16,
+    /// A description of the response.
+    pub description: &'static str,
+    /// The name of the schema of the response body, if any.
+    pub schema: Option<&'static str>,
+}
+
+/// Metadata describing a route served by a handler.
+#[derive(Debug)]
+pub struct Route {
+    /// The HTTP method.
+    pub method: Method,
+    /// The path template, e.g. `/users/{id}`.
+    pub path: &'static str,
+    /// A unique name for the operation.
+    pub operation: &'static str,
+    /// A short summary of what the route does.
+    pub summary: &'static str,
+    /// Tags used to group routes.
+    pub tags: &'static [&'static str],
+    /// The name of the schema of the request body, if any.
+    pub request: Option<&'static str>,
+    /// The possible responses.
+    pub responses: &'static [Response],
+}
+
+fn schema_ref(out: &mut String, schema: &str) {
+    out.push_str(r#"{"content":{"application/json":{"schema":{"$ref":"#);
+    json::string(out, &format!("#/components/schemas/{}", schema));
+    out.push_str("}}}}");
+}
+
+fn operation(out: &mut String, route: &Route) {
+    out.push_str(r#"{"operationId":"#);
+    json::string(out, route.operation);
+    out.push_str(r#","summary":"#);
+    json::string(out, route.summary);
+    if !route.tags.is_empty() {
+        out.push_str(r#","tags":["#);
+        for (i, tag) in route.tags.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            json::string(out, tag);
+        }
+        out.push(']');
+    }
+    if let Some(schema) = route.request {
+        out.push_str(r#","requestBody":"#);
+        schema_ref(out, schema);
+    }
+    out.push_str(r#","responses":{"#);
+    for (i, resp) in route.responses.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write!(out, r#""{}":{{"description":"#, resp.status).unwrap();
+        json::string(out, resp.description);
+        if let Some(schema) = resp.schema {
+            out.push(',');
+            let mut content = String::new();
+            schema_ref(&mut content, schema);
+            // splice the content member into this response object
+            out.push_str(&content[1..content.len() - 1]);
+        }
+        out.push('}');
+    }
+    out.push_str("}}");
+}
+
+/// Produce an OpenAPI document describing the given routes.
+pub fn document<'a, I>(title: &str, version: &str, routes: I) -> String
+where
+    I: IntoIterator<Item = &'a Route>,
+{
+    let mut routes = routes.into_iter().collect::<Vec<_>>();
+    routes.sort_by_key(|r| (r.path, r.method));
+
+    let mut out = String::from(r#"{"openapi":"3.0.3","info":{"title":"#);
+    json::string(&mut out, title);
+    out.push_str(r#","version":"#);
+    json::string(&mut out, version);
+    out.push_str(r#"},"paths":{"#);
+    let mut schemas = BTreeSet::new();
+    let mut last = None;
+    for route in routes {
+        if last != Some(route.path) {
+            if last.is_some() {
+                out.push_str("},");
+            }
+            json::string(&mut out, route.path);
+            out.push_str(":{");
+            last = Some(route.path);
+        } else {
+            out.push(',');
+        }
+        json::string(&mut out, route.method.as_str());
+        out.push(':');
+        operation(&mut out, route);
+        schemas.extend(route.request);
+        schemas.extend(route.responses.iter().filter_map(|r| r.schema));
+    }
+    if last.is_some() {
+        out.push('}');
+    }
+    out.push_str(r#"},"components":{"schemas":{"#);
+    for (i, schema) in schemas.into_iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        json::string(&mut out, schema);
+        out.push_str(":{}");
+    }
+    out.push_str("}}}");
+    out
+}
+
+/// Produce an OpenAPI document describing all routes in the [route] set.
+pub fn openapi(title: &str, version: &str) -> String {
+    document(title, version, set!(route))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[set_entry(crate::routes::route)]
+    static CREATE: Route = Route {
+        method: Method::Post,
+        path: "/items",
+        operation: "createItem",
+        summary: "Create an item",
+        tags: &["items"],
+        request: Some("NewItem"),
+        responses: &[
+            Response {
+                status: 201,
+                description: "Created",
+                schema: Some("Item"),
+            },
+            Response {
+                status: 400,
+                description: "Bad \"request\"",
+                schema: None,
+            },
+        ],
+    };
+
+    #[set_entry(crate::routes::route)]
+    static LIST: Route = Route {
+        method: Method::Get,
+        path: "/items",
+        operation: "listItems",
+        summary: "List items",
+        tags: &[],
+        request: None,
+        responses: &[],
+    };
+
+    #[test]
+    fn test_openapi() {
+        let doc = openapi("test", "0.1");
+        let expect = concat!(
+            r#"{"openapi":"3.0.3","info":{"title":"test","version":"0.1"},"#,
+            r#""paths":{"/items":{"get":{"operationId":"listItems","#,
+            r#""summary":"List items","responses":{}},"#,
+            r#""post":{"operationId":"createItem","summary":"Create an item","#,
+            r#""tags":["items"],"requestBody":{"content":{"application/json":"#,
+            r##"{"schema":{"$ref":"#/components/schemas/NewItem"}}}},"##,
+            r#""responses":{"201":{"description":"Created","content":"#,
+            r#"{"application/json":{"schema":"#,
+            r##"{"$ref":"#/components/schemas/Item"}}}},"##,
+            r#""400":{"description":"Bad \"request\""}}}}},"#,
+            r#""components":{"schemas":{"Item":{},"NewItem":{}}}}"#,
+        );
+        assert_eq!(doc, expect);
+    }
+
+    #[test]
+    fn test_empty() {
+        let doc = document("x", "1", []);
+        let expect = concat!(
+            r#"{"openapi":"3.0.3","info":{"title":"x","version":"1"},"#,
+            r#""paths":{},"components":{"schemas":{}}}"#,
+        );
+        assert_eq!(doc, expect);
+    }
+}
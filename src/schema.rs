@@ -0,0 +1,97 @@
+//! A description of each declared set embedded in the binary.
+//!
+//! The [set_declare!](crate::set_declare) macro places a line of JSON
+//! describing the set, including the path of the module that it declares,
+//! into the [SECTION] section, so that tools can decode the contents of a
+//! set from a binary or core dump without its source:
+//!
+//! ```text
+//! {"name":"stuff","crate":"app","module":"app::config::stuff","section":"set_stuff","type":"u64","size":8,"align":8}
+//! ```
+//!
+//! The `section` key names the section holding the entries of the set,
+//! which is named also for the crate of a namespaced or sealed set.  The
+//! `test_section` key, only for a set declared with `test_entries`, names
+//! the section holding its [test entries](crate::test_entry).
+//!
+//! [inspect::schema](crate::inspect::schema) reads these lines back.  A
+//! build with the default `schema` feature disabled embeds none.
+
+use crate::json::Writer;
+
+/// Name of the section holding the descriptions of the declared sets,
+/// which lacks the prefix of the sections of sets, so that no set is
+/// named for it.
+pub const SECTION: &str = "linker_set_schema";
+
+/// The sections of a set: that of its entries, and that of its test
+/// entries, if declared.
+#[doc(hidden)]
+pub type Sections<'a> = (&'a str, Option<&'a str>);
+
+const fn write<const N: usize>(
+    name: &str, module: &str, sections: Sections, ty: &str, size: usize,
+    align: usize,
+) -> Writer<N> {
+    let w = Writer::new().raw("{\"name\":").string(name, 0);
+    let w = w.raw(",\"crate\":").string(module, b':');
+    let w = w.raw(",\"module\":").string(module, 0);
+    let w = w.raw(",\"section\":").string(sections.0, 0);
+    let w = match sections.1 {
+        Some(test) => w.raw(",\"test_section\":").string(test, 0),
+        None => w,
+    };
+    let w = w.raw(",\"type\":").string(ty, 0);
+    let w = w.raw(",\"size\":").number(size);
+    let w = w.raw(",\"align\":").number(align);
+    w.raw("}\n")
+}
+
+/// Returns the length of the description of a set.
+#[doc(hidden)]
+pub const fn len(
+    name: &str, module: &str, sections: Sections, ty: &str, size: usize,
+    align: usize,
+) -> usize {
+    write::<0>(name, module, sections, ty, size, align).len()
+}
+
+/// Returns the description of a set.
+#[doc(hidden)]
+pub const fn encode<const N: usize>(
+    name: &str, module: &str, sections: Sections, ty: &str, size: usize,
+    align: usize,
+) -> [u8; N] {
+    write::<N>(name, module, sections, ty, size, align).into_inner()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        const SECTIONS: Sections = ("set_x", None);
+        const TY: &str = "&'static \"\n";
+        const LEN: usize = len("x", "a::b", SECTIONS, TY, 0, 16);
+        const ENCODED: [u8; LEN] = encode("x", "a::b", SECTIONS, TY, 0, 16);
+        let expect = concat!(
+            r#"{"name":"x","crate":"a","module":"a::b","section":"set_x","#,
+            r#""type":"&'static \"\u000a","size":0,"align":16}"#,
+            "\n"
+        );
+        assert_eq!(std::str::from_utf8(&ENCODED).unwrap(), expect);
+
+        const TESTS: Sections = ("set_a__y", Some("set_a___test_y"));
+        const TEST_LEN: usize = len("y", "a", TESTS, "u8", 1, 1);
+        const TEST_ENCODED: [u8; TEST_LEN] =
+            encode("y", "a", TESTS, "u8", 1, 1);
+        let expect = concat!(
+            r#"{"name":"y","crate":"a","module":"a","section":"set_a__y","#,
+            r#""test_section":"set_a___test_y","type":"u8","size":1,"#,
+            r#""align":1}"#,
+            "\n"
+        );
+        assert_eq!(std::str::from_utf8(&TEST_ENCODED).unwrap(), expect);
+    }
+}
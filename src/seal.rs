@@ -0,0 +1,293 @@
+//! The boundary between composing a program from its linker sets and
+//! running it.
+//!
+//! A program composes itself from its linker sets at startup, resolving
+//! the entries of keyed sets, patching sets of `BootCell`s, merging in the
+//! entries of plugins, and so on.  [seal_all] marks the end of that phase:
+//! it builds every structure derived lazily from a set, such as the entries
+//! chosen by keyed and singleton sets and the index of each keyed set, and
+//! runs every `on_first_access` hook, so that none of this happens later
+//! on a hot path, and from then on, anything that would still change a
+//! set fails loudly.
+//!
+//! ```
+//! use linker_set::*;
+//!
+//! set_declare!(backend, &'static str, singleton);
+//!
+//! #[set_entry(backend)]
+//! static MEMORY: &str = "memory";
+//!
+//! # fn main() {
+//! seal::seal_all().unwrap();
+//! assert!(seal::is_sealed());
+//! assert_eq!(*backend::get(), "memory");
+//! # }
+//! ```
+//!
+//! [seal_all_read_only] also makes the memory of the sets read-only, so
+//! that a stray write to an entry faults rather than corrupting it.  Code
+//! that changes sets after startup, such as a loader merging the entries
+//! of plugins into a registry, should call [assert_unsealed] first.
+//!
+//! Once sealed, each of the following panics:
+//!
+//! - patching a set of `BootCell`s with `as_mut_slice_once`,
+//! - adding an entry to an `ExtendedSet`,
+//! - activating plugins with `plugins::activate`, and
+//! - reloading a `ReloadableSet`, with `reload`, `reload_text`, or
+//!   `write_iter`.
+//!
+//! Sealing does not cover interior mutability of the elements themselves,
+//! such as atomic counters or `Tracked` entries, which may still change.
+
+use crate::reflect;
+use std::collections::BTreeMap;
+use std::ffi::{c_int, c_void};
+use std::fmt;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static SEALED: AtomicBool = AtomicBool::new(false);
+
+/// Sets that could not be sealed.
+#[derive(Debug)]
+pub struct SealError {
+    /// The name of each set that could not be sealed, and why, such as
+    /// that a singleton set does not have exactly one entry.
+    pub failures: Vec<(&'static str, String)>,
+}
+
+impl fmt::Display for SealError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} linker sets could not be sealed", self.failures.len())?;
+        for (name, failure) in &self.failures {
+            write!(f, "; {}: {}", name, failure)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SealError {}
+
+extern "C" {
+    fn getpagesize() -> c_int;
+    fn mprotect(addr: *mut c_void, len: usize, prot: c_int) -> c_int;
+}
+
+const PROT_READ: c_int = 1;
+
+/// Returns the pages wholly within a range of addresses, which hold
+/// nothing outside of it.
+fn whole_pages(span: Range<usize>, page: usize) -> Range<usize> {
+    let start = span.start.next_multiple_of(page);
+    let end = span.end / page * page;
+    start..end.max(start)
+}
+
+/// Returns the sets whose sections are distinct but whose shortened names
+/// on Mach-O are the same, given the name, section, and Mach-O section of
+/// each set, and the set whose section each shares.
+///
+/// The linker would merge the entries of such sets into one section, so
+/// that each set would yield the entries of the other.
+#[cfg_attr(not(target_vendor = "apple"), allow(dead_code))]
+fn mach_o_collisions(
+    sets: impl IntoIterator<Item = (&'static str, &'static str, &'static str)>,
+) -> Vec<(&'static str, String)> {
+    let mut owners = BTreeMap::new();
+    let mut failures = Vec::new();
+    for (name, section, mach_o) in sets {
+        let (owner, owner_section) =
+            *owners.entry(mach_o).or_insert((name, section));
+        if owner_section != section {
+            let why = format!(
+                "shares the Mach-O section {} with linker set {}",
+                mach_o, owner
+            );
+            failures.push((name, why));
+        }
+    }
+    failures
+}
+
+fn seal(protect: bool) -> Result<(), SealError> {
+    let mut failures = Vec::new();
+    #[cfg(target_vendor = "apple")]
+    failures.extend(mach_o_collisions(
+        reflect::sets()
+            .iter()
+            .map(|i| (i.name(), i.section(), i.mach_o_section())),
+    ));
+    for info in reflect::sets() {
+        if let Err(e) = info.finalize() {
+            failures.push((info.name(), e));
+        }
+    }
+    SEALED.store(true, Ordering::Release);
+    if protect {
+        let page = unsafe { getpagesize() } as usize;
+        for info in reflect::sets() {
+            let pages = whole_pages(info.span(), page);
+            if pages.is_empty() {
+                continue;
+            }
+            let addr = pages.start as *mut c_void;
+            if unsafe { mprotect(addr, pages.len(), PROT_READ) } != 0 {
+                let e = std::io::Error::last_os_error();
+                failures.push((info.name(), e.to_string()));
+            }
+        }
+    }
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(SealError { failures })
+    }
+}
+
+/// Seal the linker sets of the program.
+///
+/// Every set is finalized and the program is marked as sealed, even if
+/// some sets fail, such as a keyed set with two entries of a key tying
+/// for highest priority, which are returned as an error.  On Mach-O, so
+/// are two sets whose long names were shortened to the same section.
+/// Finalizing a set accesses it, so it runs its `on_first_access` hook.
+pub fn seal_all() -> Result<(), SealError> {
+    seal(false)
+}
+
+/// Seal the linker sets of the program, as [seal_all] does, and make the
+/// memory holding their elements read-only.
+///
+/// Only the pages that hold nothing but the elements of a set are made
+/// read-only, so the elements sharing a page with other data, at either
+/// end of a small set, stay writable.
+///
+/// # Safety
+/// No element of any set may be written after this call, which is the
+/// case unless the element type of a set has interior mutability, such as
+/// atomic counters, `Tracked` entries, or the links of intrusive lists.
+pub unsafe fn seal_all_read_only() -> Result<(), SealError> {
+    seal(true)
+}
+
+/// Returns true if the linker sets have been sealed.
+pub fn is_sealed() -> bool {
+    SEALED.load(Ordering::Acquire)
+}
+
+/// Check that the linker sets have not been sealed, before doing what
+/// would change a set.
+///
+/// # Panics
+/// Panics, naming what was to be done, if the sets have been sealed.
+pub fn assert_unsealed(what: &str) {
+    assert!(!is_sealed(), "cannot {} after linker sets are sealed", what);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::*;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::{Command, ExitStatus};
+    use std::sync::atomic::AtomicU64;
+
+    /* larger than a page of any common size, so that sealing the set
+     * makes at least one page read-only */
+    #[repr(C, align(65536))]
+    pub struct Page {
+        words: [AtomicU64; 8192],
+    }
+
+    set_declare!(pages, Page);
+
+    set_declare!(knobs, reloadable::Reloadable<u64>);
+
+    #[set_entry(pages)]
+    static PAGE: Page = Page {
+        words: [const { AtomicU64::new(0) }; 8192],
+    };
+
+    /// Run an ignored test in a process of its own, since sealing the
+    /// linker sets cannot be undone.
+    fn alone(name: &str) -> ExitStatus {
+        let exe = std::env::current_exe().unwrap();
+        let args = [name, "--exact", "--ignored", "--test-threads=1"];
+        Command::new(exe).args(args).output().unwrap().status
+    }
+
+    #[test]
+    fn test_seal() {
+        assert!(alone("seal::test::sealed").success());
+        assert!(alone("seal::test::protected").signal().is_some());
+        assert!(!is_sealed());
+    }
+
+    #[test]
+    #[ignore = "seals this process; run by test_seal"]
+    fn sealed() {
+        let e = seal_all().unwrap_err();
+        let (_, why) = e.failures.iter().find(|(n, _)| *n == "tied").unwrap();
+        assert_eq!(why, "has entries of equal priority for k");
+        assert!(is_sealed());
+        assert!(std::panic::catch_unwind(|| assert_unsealed("merge")).is_err());
+        let activate = || plugins::activate(|_| false);
+        assert!(std::panic::catch_unwind(activate).is_err());
+        let knobs = reloadable::ReloadableSet::new(set!(knobs));
+        assert!(std::panic::catch_unwind(|| knobs.reload([("k", 1)])).is_err());
+        PAGE.words[0].store(1, Ordering::Relaxed);
+    }
+
+    #[test]
+    #[ignore = "faults; run by test_seal"]
+    fn protected() {
+        let _ = unsafe { seal_all_read_only() };
+        PAGE.words[0].store(1, Ordering::Relaxed);
+    }
+
+    /* set_clashing_7e78b and set_clashing_85c38 have the same hash, and so
+     * are both shortened to set_clasbb9741a3 */
+    const CLASH: &str = "
+        mod a { linker_set::set_declare!(clashing_7e78b, u8); }
+        mod b { linker_set::set_declare!(SET, u8); }
+        fn main() {}
+    ";
+
+    #[test]
+    fn test_mach_o_guard() {
+        let twice = CLASH.replace("SET", "clashing_7e78b");
+        if let Err(e) = crate::diagnostics::link(&twice) {
+            panic!("{}", e);
+        }
+        let clash = CLASH.replace("SET", "clashing_85c38");
+        let e = crate::diagnostics::link(&clash).unwrap_err();
+        assert!(e.contains("linker_set.mach_o.set_clasbb9741a3"), "{}", e);
+    }
+
+    #[test]
+    fn test_mach_o_collisions() {
+        let sets = [
+            ("fatal_handler", "set_fatal_handler", "set_fata305acff5"),
+            ("fatal_handler", "set_fatal_handler", "set_fata305acff5"),
+            ("fatal_hook", "set_fatal_hook", "set_fatal_hook"),
+            ("impostor", "set_impostor", "set_fata305acff5"),
+        ];
+        let failures = mach_o_collisions(sets);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].0, "impostor");
+        assert_eq!(
+            failures[0].1,
+            "shares the Mach-O section set_fata305acff5 with linker set \
+             fatal_handler"
+        );
+    }
+
+    #[test]
+    fn test_whole_pages() {
+        assert_eq!(whole_pages(0x1000..0x3000, 0x1000), 0x1000..0x3000);
+        assert_eq!(whole_pages(0x1008..0x3008, 0x1000), 0x2000..0x3000);
+        assert!(whole_pages(0x1008..0x1ff8, 0x1000).is_empty());
+    }
+}
@@ -0,0 +1,62 @@
+//! Checks recorded with the entries of linker sets.
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+/// A check of an entry in a linker set, recorded by the
+/// [set_entry](crate::set_entry) attribute when given `self_test = path`.
+pub struct SelfTest<T>
+where
+    T: 'static,
+{
+    name: &'static str,
+    entry: &'static T,
+    check: fn(&T) -> Result<(), String>,
+}
+
+impl<T> SelfTest<T>
+where
+    T: 'static,
+{
+    #[doc(hidden)]
+    pub const fn new(
+        name: &'static str, entry: &'static T,
+        check: fn(&T) -> Result<(), String>,
+    ) -> Self {
+        Self { name, entry, check }
+    }
+
+    /// Returns the name of the static holding the entry.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the entry.
+    pub fn entry(&self) -> &'static T {
+        self.entry
+    }
+
+    /// Run the check of the entry.
+    ///
+    /// A check that panics fails with the message of the panic.
+    pub fn run(&self) -> Result<(), String> {
+        caught(|| (self.check)(self.entry))
+    }
+}
+
+/// Call a check, returning a panic in it as an error, so that one check
+/// that panics does not stop the others from running.
+pub(crate) fn caught<F>(check: F) -> Result<(), String>
+where
+    F: FnOnce() -> Result<(), String>,
+{
+    catch_unwind(AssertUnwindSafe(check)).unwrap_or_else(|payload| {
+        let msg = match payload.downcast_ref::<&str>() {
+            Some(msg) => msg.to_string(),
+            None => match payload.downcast_ref::<String>() {
+                Some(msg) => msg.clone(),
+                None => "Box<dyn Any>".to_string(),
+            },
+        };
+        Err(format!("panicked: {}", msg))
+    })
+}
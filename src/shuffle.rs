@@ -0,0 +1,125 @@
+//! Iteration of linker sets in an order shuffled on each run.
+//!
+//! The order of the entries of a set is whatever the linker makes it, which
+//! usually stays the same from one build to the next until a change to an
+//! unrelated crate, or to the linker, upsets it.  With the `shuffle`
+//! feature enabled, such as for the tests run in CI, the iterator of every
+//! set yields the entries in an order that depends on a seed chosen for the
+//! run, so that code that relies on the order fails now rather than then.
+//!
+//! The seed is written to standard error the first time a set is iterated,
+//! and running with the environment variable `LINKER_SET_SHUFFLE_SEED` set
+//! to it repeats the order of that run.  Within one run, each iteration of
+//! a set yields its entries in the same order, as it would without the
+//! feature, and sets of the same type and length are shuffled alike.
+//! Indexing a set, and its slices, are not shuffled.
+
+use std::hash::{BuildHasher, Hasher};
+use std::io::Write;
+use std::sync::OnceLock;
+
+/// The name of the environment variable that gives the seed.
+pub const SEED_VAR: &str = "LINKER_SET_SHUFFLE_SEED";
+
+static SEED: OnceLock<u64> = OnceLock::new();
+
+/// Returns the seed of the order in which sets are iterated in this run,
+/// choosing it, and writing it to standard error, on the first call.
+///
+/// # Panics
+/// Panics if `LINKER_SET_SHUFFLE_SEED` is set but not a number.
+pub fn seed() -> u64 {
+    *SEED.get_or_init(|| {
+        let seed = match std::env::var(SEED_VAR) {
+            Ok(s) => s.parse().unwrap_or_else(|_| {
+                panic!("{} is not a number: {:?}", SEED_VAR, s)
+            }),
+            Err(_) => std::hash::RandomState::new().build_hasher().finish(),
+        };
+        /* not through eprintln, which the test harness captures, so that
+         * the seed appears whichever test fails */
+        let _ = writeln!(
+            std::io::stderr(),
+            "linker-set: shuffling sets with {}={}",
+            SEED_VAR,
+            seed
+        );
+        seed
+    })
+}
+
+/// Mix the bits of a number, as the finalizer of SplitMix64 does.
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+/// A permutation of the indices of a set, computed an index at a time, so
+/// that iterating does not allocate.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Order {
+    key: u64,
+    half: u32,
+}
+
+impl Order {
+    /// Create the order of a set of `len` elements of type `T`.
+    ///
+    /// Sets of different types are shuffled differently, but not by their
+    /// addresses, which change from run to run, so that the seed alone
+    /// repeats the order.
+    pub(crate) fn new<T>(len: usize) -> Self {
+        let mut hasher = std::hash::DefaultHasher::new();
+        hasher.write(std::any::type_name::<T>().as_bytes());
+        let bits = usize::BITS - len.saturating_sub(1).leading_zeros();
+        Self {
+            key: mix(seed() ^ hasher.finish()),
+            half: bits.div_ceil(2).max(1),
+        }
+    }
+
+    /// A permutation of the numbers below `1 << 2 * half`, as a Feistel
+    /// network.
+    fn permute(&self, x: u64) -> u64 {
+        let mask = (1 << self.half) - 1;
+        let (mut l, mut r) = (x >> self.half, x & mask);
+        for round in 0..4 {
+            let f = mix(self.key ^ (round << 32) ^ r) & mask;
+            (l, r) = (r, l ^ f);
+        }
+        (l << self.half) | r
+    }
+
+    /// Returns the index yielded at position `i` of a set of `len`
+    /// elements, walking the cycle of the permutation until it falls
+    /// within the set.
+    pub(crate) fn index(&self, i: usize, len: usize) -> usize {
+        let mut x = self.permute(i as u64);
+        while x >= len as u64 {
+            x = self.permute(x);
+        }
+        x as usize
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_order() {
+        let mut moved = false;
+        for len in [0, 1, 2, 3, 7, 8, 100, 1000] {
+            for order in [Order::new::<u8>(len), Order::new::<String>(len)] {
+                let mut seen =
+                    (0..len).map(|i| order.index(i, len)).collect::<Vec<_>>();
+                moved |= seen.iter().enumerate().any(|(i, &x)| i != x);
+                seen.sort();
+                assert_eq!(seen, (0..len).collect::<Vec<_>>());
+            }
+        }
+        assert!(moved);
+        assert_eq!(seed(), seed());
+    }
+}
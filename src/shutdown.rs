@@ -0,0 +1,285 @@
+//! Ordered shutdown hooks, run in the reverse order of their phases.
+//!
+//! Whatever a service sets up at startup, in phases, it should tear down
+//! in the opposite order.  A function registered with the
+//! [shutdown](macro@crate::shutdown) attribute is placed into the
+//! [shutdown_hook] linker set, and [run_all] calls the hooks of the
+//! highest phase first, down to phase zero, so that a hook registered
+//! alongside what it tears down runs before the teardown of anything it
+//! depends on.
+//!
+//! ```
+//! use linker_set::*;
+//! use linker_set::shutdown::{self, Policy};
+//!
+//! #[shutdown(phase = 2)]
+//! fn close_listeners() -> Result<(), String> {
+//!     Ok(())
+//! }
+//!
+//! #[shutdown(phase = 1)]
+//! fn flush_database() -> Result<(), String> {
+//!     Err("disk full".to_string())
+//! }
+//!
+//! # fn main() {
+//! let e = shutdown::run_all(&Policy::default()).unwrap_err();
+//! assert!(e.to_string().contains("flush_database: disk full"));
+//! # }
+//! ```
+//!
+//! A [Policy] bounds how long each hook may take, and whether the hooks
+//! after one that fails still run.  Every failure is collected into the
+//! [ShutdownError] returned, rather than only the first.
+
+use crate::*;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::time::Duration;
+
+set_declare!(shutdown_hook, Hook, namespaced);
+
+/// A function to call at shutdown.
+pub struct Hook {
+    name: &'static str,
+    phase: u32,
+    run: fn() -> Result<(), String>,
+}
+
+impl Hook {
+    /// Create a hook from its name, its phase, and the function to call.
+    pub const fn new(
+        name: &'static str, phase: u32, run: fn() -> Result<(), String>,
+    ) -> Self {
+        Self { name, phase, run }
+    }
+
+    /// Returns the name of the hook, which is the path of its function.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the phase of the hook.
+    pub fn phase(&self) -> u32 {
+        self.phase
+    }
+}
+
+/// How to run shutdown hooks.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Policy {
+    /// How long each hook may run before it is abandoned, if limited.  A
+    /// limited hook runs on a thread of its own, which is left running if
+    /// it takes too long.
+    pub timeout: Option<Duration>,
+    /// Skip the hooks after the first that fails, rather than run them.
+    pub stop_on_error: bool,
+}
+
+/// How a hook failed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Failure {
+    /// The hook returned an error.
+    Error(String),
+    /// The hook panicked.
+    Panicked,
+    /// The hook did not finish within the timeout.
+    TimedOut,
+    /// The hook was not run, because an earlier one failed.
+    Skipped,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error(e) => write!(f, "{}", e),
+            Self::Panicked => write!(f, "panicked"),
+            Self::TimedOut => write!(f, "timed out"),
+            Self::Skipped => write!(f, "skipped"),
+        }
+    }
+}
+
+/// The failures of the hooks run at shutdown.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ShutdownError {
+    /// The name of each hook that failed, and how, in the order run.
+    pub failures: Vec<(&'static str, Failure)>,
+}
+
+impl fmt::Display for ShutdownError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} shutdown hooks failed", self.failures.len())?;
+        for (name, failure) in &self.failures {
+            write!(f, "; {}: {}", name, failure)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ShutdownError {}
+
+fn call(hook: &'static Hook, timeout: Option<Duration>) -> Result<(), Failure> {
+    let Some(timeout) = timeout else {
+        return match std::panic::catch_unwind(hook.run) {
+            Ok(result) => result.map_err(Failure::Error),
+            Err(_) => Err(Failure::Panicked),
+        };
+    };
+    let (tx, rx) = mpsc::channel();
+    std::thread::Builder::new()
+        .name(format!("shutdown {}", hook.name))
+        .spawn(move || tx.send((hook.run)()))
+        .map_err(|e| Failure::Error(e.to_string()))?;
+    match rx.recv_timeout(timeout) {
+        Ok(result) => result.map_err(Failure::Error),
+        Err(mpsc::RecvTimeoutError::Timeout) => Err(Failure::TimedOut),
+        Err(mpsc::RecvTimeoutError::Disconnected) => Err(Failure::Panicked),
+    }
+}
+
+/// Run the given hooks, those of the highest phase first, and those of the
+/// same phase in order of name.
+pub fn run<I>(hooks: I, policy: &Policy) -> Result<(), ShutdownError>
+where
+    I: IntoIterator<Item = &'static Hook>,
+{
+    let mut hooks = hooks.into_iter().collect::<Vec<_>>();
+    hooks.sort_by(|a, b| b.phase.cmp(&a.phase).then(a.name.cmp(b.name)));
+    let mut failures = Vec::new();
+    for hook in hooks {
+        if policy.stop_on_error && !failures.is_empty() {
+            failures.push((hook.name, Failure::Skipped));
+        } else if let Err(failure) = call(hook, policy.timeout) {
+            failures.push((hook.name, failure));
+        }
+    }
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(ShutdownError { failures })
+    }
+}
+
+static RAN: AtomicBool = AtomicBool::new(false);
+
+/// Run all hooks registered in the [shutdown_hook] linker set, the first
+/// time it is called, so that every path by which a program exits can
+/// call it.  Later calls do nothing.
+pub fn run_all(policy: &Policy) -> Result<(), ShutdownError> {
+    if RAN.swap(true, Ordering::AcqRel) {
+        return Ok(());
+    }
+    run(set!(shutdown_hook), policy)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Mutex;
+
+    static ORDER: Mutex<Vec<&str>> = Mutex::new(Vec::new());
+
+    fn record(name: &'static str) {
+        ORDER.lock().unwrap().push(name);
+    }
+
+    #[shutdown(phase = 1)]
+    fn close_files() -> Result<(), String> {
+        record("close_files");
+        Ok(())
+    }
+
+    #[shutdown(phase = 3)]
+    fn stop_server() -> Result<(), String> {
+        record("stop_server");
+        Err("connections remain".to_string())
+    }
+
+    #[shutdown]
+    fn flush_logs() -> Result<(), String> {
+        record("flush_logs");
+        Ok(())
+    }
+
+    #[shutdown(phase = 2)]
+    #[cfg(any())]
+    fn never() -> Result<(), String> {
+        unreachable!()
+    }
+
+    fn slow() -> Result<(), String> {
+        std::thread::sleep(Duration::from_secs(1));
+        Ok(())
+    }
+
+    fn panics() -> Result<(), String> {
+        panic!("hook panicked")
+    }
+
+    fn fails() -> Result<(), String> {
+        Err("failed".to_string())
+    }
+
+    static SLOW: Hook = Hook::new("slow", 2, slow);
+    static PANICS: Hook = Hook::new("panics", 1, panics);
+    static FAILS: Hook = Hook::new("fails", 2, fails);
+
+    #[test]
+    fn test_run_all() {
+        let e = run_all(&Policy::default()).unwrap_err();
+        let stop_server = "linker_set::shutdown::test::stop_server";
+        assert_eq!(
+            e.failures,
+            [(
+                stop_server,
+                Failure::Error("connections remain".to_string())
+            )]
+        );
+        assert_eq!(
+            *ORDER.lock().unwrap(),
+            ["stop_server", "close_files", "flush_logs"]
+        );
+        assert_eq!(run_all(&Policy::default()), Ok(()));
+        assert_eq!(ORDER.lock().unwrap().len(), 3);
+
+        let mut phases = set!(shutdown_hook)
+            .iter()
+            .map(Hook::phase)
+            .collect::<Vec<_>>();
+        phases.sort();
+        assert_eq!(phases, [0, 1, 3]);
+    }
+
+    #[test]
+    fn test_policy() {
+        let policy = Policy {
+            timeout: Some(Duration::from_millis(50)),
+            stop_on_error: false,
+        };
+        let e = run([&SLOW, &PANICS], &policy).unwrap_err();
+        assert_eq!(
+            e.failures,
+            [("slow", Failure::TimedOut), ("panics", Failure::Panicked)]
+        );
+
+        let policy = Policy {
+            timeout: None,
+            stop_on_error: true,
+        };
+        let e = run([&PANICS, &FAILS], &policy).unwrap_err();
+        assert_eq!(
+            e.failures,
+            [
+                ("fails", Failure::Error("failed".to_string())),
+                ("panics", Failure::Skipped)
+            ]
+        );
+        assert_eq!(
+            e.to_string(),
+            "2 shutdown hooks failed; fails: failed; \
+                                   panics: skipped"
+        );
+    }
+}
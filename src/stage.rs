@@ -0,0 +1,218 @@
+//! Boot stages, which gate linker sets and their initializers.
+//!
+//! A kernel or firmware comes up in stages, such as running on one CPU
+//! with physical addresses, then with the MMU enabled, then on every CPU,
+//! and some of what it registers is usable only from a certain stage on.
+//! A set declared as `set_declare!(name, type, stage = "mmu_up")` belongs
+//! to a stage: in debug builds, accessing it with the [set!] macro before
+//! the stage is reached panics, naming the set and the stage, rather than
+//! reading entries that refer to memory not yet mapped.  A function
+//! registered with the [stage_init](macro@crate::stage_init) attribute is
+//! placed into the [stage_init](mod@stage_init) linker set, and [advance]
+//! calls the initializers of a stage, in order of name, once it has
+//! reached it.
+//!
+//! ```
+//! use linker_set::*;
+//! use linker_set::stage;
+//!
+//! pub struct Device {
+//!     name: &'static str,
+//! }
+//!
+//! set_declare!(devices, Device, stage = "mmu_up");
+//!
+//! #[set_entry(devices)]
+//! static UART: Device = Device { name: "uart" };
+//!
+//! #[stage_init("mmu_up")]
+//! fn probe_devices() {
+//!     assert_eq!(set!(devices).iter().count(), 1);
+//! }
+//!
+//! # fn main() {
+//! stage::advance("early");
+//! assert!(!stage::reached("mmu_up"));
+//! stage::advance("mmu_up");
+//! assert_eq!(stage::current(), Some("mmu_up"));
+//! assert_eq!(set!(devices)[0].name, "uart");
+//! # }
+//! ```
+//!
+//! Stages are named by strings, and are reached in the order in which
+//! [advance] is called, each once.  Release builds do not check accesses,
+//! so that a set of a stage costs nothing more to access than any other.
+
+use crate::*;
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::Mutex;
+
+set_declare!(stage_init, Init, namespaced);
+
+/// A function to call when a boot stage is reached.
+pub struct Init {
+    name: &'static str,
+    stage: &'static str,
+    run: fn(),
+}
+
+impl Init {
+    /// Create an initializer from its name, its stage, and the function to
+    /// call.
+    pub const fn new(
+        name: &'static str, stage: &'static str, run: fn(),
+    ) -> Self {
+        Self { name, stage, run }
+    }
+
+    /// Returns the name of the initializer, which is the path of its
+    /// function.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the stage of the initializer.
+    pub fn stage(&self) -> &'static str {
+        self.stage
+    }
+}
+
+/* a stage reached, linked to the one reached before it, so that the
+ * stages reached can be read without a lock by set!() in debug builds */
+struct Reached {
+    stage: &'static str,
+    prev: Option<&'static Reached>,
+}
+
+/* serializes advance(), the only writer of LAST */
+static ADVANCE: Mutex<()> = Mutex::new(());
+static LAST: AtomicPtr<Reached> = AtomicPtr::new(null_mut());
+
+fn last() -> Option<&'static Reached> {
+    /* every node is leaked, and published only once initialized */
+    unsafe { LAST.load(Ordering::Acquire).as_ref() }
+}
+
+fn stages() -> impl Iterator<Item = &'static str> {
+    std::iter::successors(last(), |r| r.prev).map(|r| r.stage)
+}
+
+/// Reach a boot stage, making the linker sets of the stage available, and
+/// then call the initializers of the stage registered in the
+/// [stage_init](mod@stage_init) linker set, in order of name.
+///
+/// # Panics
+/// Panics if the stage has already been reached.
+pub fn advance(stage: &'static str) {
+    {
+        let _guard = ADVANCE.lock().unwrap();
+        assert!(
+            !reached(stage),
+            "boot stage {} has already been reached",
+            stage
+        );
+        let node = Box::leak(Box::new(Reached {
+            stage,
+            prev: last(),
+        }));
+        LAST.store(node, Ordering::Release);
+    }
+    let mut inits = set!(stage_init)
+        .iter()
+        .filter(|i| i.stage == stage)
+        .collect::<Vec<_>>();
+    inits.sort_by_key(|i| i.name);
+    inits.into_iter().for_each(|i| (i.run)());
+}
+
+/// Returns true if a boot stage has been reached.
+pub fn reached(stage: &str) -> bool {
+    stages().any(|s| s == stage)
+}
+
+/// Returns the boot stage reached last, if any.
+pub fn current() -> Option<&'static str> {
+    last().map(|r| r.stage)
+}
+
+/// Check that the stage of a linker set, if any, has been reached.
+///
+/// Users should call the [set!](crate::set) macro instead of this
+/// function.
+#[doc(hidden)]
+pub fn __check(stage: Option<&str>, set: &str) {
+    if let Some(stage) = stage {
+        assert!(
+            reached(stage),
+            "linker set {} accessed before boot stage {}",
+            set,
+            stage
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::panic::catch_unwind;
+
+    static ORDER: Mutex<Vec<&str>> = Mutex::new(Vec::new());
+
+    set_declare!(mapped, u32, stage = "test_mapped");
+
+    #[set_entry(mapped)]
+    static REGION: u32 = 0x1000;
+
+    #[stage_init("test_mapped")]
+    fn map_second() {
+        ORDER.lock().unwrap().push("second");
+    }
+
+    #[stage_init("test_mapped")]
+    fn map_first() {
+        assert_eq!(set!(mapped)[0], REGION);
+        ORDER.lock().unwrap().push("first");
+    }
+
+    #[stage_init("test_other")]
+    fn other() {
+        ORDER.lock().unwrap().push("other");
+    }
+
+    #[stage_init("test_mapped")]
+    #[cfg(any())]
+    fn never() {
+        unreachable!()
+    }
+
+    #[test]
+    fn test_advance() {
+        if cfg!(debug_assertions) {
+            let e = catch_unwind(|| set!(mapped).len()).unwrap_err();
+            assert_eq!(
+                e.downcast_ref::<String>().unwrap(),
+                "linker set mapped accessed before boot stage test_mapped"
+            );
+        }
+        assert!(!reached("test_mapped"));
+        let info = reflect::sets()
+            .iter()
+            .find(|i| i.name() == "mapped")
+            .unwrap();
+        assert_eq!(info.stage(), Some("test_mapped"));
+
+        advance("test_mapped");
+        assert!(reached("test_mapped"));
+        assert_eq!(*ORDER.lock().unwrap(), ["first", "second"]);
+        assert_eq!(set!(mapped).len(), 1);
+        assert!(catch_unwind(|| advance("test_mapped")).is_err());
+        assert_eq!(ORDER.lock().unwrap().len(), 2);
+
+        let inits = set!(stage_init)
+            .iter()
+            .filter(|i| i.stage().starts_with("test_"))
+            .count();
+        assert_eq!(inits, 3);
+    }
+}
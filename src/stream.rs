@@ -0,0 +1,256 @@
+//! Linker sets consumed asynchronously.
+//!
+//! With the `async` feature enabled, `LinkerSet::stream` yields the
+//! elements of a set as a stream, and `LinkerSet::for_each_concurrent`
+//! runs an asynchronous function on each, such as to initialize every
+//! registered component at startup, with at most a given number running
+//! at once.  Neither needs a particular runtime.
+//!
+//! ```
+//! use linker_set::*;
+//!
+//! pub struct Component {
+//!     name: &'static str,
+//! }
+//!
+//! impl Component {
+//!     async fn init(&self) {
+//!         // connect, load, warm a cache, ...
+//!     }
+//! }
+//!
+//! set_declare!(components, Component);
+//!
+//! #[set_entry(components)]
+//! static CACHE: Component = Component { name: "cache" };
+//!
+//! async fn startup() {
+//!     set!(components).for_each_concurrent(8, |c| c.init()).await;
+//! }
+//! # fn main() {}
+//! ```
+//!
+//! A [SetStream] is a `Stream` of the `futures-core` crate, and so takes
+//! the combinators of `futures::StreamExt` and those of the runtimes.
+
+use crate::{LinkerSet, LinkerSetIter};
+use futures_core::{FusedStream, Stream};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A stream of the elements of a linker set, which are always ready.
+pub struct SetStream<T> {
+    iter: LinkerSetIter<T>,
+}
+
+impl<T> SetStream<T>
+where
+    T: 'static,
+{
+    /// Returns the next element, or `None` at the end of the set.
+    pub async fn next(&mut self) -> Option<&'static T> {
+        self.iter.next()
+    }
+}
+
+impl<T> Stream for SetStream<T>
+where
+    T: 'static,
+{
+    type Item = &'static T;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>, _: &mut Context<'_>,
+    ) -> Poll<Option<&'static T>> {
+        Poll::Ready(self.iter.next())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<T> FusedStream for SetStream<T>
+where
+    T: 'static,
+{
+    fn is_terminated(&self) -> bool {
+        self.iter.len() == 0
+    }
+}
+
+/// The future returned by `LinkerSet::for_each_concurrent`.
+pub struct ForEachConcurrent<T, F, Fut>
+where
+    T: 'static,
+{
+    iter: LinkerSetIter<T>,
+    f: F,
+    limit: usize,
+    running: Vec<Pin<Box<Fut>>>,
+}
+
+impl<T, F, Fut> Future for ForEachConcurrent<T, F, Fut>
+where
+    F: FnMut(&'static T) -> Fut + Unpin,
+    Fut: Future<Output = ()>,
+{
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = &mut *self;
+        loop {
+            while this.running.len() < this.limit {
+                let Some(entry) = this.iter.next() else {
+                    break;
+                };
+                this.running.push(Box::pin((this.f)(entry)));
+            }
+            let before = this.running.len();
+            this.running
+                .retain_mut(|f| f.as_mut().poll(cx).is_pending());
+            if this.running.is_empty() && this.iter.len() == 0 {
+                return Poll::Ready(());
+            }
+            /* start more only if some finished, or else wait to be woken */
+            if this.running.len() == before {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+impl<T> LinkerSet<T>
+where
+    T: 'static,
+{
+    /// Returns a stream of the elements of the linker set.
+    pub fn stream(&self) -> SetStream<T> {
+        SetStream { iter: self.iter() }
+    }
+
+    /// Returns a future that calls `f` on each element of the linker set
+    /// and awaits the futures it returns, running at most `limit` at once,
+    /// or one if `limit` is zero.
+    ///
+    /// The futures run on the task awaiting this one, as those of
+    /// `futures::StreamExt::for_each_concurrent` do, rather than being
+    /// spawned, so they need not be `Send`.
+    pub fn for_each_concurrent<F, Fut>(
+        &self, limit: usize, f: F,
+    ) -> ForEachConcurrent<T, F, Fut>
+    where
+        F: FnMut(&'static T) -> Fut + Unpin,
+        Fut: Future<Output = ()>,
+    {
+        ForEachConcurrent {
+            iter: self.iter(),
+            f,
+            limit: limit.max(1),
+            running: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+    use futures_core::{FusedStream, Stream};
+    use std::future::Future;
+    use std::pin::pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll, Wake, Waker};
+
+    struct Unpark(std::thread::Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(f: F) -> F::Output {
+        let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut f = pin!(f);
+        loop {
+            match f.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => std::thread::park(),
+            }
+        }
+    }
+
+    /// A future that is pending the first time it is polled.
+    async fn yield_now() {
+        let mut yielded = false;
+        std::future::poll_fn(|cx| {
+            if yielded {
+                Poll::Ready(())
+            } else {
+                yielded = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    set_declare!(components, u32);
+
+    #[set_entry(components)]
+    static DATABASE: u32 = 1;
+    #[set_entry(components)]
+    static CACHE: u32 = 2;
+    #[set_entry(components)]
+    static QUEUE: u32 = 4;
+    #[set_entry(components)]
+    static METRICS: u32 = 8;
+    #[set_entry(components)]
+    static TRACING: u32 = 16;
+
+    #[test]
+    fn test_stream() {
+        let mut stream = set!(components).stream();
+        assert_eq!(stream.size_hint(), (5, Some(5)));
+        let sum = block_on(async {
+            let mut sum = 0;
+            while let Some(x) = stream.next().await {
+                sum += x;
+            }
+            sum
+        });
+        assert_eq!(sum, 31);
+        assert!(stream.is_terminated());
+        let waker = Waker::noop();
+        let polled = pin!(stream).poll_next(&mut Context::from_waker(waker));
+        assert_eq!(polled, Poll::Ready(None));
+    }
+
+    #[test]
+    fn test_for_each_concurrent() {
+        for limit in [0, 1, 2, 3, 10] {
+            let running = AtomicUsize::new(0);
+            let most = AtomicUsize::new(0);
+            let done = Mutex::new(Vec::new());
+            block_on(set!(components).for_each_concurrent(limit, |&x| {
+                let (running, most, done) = (&running, &most, &done);
+                async move {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    most.fetch_max(now, Ordering::SeqCst);
+                    for _ in 0..x {
+                        yield_now().await;
+                    }
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    done.lock().unwrap().push(x);
+                }
+            }));
+            let mut done = done.into_inner().unwrap();
+            done.sort();
+            assert_eq!(done, [1, 2, 4, 8, 16]);
+            assert_eq!(most.into_inner(), limit.clamp(1, 5));
+        }
+    }
+}
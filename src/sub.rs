@@ -0,0 +1,181 @@
+//! Sub-sets sharing the section of a parent linker set.
+
+use crate::{LinkerSet, LinkerSetIter};
+use std::marker::PhantomData;
+
+/// The identity of a sub-set, declared by
+/// [set_declare_sub!](crate::set_declare_sub).
+///
+/// Entries of a sub-set refer to its identity, and iteration over the
+/// sub-set keeps the entries of the parent whose identity is at the same
+/// address.
+pub struct SubsetId<T> {
+    name: &'static str,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T> SubsetId<T> {
+    #[doc(hidden)]
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            _type: PhantomData,
+        }
+    }
+
+    /// Returns the name of the sub-set.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+/// An entry of a sub-set, the element type of its parent linker set.
+pub struct Sub<T>
+where
+    T: 'static,
+{
+    set: &'static SubsetId<T>,
+    entry: &'static T,
+}
+
+impl<T> Sub<T>
+where
+    T: 'static,
+{
+    #[doc(hidden)]
+    pub const fn new(set: &'static SubsetId<T>, entry: &'static T) -> Self {
+        Self { set, entry }
+    }
+
+    /// Returns the identity of the sub-set holding the entry.
+    pub fn set(&self) -> &'static SubsetId<T> {
+        self.set
+    }
+
+    /// Returns the entry.
+    pub fn entry(&self) -> &'static T {
+        self.entry
+    }
+}
+
+/// An iterator over the entries of a sub-set.
+pub struct SubsetIter<T>
+where
+    T: 'static,
+{
+    iter: LinkerSetIter<Sub<T>>,
+    set: &'static SubsetId<T>,
+}
+
+impl<T> Iterator for SubsetIter<T>
+where
+    T: 'static,
+{
+    type Item = &'static T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter
+            .find(|s| std::ptr::eq(s.set, self.set))
+            .map(|s| s.entry)
+    }
+}
+
+impl<T> LinkerSet<Sub<T>>
+where
+    T: 'static,
+{
+    /// Returns an iterator over the entries of one sub-set of the linker
+    /// set.
+    pub fn subset(&self, set: &'static SubsetId<T>) -> SubsetIter<T> {
+        SubsetIter {
+            iter: self.iter(),
+            set,
+        }
+    }
+}
+
+/// Declare a sub-set of a linker set.
+///
+/// Many small linker sets each cost a few sections, each with its own
+/// header, symbols, and alignment padding.  Related small sets can
+/// instead share the section of one parent set, whose element type is
+/// [Sub], at the cost of two pointers per entry and of skipping the
+/// entries of other sub-sets when iterating.
+///
+/// Like [set_declare!](crate::set_declare), this macro outputs a module,
+/// which has the identity of the sub-set, `ID`, and `iter()`, which
+/// returns an iterator over its entries.  Entries are placed into the
+/// sub-set with `#[set_entry(parent, sub = child)]`.
+///
+/// ```
+/// use linker_set::*;
+///
+/// set_declare!(handlers, Sub<fn() -> u32>);
+/// set_declare_sub!(handlers, http, fn() -> u32);
+/// set_declare_sub!(handlers, ftp, fn() -> u32);
+///
+/// fn get() -> u32 { 200 }
+///
+/// #[set_entry(handlers, sub = http)]
+/// static GET: fn() -> u32 = get;
+///
+/// # fn main() {
+/// assert_eq!(http::iter().map(|f| f()).collect::<Vec<_>>(), [200]);
+/// assert_eq!(ftp::iter().count(), 0);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! set_declare_sub {
+    ($parent:ident, $child:ident, $type:ty) => {
+        /// A sub-set of a linker set.
+        pub mod $child {
+            #[allow(unused_imports)]
+            use super::*;
+            #[allow(unused_imports)]
+            use $crate::LinkerSet;
+
+            /// The identity of the sub-set.
+            pub static ID: $crate::SubsetId<$type> =
+                $crate::SubsetId::new(stringify!($child));
+
+            /// Returns an iterator over the entries of the sub-set.
+            #[allow(dead_code)]
+            pub fn iter() -> $crate::SubsetIter<$type> {
+                $crate::set!($parent).subset(&ID)
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+
+    set_declare!(shapes, Sub<u32>);
+    set_declare_sub!(shapes, squares, u32);
+    set_declare_sub!(shapes, primes, u32);
+
+    #[set_entry(shapes, sub = squares)]
+    static FOUR: u32 = 4;
+    #[set_entry(shapes, sub = squares)]
+    const NINE: u32 = 9;
+    #[set_entry(shapes, sub = primes)]
+    static SEVEN: u32 = 7;
+
+    #[test]
+    fn test_subset() {
+        let mut squares = squares::iter().copied().collect::<Vec<_>>();
+        squares.sort();
+        assert_eq!(squares, [4, 9]);
+        assert_eq!(primes::iter().collect::<Vec<_>>(), [&SEVEN]);
+        assert_eq!(set!(shapes).len(), 3);
+        assert_eq!(squares::ID.name(), "squares");
+        assert!(std::ptr::eq(
+            set!(shapes)
+                .subset(&squares::ID)
+                .find(|&&n| n == 4)
+                .unwrap(),
+            &FOUR
+        ));
+    }
+}
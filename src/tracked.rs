@@ -0,0 +1,142 @@
+//! Entries that must be initialized at startup, and checks that they were.
+
+use crate::LinkerSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// An entry of a linker set that records whether it has been initialized.
+///
+/// Some subsystems register descriptors that later need an `init()` call
+/// to wire them up.  Forgetting one leaves an entry that is registered
+/// but inert, which nothing notices until it is used.  A set whose
+/// element type is `Tracked` catches such entries: whatever initializes
+/// an entry calls [Tracked::mark_init], and at the end of startup,
+/// `LinkerSet::assert_all_initialized` panics, naming every entry that
+/// was not initialized:
+///
+/// ```should_panic
+/// use linker_set::*;
+///
+/// pub struct Driver {
+///     name: &'static str,
+/// }
+///
+/// set_declare!(drivers, Tracked<Driver>);
+///
+/// #[set_entry(drivers)]
+/// static DISK: Tracked<Driver> = Tracked::new(Driver { name: "disk" });
+/// #[set_entry(drivers)]
+/// static NET: Tracked<Driver> = Tracked::new(Driver { name: "net" });
+///
+/// # fn main() {
+/// DISK.mark_init();
+/// set!(drivers).assert_all_initialized(); // panics, naming NET
+/// # }
+/// ```
+///
+/// Entries are named by their statics with the `metadata` feature, and by
+/// their index and address without it.
+pub struct Tracked<T> {
+    value: T,
+    initialized: AtomicBool,
+}
+
+impl<T> Tracked<T> {
+    /// Create an entry holding a value, not yet initialized.
+    pub const fn new(value: T) -> Self {
+        Self {
+            value,
+            initialized: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the value of the entry.
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+
+    /// Record that the entry has been initialized.
+    pub fn mark_init(&self) {
+        self.initialized.store(true, Ordering::Release);
+    }
+
+    /// Returns true if the entry has been initialized.
+    pub fn is_initialized(&self) -> bool {
+        self.initialized.load(Ordering::Acquire)
+    }
+}
+
+impl<T> LinkerSet<Tracked<T>>
+where
+    T: 'static,
+{
+    /// Returns a description of each entry in the linker set that has not
+    /// been initialized: the path of its static, with the `metadata`
+    /// feature, or otherwise its index and address.
+    pub fn uninitialized(&self) -> Vec<String> {
+        self.iter()
+            .filter(|e| !e.is_initialized())
+            .map(|e| self.describe(e))
+            .collect()
+    }
+
+    /// Check that every entry in the linker set has been initialized, such
+    /// as at the end of startup.
+    ///
+    /// # Panics
+    /// Panics if any entry has not been marked as initialized, naming
+    /// each such entry.
+    pub fn assert_all_initialized(&self) {
+        let missing = self.uninitialized();
+        assert!(
+            missing.is_empty(),
+            "linker set {} has uninitialized entries: {}",
+            self.name().unwrap_or("<unnamed>"),
+            missing.join(", ")
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+
+    pub struct Port {
+        number: u16,
+    }
+
+    set_declare!(ports, Tracked<Port>);
+
+    #[set_entry(ports)]
+    static HTTP: Tracked<Port> = Tracked::new(Port { number: 80 });
+    #[set_entry(ports)]
+    static SSH: Tracked<Port> = Tracked::new(Port { number: 22 });
+
+    set_declare!(wired, Tracked<Port>);
+
+    #[set_entry(wired)]
+    static DNS: Tracked<Port> = Tracked::new(Port { number: 53 });
+
+    #[test]
+    fn test_tracked() {
+        HTTP.mark_init();
+        assert!(HTTP.is_initialized() && !SSH.is_initialized());
+        let missing = set!(ports).uninitialized();
+        assert_eq!(missing.len(), 1);
+        #[cfg(feature = "metadata")]
+        assert_eq!(missing[0], "linker_set::tracked::test::SSH");
+        #[cfg(not(feature = "metadata"))]
+        assert!(missing[0].starts_with("entry "));
+        assert_eq!(SSH.get().number, 22);
+
+        for port in set!(wired) {
+            port.mark_init();
+        }
+        set!(wired).assert_all_initialized();
+    }
+
+    #[test]
+    #[should_panic(expected = "linker set ports has uninitialized entries")]
+    fn test_not_initialized() {
+        set!(ports).assert_all_initialized();
+    }
+}
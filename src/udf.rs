@@ -0,0 +1,433 @@
+//! Registration of SQL user-defined functions.
+//!
+//! Functions for an embedded database are described by [Function]s
+//! registered into the [function] linker set, wherever in the program they
+//! are defined.  When a connection is opened, [install] hands each of them
+//! to the connection through the [Install] trait, which an adapter for the
+//! database implements.
+//!
+//! ```
+//! use linker_set::*;
+//! use linker_set::udf::{function, Arity, Function, Kind, Value};
+//!
+//! #[set_entry(linker_set::udf::function)]
+//! static DOUBLE: Function = Function {
+//!     name: "double",
+//!     arity: Arity::Exact(1),
+//!     deterministic: true,
+//!     kind: Kind::Scalar(|args| match args {
+//!         [Value::Integer(i)] => Ok(Value::Integer(i * 2)),
+//!         _ => Err("double takes an integer".into()),
+//!     }),
+//! };
+//!
+//! # fn main() {
+//! let double = set!(function).iter().find(|f| f.name == "double");
+//! let result = double.unwrap().call(&[Value::Integer(21)]);
+//! assert_eq!(result.unwrap(), Value::Integer(42));
+//! # #[cfg(feature = "rusqlite")]
+//! # {
+//! let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+//! linker_set::udf::install(&mut conn).unwrap();
+//! let sql = "SELECT double(21)";
+//! let n: i64 = conn.query_row(sql, [], |row| row.get(0)).unwrap();
+//! assert_eq!(n, 42);
+//! # }
+//! # }
+//! ```
+//!
+//! With the `rusqlite` feature enabled, `rusqlite::Connection` implements
+//! [Install], converting between [Value] and the values of SQLite, so that
+//! every function can be installed into a connection as soon as it is
+//! opened, as above.  Other databases need an adapter of their own.  The
+//! `duckdb` crate has no aggregate functions, and it needs the types of the
+//! arguments and result of a scalar function in advance, which a [Function]
+//! does not give, so no adapter for it is included.
+
+use crate::di::BoxError;
+use crate::*;
+use std::fmt;
+
+set_declare!(function, Function, namespaced);
+
+/// A value passed to or returned from a function.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    /// SQL NULL.
+    Null,
+    /// An integer.
+    Integer(i64),
+    /// A floating point number.
+    Real(f64),
+    /// A string.
+    Text(String),
+    /// A byte string.
+    Blob(Vec<u8>),
+}
+
+/// The number of arguments a function takes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Arity {
+    /// Exactly this many.
+    Exact(usize),
+    /// Any number.
+    Variadic,
+}
+
+impl Arity {
+    /// Returns true if a function of this arity may take `n` arguments.
+    pub fn accepts(&self, n: usize) -> bool {
+        match self {
+            Self::Exact(m) => *m == n,
+            Self::Variadic => true,
+        }
+    }
+}
+
+/// The callbacks of an aggregate function.
+pub struct Aggregate {
+    /// Returns the initial state of the aggregation.
+    pub init: fn() -> Value,
+    /// Fold the arguments from one row into the state.
+    pub step: fn(&mut Value, &[Value]) -> Result<(), BoxError>,
+    /// Returns the result of the aggregation from its final state.
+    pub finish: fn(Value) -> Result<Value, BoxError>,
+}
+
+/// What kind of function a function is.
+pub enum Kind {
+    /// A function of the values in one row.
+    Scalar(fn(&[Value]) -> Result<Value, BoxError>),
+    /// A function of the values in a group of rows.
+    Aggregate(Aggregate),
+}
+
+/// The description of a user-defined function.
+pub struct Function {
+    /// Name of the function in SQL.
+    pub name: &'static str,
+    /// The number of arguments that the function takes.
+    pub arity: Arity,
+    /// True if the function always returns the same result for the same
+    /// arguments, which lets the database optimize calls to it.
+    pub deterministic: bool,
+    /// The implementation of the function.
+    pub kind: Kind,
+}
+
+/// An error calling a function.
+#[derive(Debug)]
+pub enum Error {
+    /// The function was called with the wrong number of arguments.
+    Arity(&'static str, usize),
+    /// A scalar function was called as an aggregate or vice versa.
+    Kind(&'static str),
+    /// The function failed.
+    Failed(&'static str, BoxError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Arity(name, n) => {
+                write!(f, "function {} does not take {} arguments", name, n)
+            }
+            Self::Kind(name) => {
+                write!(f, "function {} is the wrong kind", name)
+            }
+            Self::Failed(name, e) => {
+                write!(f, "function {} failed: {}", name, e)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Function {
+    fn check(&self, args: &[Value]) -> Result<(), Error> {
+        if self.arity.accepts(args.len()) {
+            Ok(())
+        } else {
+            Err(Error::Arity(self.name, args.len()))
+        }
+    }
+
+    /// Call a scalar function.
+    pub fn call(&self, args: &[Value]) -> Result<Value, Error> {
+        let Kind::Scalar(f) = &self.kind else {
+            return Err(Error::Kind(self.name));
+        };
+        self.check(args)?;
+        f(args).map_err(|e| Error::Failed(self.name, e))
+    }
+
+    /// Run an aggregate function over the arguments from each of a group of
+    /// rows.
+    pub fn aggregate<'a, I>(&self, rows: I) -> Result<Value, Error>
+    where
+        I: IntoIterator<Item = &'a [Value]>,
+    {
+        let Kind::Aggregate(agg) = &self.kind else {
+            return Err(Error::Kind(self.name));
+        };
+        let mut state = (agg.init)();
+        for args in rows {
+            self.check(args)?;
+            (agg.step)(&mut state, args)
+                .map_err(|e| Error::Failed(self.name, e))?;
+        }
+        (agg.finish)(state).map_err(|e| Error::Failed(self.name, e))
+    }
+}
+
+/// A database connection into which functions can be installed.
+pub trait Install {
+    /// The error returned by the database.
+    type Error;
+
+    /// Make a function available to SQL on the connection.
+    fn install(
+        &mut self, function: &'static Function,
+    ) -> Result<(), Self::Error>;
+}
+
+/// Install the given functions into a connection.
+pub fn install_from<C, I>(conn: &mut C, functions: I) -> Result<(), C::Error>
+where
+    C: Install + ?Sized,
+    I: IntoIterator<Item = &'static Function>,
+{
+    functions.into_iter().try_for_each(|f| conn.install(f))
+}
+
+/// Install all functions in the [function] set into a connection.
+pub fn install<C>(conn: &mut C) -> Result<(), C::Error>
+where
+    C: Install + ?Sized,
+{
+    install_from(conn, set!(function))
+}
+
+#[cfg(feature = "rusqlite")]
+mod sqlite {
+    use super::*;
+    use rusqlite::functions::{Context, FunctionFlags};
+    use rusqlite::types::Value as SqlValue;
+    use rusqlite::Connection;
+    use std::ffi::c_int;
+
+    impl From<SqlValue> for Value {
+        fn from(value: SqlValue) -> Self {
+            match value {
+                SqlValue::Null => Self::Null,
+                SqlValue::Integer(i) => Self::Integer(i),
+                SqlValue::Real(r) => Self::Real(r),
+                SqlValue::Text(s) => Self::Text(s),
+                SqlValue::Blob(b) => Self::Blob(b),
+            }
+        }
+    }
+
+    impl From<Value> for SqlValue {
+        fn from(value: Value) -> Self {
+            match value {
+                Value::Null => Self::Null,
+                Value::Integer(i) => Self::Integer(i),
+                Value::Real(r) => Self::Real(r),
+                Value::Text(s) => Self::Text(s),
+                Value::Blob(b) => Self::Blob(b),
+            }
+        }
+    }
+
+    fn values(ctx: &Context<'_>) -> rusqlite::Result<Vec<Value>> {
+        (0..ctx.len())
+            .map(|i| ctx.get::<SqlValue>(i).map(Value::from))
+            .collect()
+    }
+
+    fn user(e: Error) -> rusqlite::Error {
+        rusqlite::Error::UserFunctionError(Box::new(e))
+    }
+
+    /* rusqlite calls init only for the first row of a group, so finalize
+     * makes the initial state itself for a group without rows */
+    struct Fold(&'static Function, &'static Aggregate);
+
+    impl rusqlite::functions::Aggregate<Value, SqlValue> for Fold {
+        fn init(&self, _: &mut Context<'_>) -> rusqlite::Result<Value> {
+            Ok((self.1.init)())
+        }
+
+        fn step(
+            &self, ctx: &mut Context<'_>, state: &mut Value,
+        ) -> rusqlite::Result<()> {
+            let args = values(ctx)?;
+            self.0.check(&args).map_err(user)?;
+            (self.1.step)(state, &args)
+                .map_err(|e| user(Error::Failed(self.0.name, e)))
+        }
+
+        fn finalize(
+            &self, _: &mut Context<'_>, state: Option<Value>,
+        ) -> rusqlite::Result<SqlValue> {
+            let state = state.unwrap_or_else(self.1.init);
+            (self.1.finish)(state)
+                .map(SqlValue::from)
+                .map_err(|e| user(Error::Failed(self.0.name, e)))
+        }
+    }
+
+    impl Install for Connection {
+        type Error = rusqlite::Error;
+
+        fn install(&mut self, f: &'static Function) -> rusqlite::Result<()> {
+            let mut flags = FunctionFlags::SQLITE_UTF8;
+            if f.deterministic {
+                flags |= FunctionFlags::SQLITE_DETERMINISTIC;
+            }
+            /* sqlite itself refuses more than 127 arguments */
+            let arity = match f.arity {
+                Arity::Exact(n) => c_int::try_from(n).unwrap_or(c_int::MAX),
+                Arity::Variadic => -1,
+            };
+            match &f.kind {
+                Kind::Scalar(_) => {
+                    self.create_scalar_function(f.name, arity, flags, |ctx| {
+                        let args = values(ctx)?;
+                        f.call(&args).map(SqlValue::from).map_err(user)
+                    })
+                }
+                Kind::Aggregate(agg) => self.create_aggregate_function(
+                    f.name,
+                    arity,
+                    flags,
+                    Fold(f, agg),
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[set_entry(crate::udf::function)]
+    static SUM: Function = Function {
+        name: "int_sum",
+        arity: Arity::Exact(1),
+        deterministic: true,
+        kind: Kind::Aggregate(Aggregate {
+            init: || Value::Integer(0),
+            step: |state, args| match (state, args) {
+                (Value::Integer(s), [Value::Integer(i)]) => {
+                    *s += i;
+                    Ok(())
+                }
+                _ => Err("int_sum takes an integer".into()),
+            },
+            finish: Ok,
+        }),
+    };
+
+    #[set_entry(crate::udf::function)]
+    static CONCAT: Function = Function {
+        name: "concat_all",
+        arity: Arity::Variadic,
+        deterministic: true,
+        kind: Kind::Scalar(|args| {
+            let mut out = String::new();
+            for arg in args {
+                match arg {
+                    Value::Text(s) => out.push_str(s),
+                    Value::Null => (),
+                    v => return Err(format!("not text: {:?}", v).into()),
+                }
+            }
+            Ok(Value::Text(out))
+        }),
+    };
+
+    #[derive(Default)]
+    struct Registry(BTreeMap<&'static str, &'static Function>);
+
+    impl Install for Registry {
+        type Error = String;
+
+        fn install(&mut self, f: &'static Function) -> Result<(), String> {
+            match self.0.insert(f.name, f) {
+                None => Ok(()),
+                Some(_) => Err(format!("duplicate function {}", f.name)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_install() {
+        let mut registry = Registry::default();
+        install(&mut registry).unwrap();
+        assert_eq!(
+            registry.0.keys().copied().collect::<Vec<_>>(),
+            ["concat_all", "int_sum"]
+        );
+        assert!(install(&mut registry).is_err());
+
+        let concat = registry.0["concat_all"];
+        let args = [
+            Value::Text("a".into()),
+            Value::Null,
+            Value::Text("b".into()),
+        ];
+        assert_eq!(concat.call(&args).unwrap(), Value::Text("ab".into()));
+        assert!(matches!(
+            concat.call(&[Value::Real(1.0)]),
+            Err(Error::Failed(..))
+        ));
+        assert!(matches!(concat.aggregate([]), Err(Error::Kind(_))));
+
+        let sum = registry.0["int_sum"];
+        let rows = [[Value::Integer(1)], [Value::Integer(2)]];
+        let total = sum.aggregate(rows.iter().map(|r| &r[..])).unwrap();
+        assert_eq!(total, Value::Integer(3));
+        assert!(matches!(sum.aggregate([&[][..]]), Err(Error::Arity(_, 0))));
+    }
+
+    #[cfg(feature = "rusqlite")]
+    #[test]
+    fn test_rusqlite() {
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        install(&mut conn).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE t (g TEXT, n INTEGER);
+             INSERT INTO t VALUES ('a', 1), ('a', 2), ('b', 5);",
+        )
+        .unwrap();
+
+        let sql = "SELECT g, int_sum(n) FROM t GROUP BY g ORDER BY g";
+        let mut stmt = conn.prepare(sql).unwrap();
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<Vec<(String, i64)>, _>>()
+            .unwrap();
+        assert_eq!(rows, [("a".into(), 3), ("b".into(), 5)]);
+
+        fn query<T: rusqlite::types::FromSql>(
+            conn: &rusqlite::Connection, sql: &str,
+        ) -> rusqlite::Result<T> {
+            conn.query_row(sql, [], |row| row.get(0))
+        }
+        let sql = "SELECT int_sum(n) FROM t WHERE n > 9";
+        assert_eq!(query::<i64>(&conn, sql).unwrap(), 0);
+        let sql = "SELECT concat_all('a', NULL, 'b')";
+        assert_eq!(query::<String>(&conn, sql).unwrap(), "ab");
+        let sql = "SELECT concat_all(1.5)";
+        let e = query::<String>(&conn, sql).unwrap_err();
+        assert!(e.to_string().contains("not text"), "{}", e);
+        assert!(query::<i64>(&conn, "SELECT int_sum('x')").is_err());
+        assert!(conn.prepare("SELECT int_sum(1, 2)").is_err());
+    }
+}
@@ -0,0 +1,256 @@
+//! Linker set entries whose layout may change between versions.
+//!
+//! A plugin built against an old version of an element type carries the
+//! old layout.  To let such entries live alongside new ones, each entry
+//! is a `#[repr(C)]` struct beginning with a [Header] that records its
+//! version and size, and the set holds a pointer-sized [Entry] referring
+//! to it.  Viewing an entry as the current version with [Entry::view]
+//! gives access to the whole struct if the entry is new enough, and to
+//! each field that the entry has, with a default for those it lacks,
+//! through the [field!](crate::field) macro.
+//!
+//! ```
+//! use linker_set::*;
+//! use linker_set::versioned::{Entry, Header, Versioned};
+//!
+//! #[repr(C)]
+//! struct Plugin {
+//!     header: Header,
+//!     name: &'static str,
+//!     priority: u32, // added in version 2
+//! }
+//!
+//! unsafe impl Versioned for Plugin {
+//!     const VERSION: u16 = 2;
+//! }
+//!
+//! set_declare!(plugins, Entry);
+//!
+//! static MINE: Plugin = Plugin {
+//!     header: Header::of::<Plugin>(),
+//!     name: "mine",
+//!     priority: 5,
+//! };
+//!
+//! #[set_entry(plugins)]
+//! static MINE_ENTRY: Entry = Entry::new(&MINE);
+//!
+//! # fn main() {
+//! for entry in set!(plugins) {
+//!     let plugin = entry.view::<Plugin>();
+//!     assert_eq!(field!(plugin, Plugin, priority, 0), 5);
+//! }
+//! # }
+//! ```
+
+use std::marker::PhantomData;
+
+/// The header at the start of a versioned entry.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Header {
+    version: u16,
+    size: u16,
+}
+
+impl Header {
+    /// Returns the header for an entry of the current version of a type.
+    pub const fn of<T>() -> Self
+    where
+        T: Versioned,
+    {
+        let size = std::mem::size_of::<T>();
+        assert!(size <= u16::MAX as usize, "versioned entry is too large");
+        Self {
+            version: T::VERSION,
+            size: size as u16,
+        }
+    }
+
+    /// Returns the version of the entry.
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    /// Returns the size of the entry in bytes.
+    pub fn size(&self) -> usize {
+        self.size as usize
+    }
+}
+
+/// An element type whose entries may have been built against older
+/// versions of it.
+///
+/// # Safety
+/// The type must be `#[repr(C)]` and begin with a [Header], and each
+/// version must only add fields to the end of the previous one.
+pub unsafe trait Versioned: Sized + 'static {
+    /// The current version of the type.
+    const VERSION: u16;
+}
+
+/// The element of a linker set of versioned entries.
+#[derive(Clone, Copy)]
+pub struct Entry {
+    header: &'static Header,
+}
+
+impl Entry {
+    /// Create an element referring to a versioned entry.
+    pub const fn new<T>(entry: &'static T) -> Self
+    where
+        T: Versioned,
+    {
+        let header = entry as *const T as *const Header;
+        Self {
+            header: unsafe { &*header },
+        }
+    }
+
+    /// Returns the header of the entry.
+    pub fn header(&self) -> &'static Header {
+        self.header
+    }
+
+    /// View the entry as the current version of a type.
+    pub fn view<T>(&self) -> View<T>
+    where
+        T: Versioned,
+    {
+        View {
+            header: self.header,
+            _type: PhantomData,
+        }
+    }
+}
+
+/// A versioned entry viewed as the current version of a type.
+pub struct View<T> {
+    header: &'static Header,
+    _type: PhantomData<T>,
+}
+
+impl<T> View<T>
+where
+    T: Versioned,
+{
+    /// Returns the version of the entry.
+    pub fn version(&self) -> u16 {
+        self.header.version
+    }
+
+    /// Returns the entry if it has every field of the current version.
+    pub fn get(&self) -> Option<&'static T> {
+        if self.header.size() >= std::mem::size_of::<T>() {
+            Some(unsafe { &*(self.header as *const Header as *const T) })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the field at an offset, or a default if the entry is too
+    /// old to have it.
+    ///
+    /// Users should call the [field!](crate::field) macro instead of this
+    /// function.
+    ///
+    /// # Safety
+    /// The offset must be that of a field of `T` of type `F`.
+    pub unsafe fn field<F>(&self, offset: usize, default: F) -> F
+    where
+        F: Copy,
+    {
+        if offset + std::mem::size_of::<F>() <= self.header.size() {
+            let base = self.header as *const Header as *const u8;
+            *(base.add(offset) as *const F)
+        } else {
+            default
+        }
+    }
+}
+
+/// Read a field of a versioned entry, or a default if the entry is too
+/// old to have it.
+///
+/// Given a [View](crate::versioned::View) of a type, the name of the type, a
+/// field, and a default of the same type as the field.
+#[macro_export]
+macro_rules! field {
+    ($view:expr, $type:ty, $field:ident, $default:expr) => {{
+        let view: &$crate::versioned::View<$type> = &$view;
+        let default = $default;
+        if false {
+            // for typechecking
+            fn same<T>(_: &T, _: &T) {}
+            let entry: &$type = view.get().unwrap();
+            same(&entry.$field, &default);
+        }
+        unsafe { view.field(::std::mem::offset_of!($type, $field), default) }
+    }};
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::*;
+
+    #[repr(C)]
+    struct PluginV1 {
+        header: Header,
+        id: u32,
+    }
+
+    unsafe impl Versioned for PluginV1 {
+        const VERSION: u16 = 1;
+    }
+
+    #[repr(C)]
+    struct Plugin {
+        header: Header,
+        id: u32,
+        weight: u64,
+    }
+
+    unsafe impl Versioned for Plugin {
+        const VERSION: u16 = 2;
+    }
+
+    set_declare!(plugins, Entry);
+
+    static OLD: PluginV1 = PluginV1 {
+        header: Header::of::<PluginV1>(),
+        id: 1,
+    };
+
+    static NEW: Plugin = Plugin {
+        header: Header::of::<Plugin>(),
+        id: 2,
+        weight: 20,
+    };
+
+    #[set_entry(plugins)]
+    static OLD_ENTRY: Entry = Entry::new(&OLD);
+    #[set_entry(plugins)]
+    static NEW_ENTRY: Entry = Entry::new(&NEW);
+
+    #[test]
+    fn test_versioned() {
+        let mut seen = set!(plugins)
+            .iter()
+            .map(|e| {
+                let p = e.view::<Plugin>();
+                let id = field!(p, Plugin, id, 0);
+                (
+                    p.version(),
+                    id,
+                    field!(p, Plugin, weight, 7),
+                    p.get().is_some(),
+                )
+            })
+            .collect::<Vec<_>>();
+        seen.sort();
+        assert_eq!(seen, [(1, 1, 7, false), (2, 2, 20, true)]);
+        assert_eq!(NEW_ENTRY.view::<Plugin>().get().unwrap().weight, 20);
+        assert_eq!(OLD_ENTRY.header().size(), 8);
+    }
+}
@@ -0,0 +1,294 @@
+//! Declarative registration of background workers.
+//!
+//! Workers are registered into the [worker] linker set and started all at
+//! once with [start_all], which returns a [Supervisor].  Shutting down the
+//! supervisor stops the workers in the reverse of the order in which they
+//! were started.
+//!
+//! ```
+//! use linker_set::*;
+//! use linker_set::workers::{self, Context, Threads, Worker};
+//!
+//! fn tick(ctx: &Context) {
+//!     while !ctx.stopping() {
+//!         std::thread::yield_now();
+//!     }
+//! }
+//!
+//! #[set_entry(linker_set::workers::worker)]
+//! static TICKER: Worker = Worker {
+//!     name: "ticker",
+//!     count: 2,
+//!     entry: tick,
+//!     shutdown: None,
+//! };
+//!
+//! # fn main() {
+//! let supervisor = workers::start_all(Threads);
+//! assert!(supervisor.shutdown().is_ok());
+//! # }
+//! ```
+
+use crate::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+set_declare!(worker, Worker, namespaced);
+
+/// The definition of a background worker.
+pub struct Worker {
+    /// Name of the worker, used to name its instances.
+    pub name: &'static str,
+    /// Number of instances of the worker to start.
+    pub count: usize,
+    /// Body of the worker, which should return once stopping is signalled.
+    pub entry: fn(&Context),
+    /// Hook called during shutdown, before the instances are joined.
+    pub shutdown: Option<fn()>,
+}
+
+/// State handed to each running instance of a worker.
+#[derive(Clone)]
+pub struct Context {
+    name: &'static str,
+    index: usize,
+    stop: Arc<AtomicBool>,
+}
+
+impl Context {
+    /// Returns the name of the worker.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns which of the worker's instances this is.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns true once the supervisor has begun shutting down.
+    pub fn stopping(&self) -> bool {
+        self.stop.load(Ordering::Acquire)
+    }
+}
+
+/// Something that can run instances of workers.
+pub trait Runtime {
+    /// A handle that can be used to wait for an instance to finish.
+    type Handle;
+
+    /// Start running a function.
+    fn spawn(&self, name: String, f: Box<dyn FnOnce() + Send>) -> Self::Handle;
+
+    /// Wait for a function to finish, returning false if it panicked.
+    fn join(&self, handle: Self::Handle) -> bool;
+}
+
+/// A runtime that runs each instance of a worker on its own thread.
+pub struct Threads;
+
+impl Runtime for Threads {
+    type Handle = JoinHandle<()>;
+
+    fn spawn(&self, name: String, f: Box<dyn FnOnce() + Send>) -> Self::Handle {
+        std::thread::Builder::new()
+            .name(name)
+            .spawn(f)
+            .expect("failed to spawn worker thread")
+    }
+
+    fn join(&self, handle: Self::Handle) -> bool {
+        handle.join().is_ok()
+    }
+}
+
+/// A handle to a group of running workers.
+///
+/// Dropping the supervisor shuts down the workers, but only an explicit
+/// call to [Supervisor::shutdown] reports workers that panicked.
+pub struct Supervisor<R>
+where
+    R: Runtime,
+{
+    runtime: R,
+    stop: Arc<AtomicBool>,
+    running: Vec<(&'static Worker, Vec<R::Handle>)>,
+}
+
+impl<R> Supervisor<R>
+where
+    R: Runtime,
+{
+    /// Returns the names of the running workers in the order started.
+    pub fn workers(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.running.iter().map(|(w, _)| w.name)
+    }
+
+    /// Stop all workers in the reverse of the order in which they started.
+    ///
+    /// Returns the names of any workers having an instance that panicked.
+    pub fn shutdown(mut self) -> Result<(), Vec<&'static str>> {
+        let failed = self.stop();
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(failed)
+        }
+    }
+
+    fn stop(&mut self) -> Vec<&'static str> {
+        let mut failed = Vec::new();
+        self.stop.store(true, Ordering::Release);
+        while let Some((worker, handles)) = self.running.pop() {
+            if let Some(hook) = worker.shutdown {
+                hook();
+            }
+            let mut ok = true;
+            for handle in handles.into_iter().rev() {
+                ok &= self.runtime.join(handle);
+            }
+            if !ok {
+                failed.push(worker.name);
+            }
+        }
+        failed
+    }
+}
+
+impl<R> Drop for Supervisor<R>
+where
+    R: Runtime,
+{
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Start the given workers on a runtime.
+pub fn start<R, I>(runtime: R, workers: I) -> Supervisor<R>
+where
+    R: Runtime,
+    I: IntoIterator<Item = &'static Worker>,
+{
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut running = Vec::new();
+    for worker in workers {
+        let handles = (0..worker.count)
+            .map(|index| {
+                let ctx = Context {
+                    name: worker.name,
+                    index,
+                    stop: stop.clone(),
+                };
+                let name = format!("{}-{}", worker.name, index);
+                runtime.spawn(name, Box::new(move || (worker.entry)(&ctx)))
+            })
+            .collect();
+        running.push((worker, handles));
+    }
+    Supervisor {
+        runtime,
+        stop,
+        running,
+    }
+}
+
+/// Start all workers registered in the [worker] linker set.
+pub fn start_all<R>(runtime: R) -> Supervisor<R>
+where
+    R: Runtime,
+{
+    start(runtime, set!(worker))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Mutex;
+
+    static STARTED: AtomicUsize = AtomicUsize::new(0);
+
+    fn spin(ctx: &Context) {
+        STARTED.fetch_add(1, Ordering::SeqCst);
+        while !ctx.stopping() {
+            std::thread::yield_now();
+        }
+    }
+
+    #[set_entry(crate::workers::worker)]
+    static SPINNER: Worker = Worker {
+        name: "spinner",
+        count: 3,
+        entry: spin,
+        shutdown: None,
+    };
+
+    #[test]
+    fn test_start_all() {
+        let supervisor = start_all(Threads);
+        assert_eq!(supervisor.workers().collect::<Vec<_>>(), ["spinner"]);
+        assert!(supervisor.shutdown().is_ok());
+        assert_eq!(STARTED.load(Ordering::SeqCst), 3);
+    }
+
+    static ORDER: Mutex<Vec<&str>> = Mutex::new(Vec::new());
+
+    fn idle(_: &Context) {}
+
+    static FIRST: Worker = Worker {
+        name: "first",
+        count: 1,
+        entry: idle,
+        shutdown: Some(|| ORDER.lock().unwrap().push("first")),
+    };
+    static SECOND: Worker = Worker {
+        name: "second",
+        count: 1,
+        entry: |_| panic!("oops"),
+        shutdown: Some(|| ORDER.lock().unwrap().push("second")),
+    };
+
+    #[test]
+    fn test_shutdown_order() {
+        let supervisor = start(Threads, [&FIRST, &SECOND]);
+        assert_eq!(supervisor.shutdown(), Err(vec!["second"]));
+        assert_eq!(*ORDER.lock().unwrap(), ["second", "first"]);
+    }
+
+    /* a set of the program named worker has a section of its own, not
+     * that of the set of this crate */
+    const SEPARATE: &str = "
+        use linker_set::*;
+        use linker_set::workers::{self, Context, Threads, Worker};
+
+        set_declare!(worker, u8);
+
+        #[set_entry(worker)]
+        static MINE: u8 = 7;
+
+        #[set_entry(linker_set::workers::worker)]
+        static THEIRS: Worker = Worker {
+            name: \"theirs\",
+            count: 1,
+            entry: |_: &Context| (),
+            shutdown: None,
+        };
+
+        fn main() {
+            assert_eq!(set!(worker).iter().collect::<Vec<_>>(), [&7]);
+            let supervisor = workers::start_all(Threads);
+            let names = supervisor.workers().collect::<Vec<_>>();
+            assert!(supervisor.shutdown().is_ok());
+            assert_eq!(names, [\"theirs\"]);
+        }
+    ";
+
+    #[test]
+    fn test_separate() {
+        if let Err(e) = crate::diagnostics::run(SEPARATE) {
+            panic!("{}", e);
+        }
+    }
+}